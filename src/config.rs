@@ -0,0 +1,324 @@
+// Kosik Project Configuration
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Reads the settings shared by the manuscripts of a project from a
+//! <tt>kosik.toml</tt> file
+//!
+//! Kosik looks for <tt>kosik.toml</tt> in the directory of the input
+//! file, and then in each of its parents, and uses the first one it
+//! finds.  When the manuscript comes from the standard input, the
+//! search starts in the current directory.  The settings in the file
+//! take the place of Kosik's built-in defaults.  Attributes in the
+//! manuscript and options on the command line take precedence over
+//! the file.
+//!
+//! All of the settings are optional:
+//!
+//! ```toml
+//! # Contact information, for manuscripts whose head has none
+//! contact = [
+//!     "MATCHLOCK PRESS",
+//!     "P.O. Box 90606",
+//!     "Brooklyn, NY 11209",
+//! ]
+//!
+//! # Number of the first page, for manuscripts with no firstPage
+//! first_page = 1
+//!
+//! # Output format, as given to -f
+//! format = "pdf"
+//!
+//! # Line spacing of paragraphs that set none, and inherit none
+//! line_spacing = "double"
+//!
+//! # Fail on footnote errors, as --strict does
+//! strict = true
+//!
+//! # Title and author name for the slug line, in place of the ones
+//! # taken from the head.  They are printed in uppercase.
+//! [slug_line]
+//! title = "Youth"
+//! author = "Conrad"
+//!
+//! # Word count on the title page, as --word-count, --round and
+//! # --body-only give it
+//! [word_count]
+//! method = "lines"
+//! round = 500
+//! body_only = true
+//! ```
+//!
+//! A setting that Kosik does not know is an error, so that a typing
+//! mistake does not go unnoticed.
+//!
+//! There are no settings for the paper size, the page geometry, the
+//! locale or the style of the footnotes, because the page is laid out
+//! as a typewriter would lay it out.  Manuscripts are always typed on
+//! US letter paper, with the margins of a typewritten page, with
+//! American quotation marks and dashes, and with each footnote at the
+//! foot of the page that refers to it.  Giving <tt>paper</tt>,
+//! <tt>geometry</tt>, <tt>locale</tt> or <tt>footnote_style</tt> is
+//! an error that says so.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::path::Path;
+//! use kosik::config::Config;
+//!
+//! let config = Config::from_toml(Path::new("kosik.toml"), "first_page = 3").unwrap();
+//! assert_eq!(config.first_page, Some(3));
+//!
+//! let error = Config::from_toml(Path::new("kosik.toml"), "frist_page = 3").unwrap_err();
+//! assert!(error.to_string().starts_with("kosik.toml: unknown field `frist_page`"));
+//!
+//! let error = Config::from_toml(Path::new("kosik.toml"), "paper = \"a4\"").unwrap_err();
+//! assert_eq!(error.to_string(),
+//!            "kosik.toml: paper is not supported, because manuscripts \
+//!             are always typed on US letter paper");
+//!
+//! let error = Config::from_toml(Path::new("kosik.toml"), "[geometry]\nmargin = 1").unwrap_err();
+//! assert!(error.to_string().starts_with("kosik.toml: geometry is not supported"));
+//! ```
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde::de::IgnoredAny;
+
+use crate::Format;
+use crate::document::*;
+use crate::document::reader::Reader;
+
+/// Name of the configuration file
+pub const FILE_NAME: &str = "kosik.toml";
+
+// Layout of the configuration file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    contact: Option<Vec<String>>,
+    first_page: Option<i32>,
+    footnote_style: Option<IgnoredAny>,
+    format: Option<String>,
+    geometry: Option<IgnoredAny>,
+    line_spacing: Option<String>,
+    locale: Option<IgnoredAny>,
+    paper: Option<IgnoredAny>,
+    slug_line: Option<SlugLineTable>,
+    strict: Option<bool>,
+    word_count: Option<WordCountTable>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SlugLineTable {
+    author: Option<String>,
+    title: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WordCountTable {
+    body_only: Option<bool>,
+    method: Option<String>,
+    round: Option<usize>,
+}
+
+/// Project settings, each of which is unset unless the file gives it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// File that the settings were read from
+    pub path: Option<PathBuf>,
+    /// Leave the front and back matter out of the word count.
+    pub body_only: Option<bool>,
+    /// Lines of contact information, for manuscripts whose head has
+    /// none
+    pub contact: Option<Vec<String>>,
+    /// Number of the first page, for manuscripts with no
+    /// <tt>firstPage</tt> attribute
+    pub first_page: Option<i32>,
+    /// Output format
+    pub format: Option<Format>,
+    /// Multiple to round the word count to
+    pub granularity: Option<usize>,
+    /// Line spacing of paragraphs that set none, and are not inside
+    /// an element that sets one
+    pub line_spacing: Option<LineSpacing>,
+    /// Way of counting the words
+    pub method: Option<WordCountMethod>,
+    /// Author name for the slug line
+    pub short_author_name: Option<String>,
+    /// Title for the slug line
+    pub short_title: Option<String>,
+    /// Fail on footnote errors.
+    pub strict: Option<bool>,
+}
+
+impl Config {
+    /// Find the configuration file for an input file, searching its
+    /// directory and then each of the parents
+    pub fn find(input_file: &Path) -> Option<PathBuf> {
+        let dir = match input_file.parent() {
+            Some(dir) if input_file != Path::new("-") && dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+
+        fs::canonicalize(dir).ok()?
+            .ancestors()
+            .map(|dir| dir.join(FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// Read the configuration file for an input file, if there is
+    /// one.  Otherwise, every setting is left unset.
+    pub fn discover(input_file: &Path) -> Result<Config, Box<dyn Error>> {
+        match Config::find(input_file) {
+            Some(path) => Config::open(&path),
+            None => Ok(Default::default()),
+        }
+    }
+
+    /// Read a configuration file.
+    pub fn open(path: &Path) -> Result<Config, Box<dyn Error>> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Config::from_toml(path, &text)
+    }
+
+    /// Parse the text of a configuration file.  The path is used in
+    /// error messages.
+    pub fn from_toml(path: &Path, text: &str) -> Result<Config, Box<dyn Error>> {
+        let file: File = basic_toml::from_str(text)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        // These are asked for often enough to deserve a better answer
+        // than an unknown field.
+        let unsupported = [
+            (file.paper.is_some(), "paper",
+             "manuscripts are always typed on US letter paper"),
+            (file.geometry.is_some(), "geometry",
+             "manuscripts are always typed with the margins of a typewritten page"),
+            (file.locale.is_some(), "locale",
+             "manuscripts are always typed with American quotation marks and dashes"),
+            (file.footnote_style.is_some(), "footnote_style",
+             "footnotes are always typed at the foot of the page that refers to them"),
+        ];
+
+        for (given, key, reason) in unsupported.iter() {
+            if *given {
+                return Err(format!("{}: {} is not supported, because {}",
+                                   path.display(), key, reason).into());
+            }
+        }
+
+        let format = match file.format {
+            Some(name) => Some(Format::from_str(&name, true).map_err(|_| {
+                format!("{}: unknown format {}", path.display(), name)
+            })?),
+            None => None,
+        };
+
+        let line_spacing = match file.line_spacing.as_deref() {
+            Some("double") => Some(LineSpacing::Double),
+            Some("single") => Some(LineSpacing::Single),
+            Some(name) => {
+                return Err(format!("{}: unknown line spacing {}, expected single or double",
+                                   path.display(), name).into());
+            },
+            None => None,
+        };
+
+        let (short_author_name, short_title) = match file.slug_line {
+            Some(table) => (table.author, table.title),
+            None => (None, None),
+        };
+
+        let (body_only, method, granularity) = match file.word_count {
+            Some(table) => {
+                let method = match table.method {
+                    Some(name) => Some(WordCountMethod::from_str(&name, true).map_err(|_| {
+                        format!("{}: unknown word count method {}", path.display(), name)
+                    })?),
+                    None => None,
+                };
+
+                (table.body_only, method, table.round)
+            },
+            None => (None, None, None),
+        };
+
+        Ok(Config {
+            path: Some(path.to_path_buf()),
+            body_only: body_only,
+            contact: file.contact,
+            first_page: file.first_page,
+            format: format,
+            granularity: granularity,
+            line_spacing: line_spacing,
+            method: method,
+            short_author_name: short_author_name,
+            short_title: short_title,
+            strict: file.strict,
+        })
+    }
+
+    /// Add what the settings supply, and the document leaves out, to
+    /// an element tree: for now, the contact information in the head
+    /// of a manuscript or screenplay
+    pub fn complete(&self, root: &mut ElementType) {
+        let contact = match &self.contact {
+            Some(contact) => contact,
+            None => return,
+        };
+
+        let children = match root {
+            ElementType::Manuscript(elem) => &mut elem.children,
+            ElementType::Screenplay(elem) => &mut elem.children,
+            _ => return,
+        };
+
+        for child in children.iter_mut() {
+            if let ElementType::Head(head) = child {
+                if head.children.iter().any(|x| matches!(x, ElementType::Contact(_))) {
+                    return;
+                }
+
+                let lines = contact.iter()
+                    .map(|line| {
+                        line.replace('&', "&amp;")
+                            .replace('<', "&lt;")
+                            .replace('>', "&gt;")
+                    })
+                    .collect::<Vec<String>>()
+                    .join("<br/>");
+
+                let xml = format!("<contact>{}</contact>", lines);
+
+                if let Ok(Some(elem)) = Reader::new(&xml).run() {
+                    head.children.push(elem);
+                }
+
+                return;
+            }
+        }
+    }
+}
@@ -17,6 +17,15 @@
 
 //! In-memory representation of a document
 //!
+//! * The [`include`] module expands included files, so that a
+//!   manuscript can be kept in several files.
+//!
+//! * The [`lint`] module checks manuscript sources for slips in the
+//!   text, for <tt>kosik lint</tt>.
+//!
+//! * The [`pretty`] module lays out manuscript sources in a canonical
+//!   form, for <tt>kosik fmt</tt>.
+//!
 //! * The [`reader`] module builds an element tree, tokenizing the
 //!   contents of each text element.
 //!
@@ -26,6 +35,9 @@
 //! * The [`compositor`] module flows the text blocks into pages,
 //!   splitting them when necessary, and adds a header to each page.
 //!
+//! * The [`cache`] module keeps formatted blocks between runs, for
+//!   <tt>kosik --watch</tt>.
+//!
 //! * The [`writer`] module writes the pages to the standard output
 //!   using the Latin-9 character set.
 
@@ -33,13 +45,21 @@ use std::cmp::max;
 use std::error::Error;
 use std::fmt::Debug;
 
+use math::round;
+use thousands::Separable;
+
 use crate::text::Line;
 use crate::text::Segment;
 use crate::text::tokens::*;
 
+pub mod include;
+pub mod lint;
+pub mod pretty;
 pub mod reader;
 pub mod formatter;
 pub mod compositor;
+pub mod cache;
+pub mod collection;
 pub mod writer;
 
 // configuration
@@ -59,6 +79,33 @@ pub const LEFT_MARGIN: usize = 10;
 /// Right margin in spaces
 pub const RIGHT_MARGIN: usize = 74;
 
+/// Left margin of an epigraph in spaces
+pub const EPIGRAPH_MARGIN: usize = LEFT_MARGIN + 4 * INDENT;
+
+/// Tab stop interval for preformatted text
+pub const TAB_WIDTH: usize = 8;
+
+/// Left margin of screenplay scene headings and action in spaces
+pub const SCREENPLAY_MARGIN: usize = 15;
+
+/// Left margin of screenplay dialogue in spaces
+pub const DIALOGUE_MARGIN: usize = 25;
+
+/// Right margin of screenplay dialogue in spaces
+pub const DIALOGUE_RIGHT_MARGIN: usize = 59;
+
+/// Left margin of screenplay parentheticals in spaces
+pub const PARENTHETICAL_MARGIN: usize = 31;
+
+/// Right margin of screenplay parentheticals in spaces
+pub const PARENTHETICAL_RIGHT_MARGIN: usize = 54;
+
+/// Left margin of screenplay character cues in spaces
+pub const CHARACTER_MARGIN: usize = 37;
+
+/// Left margin of stage directions in spaces
+pub const DIRECTION_MARGIN: usize = LEFT_MARGIN + 4 * INDENT;
+
 /// Slug line height
 pub const SLUG_LINE: usize = 62;
 
@@ -80,16 +127,27 @@ pub const CHAPTER_SKIP: usize = 11;
 /// Number of lines to skip after a section title
 pub const SECTION_SKIP: usize = 5;
 
+/// Page layout conventions
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Layout {
+    /// Manuscript format, with a slug line at the top of each page
+    Manuscript,
+    /// Screenplay format, with the page number in the top right
+    /// corner, starting from the second page
+    Screenplay,
+}
+
 /// Sequence of composited pages plus slug line info
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Typescript {
+    /// Page layout conventions to follow
+    pub layout: Layout,
     /// If there is contact information here, it will be printed in
     /// the top left corner of the title page.
     pub contact: Option<Block>,
     /// If there is word count information here, it will be printed in
-    /// the top right corner of the title page, rounded to the nearest
-    /// thousand.
-    pub word_count: Option<usize>,
+    /// the top right corner of the title page.
+    pub word_count: Option<WordCount>,
     /// This flag indicates whether the document contained any
     /// subdivisions:  parts, chapters or sectdions.
     pub has_structure: bool,
@@ -98,13 +156,178 @@ pub struct Typescript {
     /// The surname of the first author listed in the head, formatted
     /// for the slug line.
     pub short_author_name: Segment,
+    /// Slug line names for the manuscripts of a collection, in page
+    /// order.  A single manuscript has none, and uses the names
+    /// above on every page.
+    pub running_heads: Vec<RunningHead>,
     /// The page list to write to the output stream
     pub pages: PageList,
 }
 
+/// Slug line names for the pages of one manuscript in a collection
+#[derive(Debug, Clone)]
+pub struct RunningHead {
+    /// Number of the first page the names are printed on
+    pub first_page: i32,
+    /// Title of the manuscript, formatted for the slug line
+    pub short_title: Segment,
+    /// Surname of its author, formatted for the slug line
+    pub short_author_name: Segment,
+}
+
+/// Word count for the title page, with the granularity to round it
+/// to
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WordCount {
+    /// Number of words
+    pub words: usize,
+    /// See [`WordCountPolicy::granularity`].
+    pub granularity: usize,
+}
+
+/// Ways of counting the words in a manuscript
+#[derive(clap::ValueEnum, Debug, Copy, Clone, Default, PartialEq)]
+pub enum WordCountMethod {
+    /// Count the words in the text and the footnotes
+    #[default]
+    Exact,
+    /// Count ten words to each typed line
+    Lines,
+    /// Count one word for every six characters, including spaces
+    Typesetter,
+}
+
+/// Word count conventions, which differ from one publisher to the
+/// next
+#[derive(clap::Args, Debug, Copy, Clone, Default, PartialEq)]
+pub struct WordCountPolicy {
+    #[clap(long = "word-count", value_enum, default_value_t = WordCountMethod::Exact)]
+    /// How to count the words
+    pub method: WordCountMethod,
+
+    #[clap(long = "round", value_name = "N", default_value_t = 0)]
+    /// Round the word count to the nearest multiple of N.  By default, it is rounded to the nearest thousand, or to the nearest hundred for short works.
+    pub granularity: usize,
+
+    #[clap(long)]
+    /// Leave the front and back matter out of the word count.
+    pub body_only: bool,
+}
+
+/// Rounds a word count to the nearest multiple of the granularity
+///
+/// If the granularity is zero, the count is rounded to the nearest
+/// thousand, or to the nearest hundred for short works.
+///
+/// # Examples
+///
+/// ```
+/// # use kosik::document::round_word_count;
+/// assert_eq!(round_word_count(13582, 0), 14000);
+/// assert_eq!(round_word_count(13582, 500), 13500);
+/// assert_eq!(round_word_count(13582, 1), 13582);
+/// assert_eq!(round_word_count(750, 0), 800);
+/// ```
+pub fn round_word_count(word_count: usize, granularity: usize) -> usize {
+    if granularity > 0 {
+        (round::half_to_even(word_count as f64 / granularity as f64, 0)
+         * granularity as f64) as usize
+    } else if word_count > 1000 {
+        // nearest thousand
+        (round::half_to_even(word_count as f64 / 10000.0, 1)
+         * 10000.0) as usize
+    } else {
+        // nearest hundred
+        (round::half_to_even(word_count as f64 / 1000.0, 1)
+         * 1000.0) as usize
+    }
+}
+
+/// Formats the approximate word count for the title page
+///
+/// See [`round_word_count`] for the rounding.
+pub fn word_count_text(word_count: usize, granularity: usize) -> String {
+    let n = round_word_count(word_count, granularity);
+    format!("Approx. {} words", n.separate_with_commas())
+}
+
+impl Typescript {
+    /// Returns the approximate word count for the title page, if
+    /// there is one
+    pub fn word_count_line(&self) -> Option<Line> {
+        if let Some(word_count) = self.word_count {
+            let s = word_count_text(word_count.words, word_count.granularity);
+
+            Some(Line {
+                column: RIGHT_MARGIN - s.chars().count(),
+                segments: vec![Segment::from(s)],
+                note_refs: Vec::new(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the running head for a page, if it has one
+    ///
+    /// Manuscript pages carry a slug line with the author, title and
+    /// page number, except for an unstructured title page.  In a
+    /// collection, the author and title are those of the manuscript
+    /// the page belongs to.  Screenplay pages are numbered in the top
+    /// right corner, starting from the second page.
+    pub fn slug_line(&self, page_no: i32) -> Option<Line> {
+        if self.layout == Layout::Screenplay {
+            if page_no > 1 {
+                let s = format!("{}.", page_no);
+
+                Some(Line {
+                    column: RIGHT_MARGIN - s.chars().count(),
+                    segments: vec![Segment::from(s)],
+                    note_refs: Vec::new(),
+                })
+            } else {
+                None
+            }
+
+        } else if page_no > 1
+            || (page_no == 1 && self.has_structure)
+            || (page_no == 1
+                && !self.has_structure
+                && self.contact.is_none()
+                && self.word_count.is_none())
+        {
+            let (short_author_name, short_title) = match self.running_heads.iter()
+                .rev()
+                .find(|x| x.first_page <= page_no)
+            {
+                Some(head) => (&head.short_author_name, &head.short_title),
+                None => (&self.short_author_name, &self.short_title),
+            };
+
+            // The title is kept in mixed case for the document
+            // properties, but printed in uppercase.
+            let mut short_title = short_title.clone();
+            short_title.text = short_title.text.to_uppercase();
+
+            Some(Line {
+                column: LEFT_MARGIN,
+                segments: vec![
+                    short_author_name.clone(),
+                    Segment::from("/"),
+                    short_title,
+                    Segment::from(format!("/{}", page_no)),
+                ],
+                note_refs: Vec::new(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
 /// Numbered page including the page height, the lines to output, and
 /// accompanying footnotes
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Page {
     /// Page number
     ///
@@ -152,8 +375,26 @@ pub struct Scroll {
 pub enum Tag {
     /// Contact information is set aside for the writer.
     Contact,
+    /// A dedication is set on a page of its own, which is not
+    /// numbered.
+    Dedication,
+    /// Screenplay dialogue is split across pages with
+    /// <tt>(MORE)</tt> and <tt>(CONT'D)</tt> markers.
+    Dialogue,
     /// Head elements are marked but not extracted from the stream.
     Head,
+    /// The compositor notes the page on which each part, chapter,
+    /// section, act and scene heading falls, as well as the front and
+    /// back matter headings.
+    Heading,
+    /// Headings such as screenplay scene headings and stage play
+    /// speakers are kept with the block that follows.
+    KeepWithNext,
+    /// Table header rows are repeated by the compositor when a table
+    /// is split across pages.
+    TableHeader,
+    /// Table rows are kept together on a page.
+    TableRow,
     /// Table of contents elements are set aside by the compositor and
     /// formatted after the rest of the document is finished.
     ToC,
@@ -228,7 +469,7 @@ pub fn count_lines(blocks: &BlockList) -> usize {
 // generic elements
 
 /// Generic container element contains only other elements, no text
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ContainerElement<Attributes> {
     /// Parameter struct
     pub attributes: Attributes,
@@ -246,7 +487,7 @@ impl<Attributes> ContainerElement<Attributes> {
 }
 
 /// Generic empty element contains only attributes, no content
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EmptyElement<Attributes> {
     /// Parameter struct
     pub attributes: Attributes,
@@ -262,7 +503,7 @@ impl<Attributes> EmptyElement<Attributes> {
 
 /// Generic text element contains mixed content, and footnote elements
 /// are set aside
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TextElement<Attributes> {
     /// Parameter struct
     pub attributes: Attributes,
@@ -285,8 +526,10 @@ impl<Attributes> TextElement<Attributes> {
 // element type enum
 
 /// Element type enum for in-memory representation of XML elements
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ElementType {
+    Act        (TextElement     <Act        >),
+    Action     (TextElement     <Action     >),
     Attribution(TextElement     <Attribution>),
     Authors    (ContainerElement<Authors    >),
     Backmatter (ContainerElement<Backmatter >),
@@ -294,10 +537,19 @@ pub enum ElementType {
     Blockquote (ContainerElement<Blockquote >),
     Body       (ContainerElement<Body       >),
     Br         (EmptyElement    <Br         >),
+    CastList   (ContainerElement<CastList   >),
     Chapter    (TextElement     <Chapter    >),
+    Character  (TextElement     <Character  >),
     Contact    (TextElement     <Contact    >),
+    Dd         (ContainerElement<Dd         >),
+    Dedication (ContainerElement<Dedication >),
+    Dialogue   (ContainerElement<Dialogue   >),
+    Direction  (TextElement     <Direction  >),
     Div        (EmptyElement    <Div        >),
+    Dl         (ContainerElement<Dl         >),
+    Dt         (TextElement     <Dt         >),
     Em         (TextElement     <Em         >),
+    Epigraph   (ContainerElement<Epigraph   >),
     Footnote   (ContainerElement<Footnote   >),
     Frontmatter(ContainerElement<Frontmatter>),
     Gn         (TextElement     <Gn         >),
@@ -308,16 +560,27 @@ pub enum ElementType {
     Ol         (ContainerElement<Ol         >),
     P          (TextElement     <P          >),
     PageBreak  (EmptyElement    <PageBreak  >),
+    Parenthetical(TextElement   <Parenthetical>),
     Part       (TextElement     <Part       >),
     Person     (ContainerElement<Person     >),
+    Pre        (TextElement     <Pre        >),
     Prefix     (TextElement     <Prefix     >),
+    Scene      (TextElement     <Scene      >),
+    SceneHeading(TextElement    <SceneHeading>),
+    Screenplay (ContainerElement<Screenplay >),
     Section    (TextElement     <Section    >),
     Sn         (TextElement     <Sn         >),
+    Speaker    (TextElement     <Speaker    >),
+    Speech     (ContainerElement<Speech     >),
     Sub        (TextElement     <Sub        >),
     Subtitle   (TextElement     <Subtitle   >),
     Suffix     (TextElement     <Suffix     >),
     Sup        (TextElement     <Sup        >),
+    Table      (ContainerElement<Table      >),
+    Td         (TextElement     <Td         >),
     Title      (TextElement     <Title      >),
+    Tr         (ContainerElement<Tr         >),
+    Transition (TextElement     <Transition >),
     Ul         (ContainerElement<Ul         >),
 }
 
@@ -346,41 +609,70 @@ impl From<&str> for LineSpacing {
 // elements with no attributes
 
 /// The main body of the document
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Body {}
 
 /// Mandatory line break
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Br {}
 
+/// Screenplay character cue, including any extension such as
+/// <tt>(V.O.)</tt>
+#[derive(Debug, Clone)]
+pub struct Character {}
+
+/// Screenplay dialogue
+///
+/// A character cue followed by lines of dialogue, which may be
+/// interrupted by parentheticals.  When dialogue is split across
+/// pages, <tt>(MORE)</tt> is printed at the bottom of the first page
+/// and the cue is repeated with <tt>(CONT'D)</tt> at the top of the
+/// next.
+///
+/// # Examples
+///
+/// ```xml
+/// <dialogue>
+///   <character>STEEL</character>
+///   <parenthetical>(beat)</parenthetical>
+///   <p>I don't know what you mean.</p>
+/// </dialogue>
+/// ```
+#[derive(Debug, Clone)]
+pub struct Dialogue {}
+
 /// Scene divider
 ///
 /// Manuscript format dictates that a scene divider is a single
 /// <tt>#</tt> character (<tt>U+0023</tt>) centered on the page, with
 /// one blank line before and after.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Div {}
 
 /// Emphasis
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Em {}
 
 /// Given name.  Multiple given names are allowed, so middle names
 /// should use this element.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Gn {}
 
 /// Document header containing identifying information
 ///
 /// This is an element-only container holding [`Title`], [`Subtitle`],
 /// and [`Authors`] in sequence.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Head {}
 
 /// Mandatory page break
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PageBreak {}
 
+/// Screenplay parenthetical, an actor's direction within dialogue
+#[derive(Debug, Clone)]
+pub struct Parenthetical {}
+
 /// An element-only container holding personal name components
 ///
 /// # Examples
@@ -410,35 +702,140 @@ pub struct PageBreak {}
 /// 
 /// Output:
 /// <pre>Dr. Martin Luther King, Jr.<sup>*×</sup></pre>
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Person {}
 
 /// The prefix of a personal name, such as Mr., Ms., Dr., etc.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Prefix {}
 
+/// Screenplay scene heading, or slug line
+#[derive(Debug, Clone)]
+pub struct SceneHeading {}
+
+/// Screenplay root
+///
+/// A screenplay is laid out in the standard format for Courier 12.
+/// Scene headings and action start 1.5 inches from the edge of the
+/// page, dialogue 2.5 inches, parentheticals 3.1 inches and
+/// character cues 3.7 inches, and transitions are set flush right.
+/// If there is a head, it is set on an unnumbered title page.  Pages
+/// are numbered in the top right corner, starting from the second
+/// page.
+///
+/// # Examples
+///
+/// ```xml
+/// <screenplay>
+///   <sceneHeading>INT. BRICK'S PATIO - DAY</sceneHeading>
+///   <action>A gorgeous day.  The sun is shining.</action>
+///   <dialogue>
+///     <character>STEEL</character>
+///     <p>Beer's ready!</p>
+///   </dialogue>
+///   <transition>CUT TO:</transition>
+/// </screenplay>
+/// ```
+#[derive(Debug, Clone)]
+pub struct Screenplay {}
+
 /// Surname
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Sn {}
 
+/// Name of a character in a stage play, which is set in capitals
+#[derive(Debug, Clone)]
+pub struct Speaker {}
+
 /// Subscript
 ///
 /// Shifts a half a line down for the duration of the element's
 /// contents, just as you would do on a typewriter.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Sub {}
 
 /// Superscript
 ///
 /// Shifts a half a line up for the duration of the element's
 /// contents, just as you would do on a typewriter.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Sup {}
 
+/// Simple monospaced table
+///
+/// Column widths are computed in character cells from the contents
+/// of the cells, and the rules are drawn with ASCII characters.  If
+/// the table is too wide for the page, the text in the cells wraps.
+/// Header rows are repeated at the top of each page when a table is
+/// split across pages.
+///
+/// # Examples
+///
+/// ```xml
+/// <table>
+///   <tr header="true"><td>Year</td><td>Event</td></tr>
+///   <tr><td>1881</td><td>American Red Cross founded</td></tr>
+///   <tr><td>1889</td><td>Johnstown Flood</td></tr>
+/// </table>
+/// ```
+///
+/// Output:
+/// ```text
+/// +------+----------------------------+
+/// | Year | Event                      |
+/// +------+----------------------------+
+/// | 1881 | American Red Cross founded |
+/// +------+----------------------------+
+/// | 1889 | Johnstown Flood            |
+/// +------+----------------------------+
+/// ```
+#[derive(Debug, Clone)]
+pub struct Table {}
+
+/// Table cell
+#[derive(Debug, Clone)]
+pub struct Td {}
+
+/// Screenplay transition, such as <tt>CUT TO:</tt>
+#[derive(Debug, Clone)]
+pub struct Transition {}
+
 // elements with attributes
 
+/// Act title in a stage play
+///
+/// Acts are numbered with Roman numerals and start on a new page,
+/// like chapters.  The numbering of scenes starts over in each act.
+///
+/// # Examples
+///
+/// ```xml
+/// <act>The Castle</act>
+/// <scene>Elsinore.  A platform before the castle.</scene>
+/// ```
+#[derive(Debug, Clone)]
+pub struct Act {
+    /// Act number.  This attribute is set automatically, but may be
+    /// overriden using an XML attribute.
+    pub number: i32,
+    /// Defaults to <tt>single</tt>, but may be overridden by an XML
+    /// attribute
+    pub line_spacing: LineSpacing,
+    /// Subdivision depth.  This attribute is set during
+    /// post-processing by the document reader, once it is known how
+    /// many levels the document contains.
+    pub depth: i32,
+}
+
+/// Screenplay action, or scene description
+#[derive(Debug, Clone)]
+pub struct Action {
+    /// True if the lines are centered on the page
+    pub centered: bool,
+}
+
 /// Right-justified block for an attribution following a blockquote
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Attribution {
     /// Defaults to <tt>single</tt>, but may be overridden by an XML
     /// attribute
@@ -446,7 +843,7 @@ pub struct Attribution {
 }
 
 /// Sequence of authors
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Authors {
     /// Defaults to <tt>single</tt>, but may be overridden by an XML
     /// attribute
@@ -454,14 +851,14 @@ pub struct Authors {
 }
 
 /// Appendix, Epilogue, Postscript, Bibliography, etc.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Backmatter {
     /// Name of section
     pub label: String,
 }
 
 /// Paragraph with a hanging indent for bibliography references
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BibRef {
     /// Defaults to <tt>single</tt>, but may be overridden by an XML
     /// attribute
@@ -469,15 +866,37 @@ pub struct BibRef {
 }
 
 /// Paragraph with narrow margins for quotations
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Blockquote {
     /// Defaults to <tt>single</tt>, but may be overridden by an XML
     /// attribute
     pub line_spacing: LineSpacing,
 }
 
+/// Cast of characters in a stage play, set on a page of its own
+///
+/// Each speaker may be followed by a short description.  Kosik puts
+/// a comma between the name and the description, so there is no need
+/// to type one.  If the description starts with a punctuation mark of
+/// its own, such as a dash, the comma is left out.
+///
+/// # Examples
+///
+/// ```xml
+/// <castList>
+///   <speaker>Hamlet</speaker> Prince of Denmark
+///   <speaker>Horatio</speaker> friend to Hamlet
+/// </castList>
+/// ```
+#[derive(Debug, Clone)]
+pub struct CastList {
+    /// Defaults to <tt>single</tt>, but may be overridden by an XML
+    /// attribute
+    pub line_spacing: LineSpacing,
+}
+
 /// Chapter name
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Chapter {
     /// Chapter number.  This attribute is set automatically, but can
     /// be overriden using ab XML attribute.
@@ -509,15 +928,121 @@ pub struct Chapter {
 /// P.O. Box 90606
 /// Brooklyn, NY 11209
 /// </pre>
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Contact {
     /// Defaults to <tt>single</tt>, but may be overridden by an XML
     /// attribute
     pub line_spacing: LineSpacing,
 }
 
+/// Definition list description
+#[derive(Debug, Clone)]
+pub struct Dd {
+    /// Inherited from the parent definition list, but may be
+    /// overridden by an XML attribute
+    pub line_spacing: LineSpacing,
+    /// Left margin of the paragraphs in the description, which
+    /// depends on the nesting level
+    pub left_margin: usize,
+}
+
+/// Dedication
+///
+/// A dedication is centered horizontally and vertically on a page of
+/// its own.  The page is not numbered, and the text following the
+/// dedication starts on a new page.
+///
+/// # Examples
+///
+/// ```xml
+/// <dedication>To my mother</dedication>
+/// ```
+#[derive(Debug, Clone)]
+pub struct Dedication {
+    /// Defaults to <tt>single</tt>, but may be overridden by an XML
+    /// attribute
+    pub line_spacing: LineSpacing,
+}
+
+/// Stage direction
+///
+/// Stage directions are indented and enclosed in parentheses, which
+/// are added if they are not already there.  They may stand on their
+/// own, or interrupt a speech.
+#[derive(Debug, Clone)]
+pub struct Direction {
+    /// Defaults to <tt>single</tt>, but may be overridden by an XML
+    /// attribute
+    pub line_spacing: LineSpacing,
+}
+
+/// Definition list
+///
+/// Each term hangs at the left margin, and its description is
+/// indented below it.
+///
+/// # Examples
+///
+/// ```xml
+/// <dl>
+///   <dt>Ballast</dt>
+///   <dd>Heavy material placed low in a vessel to improve its
+///   stability</dd>
+///   <dt>Bilge</dt>
+///   <dd>The lowest compartment on a ship</dd>
+/// </dl>
+/// ```
+///
+/// Output:
+/// ```text
+/// Ballast
+///           Heavy material placed low in a vessel to improve its
+///           stability
+///
+/// Bilge
+///           The lowest compartment on a ship
+/// ```
+#[derive(Debug, Clone)]
+pub struct Dl {
+    /// Controls line spacing for the entire list, but may be
+    /// overridden by individual terms and descriptions
+    pub line_spacing: LineSpacing,
+}
+
+/// Definition list term
+#[derive(Debug, Clone)]
+pub struct Dt {
+    /// Inherited from the parent definition list, but may be
+    /// overridden by an XML attribute
+    pub line_spacing: LineSpacing,
+    /// Left margin of the term, which depends on the nesting level
+    pub left_margin: usize,
+}
+
+/// Epigraph
+///
+/// An epigraph is a short quotation set in a narrow block toward the
+/// right margin, usually at the start of the body, a part or a
+/// chapter.  An attribution inside the epigraph is right-justified.
+///
+/// # Examples
+///
+/// ```xml
+/// <epigraph>
+///   … But the Dwarf answered:  No; something human is dearer to me
+///   than the wealth of all the world.
+///   <attribution>—<em>Grimm's Tales</em></attribution>
+/// </epigraph>
+/// ```
+#[derive(Debug, Clone)]
+pub struct Epigraph {
+    /// Defaults to <tt>single</tt>, but may be overridden by an XML
+    /// attribute
+    pub line_spacing: LineSpacing,
+}
+
 /// Footnote
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Footnote {
     /// Footnote label defaults to automatic numbering, but man be
     /// overridden by an XML attribute.
@@ -529,25 +1054,31 @@ pub struct Footnote {
 }
 
 /// Forward, Introduction, Preface, etc.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Frontmatter {
     /// Name of section
     pub label: String,
 }
 
 /// List item
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Li {
     /// List item number.  Only used by ordered lists, <tt>None</tt>
     /// for unordered lists
     pub number: Option<i32>,
+    /// Nesting level among lists of the same kind, starting from 0,
+    /// which selects the list marker
+    pub level: usize,
     /// Defaults to <tt>single</tt>, but may be overridden by an XML
     /// attribute
     pub line_spacing: LineSpacing,
+    /// Left margin of the paragraphs in the item, which depends on
+    /// the nesting level
+    pub left_margin: usize,
 }
 
 /// Document root
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Manuscript {
     /// Sets the page number of the first numbered page (not including
     /// the title page, if any)
@@ -560,7 +1091,7 @@ pub struct Manuscript {
 }
 
 /// Note reference
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NoteRef {
     /// Note reference.  A symbol or character identifying the
     /// reference will appear in superscript mode
@@ -590,7 +1121,11 @@ pub struct NoteRef {
 ///
 ///   3. Countrymen
 /// ```
-#[derive(Debug)]
+///
+/// Lists may be nested inside list items.  The markers cycle through
+/// arabic numerals, lower-case letters and lower-case roman numerals
+/// (<tt>1.</tt>, <tt>a.</tt>, <tt>i.</tt>) as the lists get deeper.
+#[derive(Debug, Clone)]
 pub struct Ol {
     /// The list item sequence number is initialized to this value,
     /// but it may be overridden by individual list items
@@ -601,7 +1136,7 @@ pub struct Ol {
 }
 
 /// Paragraph
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct P {
     /// Indent default to five spaces
     pub indent: usize,
@@ -615,7 +1150,7 @@ pub struct P {
 }
 
 /// Level 0 subdivision
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Part {
     /// Part number.  This attribute is set automatically, but may be
     /// overriden using an XML attribute.
@@ -629,8 +1164,56 @@ pub struct Part {
     pub depth: i32,
 }
 
+/// Preformatted text
+///
+/// The contents of a <tt>pre</tt> element are not tokenized by the
+/// text parser.  Spaces are kept, quotes are not converted, and there
+/// is no extra space after a full stop.  Each source line becomes one
+/// line of output, and lines that are too long for the page are
+/// folded, with a backslash marking the fold.  Tabs are expanded, and
+/// the indentation common to all of the lines is removed, so the
+/// element can be indented along with the rest of the XML source.
+///
+/// Each word token holds one complete source line.
+///
+/// # Examples
+///
+/// ```xml
+/// <pre>
+///   fn main() {
+///       println!("Hello, world!");
+///   }
+/// </pre>
+/// ```
+#[derive(Debug, Clone)]
+pub struct Pre {
+    /// Defaults to <tt>single</tt>, but may be overridden by an XML
+    /// attribute
+    pub line_spacing: LineSpacing,
+}
+
+/// Scene title in a stage play
+///
+/// Scenes are numbered within each act.  A scene starts on a new
+/// page, unless it immediately follows an act title.
+#[derive(Debug, Clone)]
+pub struct Scene {
+    /// Scene number.  This attribute is set automatically, but may be
+    /// overriden using an XML attribute.
+    pub number: i32,
+    /// Defaults to <tt>single</tt>, but may be overridden by an XML
+    /// attribute
+    pub line_spacing: LineSpacing,
+    /// Number of blank lines preceding a scene header
+    pub padding_before: i32,
+    /// Subdivision depth.  This attribute is set during
+    /// post-processing by the document reader, once it is known how
+    /// many levels the document contains.
+    pub depth: i32,
+}
+
 /// Level 2 subdivision
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Section {
     /// Section number.  This attribute is set automatically, but may be
     /// overriden using an XML attribute.
@@ -646,8 +1229,30 @@ pub struct Section {
     pub depth: i32,
 }
 
+/// Speech in a stage play
+///
+/// The speaker's name is centered in capitals above the lines, which
+/// may be interrupted by stage directions.
+///
+/// # Examples
+///
+/// ```xml
+/// <speech>
+///   <speaker>Hamlet</speaker>
+///   <p>Angels and ministers of grace defend us!</p>
+///   <direction>Ghost beckons Hamlet.</direction>
+///   <p>It waves me still.</p>
+/// </speech>
+/// ```
+#[derive(Debug, Clone)]
+pub struct Speech {
+    /// Defaults to <tt>single</tt>, but may be overridden by an XML
+    /// attribute
+    pub line_spacing: LineSpacing,
+}
+
 /// Document subtitle
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Subtitle {
     /// Defaults to <tt>single</tt>, but may be overridden by an XML
     /// attribute
@@ -655,20 +1260,28 @@ pub struct Subtitle {
 }
 
 /// Personal name suffix, such as Jr. or III
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Suffix {
     /// True if a comma should proceed the suffix when printed
     pub comma: bool,
 }
 
 /// Document title
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Title {
     /// Defaults to <tt>single</tt>, but may be overridden by an XML
     /// attribute
     pub line_spacing: LineSpacing,
 }
 
+/// Table row
+#[derive(Debug, Clone)]
+pub struct Tr {
+    /// True if the row is a header row, which is repeated when the
+    /// table is split across pages
+    pub header: bool,
+}
+
 /// Unordered list
 ///
 /// # Examples
@@ -692,7 +1305,10 @@ pub struct Title {
 ///
 ///    * Countrymen
 /// ```
-#[derive(Debug)]
+///
+/// Lists may be nested inside list items.  The markers cycle through
+/// <tt>*</tt>, <tt>-</tt> and <tt>+</tt> as the lists get deeper.
+#[derive(Debug, Clone)]
 pub struct Ul {
     /// Defaults to <tt>single</tt>, but may be overridden by an XML
     /// attribute
@@ -783,6 +1399,7 @@ impl TextElement<Title> {
                         tokens.push(TokenType::Punct(Token {
                             data: PunctData {
                                 text: token.data.text.clone(),
+                                glyph: token.data.glyph,
                             },
                             dpy: Default::default(),
                             frm: token.frm,
@@ -831,6 +1448,7 @@ impl TextElement<Title> {
                 tokens.push(TokenType::Punct(Token {
                     data: PunctData {
                         text: ". . .".to_string(),
+                        glyph: Some('\u{2026}'),
                     },
                     dpy: Default::default(),
                     frm: FormatFlags::DLB,
@@ -967,6 +1585,38 @@ impl ContainerElement<Head> {
     }
 }
 
+impl ContainerElement<Screenplay> {
+    /// Navigates to the head element
+    pub fn head(&self) -> Option<&ContainerElement<Head>> {
+        for child in self.children.iter() {
+            match child {
+                ElementType::Head(elem) => {
+                    return Some(elem);
+                },
+                _ => {},
+            }
+        }
+
+        None
+    }
+
+    /// Navigates to the Title element and calls its short_title member
+    /// function.
+    pub fn short_title(&self) -> Option<Segment> {
+        self.head()
+            .and_then(|x| x.title())
+            .and_then(|x| x.short_title())
+    }
+
+    /// Navigates to the [`Authors`] element and calls its short_author_name
+    /// member function.
+    pub fn short_author_name(&self) -> Option<Segment> {
+        self.head()
+            .and_then(|x| x.authors())
+            .and_then(|x| x.short_author_name())
+    }
+}
+
 impl ContainerElement<Manuscript> {
     /// Navigates to the head element
     pub fn head(&self) -> Option<&ContainerElement<Head>> {
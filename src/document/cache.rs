@@ -0,0 +1,518 @@
+// Kosik Formatting Cache
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Keeps read elements and formatted blocks between runs, so that a
+//! manuscript that is being edited can be typeset again quickly
+//!
+//! A manuscript is cut into parts: the head, each frontmatter and
+//! backmatter division, and the body, which is cut again before each
+//! part, chapter or act heading.  The source of each part is found
+//! with a quick scan of the XML, and a part is only read again if its
+//! text, or the numbering of the notes and headings before it, has
+//! changed since the last run.  A part that is read again but comes
+//! out the same is not formatted again.  Pagination picks up from
+//! the first part that has changed, since nothing before it can move.
+//!
+//! A cache is meant for one manuscript typed with one set of project
+//! settings.  Only manuscripts are cached.  Screenplays, and anything
+//! that cannot be cut into parts, are read in full every time.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use kosik::config::Config;
+//! use kosik::document::cache::Cache;
+//! use kosik::document::ElementType;
+//!
+//! let text = std::fs::read_to_string("dummy.sik").unwrap();
+//! let mut cache = Cache::new();
+//!
+//! if let Some(ElementType::Manuscript(elem))
+//!     = cache.read(&text, &Config::default()).unwrap()
+//! {
+//!     let compositor = cache.run(elem);
+//!     println!("{} of {} parts read again, {} reformatted",
+//!              cache.reread, cache.parts, cache.reformatted);
+//! }
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+
+use quick_xml::events::Event;
+
+use crate::config::Config;
+use crate::document::*;
+use crate::document::compositor::Compositor;
+use crate::document::reader::{Numbering, Reader, Structure, SyntaxError};
+
+/// Read elements, formatted blocks and compositor states from the
+/// last run
+#[derive(Default)]
+pub struct Cache {
+    /// Number of parts in the manuscript on the last run
+    pub parts: usize,
+    /// Number of parts that had to be read on the last run
+    pub reread: usize,
+    /// Number of parts that had to be formatted on the last run
+    pub reformatted: usize,
+    // Elements read from each span of the source, by its key
+    spans: HashMap<u64, Span>,
+    // Keys of the parts of the manuscript that was last read, for the
+    // run that formats it
+    next_keys: Vec<u64>,
+    keys: Vec<u64>,
+    blocks: HashMap<u64, BlockList>,
+    // State of the compositor before each part, and after the last
+    snapshots: Vec<Compositor>,
+}
+
+// Elements read from one span of the source, with what the reader
+// carries on to the spans after it
+#[derive(Clone)]
+struct Span {
+    elements: ElementList,
+    numbering: Numbering,
+    structure: Structure,
+    word_count: usize,
+}
+
+// Where the parts of a manuscript are in its source
+struct Outline {
+    // Start tag of the manuscript
+    manuscript: Range<usize>,
+    children: Vec<Child>,
+}
+
+// Child element of a manuscript in the source
+enum Child {
+    // Whole element, such as the head or a frontmatter division
+    Element(Range<usize>),
+    // Start tag of the body, and its content cut before each part,
+    // chapter or act heading
+    Body(Range<usize>, Vec<Range<usize>>),
+}
+
+impl Cache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Read a document, reading again only the parts of a manuscript
+    /// whose source has changed since the last run
+    pub fn read(&mut self, text: &str, config: &Config)
+                -> Result<Option<ElementType>, SyntaxError>
+    {
+        self.next_keys.clear();
+
+        let outline = match outline(text) {
+            Some(outline) => outline,
+            None => return Reader::with_config(text, config).run(),
+        };
+
+        // Any error in the start tag is left to the full reader to
+        // report.
+        let tag = outline.manuscript.clone();
+        let shell = format!("{}</manuscript>", &text[tag.clone()]);
+
+        let mut root = match Reader::with_config(&shell, config).run() {
+            Ok(Some(ElementType::Manuscript(elem))) => elem,
+            _ => return Reader::with_config(text, config).run(),
+        };
+
+        let mut old_spans = std::mem::take(&mut self.spans);
+        let mut numbering = Numbering::default();
+        let mut structure = Structure::default();
+        let mut span_keys: Vec<u64> = Vec::new();
+        self.reread = 0;
+
+        for child in outline.children {
+            match child {
+                Child::Element(range) => {
+                    let (key, span, read) = self.span(
+                        &mut old_spans, text, &[tag.clone(), range],
+                        "</manuscript>", numbering, config
+                    )?;
+
+                    let part = span.elements.iter().any(|x| matches!(
+                        x, ElementType::Backmatter(_)
+                            | ElementType::Frontmatter(_)
+                            | ElementType::Head(_)
+                    ));
+
+                    if part {
+                        span_keys.push(key);
+                        self.reread += read as usize;
+                    }
+
+                    numbering = span.numbering;
+                    structure.add(span.structure);
+                    root.attributes.word_count += span.word_count;
+                    root.children.extend(span.elements);
+                },
+                Child::Body(body_tag, groups) => {
+                    let mut body = ContainerElement::new(Body {});
+
+                    for range in groups {
+                        let (key, mut span, read) = self.span(
+                            &mut old_spans, text,
+                            &[tag.clone(), body_tag.clone(), range],
+                            "</body></manuscript>", numbering, config
+                        )?;
+
+                        let elements = match span.elements.pop() {
+                            Some(ElementType::Body(elem)) => elem.children,
+                            _ => Vec::new(),
+                        };
+
+                        if !elements.is_empty() {
+                            span_keys.push(key);
+                            self.reread += read as usize;
+                        }
+
+                        numbering = span.numbering;
+                        structure.add(span.structure);
+                        root.attributes.word_count += span.word_count;
+                        body.children.extend(elements);
+                    }
+
+                    root.children.push(ElementType::Body(body));
+                },
+            }
+        }
+
+        structure.apply(&mut root);
+
+        // The heading depths are set from the whole manuscript, so
+        // they are folded into the key of every part.
+        self.next_keys = span_keys.into_iter().map(|key| {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            structure.hash(&mut hasher);
+            hasher.finish()
+        }).collect();
+
+        Ok(Some(ElementType::Manuscript(root)))
+    }
+
+    // Read the pieces of the source joined together, followed by the
+    // end tags that close them, or take the elements from the last run
+    // if neither the text nor the numbering it starts from has
+    // changed.  The key is returned with the elements, and whether
+    // they had to be read.
+    fn span(&mut self, old_spans: &mut HashMap<u64, Span>, text: &str,
+            pieces: &[Range<usize>], end_tags: &str, numbering: Numbering,
+            config: &Config) -> Result<(u64, Span, bool), SyntaxError>
+    {
+        let mut hasher = DefaultHasher::new();
+
+        for piece in pieces {
+            text[piece.clone()].hash(&mut hasher);
+        }
+
+        numbering.hash(&mut hasher);
+        let key = hasher.finish();
+
+        if let Some(span) = self.spans.get(&key) {
+            return Ok((key, span.clone(), false));
+        }
+
+        if let Some(span) = old_spans.remove(&key) {
+            self.spans.insert(key, span.clone());
+            return Ok((key, span, false));
+        }
+
+        let mut xml: String = pieces.iter()
+            .map(|piece| &text[piece.clone()])
+            .collect();
+        xml.push_str(end_tags);
+
+        let fragment = Reader::with_config(&xml, config)
+            .with_numbering(numbering)
+            .run_fragment()
+            .map_err(|mut error| {
+                error.position = locate(pieces, error.position);
+                error
+            })?;
+
+        let span = Span {
+            elements: match fragment.root {
+                Some(ElementType::Manuscript(elem)) => elem.children,
+                _ => Vec::new(),
+            },
+            numbering: fragment.numbering,
+            structure: fragment.structure,
+            word_count: fragment.word_count,
+        };
+
+        self.spans.insert(key, span.clone());
+        Ok((key, span, true))
+    }
+
+    /// Format and paginate a manuscript, reusing whatever has not
+    /// changed since the last run.  The manuscript should be the one
+    /// last returned by [`Cache::read`]; any other is formatted in
+    /// full.
+    pub fn run(&mut self, elem: ContainerElement<Manuscript>) -> Compositor {
+        let first_page = elem.attributes.first_page;
+        let has_structure = elem.attributes.has_structure;
+        let parts = split(elem);
+        let mut keys = std::mem::take(&mut self.next_keys);
+
+        if keys.len() != parts.len() {
+            let count = parts.len();
+            let mut compositor = Compositor::new(first_page, has_structure);
+
+            for part in parts {
+                for block in format(part) {
+                    compositor.push(block);
+                }
+            }
+
+            *self = Cache {
+                parts: count,
+                reread: self.reread,
+                reformatted: count,
+                ..Default::default()
+            };
+
+            compositor.finish();
+            return compositor;
+        }
+
+        // The page numbering is folded into the first key, so that a
+        // change to it paginates everything again.
+        if let Some(key) = keys.first_mut() {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            first_page.hash(&mut hasher);
+            has_structure.hash(&mut hasher);
+            *key = hasher.finish();
+        }
+
+        let changed = keys.iter()
+            .zip(self.keys.iter())
+            .take_while(|(key, old)| key == old)
+            .count();
+
+        let mut compositor = if changed > 0 {
+            self.snapshots[changed].clone()
+        } else {
+            Compositor::new(first_page, has_structure)
+        };
+
+        let mut old_blocks = std::mem::take(&mut self.blocks);
+        self.snapshots.truncate(changed);
+        self.parts = parts.len();
+        self.reformatted = 0;
+
+        for (i, (part, key)) in parts.into_iter().zip(keys.iter()).enumerate() {
+            let blocks = match old_blocks.remove(key) {
+                Some(blocks) => blocks,
+                None => {
+                    self.reformatted += 1;
+                    format(part)
+                },
+            };
+
+            if i >= changed {
+                self.snapshots.push(compositor.clone());
+
+                for block in blocks.iter() {
+                    compositor.push(block.clone());
+                }
+            }
+
+            self.blocks.insert(*key, blocks);
+        }
+
+        self.snapshots.push(compositor.clone());
+        self.keys = keys;
+
+        compositor.finish();
+        compositor
+    }
+}
+
+// Find the parts of a manuscript in its source.  Anything that is not
+// a well-formed manuscript is left to the full reader.
+fn outline(text: &str) -> Option<Outline> {
+    let mut xml_reader = quick_xml::Reader::from_str(text);
+    let mut manuscript: Option<Range<usize>> = None;
+    let mut children: Vec<Child> = Vec::new();
+    let mut depth = 0;
+    let mut in_body = false;
+    let mut start = 0;
+
+    loop {
+        let before = xml_reader.buffer_position();
+        let event = xml_reader.read_event().ok()?;
+        let after = xml_reader.buffer_position();
+
+        match event {
+            Event::Start(ref event) => {
+                let name = event.local_name().into_inner();
+
+                match depth {
+                    0 if manuscript.is_none() && name == b"manuscript" => {
+                        manuscript = Some(before..after);
+                    },
+                    0 => return None,
+                    1 if name == b"body" => {
+                        children.push(Child::Body(before..after, Vec::new()));
+                        in_body = true;
+                        start = after;
+                    },
+                    1 => start = before,
+                    2 if in_body && is_heading(name) => {
+                        cut(&mut children, start..before);
+                        start = before;
+                    },
+                    _ => {},
+                }
+
+                depth += 1;
+            },
+            Event::Empty(ref event) => {
+                let name = event.local_name().into_inner();
+
+                match depth {
+                    0 => return None,
+                    1 => children.push(Child::Element(before..after)),
+                    2 if in_body && is_heading(name) => {
+                        cut(&mut children, start..before);
+                        start = before;
+                    },
+                    _ => {},
+                }
+            },
+            Event::End(_) => {
+                depth -= 1;
+
+                match depth {
+                    1 if in_body => {
+                        cut(&mut children, start..before);
+                        in_body = false;
+                    },
+                    1 => children.push(Child::Element(start..after)),
+                    _ => {},
+                }
+            },
+            Event::Text(ref event)
+                if depth == 0 && !event.iter().all(|x| x.is_ascii_whitespace()) =>
+            {
+                return None;
+            },
+            Event::CData(_) if depth == 0 => return None,
+            Event::Eof => break,
+            _ => {},
+        }
+    }
+
+    Some(Outline {
+        manuscript: manuscript?,
+        children: children,
+    })
+}
+
+// Whether a body element starts a new part of the manuscript
+fn is_heading(name: &[u8]) -> bool {
+    matches!(name, b"act" | b"chapter" | b"part")
+}
+
+// End a span of the body that is being cut into parts.
+fn cut(children: &mut [Child], range: Range<usize>) {
+    if let Some(Child::Body(_, groups)) = children.last_mut() {
+        groups.push(range);
+    }
+}
+
+// Find the position in the source of a position in pieces of it that
+// were joined together.  Positions past the pieces fall at the end of
+// the last one.
+fn locate(pieces: &[Range<usize>], mut position: usize) -> usize {
+    for piece in pieces {
+        if position < piece.len() {
+            return piece.start + position;
+        }
+
+        position -= piece.len();
+    }
+
+    pieces.last().map_or(0, |piece| piece.end)
+}
+
+// Cut a manuscript into the parts that are cached separately.
+fn split(elem: ContainerElement<Manuscript>) -> ElementList {
+    let mut parts: ElementList = Vec::new();
+
+    for child in elem.children {
+        match child {
+            ElementType::Backmatter(_) => {
+                parts.push(child);
+            },
+            ElementType::Body(body) => {
+                let mut children: ElementList = Vec::new();
+
+                for child in body.children {
+                    let heading = matches!(child, ElementType::Act(_)
+                                           | ElementType::Chapter(_)
+                                           | ElementType::Part(_));
+
+                    if heading && !children.is_empty() {
+                        parts.push(ElementType::Body(ContainerElement {
+                            attributes: Body {},
+                            children: std::mem::take(&mut children),
+                        }));
+                    }
+
+                    children.push(child);
+                }
+
+                if !children.is_empty() {
+                    parts.push(ElementType::Body(ContainerElement {
+                        attributes: Body {},
+                        children: children,
+                    }));
+                }
+            },
+            ElementType::Frontmatter(_) => {
+                parts.push(child);
+            },
+            ElementType::Head(_) => {
+                parts.push(child);
+            },
+            _ => {},
+        }
+    }
+
+    parts
+}
+
+// Format one part of a manuscript.
+fn format(part: ElementType) -> BlockList {
+    match part {
+        ElementType::Backmatter(elem) => elem.into(),
+        ElementType::Body(elem) => elem.into(),
+        ElementType::Frontmatter(elem) => elem.into(),
+        ElementType::Head(elem) => elem.into(),
+        _ => Vec::new(),
+    }
+}
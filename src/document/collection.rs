@@ -0,0 +1,200 @@
+// Kosik Manuscript Collections
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Types several manuscripts one after another, as a collection or
+//! an anthology
+//!
+//! The collection has a title page of its own, made from the head of
+//! a manuscript with nothing else in it.  Each manuscript starts on a
+//! new page, and the page numbers run on from one to the next.  The
+//! table of contents lists the title and authors of each manuscript,
+//! in place of their own tables of contents, and the contact
+//! information in their heads is left out.  The slug line of each
+//! page names the manuscript the page belongs to.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use kosik::document::ElementType;
+//! use kosik::document::collection::Collection;
+//! use kosik::text::Segment;
+//!
+//! let head = kosik::read(&kosik::Arguments::from("collection.sik")).unwrap();
+//! let story = kosik::read(&kosik::Arguments::from("youth.sik")).unwrap();
+//!
+//! if let (ElementType::Manuscript(head), ElementType::Manuscript(story)) = (head, story) {
+//!     let mut collection = Collection::new(head);
+//!     let alone = collection.push(story, Segment::from("YOUTH"), Segment::from("CONRAD"));
+//!     assert!(!alone.pages.is_empty());
+//!
+//!     let (compositor, running_heads) = collection.finish();
+//!     assert_eq!(running_heads[0].first_page, 1);
+//! }
+//! ```
+
+use crate::document::*;
+use crate::document::compositor::Compositor;
+use crate::export::{person_name, typed_text};
+
+/// Compositor for a collection of manuscripts, with the slug line
+/// names of each of them
+pub struct Collection {
+    compositor: Compositor,
+    running_heads: Vec<RunningHead>,
+}
+
+impl Collection {
+    /// Start a collection, typing its title page from the head of a
+    /// manuscript.  Page numbering starts from the
+    /// <tt>firstPage</tt> attribute of that manuscript.
+    pub fn new(elem: ContainerElement<Manuscript>) -> Self {
+        let mut compositor = Compositor::new(elem.attributes.first_page, true);
+        let blocks: BlockList = elem.into();
+
+        for block in blocks.into_iter() {
+            compositor.push(block);
+        }
+
+        Self {
+            compositor: compositor,
+            running_heads: Vec::new(),
+        }
+    }
+
+    /// Add a manuscript to the collection, starting on a new page,
+    /// with the title and author name to print in its slug lines.
+    /// Returns the pages of the manuscript typed on its own, for
+    /// checking its footnotes and counting its words.
+    pub fn push(&mut self, elem: ContainerElement<Manuscript>,
+                short_title: Segment, short_author_name: Segment) -> Compositor
+    {
+        let first_page = elem.attributes.first_page;
+        let has_structure = elem.attributes.has_structure;
+        let entry = toc_entry(&elem);
+        let blocks: BlockList = elem.into();
+        let alone = Compositor::new(first_page, has_structure).run(blocks.clone());
+
+        let mut blocks = blocks.into_iter()
+            .filter(|block| !matches!(block.tag, Some(Tag::Contact) | Some(Tag::ToC)));
+
+        let mut first = match blocks.next() {
+            Some(block) => block,
+            None => return alone,
+        };
+
+        // A negative padding starts a new page, with one line less
+        // of padding than its magnitude.
+        if first.padding_before >= 0 {
+            first.padding_before = -first.padding_before - 1;
+        }
+
+        self.compositor.push(first);
+
+        // The entry takes the number of the page it is pushed on,
+        // which is the first page of the manuscript.
+        let first_page = self.compositor.pages.last().map_or(0, |page| page.number);
+        self.compositor.push(entry);
+
+        self.running_heads.push(RunningHead {
+            first_page: first_page,
+            short_title: short_title,
+            short_author_name: short_author_name,
+        });
+
+        for block in blocks {
+            self.compositor.push(block);
+        }
+
+        alone
+    }
+
+    /// Add the table of contents after the last manuscript, and
+    /// return the pages with the slug line names of the manuscripts
+    pub fn finish(mut self) -> (Compositor, Vec<RunningHead>) {
+        self.compositor.finish();
+        (self.compositor, self.running_heads)
+    }
+}
+
+/// Make the table of contents entry for a manuscript, with its title
+/// and authors, wrapped to leave room for the page number
+fn toc_entry(elem: &ContainerElement<Manuscript>) -> Block {
+    let head = elem.head();
+
+    let title = match head.and_then(|x| x.title()) {
+        Some(title) => typed_text(&title.tokens[..]),
+        None => "Working Title".to_string(),
+    };
+
+    let names: Vec<String> = match head.and_then(|x| x.authors()) {
+        Some(authors) => authors.children.iter()
+            .filter_map(|child| match child {
+                ElementType::Person(child) => Some(person_name(child, typed_text)),
+                _ => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let text = match names.split_last() {
+        Some((last, [])) => format!("{}, by {}", title, last),
+        Some((last, rest)) => format!("{}, by {} and {}", title, rest.join(", "), last),
+        None => title,
+    };
+
+    // Leave room for the dot leaders and the page number, and indent
+    // the lines after the first.
+    let line_length = RIGHT_MARGIN - LEFT_MARGIN - INDENT * 3;
+    let mut lines: Vec<Line> = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let width = if lines.is_empty() { line_length } else { line_length - INDENT };
+
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(Line::from(Segment::from(&current[..])));
+            current.clear();
+        }
+
+        if !current.is_empty() {
+            current.push(' ');
+        }
+
+        current.push_str(word);
+    }
+
+    lines.push(Line::from(Segment::from(&current[..])));
+
+    let spaces = " ".repeat(INDENT);
+
+    for (i, line) in lines.iter_mut().enumerate() {
+        line.column = LEFT_MARGIN;
+
+        if i > 0 {
+            line.segments.insert(0, Segment::from(&spaces[..]));
+        }
+    }
+
+    Block {
+        lines: lines,
+        footnotes: Vec::new(),
+        line_spacing: LineSpacing::Single,
+        padding_before: 0,
+        padding_after: 1,
+        tag: Some(Tag::ToC),
+    }
+}
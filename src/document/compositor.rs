@@ -36,22 +36,77 @@
 
 use std::cmp::max;
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::iter::repeat;
 
 use crate::document::*;
 
+/// A footnote or note reference that is not printed as written
+#[derive(Debug, Clone, PartialEq)]
+pub enum FootnoteError {
+    /// A footnote whose label is taken by an earlier footnote that
+    /// has not been printed yet.  The earlier footnote is lost.
+    Duplicate(String),
+    /// A note reference that appears only in titles, whose note
+    /// references are left out of the table of contents
+    TocOnly(String),
+    /// A note reference with no footnote
+    Undefined(String),
+    /// A footnote that is never referenced, and so never printed
+    Unreferenced(String),
+}
+
+impl fmt::Display for FootnoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FootnoteError::Duplicate(label) => {
+                write!(f, "Footnote {} replaces an earlier footnote with the same label", label)
+            },
+            FootnoteError::TocOnly(label) => {
+                write!(f, "Note reference {} appears only in the table of contents", label)
+            },
+            FootnoteError::Undefined(label) => {
+                write!(f, "Note reference {} has no footnote", label)
+            },
+            FootnoteError::Unreferenced(label) => {
+                write!(f, "Footnote {} is never referenced", label)
+            },
+        }
+    }
+}
+
+impl Error for FootnoteError {}
+
 /// Turns block lists into page lists
+#[derive(Clone)]
 pub struct Compositor {
     /// If there is contact information in the document, it is set
     /// aside so it can be passed to the document writer later.
     pub contact: Option<Block>,
     /// Page list to be output by the document writer
     pub pages: PageList,
+    /// Index of the page on which each heading falls, and whether the
+    /// heading starts the page
+    pub headings: Vec<(usize, bool)>,
+    /// Index of the first table of contents page, if there is one
+    pub toc_page: Option<usize>,
     footnotes: HashMap<String, BlockList>,
+    // Labels of the footnotes, of the note references in the text,
+    // and of the note references left out of the table of contents,
+    // in order
+    footnote_labels: Vec<String>,
+    note_labels: Vec<String>,
+    toc_labels: Vec<String>,
+    duplicates: Vec<String>,
+    toc: Vec<(i32, Block)>,
+    padding_before: i32,
     first_page: i32,
     next_page_no: i32,
     has_structure: bool,
     last_padding_after: usize,
+    page_break_pending: bool,
+    table_header: Vec<Line>,
 }
 
 impl Compositor {
@@ -67,19 +122,97 @@ impl Compositor {
         Self {
             contact: None,
             pages: Vec::new(),
+            headings: Vec::new(),
+            toc_page: None,
             footnotes: HashMap::new(),
+            footnote_labels: Vec::new(),
+            note_labels: Vec::new(),
+            toc_labels: Vec::new(),
+            duplicates: Vec::new(),
+            toc: Vec::new(),
+            padding_before: 0,
             first_page: first_page,
             next_page_no: -1,
             has_structure: has_structure,
             last_padding_after: 0,
+            page_break_pending: false,
+            table_header: Vec::new(),
         }
     }
 
     /// Flow a sequence of blocks into pages
     pub fn run(mut self, blocks: BlockList) -> Self {
-        let mut toc: Vec<(i32, Block)> = Vec::new();
-        
-        if self.pages.is_empty() { // first page
+        for block in blocks.into_iter() {
+            self.push(block);
+        }
+
+        self.finish();
+        self
+    }
+
+    /// Flow one block into the pages.  The compositor can be cloned
+    /// between blocks, to pick up again from the same place later.
+    pub fn push(&mut self, block: Block) {
+        self.begin();
+
+        let mut padding_before = self.padding_before;
+
+        if block.tag.is_some() {
+            match block.tag {
+                Some(Tag::Contact) => {
+                    self.contact = Some(block);
+                },
+                Some(Tag::Dedication) => {
+                    self.compose_dedication(block, &mut padding_before);
+                },
+                Some(Tag::Dialogue) => {
+                    self.compose_dialogue(block, &mut padding_before);
+                },
+                Some(Tag::Head) => {
+                    self.compose(block, &mut padding_before);
+                },
+                Some(Tag::Heading) => {
+                    let n = self.pages.len();
+                    self.compose(block, &mut padding_before);
+                    self.headings.push((self.pages.len() - 1, self.pages.len() > n));
+                },
+                Some(Tag::KeepWithNext) => {
+                    self.compose_keep_with_next(block, &mut padding_before);
+                },
+                Some(Tag::TableHeader) => {
+                    self.table_header = block.lines.clone();
+                    self.compose_table_row(block, &mut padding_before);
+                },
+                Some(Tag::TableRow) => {
+                    self.compose_table_row(block, &mut padding_before);
+                },
+                Some(Tag::ToC) => {
+                    let number = self.cur_page().number;
+                    self.toc.push((number, block));
+                },
+                None => (),
+            }
+        } else {
+            self.compose(block, &mut padding_before);
+        }
+
+        self.padding_before = padding_before;
+    }
+
+    /// Add the table of contents, after the last block
+    pub fn finish(&mut self) {
+        self.begin();
+
+        if !self.toc.is_empty() && self.toc_page.is_none() {
+            let toc = self.toc.clone();
+            self.toc_page = Some(self.pages.len());
+            self.compose_toc(toc);
+        }
+    }
+
+    // Start the first page.
+    fn begin(&mut self) {
+        if self.pages.is_empty() {
             if self.has_structure {
                 self.start_a_new_page();
                 self.next_page_no = self.first_page;
@@ -88,33 +221,89 @@ impl Compositor {
                 self.start_a_new_page();
             }
         }
+    }
 
-        let mut padding_before: i32 = 0;
-        
-        for block in blocks.into_iter() {
-            if block.tag.is_some() {
-                match block.tag {
-                    Some(Tag::Contact) => {
-                        self.contact = Some(block);
-                    },
-                    Some(Tag::Head) => {
-                        self.compose(block, &mut padding_before);
-                    },
-                    Some(Tag::ToC) => {
-                        toc.push((self.cur_page().number, block));
-                    },
-                    None => (),
-                }
-            } else {
-                self.compose(block, &mut padding_before);
+    /// List the entries in the table of contents, with the number of
+    /// the page on which each one starts
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kosik::document::compositor::Compositor;
+    /// use kosik::document::{Block, Tag};
+    /// use kosik::text::{Line, Segment};
+    ///
+    /// let mut block: Block = Default::default();
+    /// block.lines.push(Line::from(Segment::from("Chapter One")));
+    /// block.tag = Some(Tag::ToC);
+    ///
+    /// let compositor = Compositor::new(1, false).run(vec![block]);
+    ///
+    /// assert_eq!(compositor.contents(), vec![(1, "Chapter One".to_string())]);
+    /// ```
+    pub fn contents(&self) -> Vec<(i32, String)> {
+        self.toc.iter().map(|(page_no, block)| {
+            let text = block.lines.iter()
+                .map(|line| line.segments.iter().map(|x| &x.text[..]).collect::<String>())
+                .map(|text| text.trim().to_string())
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            (*page_no, text)
+        }).collect()
+    }
+
+    /// List the footnotes and note references that were not printed
+    /// as written.  Note references to footnotes that were printed
+    /// earlier are not counted as errors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kosik::document::compositor::{Compositor, FootnoteError};
+    /// use kosik::document::Block;
+    /// use kosik::text::{Line, Segment};
+    ///
+    /// let mut line = Line::from(Segment::from("foo"));
+    /// line.note_refs.push("x".to_string());
+    ///
+    /// let mut block: Block = Default::default();
+    /// block.lines.push(line);
+    ///
+    /// let compositor = Compositor::new(1, false).run(vec![block]);
+    ///
+    /// assert_eq!(compositor.footnote_errors(),
+    ///            vec![FootnoteError::Undefined("x".to_string())]);
+    /// ```
+    pub fn footnote_errors(&self) -> Vec<FootnoteError> {
+        let mut errors: Vec<FootnoteError> = self.duplicates.iter()
+            .map(|label| FootnoteError::Duplicate(label.clone()))
+            .collect();
+
+        let mut seen: Vec<&String> = Vec::new();
+
+        for label in self.note_labels.iter() {
+            if !seen.contains(&label) && !self.footnote_labels.contains(label) {
+                errors.push(FootnoteError::Undefined(label.clone()));
             }
+            seen.push(label);
         }
 
-        if !toc.is_empty() {
-            self.compose_toc(toc);
+        for label in self.toc_labels.iter() {
+            if !seen.contains(&label) {
+                errors.push(FootnoteError::TocOnly(label.clone()));
+            }
+            seen.push(label);
         }
 
-        self
+        for label in self.footnote_labels.iter() {
+            if !seen.contains(&label) && self.footnotes.contains_key(label) {
+                errors.push(FootnoteError::Unreferenced(label.clone()));
+            }
+            seen.push(label);
+        }
+
+        errors
     }
     
     /// Consume a block, adding it to the current page
@@ -124,12 +313,18 @@ impl Compositor {
             *padding_before = -block.padding_before - 1;
             self.last_padding_after = 0;
 
+        } else if self.page_break_pending {
+            self.start_a_new_page();
+            *padding_before = 0;
+            self.last_padding_after = 0;
+
         } else {
             *padding_before = block.padding_before;
         };
             
         let padding = max(*padding_before as usize, self.last_padding_after);
         self.last_padding_after = block.padding_after;
+        self.page_break_pending = false;
 
         for _ in 0..padding {
             self.cur_page().lines.push(None);
@@ -138,6 +333,161 @@ impl Compositor {
         self.compose_block(block);
     }
 
+    /// Consume a dedication block, placing it on an unnumbered page
+    /// of its own
+    fn compose_dedication(&mut self, block: Block, padding_before: &mut i32) {
+        let next_page_no = self.next_page_no;
+
+        self.next_page_no = 0;
+        self.compose(block, padding_before);
+        self.next_page_no = next_page_no;
+        self.page_break_pending = true;
+    }
+
+    /// Consume a screenplay dialogue block.  If it does not fit on
+    /// the current page, it is split between lines of speech, with
+    /// <tt>(MORE)</tt> at the bottom of the page and the character
+    /// cue repeated with <tt>(CONT'D)</tt> at the top of the next.
+    fn compose_dialogue(&mut self, mut block: Block, padding_before: &mut i32) {
+        // Start any pending page here, so that the split is measured
+        // against the page the block will actually go on.
+        if block.padding_before < 0 {
+            self.start_a_new_page();
+            block.padding_before = -block.padding_before - 1;
+            self.last_padding_after = 0;
+
+        } else if self.page_break_pending {
+            self.start_a_new_page();
+            block.padding_before = 0;
+            self.last_padding_after = 0;
+            self.page_break_pending = false;
+        }
+
+        let padding = max(block.padding_before as usize, self.last_padding_after);
+        let remainder = self.remainder(padding);
+        let n = block.lines.len();
+
+        if remainder >= n as i32 {
+            self.compose(block, padding_before);
+            return;
+        }
+
+        // Leave at least two lines of speech on either side of the
+        // split, and never end a page on a parenthetical.
+        let cue = block.lines.iter()
+            .take_while(|line| line.column == CHARACTER_MARGIN)
+            .count();
+        let mut split: Option<usize> = None;
+        let mut speech: usize = 0;
+
+        for k in cue..n.saturating_sub(2) {
+            if block.lines[k].column == DIALOGUE_MARGIN {
+                speech += 1;
+            }
+
+            if k as i32 + 2 > remainder {
+                break;
+            }
+
+            if speech >= 2 && block.lines[k].column == DIALOGUE_MARGIN {
+                split = Some(k + 1);
+            }
+        }
+
+        match split {
+            Some(k) => {
+                let rest = block.lines.split_off(k);
+                let mut cont = block.lines[..cue].to_vec();
+
+                if let Some(line) = cont.last_mut() {
+                    if !line.segments.iter().any(|x| x.text.contains("(CONT'D)")) {
+                        line.segments.push(Segment::from(" (CONT'D)"));
+                    }
+                }
+
+                cont.extend(rest.into_iter());
+
+                let mut more = Line::from(Segment::from("(MORE)"));
+                more.column = CHARACTER_MARGIN;
+                block.lines.push(more);
+
+                let padding_after = block.padding_after;
+                block.padding_after = 0;
+                self.compose(block, padding_before);
+                self.page_break_pending = true;
+
+                self.compose_dialogue(Block {
+                    lines: cont,
+                    footnotes: Vec::new(),
+                    line_spacing: LineSpacing::Single,
+                    padding_before: 0,
+                    padding_after: padding_after,
+                    tag: Some(Tag::Dialogue),
+                }, padding_before);
+            },
+            None => {
+                // Move the whole block to the next page, unless it
+                // is already at the top of one.
+                if self.cur_page().lines.is_empty() {
+                    self.compose(block, padding_before);
+                } else {
+                    self.page_break_pending = true;
+                    self.compose_dialogue(block, padding_before);
+                }
+            },
+        }
+    }
+
+    /// Consume a heading, moving it to the next page if there is no
+    /// room for at least two lines after it.
+    fn compose_keep_with_next(&mut self, block: Block, padding_before: &mut i32) {
+        if block.padding_before >= 0
+            && !self.page_break_pending
+            && !self.cur_page().lines.is_empty()
+        {
+            let padding = max(block.padding_before as usize,
+                              self.last_padding_after);
+            let needed = block.lines.len() + block.padding_after + 2;
+
+            if self.remainder(padding) < needed as i32 {
+                self.page_break_pending = true;
+            }
+        }
+
+        self.compose(block, padding_before);
+    }
+
+    /// Consume a table row, keeping it together on a page.  If the
+    /// row does not fit on the current page, the table header is
+    /// repeated at the top of the next one.
+    fn compose_table_row(&mut self, block: Block, padding_before: &mut i32) {
+        let padding = max(max(block.padding_before, 0) as usize,
+                          self.last_padding_after);
+        let remainder = self.remainder(padding);
+
+        if remainder < block.lines.len() as i32
+            && !self.cur_page().lines.is_empty()
+        {
+            self.page_break_pending = true;
+
+            if matches!(block.tag, Some(Tag::TableRow)) {
+                // Repeat the header, without its note references.
+                let mut header = Block {
+                    lines: self.table_header.clone(),
+                    ..Default::default()
+                };
+
+                for line in header.lines.iter_mut() {
+                    line.note_refs.clear();
+                }
+
+                self.compose(header, padding_before);
+            }
+        }
+
+        self.compose(block, padding_before);
+    }
+
     fn compose_toc(&mut self, blocks: Vec<(i32, Block)>) {
         let center = LEFT_MARGIN + (RIGHT_MARGIN - LEFT_MARGIN) / 2;
         let s = Segment::from("Table of Contents");
@@ -161,6 +511,12 @@ impl Compositor {
         }, &mut padding_before);
 
         for (page_no, mut block) in blocks.into_iter() {
+            // Set aside the labels of the note references that were
+            // left out of the entry.
+            for line in block.lines.iter_mut() {
+                self.toc_labels.append(&mut line.note_refs);
+            }
+
             if let Some(_) = block.lines.first() {
                 let line_length = RIGHT_MARGIN - LEFT_MARGIN + 1;
 
@@ -226,6 +582,21 @@ impl Compositor {
 	self.next_page_no += 1;
     }
 
+    /// Count the lines still free on the current page after the
+    /// given padding, allowing for the footer
+    fn remainder(&mut self, padding: usize) -> i32 {
+        let mut remainder = self.cur_page().height as i32
+            - self.cur_page().lines.len() as i32
+            - padding as i32
+            - 1;
+
+        if !self.cur_page().footer.is_empty() {
+            remainder -= self.cur_page().footer.len() as i32 + 2;
+        }
+
+        remainder
+    }
+
     fn cur_page(&mut self) -> &mut Page {
 	assert!(!self.pages.is_empty());
 	self.pages.iter_mut().last().unwrap()
@@ -234,7 +605,11 @@ impl Compositor {
     fn compose_block(&mut self, block: Block) {
         // Transfer footnotes to the hash map.
         for (label, footnote) in block.footnotes {
-            self.footnotes.insert(label, footnote);
+            self.footnote_labels.push(label.clone());
+
+            if self.footnotes.insert(label.clone(), footnote).is_some() {
+                self.duplicates.push(label);
+            }
         }
 
         let page_height = block.lines.len();
@@ -246,6 +621,8 @@ impl Compositor {
                 let mut j = 0;
         
                 for label in line.note_refs.iter() {
+                    self.note_labels.push(label.clone());
+
                     if j > 0 {
                         footer_height += 1;
                     }
@@ -44,6 +44,7 @@
 //! assert_eq!(block.lines.len(), 2);
 //! ```
 
+use std::cmp::max;
 use std::iter::repeat;
 
 use crate::document::*;
@@ -53,6 +54,9 @@ use crate::lut::ROMAN_NUMERALS;
 #[macro_use]
 mod macros;
 
+// List markers for each level of nesting
+pub(crate) const UNORDERED_MARKERS: [&str; 3] = ["*", "-", "+"];
+
 // container elements
 
 impl From<ContainerElement<Authors>> for Block {
@@ -123,7 +127,7 @@ impl From<ContainerElement<Backmatter>> for BlockList {
             line_spacing: LineSpacing::Single,
             padding_before: -1,
             padding_after: CHAPTER_SKIP,
-            tag: None,
+            tag: Some(Tag::Heading),
         });
 
         let toc_entry = format_toc_entry!(elem.attributes.label);
@@ -147,6 +151,10 @@ impl From<ContainerElement<Backmatter>> for BlockList {
                 ElementType::Div(child) => {
                     blocks.push(child.into());
                 },
+                ElementType::Dl(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
                 ElementType::Ol(child) => {
                     let child_blocks: BlockList = child.into();
                     blocks.extend(child_blocks.into_iter());
@@ -157,6 +165,13 @@ impl From<ContainerElement<Backmatter>> for BlockList {
                 ElementType::PageBreak(child) => {
                     blocks.push(child.into());
                 },
+                ElementType::Pre(child) => {
+                    blocks.push(child.into());
+                },
+                ElementType::Table(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
                 ElementType::Ul(child) => {
                     let child_blocks: BlockList = child.into();
                     blocks.extend(child_blocks.into_iter());
@@ -202,6 +217,10 @@ impl From<ContainerElement<Body>> for BlockList {
         
         for child in elem.children {
             match child {
+                ElementType::Act(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
                 ElementType::Attribution(child) => {
                     blocks.push(child.into());
                 },
@@ -212,13 +231,31 @@ impl From<ContainerElement<Body>> for BlockList {
                 ElementType::Br(child) => {
                     blocks.push(child.into());
                 },
+                ElementType::CastList(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
                 ElementType::Chapter(child) => {
                     let child_blocks: BlockList = child.into();
                     blocks.extend(child_blocks.into_iter());
                 },
+                ElementType::Dedication(child) => {
+                    blocks.push(child.into());
+                },
+                ElementType::Direction(child) => {
+                    blocks.push(child.into());
+                },
                 ElementType::Div(child) => {
                     blocks.push(child.into());
                 },
+                ElementType::Dl(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
+                ElementType::Epigraph(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
                 ElementType::Ol(child) => {
                     let child_blocks: BlockList = child.into();
                     blocks.extend(child_blocks.into_iter());
@@ -229,18 +266,269 @@ impl From<ContainerElement<Body>> for BlockList {
                 ElementType::PageBreak(child) => {
                     blocks.push(child.into());
                 },
+                ElementType::Pre(child) => {
+                    blocks.push(child.into());
+                },
                 ElementType::Part(child) => {
                     let child_blocks: BlockList = child.into();
                     blocks.extend(child_blocks.into_iter());
                 },
+                ElementType::Scene(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
                 ElementType::Section(child) => {
                     let child_blocks: BlockList = child.into();
                     blocks.extend(child_blocks.into_iter());
                 },
+                ElementType::Speech(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
+                ElementType::Table(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
+                ElementType::Ul(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
+                _ => {},
+            }
+        }
+
+        blocks
+    }
+}
+
+impl From<ContainerElement<CastList>> for BlockList {
+    fn from(elem: ContainerElement<CastList>) -> BlockList {
+        let line_length = RIGHT_MARGIN - LEFT_MARGIN + 1;
+        let center =  LEFT_MARGIN + (RIGHT_MARGIN - LEFT_MARGIN) / 2;
+        let label = "Cast of Characters";
+        let mut headline = Line::from(Segment::from(label));
+        let n = headline.length();
+        headline.column = center - n / 2 - n % 2;
+
+        let mut blocks: BlockList = Vec::with_capacity(elem.children.len() + 2);
+
+        blocks.push(Block {
+            lines: vec![headline],
+            footnotes: Vec::new(),
+            line_spacing: LineSpacing::Single,
+            padding_before: -1,
+            padding_after: CHAPTER_SKIP,
+            tag: None,
+        });
+
+        let toc_entry = format_toc_entry!(label);
+        blocks.push(toc_entry);
+
+        let mut children = elem.children.into_iter().peekable();
+
+        // Each speaker is followed by an optional description, set as
+        // one entry with a hanging indent.
+        while let Some(child) = children.next() {
+            match child {
+                ElementType::Speaker(child) => {
+                    let mut tokens = uppercase(child.tokens);
+                    let mut footnotes = child.footnotes;
+
+                    if let Some(ElementType::P(_)) = children.peek() {
+                        if let Some(ElementType::P(description))
+                            = children.next()
+                        {
+                            let starts_with_punct = matches!(
+                                description.tokens.first(),
+                                Some(TokenType::Punct(_))
+                            );
+
+                            if !starts_with_punct {
+                                tokens.push(TokenType::Punct(Token::from(",")));
+                            }
+
+                            if !description.tokens.is_empty() {
+                                if !starts_with_punct {
+                                    tokens.push(TokenType::Space(Token::from(1)));
+                                }
+
+                                tokens.extend(description.tokens.into_iter());
+                            }
+
+                            footnotes.extend(description.footnotes.into_iter());
+                        }
+                    }
+
+                    let mut lines = text::linebreak_hang(&tokens[..], line_length);
+
+                    for line in lines.iter_mut() {
+                        line.column = LEFT_MARGIN;
+                    }
+
+                    blocks.push(Block {
+                        lines: lines,
+                        footnotes: format_footnotes(footnotes),
+                        line_spacing: elem.attributes.line_spacing,
+                        padding_before: 0,
+                        padding_after: 1,
+                        tag: None,
+                    });
+                },
+                ElementType::P(child) => {
+                    blocks.push(child.into());
+                },
+                _ => {},
+            }
+        }
+
+        blocks
+    }
+}
+
+impl From<ContainerElement<Dedication>> for Block {
+    fn from(elem: ContainerElement<Dedication>) -> Self {
+        let line_length = RIGHT_MARGIN - LEFT_MARGIN - 4 * INDENT + 1;
+        let center = LEFT_MARGIN + (RIGHT_MARGIN - LEFT_MARGIN) / 2;
+        let mut lines: Vec<Line> = Vec::new();
+        let mut footnotes: Vec<(String, BlockList)> = Vec::new();
+
+        for child in elem.children {
+            match child {
+                ElementType::P(child) => {
+                    // Separate paragraphs with a blank line.
+                    if !lines.is_empty() {
+                        lines.push(Line {
+                            column: center,
+                            segments: vec![Segment::from("")],
+                            note_refs: Vec::new(),
+                        });
+                    }
+
+                    let mut p_lines = text::linebreak_balance(&child.tokens[..],
+                                                              line_length);
+
+                    for line in p_lines.iter_mut() {
+                        let n = line.length();
+                        line.column = center - n / 2 - n % 2;
+                    }
+
+                    lines.extend(p_lines.into_iter());
+                    footnotes.extend(format_footnotes(child.footnotes).into_iter());
+                },
+                _ => {},
+            }
+        }
+
+        let mut block = Block {
+            lines: lines,
+            footnotes: footnotes,
+            line_spacing: elem.attributes.line_spacing,
+            padding_before: 0,
+            padding_after: 0,
+            tag: Some(Tag::Dedication),
+        };
+
+        // Center the block vertically on a new page.
+        let height = block.count_lines();
+        block.padding_before = -((MIDDLE_LINE - height / 2 - height % 2 + 1) as i32);
+        block
+    }
+}
+
+impl From<ContainerElement<Dd>> for BlockList {
+    fn from(elem: ContainerElement<Dd>) -> Self {
+        let p_count = elem.children.len();
+        let mut blocks: BlockList = Vec::with_capacity(p_count);
+        let nested: Vec<bool> = elem.children.iter()
+            .map(|child| is_list(child))
+            .collect();
+
+        for (i, child) in elem.children.into_iter().enumerate() {
+            match child {
+                ElementType::Dl(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
+                ElementType::Ol(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
+                ElementType::P(mut child) => {
+                    if i == 0 { // first paragraph
+                        child.attributes.indent = 0;
+                    }
+
+                    let mut block: Block = child.into();
+
+                    // last paragraph, or followed by a nested list
+                    if i == p_count - 1 || nested.get(i + 1) == Some(&true) {
+                        block.padding_after = 1;
+                    }
+
+                    blocks.push(block);
+                },
+                ElementType::PageBreak(child) => {
+                    blocks.push(child.into());
+                },
                 ElementType::Ul(child) => {
                     let child_blocks: BlockList = child.into();
                     blocks.extend(child_blocks.into_iter());
                 },
+                _ => (),
+            }
+        }
+
+        blocks
+    }
+}
+
+impl From<ContainerElement<Dialogue>> for Block {
+    fn from(elem: ContainerElement<Dialogue>) -> Self {
+        let mut lines: Vec<Line> = Vec::new();
+        let mut footnotes: Vec<(String, BlockList)> = Vec::new();
+
+        // The cue, parentheticals and speech are run together into one
+        // block so the compositor can split it with (MORE) and
+        // (CONT'D).
+        for child in elem.children {
+            let block: Block = match child {
+                ElementType::Character(child) => child.into(),
+                ElementType::P(child) => child.into(),
+                ElementType::Parenthetical(child) => child.into(),
+                _ => continue,
+            };
+
+            lines.extend(block.lines.into_iter());
+            footnotes.extend(block.footnotes.into_iter());
+        }
+
+        Block {
+            lines: lines,
+            footnotes: footnotes,
+            line_spacing: LineSpacing::Single,
+            padding_before: 1,
+            padding_after: 1,
+            tag: Some(Tag::Dialogue),
+        }
+    }
+}
+
+impl From<ContainerElement<Dl>> for BlockList {
+    fn from(elem: ContainerElement<Dl>) -> BlockList {
+        let mut blocks: BlockList = Vec::with_capacity(elem.children.len());
+            
+        for child in elem.children {
+            match child {
+                ElementType::Dd(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
+                ElementType::Dt(child) => {
+                    blocks.push(child.into());
+                },
+                ElementType::PageBreak(child) => {
+                    blocks.push(child.into());
+                },
                 _ => {},
             }
         }
@@ -249,6 +537,31 @@ impl From<ContainerElement<Body>> for BlockList {
     }
 }
 
+impl From<ContainerElement<Epigraph>> for BlockList {
+    fn from(elem: ContainerElement<Epigraph>) -> Self {
+        let mut blocks: BlockList = Vec::with_capacity(elem.children.len());
+
+        for child in elem.children {
+            match child {
+                ElementType::Attribution(child) => {
+                    blocks.push(child.into());
+                },
+                ElementType::P(child) => {
+                    blocks.push(child.into());
+                },
+                _ => (),
+            }
+        }
+
+        // Leave some room between the epigraph and the text.
+        if let Some(block) = blocks.last_mut() {
+            block.padding_after = 2;
+        }
+        
+        blocks
+    }
+}
+
 impl From<ContainerElement<Frontmatter>> for BlockList {
     fn from(elem: ContainerElement<Frontmatter>) -> BlockList {
         let center =  LEFT_MARGIN + (RIGHT_MARGIN - LEFT_MARGIN) / 2;
@@ -264,7 +577,7 @@ impl From<ContainerElement<Frontmatter>> for BlockList {
             line_spacing: LineSpacing::Single,
             padding_before: -1,
             padding_after: CHAPTER_SKIP,
-            tag: None,
+            tag: Some(Tag::Heading),
         });
 
         let toc_entry = format_toc_entry!(elem.attributes.label);
@@ -285,6 +598,10 @@ impl From<ContainerElement<Frontmatter>> for BlockList {
                 ElementType::Div(child) => {
                     blocks.push(child.into());
                 },
+                ElementType::Dl(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
                 ElementType::Ol(child) => {
                     let child_blocks: BlockList = child.into();
                     blocks.extend(child_blocks.into_iter());
@@ -295,6 +612,13 @@ impl From<ContainerElement<Frontmatter>> for BlockList {
                 ElementType::PageBreak(child) => {
                     blocks.push(child.into());
                 },
+                ElementType::Pre(child) => {
+                    blocks.push(child.into());
+                },
+                ElementType::Table(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
                 ElementType::Ul(child) => {
                     let child_blocks: BlockList = child.into();
                     blocks.extend(child_blocks.into_iter());
@@ -382,37 +706,47 @@ impl From<ContainerElement<Li>> for BlockList {
     fn from(elem: ContainerElement<Li>) -> Self {
         let p_count = elem.children.len();
         let mut blocks: BlockList = Vec::with_capacity(p_count);
-        let prefix: String;
-        
-        if let Some(n) = elem.attributes.number { // ordered
-            let indent = repeat(' ').take(INDENT).collect::<String>();
-            let label = format!("{}", n);
-            let w = label.chars().count();
-            let n = max(INDENT - w - 2, 0);
-
-            if n > 0 {
-                let pad = repeat(' ').take(n).collect::<String>();
-                prefix = format!("{}{}. {}", indent, label, pad);
-            } else {
-                prefix = format!("{}{}. ", indent, label);
-            }
-            
-        } else { // unordered
-            let indent = repeat(' ').take(INDENT).collect::<String>();
-            let n = max(INDENT - 2, 0);
 
-            if n > 0 {
-                let pad = repeat(' ').take(n).collect::<String>();
-                prefix = format!("{}* {}", indent, pad);
-            } else {
-                prefix = format!("{}* ", indent);
-            }
-        }
+        let marker = match elem.attributes.number {
+            Some(n) => format!("{}.", ordered_label(n, elem.attributes.level)),
+            None => String::from(UNORDERED_MARKERS[elem.attributes.level % 3]),
+        };
 
+        let indent = repeat(' ').take(INDENT).collect::<String>();
+        let n = INDENT.saturating_sub(marker.chars().count() + 1);
+        let pad = repeat(' ').take(n).collect::<String>();
+        let prefix = format!("{}{} {}", indent, marker, pad);
         let indent = repeat(' ').take(INDENT * 2).collect::<String>();
+        let column = elem.attributes.left_margin - INDENT * 2;
+
+        // Paragraphs followed by nested lists are separated from them
+        // by a blank line, like the items themselves.
+        let nested: Vec<bool> = elem.children.iter()
+            .map(|child| is_list(child))
+            .collect();
 
         for (i, child) in elem.children.into_iter().enumerate() {
             match child {
+                ElementType::Dl(_) | ElementType::Ol(_) | ElementType::Ul(_) => {
+                    if i == 0 { // no text before the nested list
+                        let mut line = Line::from(Segment::from(&prefix[..]));
+                        line.column = column;
+
+                        blocks.push(Block {
+                            lines: vec![line],
+                            padding_after: 1,
+                            ..Default::default()
+                        });
+                    }
+
+                    let child_blocks: BlockList = match child {
+                        ElementType::Dl(child) => child.into(),
+                        ElementType::Ol(child) => child.into(),
+                        ElementType::Ul(child) => child.into(),
+                        _ => Vec::new(),
+                    };
+                    blocks.extend(child_blocks.into_iter());
+                },
                 ElementType::P(mut child) => {
                     if i == 0 { // first paragraph
                         child.attributes.indent = 0;
@@ -421,7 +755,7 @@ impl From<ContainerElement<Li>> for BlockList {
                     let mut block: Block = child.into();
                     
                     for (j, line) in block.lines.iter_mut().enumerate() {
-                        line.column -= INDENT * 2;
+                        line.column = column;
 
                         if i == 0 && j == 0 {
                             line.segments.insert(0, Segment::from(&prefix[..]));
@@ -430,7 +764,8 @@ impl From<ContainerElement<Li>> for BlockList {
                         }
                     }
 
-                    if i == p_count - 1 { // last paragraph
+                    // last paragraph, or followed by a nested list
+                    if i == p_count - 1 || nested.get(i + 1) == Some(&true) {
                         block.padding_after = 1;
                     }
 
@@ -592,46 +927,291 @@ impl From<ContainerElement<Person>> for (TokenList, ElementList) {
     }
 }
 
-impl From<ContainerElement<Ul>> for BlockList {
-    fn from(elem: ContainerElement<Ul>) -> BlockList {
+impl From<ContainerElement<Screenplay>> for BlockList {
+    fn from(elem: ContainerElement<Screenplay>) -> Self {
         let mut blocks: BlockList = Vec::with_capacity(elem.children.len());
-            
+        let mut first_page: Option<usize> = None;
+
         for child in elem.children {
             match child {
-                ElementType::Li(child) => {
-                    let child_blocks: BlockList = child.into();
-                    blocks.extend(child_blocks.into_iter());
+                ElementType::Action(child) => {
+                    blocks.push(child.into());
+                },
+                ElementType::Dialogue(child) => {
+                    blocks.push(child.into());
+                },
+                ElementType::Head(child) => {
+                    blocks.extend(format_title_page(child).into_iter());
+                    first_page = Some(blocks.len());
                 },
                 ElementType::PageBreak(child) => {
                     blocks.push(child.into());
                 },
+                ElementType::SceneHeading(child) => {
+                    blocks.push(child.into());
+                },
+                ElementType::Transition(child) => {
+                    blocks.push(child.into());
+                },
                 _ => {},
             }
         }
 
+        // The script itself starts on the page after the title page.
+        if let Some(i) = first_page {
+            if let Some(block) = blocks.get_mut(i) {
+                block.padding_before = -1;
+            }
+        }
+
         blocks
     }
 }
 
-// text elements
-
-impl From<TextElement<Attribution>> for Block {
-    fn from(elem: TextElement<Attribution>) -> Self {
-        let tokens = elem.tokens;
-        let line_length = RIGHT_MARGIN - LEFT_MARGIN - 4 * INDENT + 1;
-        let mut lines = text::linebreak_balance(&tokens[..], line_length);
-
-        for line in lines.iter_mut() {
-            line.column = RIGHT_MARGIN - line.length();
-        }
+impl From<ContainerElement<Speech>> for BlockList {
+    fn from(elem: ContainerElement<Speech>) -> Self {
+        let mut blocks: BlockList = Vec::with_capacity(elem.children.len());
 
-        Block {
-            lines: lines,
-            footnotes: format_footnotes(elem.footnotes),
-            line_spacing: elem.attributes.line_spacing,
-            padding_before: 1,
-            padding_after: 1,
-            tag: None,
+        for child in elem.children {
+            match child {
+                ElementType::Direction(child) => {
+                    let mut block: Block = child.into();
+                    block.padding_before = 0;
+                    block.padding_after = 0;
+                    blocks.push(block);
+                },
+                ElementType::P(child) => {
+                    blocks.push(child.into());
+                },
+                ElementType::Speaker(child) => {
+                    blocks.push(child.into());
+                },
+                _ => {},
+            }
+        }
+
+        if let Some(block) = blocks.last_mut() {
+            block.padding_after = 1;
+        }
+
+        blocks
+    }
+}
+
+impl From<ContainerElement<Table>> for BlockList {
+    fn from(elem: ContainerElement<Table>) -> Self {
+        let rows: Vec<ContainerElement<Tr>> = elem.children.into_iter()
+            .filter_map(|child| match child {
+                ElementType::Tr(row) => Some(row),
+                _ => None,
+            })
+            .collect();
+
+        if rows.is_empty() {
+            return Vec::new();
+        }
+
+        // Measure the columns.
+        let n_cols = rows.iter()
+            .map(|row| row.children.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let mut min_widths: Vec<usize> = vec![1; n_cols];
+        let mut max_widths: Vec<usize> = vec![1; n_cols];
+
+        for row in rows.iter() {
+            for (i, child) in row.children.iter().enumerate() {
+                if let ElementType::Td(cell) = child {
+                    let (min_width, max_width) = measure_cell(&cell.tokens[..]);
+                    min_widths[i] = max(min_widths[i], min_width);
+                    max_widths[i] = max(max_widths[i], max_width);
+                }
+            }
+        }
+
+        // Each column takes three characters for its left rule and
+        // padding, and the table takes one more for its right rule.
+        let available = (RIGHT_MARGIN - LEFT_MARGIN + 1)
+            .saturating_sub(3 * n_cols + 1);
+        let mut widths = max_widths.clone();
+
+        if max_widths.iter().sum::<usize>() > available {
+            let min_total: usize = min_widths.iter().sum();
+            let slack_total: usize = max_widths.iter()
+                .zip(min_widths.iter())
+                .map(|(max_width, min_width)| max_width - min_width)
+                .sum();
+            let extra = available.saturating_sub(min_total);
+
+            for i in 0..n_cols {
+                let slack = max_widths[i] - min_widths[i];
+                widths[i] = min_widths[i] + extra * slack / max(slack_total, 1);
+            }
+        }
+
+        let rule = table_rule(&widths[..]);
+        let mut blocks: BlockList = Vec::with_capacity(rows.len() + 1);
+        let mut header = Block {
+            lines: vec![rule.clone()],
+            padding_before: 1,
+            tag: Some(Tag::TableHeader),
+            ..Default::default()
+        };
+        let mut in_header = true;
+
+        for row in rows {
+            in_header = in_header && row.attributes.header;
+
+            let (mut lines, footnotes) = format_table_row(row, &widths[..]);
+            lines.push(rule.clone());
+
+            if in_header {
+                header.lines.extend(lines.into_iter());
+                header.footnotes.extend(footnotes.into_iter());
+            } else {
+                blocks.push(Block {
+                    lines: lines,
+                    footnotes: footnotes,
+                    tag: Some(Tag::TableRow),
+                    ..Default::default()
+                });
+            }
+        }
+
+        blocks.insert(0, header);
+
+        if let Some(block) = blocks.last_mut() {
+            block.padding_after = 1;
+        }
+
+        blocks
+    }
+}
+
+impl From<ContainerElement<Ul>> for BlockList {
+    fn from(elem: ContainerElement<Ul>) -> BlockList {
+        let mut blocks: BlockList = Vec::with_capacity(elem.children.len());
+            
+        for child in elem.children {
+            match child {
+                ElementType::Li(child) => {
+                    let child_blocks: BlockList = child.into();
+                    blocks.extend(child_blocks.into_iter());
+                },
+                ElementType::PageBreak(child) => {
+                    blocks.push(child.into());
+                },
+                _ => {},
+            }
+        }
+
+        blocks
+    }
+}
+
+// text elements
+
+impl From<TextElement<Act>> for BlockList {
+    fn from(elem: TextElement<Act>) -> Self {
+        let line_length = RIGHT_MARGIN - LEFT_MARGIN - 4 * INDENT + 1;
+        let center =  LEFT_MARGIN + (RIGHT_MARGIN - LEFT_MARGIN) / 2;
+        let tag;
+
+        if let Some(roman_numeral) =
+            ROMAN_NUMERALS.numeral(elem.attributes.number as usize)
+        {
+            tag = format!("{}", roman_numeral);
+        } else {
+            tag = format!("{}", elem.attributes.number);
+        }
+
+        let headtext = format!("Act {}", &tag);
+        let mut headline = Line::from(Segment::from(headtext));
+        let n = headline.length();
+        headline.column = center - n / 2 - n % 2;
+
+        let mut blocks = vec![Block {
+            lines: vec![headline],
+            footnotes: Vec::new(),
+            line_spacing: LineSpacing::Single,
+            padding_before: -1,
+            padding_after: if !elem.tokens.is_empty() {
+                2
+            } else {
+                CHAPTER_SKIP
+            },
+            tag: Some(Tag::Heading),
+        }];
+
+        if !elem.tokens.is_empty() {
+            let mut lines = text::linebreak_balance(&elem.tokens[..], line_length);
+
+            for line in lines.iter_mut() {
+                let n = line.length();
+                line.column = center - n / 2 - n % 2;
+            }
+
+            blocks.push(Block {
+                lines: lines,
+                footnotes: format_footnotes(elem.footnotes),
+                line_spacing: elem.attributes.line_spacing,
+                padding_before: 0,
+                padding_after: CHAPTER_SKIP,
+                tag: None,
+            });
+
+            let toc_entry = format_toc_entry!(elem, tag);
+            blocks.push(toc_entry);
+        }
+
+        blocks
+    }
+}
+
+impl From<TextElement<Action>> for Block {
+    fn from(elem: TextElement<Action>) -> Self {
+        let line_length = RIGHT_MARGIN - SCREENPLAY_MARGIN + 1;
+        let mut lines = text::linebreak_fill(&elem.tokens[..], line_length);
+        let center = SCREENPLAY_MARGIN + (RIGHT_MARGIN - SCREENPLAY_MARGIN) / 2;
+
+        for line in lines.iter_mut() {
+            if elem.attributes.centered {
+                let n = line.length();
+                line.column = center - n / 2 - n % 2;
+            } else {
+                line.column = SCREENPLAY_MARGIN;
+            }
+        }
+
+        Block {
+            lines: lines,
+            footnotes: format_footnotes(elem.footnotes),
+            line_spacing: LineSpacing::Single,
+            padding_before: 1,
+            padding_after: 1,
+            tag: None,
+        }
+    }
+}
+
+impl From<TextElement<Attribution>> for Block {
+    fn from(elem: TextElement<Attribution>) -> Self {
+        let tokens = elem.tokens;
+        let line_length = RIGHT_MARGIN - LEFT_MARGIN - 4 * INDENT + 1;
+        let mut lines = text::linebreak_balance(&tokens[..], line_length);
+
+        for line in lines.iter_mut() {
+            line.column = RIGHT_MARGIN - line.length();
+        }
+
+        Block {
+            lines: lines,
+            footnotes: format_footnotes(elem.footnotes),
+            line_spacing: elem.attributes.line_spacing,
+            padding_before: 1,
+            padding_after: 1,
+            tag: None,
         }
     }
 }
@@ -677,7 +1257,7 @@ impl From<TextElement<Chapter>> for BlockList {
             } else {
                 CHAPTER_SKIP
             },
-            tag: None,
+            tag: Some(Tag::Heading),
         }];
 
         if !elem.tokens.is_empty() {
@@ -705,6 +1285,26 @@ impl From<TextElement<Chapter>> for BlockList {
     }
 }
 
+impl From<TextElement<Character>> for Block {
+    fn from(elem: TextElement<Character>) -> Self {
+        let line_length = RIGHT_MARGIN - CHARACTER_MARGIN + 1;
+        let mut lines = text::linebreak_fill(&elem.tokens[..], line_length);
+
+        for line in lines.iter_mut() {
+            line.column = CHARACTER_MARGIN;
+        }
+
+        Block {
+            lines: lines,
+            footnotes: format_footnotes(elem.footnotes),
+            line_spacing: LineSpacing::Single,
+            padding_before: 0,
+            padding_after: 0,
+            tag: None,
+        }
+    }
+}
+
 impl From<TextElement<Contact>> for Block {
     fn from(elem: TextElement<Contact>) -> Self {
         let line_length = (RIGHT_MARGIN - LEFT_MARGIN) / 2 + 1;
@@ -725,6 +1325,77 @@ impl From<TextElement<Contact>> for Block {
     }
 }
 
+impl From<TextElement<Direction>> for Block {
+    fn from(elem: TextElement<Direction>) -> Self {
+        let mut tokens = elem.tokens;
+
+        // Stage directions are parenthesized unless the author has
+        // already done so.
+        let parenthesized = matches!(
+            tokens.first(),
+            Some(TokenType::Open(token)) if token.data.text == "("
+        );
+
+        if !parenthesized && !tokens.is_empty() {
+            tokens.insert(0, TokenType::Open(Token {
+                data: OpenData {
+                    text: "(".to_string(),
+                },
+                dpy: Default::default(),
+                frm: Default::default(),
+            }));
+
+            tokens.push(TokenType::Close(Token {
+                data: CloseData {
+                    text: ")".to_string(),
+                },
+                dpy: Default::default(),
+                frm: Default::default(),
+            }));
+        }
+
+        let line_length = RIGHT_MARGIN - DIRECTION_MARGIN + 1;
+        let mut lines = text::linebreak_fill(&tokens[..], line_length);
+
+        for line in lines.iter_mut() {
+            line.column = DIRECTION_MARGIN;
+        }
+
+        Block {
+            lines: lines,
+            footnotes: format_footnotes(elem.footnotes),
+            line_spacing: elem.attributes.line_spacing,
+            padding_before: 1,
+            padding_after: 1,
+            tag: None,
+        }
+    }
+}
+
+impl From<TextElement<Dt>> for Block {
+    fn from(elem: TextElement<Dt>) -> Self {
+        let line_length = RIGHT_MARGIN - elem.attributes.left_margin + 1;
+        let mut lines = text::linebreak_fill(&elem.tokens[..], line_length);
+
+        for line in lines.iter_mut() {
+            line.column = elem.attributes.left_margin;
+        }
+
+        Block {
+            lines: lines,
+            footnotes: format_footnotes(elem.footnotes),
+            line_spacing: elem.attributes.line_spacing,
+            padding_before: 0,
+            padding_after: if elem.attributes.line_spacing == LineSpacing::Double {
+                1
+            } else {
+                0
+            },
+            tag: None,
+        }
+    }
+}
+
 impl From<TextElement<Em>> for Block {
     fn from(elem: TextElement<Em>) -> Self {
         let line_length = RIGHT_MARGIN - LEFT_MARGIN + 1;
@@ -796,6 +1467,26 @@ impl From<TextElement<P>> for Block {
     }
 }
 
+impl From<TextElement<Parenthetical>> for Block {
+    fn from(elem: TextElement<Parenthetical>) -> Self {
+        let line_length = PARENTHETICAL_RIGHT_MARGIN - PARENTHETICAL_MARGIN + 1;
+        let mut lines = text::linebreak_fill(&elem.tokens[..], line_length);
+
+        for line in lines.iter_mut() {
+            line.column = PARENTHETICAL_MARGIN;
+        }
+
+        Block {
+            lines: lines,
+            footnotes: format_footnotes(elem.footnotes),
+            line_spacing: LineSpacing::Single,
+            padding_before: 0,
+            padding_after: 0,
+            tag: None,
+        }
+    }
+}
+
 impl From<TextElement<Part>> for BlockList {
     fn from(elem: TextElement<Part>) -> Self {
         let line_length = RIGHT_MARGIN - LEFT_MARGIN - 4 * INDENT + 1;
@@ -852,13 +1543,56 @@ impl From<TextElement<Part>> for BlockList {
             } else {
                 PART_SKIP
             },
-            tag: None,
+            tag: Some(Tag::Heading),
         });
 
         blocks
     }
 }
 
+impl From<TextElement<Pre>> for Block {
+    fn from(elem: TextElement<Pre>) -> Self {
+        let line_length = RIGHT_MARGIN - LEFT_MARGIN + 1;
+        let mut lines: Vec<Line> = Vec::with_capacity(elem.tokens.len());
+
+        for token in elem.tokens.iter() {
+            match token {
+                TokenType::Word(token) => {
+                    let chars = token.data.text.chars().collect::<Vec<char>>();
+                    let mut i: usize = 0;
+
+                    // Fold the line, leaving room for the continuation
+                    // marker.
+                    while chars.len() - i > line_length {
+                        let j = i + line_length - 1;
+                        let text = chars[i..j].iter().collect::<String>();
+                        
+                        let mut line = Line::from(Segment::from(text + "\\"));
+                        line.column = LEFT_MARGIN;
+                        lines.push(line);
+                        i = j;
+                    }
+
+                    let text = chars[i..].iter().collect::<String>();
+                    let mut line = Line::from(Segment::from(text));
+                    line.column = LEFT_MARGIN;
+                    lines.push(line);
+                },
+                _ => {},
+            }
+        }
+
+        Block {
+            lines: lines,
+            footnotes: Vec::new(),
+            line_spacing: elem.attributes.line_spacing,
+            padding_before: 1,
+            padding_after: 1,
+            tag: None,
+        }
+    }
+}
+
 impl From<TextElement<Prefix>> for Block {
     fn from(elem: TextElement<Prefix>) -> Self {
         let line_length = RIGHT_MARGIN - LEFT_MARGIN + 1;
@@ -879,6 +1613,75 @@ impl From<TextElement<Prefix>> for Block {
     }
 }
 
+impl From<TextElement<Scene>> for BlockList {
+    fn from(elem: TextElement<Scene>) -> Self {
+        let line_length = RIGHT_MARGIN - LEFT_MARGIN - 4 * INDENT + 1;
+        let center =  LEFT_MARGIN + (RIGHT_MARGIN - LEFT_MARGIN) / 2;
+        let tag = format!("{}", elem.attributes.number);
+        let headtext = format!("Scene {}", &tag);
+
+        let mut headline = Line::from(Segment::from(headtext));
+        let n = headline.length();
+        headline.column = center - n / 2 - n % 2;
+
+        let mut blocks = vec![Block {
+            lines: vec![headline],
+            footnotes: Vec::new(),
+            line_spacing: LineSpacing::Single,
+            padding_before: elem.attributes.padding_before,
+            padding_after: if !elem.tokens.is_empty() {
+                2
+            } else {
+                SECTION_SKIP
+            },
+            tag: Some(Tag::Heading),
+        }];
+
+        if !elem.tokens.is_empty() {
+            let mut lines = text::linebreak_balance(&elem.tokens[..], line_length);
+
+            for line in lines.iter_mut() {
+                let n = line.length();
+                line.column = center - n / 2 - n % 2;
+            }
+
+            blocks.push(Block {
+                lines: lines,
+                footnotes: format_footnotes(elem.footnotes),
+                line_spacing: elem.attributes.line_spacing,
+                padding_before: 1,
+                padding_after: SECTION_SKIP,
+                tag: None,
+            });
+
+            let toc_entry = format_toc_entry!(elem, tag);
+            blocks.push(toc_entry);
+        }
+
+        blocks
+    }
+}
+
+impl From<TextElement<SceneHeading>> for Block {
+    fn from(elem: TextElement<SceneHeading>) -> Self {
+        let line_length = RIGHT_MARGIN - SCREENPLAY_MARGIN + 1;
+        let mut lines = text::linebreak_fill(&elem.tokens[..], line_length);
+
+        for line in lines.iter_mut() {
+            line.column = SCREENPLAY_MARGIN;
+        }
+
+        Block {
+            lines: lines,
+            footnotes: format_footnotes(elem.footnotes),
+            line_spacing: LineSpacing::Single,
+            padding_before: 1,
+            padding_after: 1,
+            tag: Some(Tag::KeepWithNext),
+        }
+    }
+}
+
 impl From<TextElement<Section>> for BlockList {
     fn from(elem: TextElement<Section>) -> Self {
         let line_length = RIGHT_MARGIN - LEFT_MARGIN - 4 * INDENT + 1;
@@ -906,7 +1709,7 @@ impl From<TextElement<Section>> for BlockList {
             } else {
                 SECTION_SKIP
             },
-            tag: None,
+            tag: Some(Tag::Heading),
         }];
 
         if !elem.tokens.is_empty() {
@@ -954,6 +1757,29 @@ impl From<TextElement<Sn>> for Block {
     }
 }
 
+impl From<TextElement<Speaker>> for Block {
+    fn from(elem: TextElement<Speaker>) -> Self {
+        let line_length = RIGHT_MARGIN - LEFT_MARGIN - 4 * INDENT + 1;
+        let center =  LEFT_MARGIN + (RIGHT_MARGIN - LEFT_MARGIN) / 2;
+        let tokens = uppercase(elem.tokens);
+        let mut lines = text::linebreak_balance(&tokens[..], line_length);
+
+        for line in lines.iter_mut() {
+            let n = line.length();
+            line.column = center - n / 2 - n % 2;
+        }
+
+        Block {
+            lines: lines,
+            footnotes: format_footnotes(elem.footnotes),
+            line_spacing: LineSpacing::Single,
+            padding_before: 1,
+            padding_after: 0,
+            tag: Some(Tag::KeepWithNext),
+        }
+    }
+}
+
 impl From<TextElement<Sub>> for Block {
     fn from(elem: TextElement<Sub>) -> Self {
         let line_length = RIGHT_MARGIN - LEFT_MARGIN + 1;
@@ -1065,6 +1891,26 @@ impl From<TextElement<Title>> for Block {
     }
 }
 
+impl From<TextElement<Transition>> for Block {
+    fn from(elem: TextElement<Transition>) -> Self {
+        let line_length = RIGHT_MARGIN - CHARACTER_MARGIN + 1;
+        let mut lines = text::linebreak_fill(&elem.tokens[..], line_length);
+
+        for line in lines.iter_mut() {
+            line.column = RIGHT_MARGIN - line.length();
+        }
+
+        Block {
+            lines: lines,
+            footnotes: format_footnotes(elem.footnotes),
+            line_spacing: LineSpacing::Single,
+            padding_before: 1,
+            padding_after: 1,
+            tag: None,
+        }
+    }
+}
+
 // empty elements
 
 impl From<EmptyElement<Br>> for Block {
@@ -1075,6 +1921,7 @@ impl From<EmptyElement<Br>> for Block {
                 segments: vec![Segment {
                     text: "".to_string(),
                     ps: "() show ".to_string(),
+                    dpy: Default::default(),
                 }],
                 note_refs: Vec::new(),
             }],
@@ -1097,6 +1944,7 @@ impl From<EmptyElement<Div>> for Block {
                 segments: vec![Segment {
                     text: "#".to_string(),
                     ps: "(#) show ".to_string(),
+                    dpy: Default::default(),
                 }],
                 note_refs: Vec::new(),
             }],
@@ -1142,6 +1990,222 @@ impl From<EmptyElement<PageBreak>> for Block {
 // functions
 
 #[doc(hidden)]
+// Tells whether an element is a list that may be nested inside a
+// list item or a description.
+fn is_list(elem: &ElementType) -> bool {
+    matches!(elem, ElementType::Dl(_) | ElementType::Ol(_) | ElementType::Ul(_))
+}
+
+// Formats a list item number for the nesting level of its list:
+// 1, 2, 3 at the top, then a, b, c, then i, ii, iii.
+// Convert the words in a speaker name to uppercase.
+fn uppercase(tokens: TokenList) -> TokenList {
+    tokens.into_iter().map(|token| match token {
+        TokenType::Word(mut token) => {
+            token.data.text = token.data.text.to_uppercase();
+            TokenType::Word(token)
+        },
+        token => token,
+    }).collect()
+}
+
+pub(crate) fn ordered_label(n: i32, level: usize) -> String {
+    match level % 3 {
+        1 if n > 0 => {
+            let mut label = String::new();
+            let mut k = n as u32;
+
+            while k > 0 {
+                k -= 1;
+                label.insert(0, char::from(b'a' + (k % 26) as u8));
+                k /= 26;
+            }
+
+            label
+        },
+        2 if n > 0 => {
+            match ROMAN_NUMERALS.numeral(n as usize) {
+                Some(numeral) => numeral.to_lowercase(),
+                None => format!("{}", n),
+            }
+        },
+        _ => format!("{}", n),
+    }
+}
+
+// Returns the minimum and maximum widths of a table cell: the
+// longest unbreakable run of text and the longest line.
+fn measure_cell(tokens: &[TokenType]) -> (usize, usize) {
+    let mut min_width: usize = 0;
+    let mut max_width: usize = 0;
+    let mut run: usize = 0;
+    let mut line: usize = 0;
+
+    for token in tokens {
+        let frm = token.format_flags();
+
+        if frm.intersects(FormatFlags::MLB) {
+            min_width = max(min_width, run);
+            max_width = max(max_width, line);
+            run = 0;
+            line = 0;
+
+        } else if frm.intersects(FormatFlags::DLB) {
+            if !frm.intersects(FormatFlags::DOB) {
+                run += token.length();
+            }
+
+            min_width = max(min_width, run);
+            line += token.length();
+            run = 0;
+
+        } else {
+            run += token.length();
+            line += token.length();
+        }
+    }
+
+    (max(min_width, run), max(max_width, line))
+}
+
+// Draws a horizontal rule across a table.
+fn table_rule(widths: &[usize]) -> Line {
+    let mut rule = String::from("+");
+
+    for width in widths {
+        rule.push_str(&repeat('-').take(width + 2).collect::<String>());
+        rule.push('+');
+    }
+
+    let mut line = Line::from(Segment::from(rule));
+    line.column = LEFT_MARGIN;
+    line
+}
+
+// Wraps the text in each cell of a table row, and lays the cells
+// side by side between vertical rules.
+fn format_table_row(row: ContainerElement<Tr>, widths: &[usize])
+                    -> (Vec<Line>, Vec<(String, BlockList)>)
+{
+    let mut columns: Vec<Vec<Line>> = Vec::with_capacity(widths.len());
+    let mut footnotes: Vec<(String, BlockList)> = Vec::new();
+
+    for (i, child) in row.children.into_iter().enumerate() {
+        if let ElementType::Td(cell) = child {
+            if i < widths.len() {
+                columns.push(text::linebreak_fill(&cell.tokens[..], widths[i]));
+            }
+
+            footnotes.extend(format_footnotes(cell.footnotes).into_iter());
+        }
+    }
+
+    let height = columns.iter().map(|lines| lines.len()).max().unwrap_or(0).max(1);
+    let mut lines: Vec<Line> = Vec::with_capacity(height);
+
+    for j in 0..height {
+        let mut line = Line::from(Segment::from("| "));
+        line.column = LEFT_MARGIN;
+
+        for (i, width) in widths.iter().enumerate() {
+            let mut n: usize = 0;
+
+            if let Some(cell_line) = columns.get_mut(i).and_then(|x| x.get_mut(j)) {
+                n = cell_line.length();
+                line.segments.append(&mut cell_line.segments);
+                line.note_refs.append(&mut cell_line.note_refs);
+            }
+
+            let padding = repeat(' ').take(width.saturating_sub(n))
+                .collect::<String>();
+
+            if i < widths.len() - 1 {
+                line.segments.push(Segment::from(format!("{} | ", padding)));
+            } else {
+                line.segments.push(Segment::from(format!("{} |", padding)));
+            }
+        }
+
+        lines.push(line);
+    }
+
+    (lines, footnotes)
+}
+
+// Formats the head of a screenplay as a title page, with the title a
+// third of the way down and the credit and authors' names below it.
+// The contact information is set aside by the compositor.
+fn format_title_page(elem: ContainerElement<Head>) -> BlockList {
+    let line_length = RIGHT_MARGIN - LEFT_MARGIN - 4 * INDENT + 1;
+    let center = LEFT_MARGIN + (RIGHT_MARGIN - LEFT_MARGIN) / 2;
+    let mut blocks: BlockList = Vec::with_capacity(elem.children.len() + 1);
+
+    for child in elem.children {
+        match child {
+            ElementType::Authors(child) => {
+                let mut tokens: TokenList = Vec::new();
+                let mut footnotes: ElementList = Vec::new();
+
+                for person in child.children {
+                    if let ElementType::Person(person) = person {
+                        if !tokens.is_empty() {
+                            tokens.push(TokenType::Space(Token::from(1)));
+                            tokens.push(TokenType::Symbol(Token::from("&")));
+                            tokens.push(TokenType::Space(Token::from(1)));
+                        }
+
+                        let (person_tokens, person_footnotes) = person.into();
+                        tokens.extend(person_tokens.into_iter());
+                        footnotes.extend(person_footnotes.into_iter());
+                    }
+                }
+
+                let mut credit = Line::from(Segment::from("Written by"));
+                let n = credit.length();
+                credit.column = center - n / 2 - n % 2;
+
+                blocks.push(Block {
+                    lines: vec![credit],
+                    padding_before: 2,
+                    padding_after: 1,
+                    tag: Some(Tag::Head),
+                    ..Default::default()
+                });
+
+                let mut lines = text::linebreak_balance(&tokens[..], line_length);
+
+                for line in lines.iter_mut() {
+                    let n = line.length();
+                    line.column = center - n / 2 - n % 2;
+                }
+
+                blocks.push(Block {
+                    lines: lines,
+                    footnotes: format_footnotes(footnotes),
+                    line_spacing: child.attributes.line_spacing,
+                    padding_before: 0,
+                    padding_after: 0,
+                    tag: Some(Tag::Head),
+                });
+            },
+            ElementType::Contact(child) => {
+                blocks.push(child.into());
+            },
+            ElementType::Subtitle(child) => {
+                blocks.push(child.into());
+            },
+            ElementType::Title(child) => {
+                let mut block: Block = child.into();
+                block.padding_before = (TOP_LINE - BOTTOM_LINE) as i32 / 3;
+                blocks.push(block);
+            },
+            _ => {},
+        }
+    }
+
+    blocks
+}
+
 fn format_footnotes(elements: ElementList) -> Vec<(String, BlockList)> {
     let mut footnotes: Vec<(String, BlockList)> = Vec::with_capacity(elements.len());
         
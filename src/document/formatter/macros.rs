@@ -36,6 +36,15 @@ macro_rules! format_toc_entry {
             
             let line_length = RIGHT_MARGIN - LEFT_MARGIN - INDENT * 2 - indent;
             let mut lines = text::linebreak_fill(&tokens[..], line_length);
+
+            // Keep the labels, so the compositor can tell whether the
+            // notes are referenced anywhere else.
+            if let Some(line) = lines.first_mut() {
+                line.note_refs = $elem.tokens.iter().filter_map(|t| match t {
+                    TokenType::NoteRef(t) => Some(t.data.text.clone()),
+                    _ => None,
+                }).collect();
+            }
             let spaces = repeat(' ').take(indent).collect::<String>();
 
             for (i, line) in lines.iter_mut().enumerate() {
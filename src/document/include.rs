@@ -0,0 +1,335 @@
+// Kosik Include Resolver
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Stitches a manuscript together from separate files
+//!
+//! A manuscript can be kept in one file per chapter, and assembled
+//! with <tt>xi:include</tt> elements, or with Kosik's own
+//! <tt>include</tt> element, before it is read.  The <tt>href</tt>
+//! attribute is resolved relative to the including file.  Includes
+//! may be nested, but an include cycle is an error.  If the
+//! <tt>parse</tt> attribute is set to <tt>text</tt>, the file is
+//! included as character data instead of markup.
+//!
+//! Each file is decoded in the character encoding named in its own
+//! XML declaration, or as UTF-8 if it names none.  A file that starts
+//! with a byte order mark for UTF-16 is decoded as UTF-16.  Included
+//! files may start with their own XML declarations, which are
+//! dropped.  The [`Source`] keeps track of where each line of the
+//! expanded text came from, so errors can be reported against the
+//! original files.
+//!
+//! Using <tt>xi:include</tt> lets you validate the assembled
+//! manuscript with <tt>xmllint --xinclude</tt>.
+//!
+//! # Examples
+//!
+//! ```xml
+//! <manuscript xmlns:xi="http://www.w3.org/2001/XInclude">
+//!   <head>...</head>
+//!   <body>
+//!     <xi:include href="chapters/one.sik"/>
+//!     <xi:include href="chapters/two.sik"/>
+//!   </body>
+//! </manuscript>
+//! ```
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use encoding::{DecoderTrap, EncodingRef};
+use encoding::all::{UTF_16BE, UTF_16LE};
+use encoding::label::encoding_from_whatwg_label;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    // Comments and CDATA sections are matched so that they can be
+    // skipped.  Only the last alternative is an include element.
+    #[doc(hidden)]
+    static ref INCLUDE: Regex = Regex::new(
+        r"(?s)<!--.*?-->|<!\[CDATA\[.*?\]\]>|<(?:[A-Za-z_][\w.-]*:)?include\b([^>]*?)(?:/>|>\s*</(?:[A-Za-z_][\w.-]*:)?include\s*>)"
+    ).unwrap();
+
+    #[doc(hidden)]
+    static ref HREF: Regex = Regex::new(
+        r#"\bhref\s*=\s*(?:"([^"]*)"|'([^']*)')"#
+    ).unwrap();
+
+    #[doc(hidden)]
+    static ref PARSE: Regex = Regex::new(
+        r#"\bparse\s*=\s*(?:"([^"]*)"|'([^']*)')"#
+    ).unwrap();
+
+    #[doc(hidden)]
+    static ref ENCODING: regex::bytes::Regex = regex::bytes::Regex::new(
+        r#"^(?-u:\xEF\xBB\xBF)?\s*<\?xml[^>]*?\bencoding\s*=\s*(?:"([^"]*)"|'([^']*)')"#
+    ).unwrap();
+
+    #[doc(hidden)]
+    static ref PROLOG: Regex = Regex::new(
+        r"^\u{feff}?\s*(?:<\?xml[^>]*\?>\s*)?(?:<!DOCTYPE[^>]*>\s*)?"
+    ).unwrap();
+}
+
+// A run of lines in the expanded text that comes from one file
+#[derive(Debug)]
+struct Span {
+    start: usize,
+    path: PathBuf,
+    line: usize,
+}
+
+/// Document text with all of its includes expanded
+#[derive(Debug)]
+pub struct Source {
+    /// The expanded text
+    pub text: String,
+    spans: Vec<Span>,
+    files: Vec<PathBuf>,
+    line: usize,
+}
+
+impl Source {
+    /// Wrap text that came from a single file, such as the output of
+    /// an importer.
+    pub fn new(path: &Path, text: String) -> Source {
+        let line = text.matches('\n').count() + 1;
+
+        Source {
+            text: text,
+            spans: vec![Span {
+                start: 1,
+                path: path.to_path_buf(),
+                line: 1,
+            }],
+            files: vec![path.to_path_buf()],
+            line: line,
+        }
+    }
+
+    /// Read a file and expand its includes recursively.
+    pub fn open(path: &Path) -> Result<Source, Box<dyn Error>> {
+        let bytes = fs::read(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Source::from_bytes(path, bytes)
+    }
+
+    /// Decode the contents of a file, which may have been read from
+    /// somewhere else, such as the standard input, and expand its
+    /// includes recursively.  Includes are resolved relative to the
+    /// directory of the path.
+    pub fn from_bytes(path: &Path, bytes: Vec<u8>) -> Result<Source, Box<dyn Error>> {
+        let mut source = Source {
+            text: String::new(),
+            spans: Vec::new(),
+            files: Vec::new(),
+            line: 1,
+        };
+
+        let mut ancestors: Vec<PathBuf> = Vec::new();
+        let text = decode(path, bytes)?;
+
+        source.expand(path, &text, &mut ancestors)?;
+        Ok(source)
+    }
+
+    /// Map a byte offset in the expanded text back to the file and
+    /// line it came from.
+    pub fn locate(&self, offset: usize) -> (&Path, usize) {
+        let mut offset = offset.min(self.text.len());
+
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let line = self.text[..offset].matches('\n').count() + 1;
+
+        match self.spans.iter().rev().find(|span| span.start <= line) {
+            Some(span) => (&span.path, span.line + line - span.start),
+            None => (Path::new(""), line),
+        }
+    }
+
+    /// List every file that went into the text: the one that was
+    /// opened, followed by the files it included.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    // Append the text of a file, expanding its includes.
+    fn expand(&mut self, path: &Path, text: &str, ancestors: &mut Vec<PathBuf>)
+              -> Result<(), Box<dyn Error>>
+    {
+        let canonical = fs::canonicalize(path)
+            .unwrap_or_else(|_| path.to_path_buf());
+        ancestors.push(canonical);
+        self.files.push(path.to_path_buf());
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut line: usize = 1;
+        let mut i: usize = 0;
+
+        for captures in INCLUDE.captures_iter(text) {
+            let attributes = match captures.get(1) {
+                Some(attributes) => attributes.as_str(),
+                None => continue, // comment or CDATA section
+            };
+
+            let m = captures.get(0).unwrap();
+            self.append(path, line, &text[i..m.start()]);
+            line += text[i..m.start()].matches('\n').count();
+
+            let href = match attribute(&HREF, attributes) {
+                Some(href) => href,
+                None => {
+                    return Err(format!("{}:{}: include without href",
+                                       path.display(), line).into());
+                },
+            };
+
+            let target = dir.join(&href);
+            let canonical = fs::canonicalize(&target)
+                .map_err(|e| format!("{}:{}: cannot include {}: {}",
+                                     path.display(), line, href, e))?;
+
+            if ancestors.contains(&canonical) {
+                let chain = ancestors.iter()
+                    .chain(std::iter::once(&canonical))
+                    .map(|x| x.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(" -> ");
+
+                return Err(format!("{}:{}: include cycle: {}",
+                                   path.display(), line, chain).into());
+            }
+
+            let included = fs::read(&target)
+                .map_err(|e| format!("{}:{}: cannot include {}: {}",
+                                     path.display(), line, href, e))?;
+            let included = decode(&target, included)?;
+
+            if attribute(&PARSE, attributes).as_deref() == Some("text") {
+                self.files.push(target.clone());
+                self.append(&target, 1, &escape(&included));
+            } else {
+                // Drop the XML declaration and document type, keeping
+                // count of the lines they occupied.
+                let prolog = PROLOG.find(&included).map_or(0, |x| x.end());
+                let skipped = included[..prolog].matches('\n').count();
+                let mut nested = Source {
+                    text: String::new(),
+                    spans: Vec::new(),
+                    files: Vec::new(),
+                    line: 1,
+                };
+
+                nested.expand(&target, &included[prolog..], ancestors)?;
+
+                for span in nested.spans {
+                    let line = if span.path == target {
+                        span.line + skipped
+                    } else {
+                        span.line
+                    };
+
+                    self.spans.push(Span {
+                        start: self.line + span.start - 1,
+                        path: span.path,
+                        line: line,
+                    });
+                }
+
+                self.files.extend(nested.files);
+                self.line += nested.line - 1;
+                self.text.push_str(&nested.text);
+            }
+
+            line += m.as_str().matches('\n').count();
+            i = m.end();
+        }
+
+        self.append(path, line, &text[i..]);
+        ancestors.pop();
+        Ok(())
+    }
+
+    // Append a chunk of text that starts on the given line of a file.
+    fn append(&mut self, path: &Path, line: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.spans.push(Span {
+            start: self.line,
+            path: path.to_path_buf(),
+            line: line,
+        });
+
+        self.text.push_str(text);
+        self.line += text.matches('\n').count();
+    }
+}
+
+// Decode the contents of a file in the encoding named in its XML
+// declaration.
+fn decode(path: &Path, bytes: Vec<u8>) -> Result<String, Box<dyn Error>> {
+    if bytes.starts_with(&[0xff, 0xfe]) || bytes.starts_with(&[0xfe, 0xff]) {
+        let encoding: EncodingRef = if bytes[0] == 0xff { UTF_16LE } else { UTF_16BE };
+
+        return encoding.decode(&bytes[2..], DecoderTrap::Strict)
+            .map_err(|e| format!("{}: {}", path.display(), e).into());
+    }
+
+    let label = ENCODING.captures(&bytes).and_then(|captures| {
+        captures.get(1)
+            .or_else(|| captures.get(2))
+            .map(|x| String::from_utf8_lossy(x.as_bytes()).to_lowercase())
+    });
+
+    match label {
+        Some(label) if label != "utf-8" && label != "utf8" => {
+            let encoding = encoding_from_whatwg_label(&label)
+                .ok_or_else(|| format!("{}: unknown encoding {}", path.display(), label))?;
+
+            encoding.decode(&bytes, DecoderTrap::Strict)
+                .map_err(|e| format!("{}: invalid {}: {}", path.display(), label, e).into())
+        },
+        _ => {
+            String::from_utf8(bytes)
+                .map_err(|e| format!("{}: {}", path.display(), e).into())
+        },
+    }
+}
+
+// Fetch the value of an attribute from the inside of a start tag.
+fn attribute(re: &Regex, attributes: &str) -> Option<String> {
+    re.captures(attributes).and_then(|captures| {
+        captures.get(1)
+            .or_else(|| captures.get(2))
+            .map(|x| x.as_str().to_string())
+    })
+}
+
+// Escape the markup characters in text included as character data.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,718 @@
+// Kosik Source Linter
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Checks manuscript sources for slips in the text
+//!
+//! The text of each element is tokenized by the [`parser`], as it is
+//! by the [`reader`], and the tokens are traced back to the source,
+//! so that every [`Finding`] has a line and a column.  Each finding
+//! belongs to a [`Rule`], and rules can be switched off one by one.
+//!
+//! Quotations that run over several paragraphs are expected to leave
+//! the closing quotation mark off every paragraph but the last, and
+//! to open each of the following paragraphs with the same quotation
+//! mark, so an unclosed quotation is only reported when the next
+//! paragraph does not reopen it.  A right single quotation mark that
+//! does not close a quotation is read as an apostrophe.
+//!
+//! Footnote labels are numbered the way the [`reader`] numbers them.
+//! Included files are not followed, so note references to footnotes
+//! in other files are reported.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::lint::{self, Rule};
+//!
+//! let text = "<chapter/>\n<p>It was the the \"best\" of times</p>";
+//! let findings = lint::check(text, &[]).unwrap();
+//! let rules: Vec<Rule> = findings.iter().map(|x| x.rule).collect();
+//!
+//! assert_eq!(rules, vec![Rule::UntitledChapter, Rule::RepeatedWord,
+//!                        Rule::StraightQuote, Rule::StraightQuote,
+//!                        Rule::MissingFullStop]);
+//! assert_eq!(findings[1].to_string(),
+//!            "2:15: \"the\" is repeated [repeated-word]");
+//!
+//! let findings = lint::check(text, &[Rule::MissingFullStop]).unwrap();
+//! assert_eq!(findings.len(), 4);
+//! ```
+//!
+//! [`parser`]: crate::text::parser
+//! [`reader`]: crate::document::reader
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str;
+
+use clap::ValueEnum;
+use encoding::{Encoding, EncoderTrap};
+use encoding::all::ISO_8859_15;
+use quick_xml::events::{BytesStart, Event};
+
+use crate::document::reader::SyntaxError;
+use crate::text::parser::Parser;
+use crate::text::tokens::*;
+
+// Elements whose text is checked.  The text in other elements is
+// ignored by the reader.
+const TEXT: [&str; 33] = [
+    "act", "action", "attribution", "bibRef", "blockquote", "castList",
+    "chapter", "character", "contact", "dd", "dedication", "dialogue",
+    "direction", "dt", "epigraph", "footnote", "gn", "li", "p",
+    "parenthetical", "part", "prefix", "scene", "sceneHeading", "section",
+    "sn", "speaker", "speech", "subtitle", "suffix", "td", "title",
+    "transition",
+];
+
+// Elements whose text belongs to the enclosing element
+const INLINE: [&str; 3] = ["em", "sub", "sup"];
+
+// Elements whose paragraphs need not end with a full stop
+const UNPUNCTUATED: [&str; 5] = ["dd", "dedication", "epigraph", "head", "li"];
+
+/// Lint rules
+///
+/// On the command line, each rule is named in lower case, with
+/// hyphens between the words, as in <tt>repeated-word</tt>.
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq)]
+pub enum Rule {
+    /// Two or more spaces inside a sentence
+    DoubleSpace,
+    /// A note reference with no footnote, or a footnote label that
+    /// is already taken
+    FootnoteLabel,
+    /// A paragraph that does not end with a full stop, a question
+    /// mark, an exclamation mark, a colon, a dash or an ellipsis
+    MissingFullStop,
+    /// A character outside the Latin-9 character set, which cannot be
+    /// typed
+    NonLatin9,
+    /// The same word twice in a row
+    RepeatedWord,
+    /// A straight quotation mark where a curly one was probably
+    /// intended
+    StraightQuote,
+    /// A parenthesis, bracket or quotation mark that is opened and
+    /// not closed, or closed and not opened
+    UnbalancedGroup,
+    /// A chapter without a title
+    UntitledChapter,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => write!(f, "{}", value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// A problem found in the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// The rule that was broken
+    pub rule: Rule,
+    /// Line number, counting from 1
+    pub line: usize,
+    /// Column number in characters, counting from 1
+    pub column: usize,
+    /// Description of the problem
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {} [{}]", self.line, self.column, self.message, self.rule)
+    }
+}
+
+/// Check a manuscript source, skipping the rules that are allowed.
+///
+/// The findings are sorted by line and column.  Fragments with more
+/// than one top-level element are accepted, as they are by the
+/// [`include`] module.
+///
+/// [`include`]: crate::document::include
+pub fn check(text: &str, allowed: &[Rule]) -> Result<Vec<Finding>, SyntaxError> {
+    let mut linter = Linter::new(text, allowed);
+    let mut reader = quick_xml::Reader::from_str(text);
+
+    loop {
+        let event = reader.read_event().map_err(|e| SyntaxError {
+            position: reader.buffer_position(),
+            message: e.to_string(),
+        })?;
+
+        let position = reader.buffer_position();
+
+        match event {
+            Event::Start(e) => {
+                let start = position - e.len() - 2;
+                linter.start(&e, start)?;
+            },
+            Event::End(_) => linter.end(),
+            Event::Empty(e) => {
+                let start = position - e.len() - 3;
+                linter.start(&e, start)?;
+                linter.end();
+            },
+            Event::Text(e) => {
+                let start = position - e.len();
+                let raw = str::from_utf8(&e).map_err(|e| SyntaxError {
+                    position: start,
+                    message: e.to_string(),
+                })?;
+                linter.text(raw, start);
+            },
+            Event::Eof => break,
+            _ => {},
+        }
+    }
+
+    Ok(linter.finish())
+}
+
+// Text collected for an element, with the source offset of each token
+struct Frame {
+    name: String,
+    offset: usize,
+    tokens: Vec<(TokenType, usize)>,
+}
+
+struct Linter<'a> {
+    text: &'a str,
+    allowed: &'a [Rule],
+    line_starts: Vec<usize>,
+    stack: Vec<Frame>,
+    // Names of the open elements, inline elements included
+    names: Vec<String>,
+    next_note_no: i32,
+    footnotes: HashMap<String, usize>,
+    note_refs: Vec<(String, usize)>,
+    // Unclosed quotations, which the next paragraph of the same kind
+    // may reopen
+    pending: Vec<(String, String, Finding)>,
+    findings: Vec<Finding>,
+}
+
+impl<'a> Linter<'a> {
+    fn new(text: &'a str, allowed: &'a [Rule]) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+
+        Linter {
+            text: text,
+            allowed: allowed,
+            line_starts: line_starts,
+            stack: Vec::new(),
+            names: Vec::new(),
+            next_note_no: 1,
+            footnotes: HashMap::new(),
+            note_refs: Vec::new(),
+            pending: Vec::new(),
+            findings: Vec::new(),
+        }
+    }
+
+    fn finding(&self, rule: Rule, offset: usize, message: String) -> Finding {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = self.line_starts[line - 1];
+
+        Finding {
+            rule: rule,
+            line: line,
+            column: self.text[line_start..offset].chars().count() + 1,
+            message: message,
+        }
+    }
+
+    fn report(&mut self, rule: Rule, offset: usize, message: String) {
+        if !self.allowed.contains(&rule) {
+            let finding = self.finding(rule, offset, message);
+            self.findings.push(finding);
+        }
+    }
+
+    fn start(&mut self, event: &BytesStart, start: usize) -> Result<(), SyntaxError> {
+        let name = match str::from_utf8(event.name().as_ref()) {
+            Ok(s) => s.to_string(),
+            Err(e) => return Err(SyntaxError { position: start, message: e.to_string() }),
+        };
+
+        match name.as_str() {
+            "br" => self.push_token(TokenType::LineBreak(Token {
+                data: LineBreakData {},
+                dpy: Default::default(),
+                frm: FormatFlags::MLB,
+            }), start),
+            "footnote" => {
+                let label = match attribute(event, "label") {
+                    Some(label) => {
+                        if let Ok(n) = label.parse::<i32>() {
+                            self.next_note_no = n + 1;
+                        }
+                        label
+                    },
+                    None => {
+                        self.next_note_no += 1;
+                        format!("{}", self.next_note_no - 1)
+                    },
+                };
+
+                if self.footnotes.contains_key(&label) {
+                    self.report(Rule::FootnoteLabel, start,
+                                format!("footnote label \"{}\" is already taken", label));
+                } else {
+                    self.footnotes.insert(label.clone(), start);
+                }
+
+                self.push_token(note_ref(label), start);
+            },
+            "noteRef" => {
+                let label = attribute(event, "label").unwrap_or("*".to_string());
+                self.note_refs.push((label.clone(), start));
+                self.push_token(note_ref(label), start);
+            },
+            _ => {},
+        }
+
+        if !INLINE.contains(&name.as_str()) {
+            self.stack.push(Frame {
+                name: name.clone(),
+                offset: start,
+                tokens: Vec::new(),
+            });
+        }
+
+        self.names.push(name);
+        Ok(())
+    }
+
+    fn end(&mut self) {
+        let name = match self.names.pop() {
+            Some(name) => name,
+            None => return,
+        };
+
+        if INLINE.contains(&name.as_str()) {
+            return;
+        }
+
+        if let Some(frame) = self.stack.pop() {
+            self.check_frame(frame);
+        }
+    }
+
+    // Add a token to the innermost element that collects text.
+    fn push_token(&mut self, token: TokenType, offset: usize) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.tokens.push((token, offset));
+        }
+    }
+
+    fn text(&mut self, raw: &str, start: usize) {
+        let (text, offsets) = unescape(raw, start);
+
+        for (ch, offset) in text.chars().zip(offsets.iter()) {
+            if !is_latin9(ch) {
+                self.report(Rule::NonLatin9, *offset,
+                            format!("'{}' (U+{:04X}) is not in the Latin-9 character set",
+                                    ch, ch as u32));
+            }
+        }
+
+        match self.stack.last() {
+            Some(frame) if TEXT.contains(&frame.name.as_str()) => {},
+            _ => return,
+        }
+
+        let (_, tokens) = Parser::new(&text, Vec::new(), Default::default()).run();
+        let chars: Vec<char> = text.chars().collect();
+        let end = start + raw.len();
+        let mut i = 0;
+
+        for token in tokens.into_iter() {
+            while i < chars.len() && !begins(&token, chars[i]) {
+                i += 1;
+            }
+
+            let j = (i + width(&token, &chars[i..])).min(chars.len());
+            let offset = offsets.get(i).copied().unwrap_or(end);
+
+            match &token {
+                TokenType::Space(_) => {
+                    let spaces = &chars[i..j];
+
+                    if spaces.len() > 1 && !spaces.contains(&'\n')
+                        && spaces[0] != '\\' && !self.after_full_stop()
+                    {
+                        self.report(Rule::DoubleSpace, offset,
+                                    "more than one space inside a sentence".to_string());
+                    }
+                },
+                TokenType::Symbol(symbol) if symbol.data.text == "\"" => {
+                    self.report(Rule::StraightQuote, offset,
+                                "straight double quotation mark".to_string());
+                },
+                TokenType::Word(word) => {
+                    self.check_word(&word.data.text, offset);
+                },
+                _ => {},
+            }
+
+            self.push_token(token, offset);
+            i = j;
+        }
+    }
+
+    // Whether the preceding token ends a sentence, starts a line or
+    // starts the element
+    fn after_full_stop(&self) -> bool {
+        let tokens = match self.stack.last() {
+            Some(frame) => &frame.tokens,
+            None => return true,
+        };
+
+        for (token, _) in tokens.iter().rev() {
+            match token {
+                TokenType::Close(_) => {},
+                TokenType::LineBreak(_) => return true,
+                TokenType::Punct(punct) => {
+                    return token.format_flags().contains(FormatFlags::FS)
+                        || punct.data.glyph == Some('\u{2026}');
+                },
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    fn check_word(&mut self, text: &str, offset: usize) {
+        let tokens = match self.stack.last() {
+            Some(frame) => &frame.tokens,
+            None => return,
+        };
+
+        let mut previous = tokens.iter().rev();
+        let finding = match (previous.next(), previous.next()) {
+            (Some((TokenType::Space(_), _)), Some((TokenType::Word(word), _)))
+                if word.data.text.to_lowercase() == text.to_lowercase()
+                && !text.chars().all(|ch| ch.is_numeric()) =>
+            {
+                Some((Rule::RepeatedWord, offset, format!("\"{}\" is repeated", text)))
+            },
+            _ => None,
+        };
+
+        if let Some((rule, offset, message)) = finding {
+            self.report(rule, offset, message);
+        }
+    }
+
+    fn check_frame(&mut self, frame: Frame) {
+        let has_words = frame.tokens.iter().any(|(token, _)| matches!(token, TokenType::Word(_)));
+
+        if frame.name == "chapter" && !has_words {
+            self.report(Rule::UntitledChapter, frame.offset,
+                        "chapter has no title".to_string());
+        }
+
+        self.check_groups(&frame);
+        self.check_single_quotes(&frame);
+
+        if frame.name == "p" && has_words
+            && !self.names.iter().any(|name| UNPUNCTUATED.contains(&name.as_str()))
+        {
+            let last = frame.tokens.iter().rev().find(|(token, _)| !matches!(
+                token,
+                TokenType::Close(_) | TokenType::LineBreak(_)
+                    | TokenType::NoteRef(_) | TokenType::Space(_)
+            ));
+
+            if let Some((token, offset)) = last {
+                let stopped = match token {
+                    TokenType::Punct(punct) => {
+                        token.format_flags().contains(FormatFlags::FS)
+                            || matches!(punct.data.glyph, Some('\u{2014}' | '\u{2026}'))
+                    },
+                    _ => false,
+                };
+
+                if !stopped {
+                    self.report(Rule::MissingFullStop, *offset,
+                                "paragraph does not end with a full stop".to_string());
+                }
+            }
+        }
+    }
+
+    fn check_groups(&mut self, frame: &Frame) {
+        // A quotation left open by the previous paragraph of this
+        // kind may be reopened here.
+        let first = frame.tokens.iter().find(|(token, _)| !matches!(token, TokenType::Space(_)));
+        let mut i = 0;
+
+        while i < self.pending.len() {
+            if self.pending[i].0 != frame.name {
+                i += 1;
+                continue;
+            }
+
+            let (_, text, finding) = self.pending.remove(i);
+
+            match first {
+                Some((TokenType::Open(open), _)) if open.data.text == text => {},
+                _ => self.findings.push(finding),
+            }
+        }
+
+        let mut open: Vec<(&str, usize)> = Vec::new();
+
+        for (k, (token, offset)) in frame.tokens.iter().enumerate() {
+            match token {
+                TokenType::Open(token) => open.push((&token.data.text, *offset)),
+                TokenType::Close(token) => {
+                    let text = token.data.text.as_str();
+                    let matching = match text {
+                        ")" => "(",
+                        "]" => "[",
+                        "}" => "{",
+                        "\u{bb}" => "\u{ab}",
+                        s => s,
+                    };
+
+                    if text == "'" {
+                        // An apostrophe, unless it closes a quotation
+                        let in_word = k > 0 && k + 1 < frame.tokens.len()
+                            && matches!(frame.tokens[k - 1].0, TokenType::Word(_))
+                            && matches!(frame.tokens[k + 1].0, TokenType::Word(_));
+
+                        if in_word || !open.iter().any(|(s, _)| *s == "'") {
+                            continue;
+                        }
+                    }
+
+                    match open.last() {
+                        Some((s, _)) if *s == matching => {
+                            open.pop();
+                        },
+                        _ => self.report(Rule::UnbalancedGroup, *offset,
+                                         format!("{} is not opened", close_glyph(text))),
+                    }
+                },
+                _ => {},
+            }
+        }
+
+        for (text, offset) in open.into_iter() {
+            let message = format!("{} is not closed", open_glyph(text));
+
+            if text == "\"" || text == "'" {
+                if !self.allowed.contains(&Rule::UnbalancedGroup) {
+                    let finding = self.finding(Rule::UnbalancedGroup, offset, message);
+                    self.pending.push((frame.name.clone(), text.to_string(), finding));
+                }
+            } else {
+                self.report(Rule::UnbalancedGroup, offset, message);
+            }
+        }
+    }
+
+    // An apostrophe at the start of a word is read as an opening
+    // quotation mark if another one closes it, after punctuation or
+    // before it, so that elisions like 'tis and goin' are let
+    // through.
+    fn check_single_quotes(&mut self, frame: &Frame) {
+        let tokens = &frame.tokens;
+        let mut opening: Option<usize> = None;
+
+        for (k, (token, offset)) in tokens.iter().enumerate() {
+            match token {
+                TokenType::Punct(punct) if punct.data.text == "'" => {},
+                _ => continue,
+            }
+
+            let before = if k > 0 { tokens.get(k - 1).map(|x| &x.0) } else { None };
+            let after = tokens.get(k + 1).map(|x| &x.0);
+
+            let opens = matches!(before, None | Some(TokenType::Space(_) | TokenType::Open(_)
+                                                    | TokenType::LineBreak(_)))
+                && matches!(after, Some(TokenType::Word(_)));
+
+            let closes = match before {
+                Some(TokenType::Punct(_)) => !matches!(after, Some(TokenType::Word(_))),
+                Some(TokenType::Word(_)) => !matches!(after, Some(TokenType::Space(_)
+                                                                  | TokenType::Word(_))),
+                _ => false,
+            };
+
+            if opens {
+                opening = Some(*offset);
+            } else if closes {
+                if let Some(start) = opening.take() {
+                    self.report(Rule::StraightQuote, start,
+                                "straight single quotation mark".to_string());
+                    self.report(Rule::StraightQuote, *offset,
+                                "straight single quotation mark".to_string());
+                }
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<Finding> {
+        for (_, _, finding) in self.pending.drain(..) {
+            self.findings.push(finding);
+        }
+
+        for (label, offset) in std::mem::take(&mut self.note_refs).into_iter() {
+            if !self.footnotes.contains_key(&label) {
+                self.report(Rule::FootnoteLabel, offset,
+                            format!("note reference \"{}\" has no footnote", label));
+            }
+        }
+
+        self.findings.sort_by_key(|x| (x.line, x.column));
+        self.findings
+    }
+}
+
+fn attribute(event: &BytesStart, key: &str) -> Option<String> {
+    event.attributes()
+        .filter_map(|attr| attr.ok())
+        .find(|attr| attr.key.as_ref() == key.as_bytes())
+        .and_then(|attr| attr.unescape_value().ok().map(|s| s.to_string()))
+}
+
+fn note_ref(label: String) -> TokenType {
+    TokenType::NoteRef(Token {
+        data: NoteRefData {
+            text: label,
+        },
+        dpy: DisplayFlags::SUP,
+        frm: Default::default(),
+    })
+}
+
+fn open_glyph(text: &str) -> &str {
+    match text {
+        "'" => "\u{2018}",
+        "\"" => "\u{201c}",
+        s => s,
+    }
+}
+
+fn close_glyph(text: &str) -> &str {
+    match text {
+        "'" => "\u{2019}",
+        "\"" => "\u{201d}",
+        s => s,
+    }
+}
+
+// Replace the character and entity references in character data,
+// and note the source offset of each character.
+fn unescape(raw: &str, start: usize) -> (String, Vec<usize>) {
+    let mut text = String::with_capacity(raw.len());
+    let mut offsets: Vec<usize> = Vec::with_capacity(raw.len());
+    let mut i = 0;
+
+    while let Some(ch) = raw[i..].chars().next() {
+        if ch == '&' {
+            if let Some(n) = raw[i..].find(';') {
+                let name = &raw[i + 1..i + n];
+                let decoded = match name {
+                    "amp" => Some('&'),
+                    "apos" => Some('\''),
+                    "gt" => Some('>'),
+                    "lt" => Some('<'),
+                    "quot" => Some('"'),
+                    s if s.starts_with("#x") => u32::from_str_radix(&s[2..], 16).ok()
+                        .and_then(char::from_u32),
+                    s if s.starts_with('#') => s[1..].parse::<u32>().ok()
+                        .and_then(char::from_u32),
+                    _ => None,
+                };
+
+                if let Some(decoded) = decoded {
+                    text.push(decoded);
+                    offsets.push(start + i);
+                    i += n + 1;
+                    continue;
+                }
+            }
+        }
+
+        text.push(ch);
+        offsets.push(start + i);
+        i += ch.len_utf8();
+    }
+
+    (text, offsets)
+}
+
+// Characters that the writer can encode, and the typographic
+// characters that the parser replaces
+fn is_latin9(ch: char) -> bool {
+    matches!(ch, '\u{2018}' | '\u{2019}' | '\u{201c}' | '\u{201d}'
+             | '\u{2013}' | '\u{2014}' | '\u{2026}')
+        || ISO_8859_15.encode(ch.encode_utf8(&mut [0; 4]), EncoderTrap::Strict).is_ok()
+}
+
+// Whether a token can start with the given source character
+fn begins(token: &TokenType, ch: char) -> bool {
+    match token {
+        TokenType::Close(token) => match token.data.text.as_str() {
+            "'" => ch == '\u{2019}',
+            "\"" => ch == '\u{201d}',
+            s => s.starts_with(ch),
+        },
+        TokenType::LineBreak(_) => false,
+        TokenType::NoteRef(_) => false,
+        TokenType::Open(token) => match token.data.text.as_str() {
+            "'" => ch == '\u{2018}',
+            "\"" => ch == '\u{201c}',
+            s => s.starts_with(ch),
+        },
+        TokenType::Punct(token) => match token.data.glyph {
+            Some(glyph) => ch == glyph,
+            None => token.data.text.starts_with(ch),
+        },
+        TokenType::Space(_) => ch.is_whitespace() || ch == '\\',
+        TokenType::Symbol(token) => {
+            ch == '\\' || token.data.text.starts_with(ch)
+                || (ch == '~' && token.data.text == " ")
+        },
+        TokenType::Word(token) => token.data.text.starts_with(ch),
+    }
+}
+
+// The number of source characters that a token was read from
+fn width(token: &TokenType, chars: &[char]) -> usize {
+    let whitespace = |chars: &[char]| chars.iter().take_while(|ch| ch.is_whitespace()).count();
+
+    match token {
+        TokenType::Space(_) if chars.first() == Some(&'\\') => 1 + whitespace(&chars[1..]),
+        TokenType::Space(_) => whitespace(chars),
+        TokenType::Symbol(_) if chars.first() == Some(&'\\') => 2,
+        TokenType::Word(token) => token.data.text.chars().count(),
+        _ => 1,
+    }
+}
@@ -0,0 +1,725 @@
+// Kosik Source Formatter
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Lays out manuscript sources in a canonical form
+//!
+//! The layout is that of the [`sik`] serializer: block elements are
+//! indented two spaces per level below the root, text is filled to
+//! the source width, and attributes are sorted, with namespace
+//! declarations first.  Unlike the serializer, the formatter works on
+//! the source text, so comments, processing instructions, the XML
+//! declaration and include elements are kept as written.
+//!
+//! Only whitespace is changed, and only where the [`reader`] ignores
+//! it or reads it as a single space, so a formatted file reads the
+//! same as the original.  An escaped space is written as
+//! <tt>\\&nbsp;</tt> and never broken, and the contents of
+//! <tt>pre</tt> elements are left alone.  A single blank line is kept
+//! between blocks that were separated by blank lines.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::pretty;
+//!
+//! let text = "<blockquote><p>Mr.\\\n  Smith said,\n\n  <em>hello</em>.</p>\n\
+//!             </blockquote>";
+//!
+//! assert_eq!(pretty::format(text).unwrap(),
+//!            "<blockquote>\n  \
+//!             <p>Mr.\\ Smith said, <em>hello</em>.</p>\n\
+//!             </blockquote>\n");
+//! ```
+//!
+//! [`sik`]: crate::export::sik
+//! [`reader`]: crate::document::reader
+
+use std::str;
+
+use quick_xml::events::{BytesStart, Event};
+
+use crate::document::reader::SyntaxError;
+use crate::export::sik::{self, Run, WIDTH};
+
+// Elements whose bare text is read as paragraphs.  The text in other
+// block elements is ignored.
+const MIXED: [&str; 9] = [
+    "blockquote", "castList", "dd", "dedication", "dialogue", "epigraph",
+    "footnote", "li", "speech",
+];
+
+// Elements whose leading and trailing spaces are trimmed by the reader
+const TEXT: [&str; 24] = [
+    "act", "action", "attribution", "bibRef", "chapter", "character", "contact",
+    "direction", "dt", "gn", "p", "parenthetical", "part", "prefix", "scene",
+    "sceneHeading", "section", "sn", "speaker", "subtitle", "suffix", "td",
+    "title", "transition",
+];
+
+// Elements that flow with the text around them
+const INLINE: [&str; 6] = ["br", "em", "footnote", "noteRef", "sub", "sup"];
+
+// Elements whose children are not indented
+const ROOTS: [&str; 2] = ["manuscript", "screenplay"];
+
+/// Format a manuscript source.
+///
+/// Fragments with more than one top-level element are accepted, as
+/// they are by the [`include`] module.
+///
+/// [`include`]: crate::document::include
+pub fn format(text: &str) -> Result<String, SyntaxError> {
+    let nodes = parse(text)?;
+    let mut xml = String::new();
+
+    for line in children(&nodes, 0, Context::Mixed).lines.iter() {
+        xml.push_str(line);
+        xml.push('\n');
+    }
+
+    Ok(xml)
+}
+
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+    empty: bool,
+}
+
+enum Node {
+    Element(Element),
+    // Character data, with references and escapes as written
+    Text(String),
+    // A comment, CDATA section, declaration, processing instruction or
+    // document type, as written
+    Markup(String),
+}
+
+fn utf8(bytes: &[u8], position: usize) -> Result<String, SyntaxError> {
+    match str::from_utf8(bytes) {
+        Ok(s) => Ok(s.to_string()),
+        Err(e) => Err(SyntaxError { position: position, message: e.to_string() }),
+    }
+}
+
+fn new_element(event: &BytesStart, empty: bool, position: usize)
+               -> Result<Element, SyntaxError>
+{
+    let mut attributes: Vec<(String, String)> = Vec::new();
+
+    for attr in event.attributes() {
+        let attr = attr.map_err(|e| SyntaxError {
+            position: position,
+            message: e.to_string(),
+        })?;
+
+        // Values written in single quotes may contain double quotes.
+        let value = utf8(&attr.value, position)?.replace('"', "&quot;");
+        attributes.push((utf8(attr.key.as_ref(), position)?, value));
+    }
+
+    attributes.sort_by_key(|(key, _)| {
+        let rank = if key == "xmlns" || key.starts_with("xmlns:") {
+            0
+        } else if key.starts_with("xsi:") {
+            1
+        } else {
+            2
+        };
+
+        (rank, key.clone())
+    });
+
+    Ok(Element {
+        name: utf8(event.name().as_ref(), position)?,
+        attributes: attributes,
+        children: Vec::new(),
+        empty: empty,
+    })
+}
+
+fn parse(text: &str) -> Result<Vec<Node>, SyntaxError> {
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut stack: Vec<Element> = Vec::new();
+    let mut nodes: Vec<Node> = Vec::new();
+
+    loop {
+        let event = reader.read_event().map_err(|e| SyntaxError {
+            position: reader.buffer_position(),
+            message: e.to_string(),
+        })?;
+
+        let position = reader.buffer_position();
+
+        let node = match event {
+            Event::Start(e) => {
+                stack.push(new_element(&e, false, position)?);
+                continue;
+            },
+            Event::End(_) => match stack.pop() {
+                Some(elem) => Node::Element(elem),
+                None => continue,
+            },
+            Event::Empty(e) => Node::Element(new_element(&e, true, position)?),
+            Event::Text(e) => Node::Text(utf8(&e, position)?),
+            Event::CData(e) => Node::Markup(format!("<![CDATA[{}]]>", utf8(&e, position)?)),
+            Event::Comment(e) => Node::Markup(format!("<!--{}-->", utf8(&e, position)?)),
+            Event::Decl(e) => Node::Markup(format!("<?{}?>", utf8(&e, position)?)),
+            Event::PI(e) => Node::Markup(format!("<?{}?>", utf8(&e, position)?)),
+            Event::DocType(e) => Node::Markup(format!("<!DOCTYPE {}>", utf8(&e, position)?)),
+            Event::Eof => break,
+        };
+
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => nodes.push(node),
+        }
+    }
+
+    match stack.last() {
+        Some(elem) => Err(SyntaxError {
+            position: text.len(),
+            message: format!("Unclosed element {}", elem.name),
+        }),
+        None => Ok(nodes),
+    }
+}
+
+// How the reader treats the text between the children of an element
+#[derive(Clone, Copy, PartialEq)]
+enum Context {
+    // Text is ignored, so whitespace may go anywhere.
+    Container,
+    // Bare text is read as paragraphs, and text after a paragraph is
+    // added to it.
+    Mixed,
+}
+
+enum Piece<'a> {
+    // Text that must not be broken
+    Word(String),
+    // Whitespace, with the number of line feeds in it
+    Space(usize),
+    // A place after a line break, where whitespace is ignored
+    Newline,
+    // A tag of an inline element
+    Tag(String),
+    // A comment or other markup
+    Markup(&'a str),
+    // A block element
+    Block(&'a Element),
+}
+
+// Split character data into words and whitespace.  An escaped space
+// is part of the words around it.
+fn split(text: &str, pieces: &mut Vec<Piece>) {
+    let mut chars = text.chars().peekable();
+    let mut word = String::new();
+
+    while let Some(c) = chars.next() {
+        if is_space(c) {
+            if !word.is_empty() {
+                pieces.push(Piece::Word(std::mem::take(&mut word)));
+            }
+
+            let mut line_feeds = usize::from(c == '\n');
+
+            while let Some(&c) = chars.peek().filter(|&&c| is_space(c)) {
+                line_feeds += usize::from(c == '\n');
+                chars.next();
+            }
+
+            pieces.push(Piece::Space(line_feeds));
+
+        } else if c == '\\' {
+            word.push(c);
+
+            match chars.peek() {
+                Some(&next) if is_space(next) => {
+                    word.push(' ');
+
+                    while chars.peek().filter(|&&c| is_space(c)).is_some() {
+                        chars.next();
+                    }
+                },
+                Some(&next) => {
+                    word.push(next);
+                    chars.next();
+                },
+                None => {},
+            }
+
+        } else {
+            word.push(c);
+        }
+    }
+
+    if !word.is_empty() {
+        pieces.push(Piece::Word(word));
+    }
+}
+
+fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
+
+// Add a node to a flow of text.
+fn flow<'a>(node: &'a Node, pieces: &mut Vec<Piece<'a>>) {
+    match node {
+        Node::Element(elem) => flow_element(elem, pieces),
+        Node::Text(text) => split(text, pieces),
+        Node::Markup(markup) => pieces.push(Piece::Markup(markup)),
+    }
+}
+
+fn flow_element<'a>(elem: &'a Element, pieces: &mut Vec<Piece<'a>>) {
+    if elem.name == "pre" {
+        let content: String = elem.children.iter().map(raw).collect();
+        pieces.push(Piece::Tag(start_tag(elem) + &content + &end_tag(elem)));
+
+    } else if elem.empty {
+        pieces.push(Piece::Tag(empty_tag(elem)));
+
+        if elem.name == "br" {
+            pieces.push(Piece::Newline);
+        }
+
+    } else {
+        pieces.push(Piece::Tag(start_tag(elem)));
+
+        for child in elem.children.iter() {
+            flow(child, pieces);
+        }
+
+        pieces.push(Piece::Tag(end_tag(elem)));
+    }
+}
+
+fn runs(pieces: &[Piece]) -> Vec<Run> {
+    pieces.iter()
+        .map(|piece| match piece {
+            Piece::Word(text) | Piece::Tag(text) => Run::Text(text.clone()),
+            Piece::Space(_) => Run::Space,
+            Piece::Newline => Run::Newline,
+            Piece::Markup(markup) => Run::Text(markup.to_string()),
+            Piece::Block(_) => Run::Newline,
+        })
+        .collect()
+}
+
+// Whitespace can be added before a flow of text if the reader would
+// trim it, which it does not when the text starts with markup or with
+// a space inside an inline element.
+fn open_edge<'a, I>(mut pieces: I) -> bool
+where I: Iterator<Item = &'a Piece<'a>>
+{
+    !matches!(pieces.find(|piece| !matches!(piece, Piece::Tag(_))),
+              Some(Piece::Markup(_)) | Some(Piece::Space(_)))
+}
+
+fn raw(node: &Node) -> String {
+    match node {
+        Node::Element(elem) if elem.empty => empty_tag(elem),
+        Node::Element(elem) => {
+            let mut text = start_tag(elem);
+
+            for child in elem.children.iter() {
+                text.push_str(&raw(child));
+            }
+
+            text + &end_tag(elem)
+        },
+        Node::Text(text) | Node::Markup(text) => text.clone(),
+    }
+}
+
+fn attributes(elem: &Element) -> String {
+    elem.attributes.iter()
+        .map(|(key, value)| format!(" {}=\"{}\"", key, value))
+        .collect()
+}
+
+fn start_tag(elem: &Element) -> String {
+    format!("<{}{}>", elem.name, attributes(elem))
+}
+
+fn empty_tag(elem: &Element) -> String {
+    format!("<{}{}/>", elem.name, attributes(elem))
+}
+
+fn end_tag(elem: &Element) -> String {
+    format!("</{}>", elem.name)
+}
+
+// Write a start or empty tag on one line if it fits, or else with
+// one attribute per line.  The attributes of the root element are
+// always on separate lines.
+fn tag_lines(elem: &Element, depth: usize) -> Vec<String> {
+    let indent = "  ".repeat(depth);
+    let close = if elem.empty { "/>" } else { ">" };
+    let line = format!("{}<{}{}{}", indent, elem.name, attributes(elem), close);
+
+    if elem.attributes.len() < 2
+        || (line.chars().count() <= WIDTH && !ROOTS.contains(&elem.name.as_str()))
+    {
+        return vec![line];
+    }
+
+    let mut lines = vec![format!("{}<{}", indent, elem.name)];
+
+    for (key, value) in elem.attributes.iter() {
+        lines.push(format!("{}    {}=\"{}\"", indent, key, value));
+    }
+
+    if let Some(last) = lines.last_mut() {
+        last.push_str(close);
+    }
+
+    lines
+}
+
+// Join the first of the following lines to the last of the preceding
+// ones.
+fn glue(lines: &mut Vec<String>, following: Vec<String>) {
+    let mut following = following.into_iter();
+
+    match (lines.last_mut(), following.next()) {
+        (Some(last), Some(first)) => last.push_str(first.trim_start()),
+        (None, Some(first)) => lines.push(first),
+        _ => {},
+    }
+
+    lines.extend(following);
+}
+
+enum Item<'a> {
+    Block(&'a Element),
+    Markup(&'a str),
+    Text(Vec<Piece<'a>>),
+}
+
+impl Item<'_> {
+    fn open_edge(&self) -> bool {
+        match self {
+            Item::Block(_) => true,
+            Item::Markup(_) => false,
+            Item::Text(pieces) => open_edge(pieces.iter()),
+        }
+    }
+
+    fn close_edge(&self) -> bool {
+        match self {
+            Item::Block(_) => true,
+            Item::Markup(_) => false,
+            Item::Text(pieces) => open_edge(pieces.iter().rev()),
+        }
+    }
+}
+
+// Whitespace between two children
+#[derive(Clone, Copy, Default)]
+struct Gap {
+    space: bool,
+    blank: bool,
+}
+
+impl Gap {
+    fn add(&mut self, line_feeds: usize) {
+        self.space = true;
+        self.blank |= line_feeds > 1;
+    }
+}
+
+struct Children {
+    lines: Vec<String>,
+    // Whether the children may start on a new line
+    open: bool,
+    // Whether the end tag may go on a new line
+    close: bool,
+    // Whether there was anything at all between the tags
+    space: bool,
+}
+
+// Divide the children of a block element into blocks, comments and
+// runs of text, with the whitespace between them.
+fn items(nodes: &[Node], context: Context) -> (Vec<(Gap, Item<'_>)>, Gap) {
+    let mut pieces: Vec<Piece> = Vec::new();
+
+    for node in nodes.iter() {
+        match node {
+            Node::Element(elem) if context == Context::Container
+                || !INLINE.contains(&elem.name.as_str()) =>
+            {
+                pieces.push(Piece::Block(elem));
+            },
+            node => flow(node, &mut pieces),
+        }
+    }
+
+    let mut items: Vec<(Gap, Item)> = Vec::new();
+    let mut gap = Gap::default();
+    let mut text: Vec<Piece> = Vec::new();
+    let mut text_gap = Gap::default();
+
+    // Markup stands on its own line in mixed content only where it is
+    // surrounded by whitespace.
+    let standalone: Vec<bool> = (0..pieces.len())
+        .map(|i| match context {
+            Context::Container => true,
+            Context::Mixed => i > 0
+                && matches!(pieces[i - 1], Piece::Space(_) | Piece::Block(_))
+                && matches!(pieces.get(i + 1), Some(Piece::Space(_)) | Some(Piece::Block(_))),
+        })
+        .collect();
+
+    for (piece, standalone) in pieces.into_iter().zip(standalone) {
+        let item = match piece {
+            Piece::Block(elem) => Item::Block(elem),
+            Piece::Markup(markup) if standalone => Item::Markup(markup),
+            Piece::Space(line_feeds) if text.is_empty() => {
+                gap.add(line_feeds);
+                continue;
+            },
+            piece => {
+                if text.is_empty() {
+                    text_gap = std::mem::take(&mut gap);
+                }
+
+                text.push(piece);
+                continue;
+            },
+        };
+
+        if !text.is_empty() {
+            items.push((text_gap, end_text(&mut text, &mut gap)));
+        }
+
+        items.push((std::mem::take(&mut gap), item));
+    }
+
+    if !text.is_empty() {
+        items.push((text_gap, end_text(&mut text, &mut gap)));
+    }
+
+    (items, gap)
+}
+
+// Finish a run of text, moving its trailing whitespace to the gap
+// after it.
+fn end_text<'a>(text: &mut Vec<Piece<'a>>, gap: &mut Gap) -> Item<'a> {
+    while let Some(Piece::Space(line_feeds)) = text.last() {
+        gap.add(*line_feeds);
+        text.pop();
+    }
+
+    Item::Text(std::mem::take(text))
+}
+
+fn children(nodes: &[Node], depth: usize, context: Context) -> Children {
+    let (items, trailing) = items(nodes, context);
+    let mut lines: Vec<String> = Vec::new();
+
+    for (i, (gap, item)) in items.iter().enumerate() {
+        let item_lines = match item {
+            Item::Block(elem) => element(elem, depth),
+            Item::Markup(markup) => vec![format!("{}{}", "  ".repeat(depth), markup)],
+            Item::Text(pieces) => sik::wrap(&runs(pieces), depth),
+        };
+
+        if i == 0 {
+            lines.extend(item_lines);
+            continue;
+        }
+
+        let prev = &items[i - 1].1;
+
+        // In mixed content, text after a paragraph is added to it.
+        let safe = context == Context::Container
+            || (prev.close_edge() && item.open_edge()
+                && !(matches!(prev, Item::Block(elem) if elem.name == "p")
+                     && matches!(item, Item::Text(_))));
+
+        if gap.space || safe {
+            if gap.blank {
+                lines.push(String::new());
+            }
+
+            lines.extend(item_lines);
+        } else {
+            glue(&mut lines, item_lines);
+        }
+    }
+
+    let first = items.first();
+    let last = items.last();
+
+    Children {
+        lines: lines,
+        open: context == Context::Container
+            || first.is_none_or(|(gap, item)| gap.space || item.open_edge()),
+        close: context == Context::Container
+            || trailing.space
+            || last.is_none_or(|(_, item)| item.close_edge()),
+        space: trailing.space || !items.is_empty(),
+    }
+}
+
+fn element(elem: &Element, depth: usize) -> Vec<String> {
+    let name = elem.name.as_str();
+
+    if elem.empty {
+        return tag_lines(elem, depth);
+    }
+
+    if name == "pre" {
+        let mut lines = tag_lines(elem, depth);
+        let content: String = elem.children.iter().map(raw).collect();
+
+        if let Some(last) = lines.last_mut() {
+            last.push_str(&content);
+            last.push_str(&end_tag(elem));
+        }
+
+        return lines;
+    }
+
+    if TEXT.contains(&name) {
+        return text(elem, depth);
+    }
+
+    if INLINE.contains(&name) {
+        let mut pieces: Vec<Piece> = Vec::new();
+        flow_element(elem, &mut pieces);
+        return sik::wrap(&runs(&pieces), depth);
+    }
+
+    if name == "person" {
+        if let Some(line) = person(elem, depth) {
+            return vec![line];
+        }
+    }
+
+    let context = if MIXED.contains(&name) { Context::Mixed } else { Context::Container };
+    let child_depth = if ROOTS.contains(&name) { depth } else { depth + 1 };
+    let children = children(&elem.children, child_depth, context);
+    let mut lines = tag_lines(elem, depth);
+    let end = format!("{}{}", "  ".repeat(depth), end_tag(elem));
+
+    if children.lines.is_empty() {
+        // A mixed container holding only whitespace has an empty
+        // paragraph in it.
+        if context == Context::Mixed && children.space {
+            lines.push(end);
+        } else {
+            glue(&mut lines, vec![end]);
+        }
+
+        return lines;
+    }
+
+    if children.open {
+        lines.extend(children.lines);
+    } else {
+        glue(&mut lines, children.lines);
+    }
+
+    if children.close {
+        lines.push(end);
+    } else {
+        glue(&mut lines, vec![end]);
+    }
+
+    lines
+}
+
+// Write a text element on one line if it fits, or else with its text
+// wrapped between the tags.
+fn text(elem: &Element, depth: usize) -> Vec<String> {
+    let mut pieces: Vec<Piece> = Vec::new();
+
+    for child in elem.children.iter() {
+        flow(child, &mut pieces);
+    }
+
+    let mut gap = Gap::default();
+
+    while let Some(Piece::Space(line_feeds)) = pieces.first() {
+        gap.add(*line_feeds);
+        pieces.remove(0);
+    }
+
+    let (leading, trimmed_start) = (gap.space, open_edge(pieces.iter()));
+    let mut gap = Gap::default();
+    let item = end_text(&mut pieces, &mut gap);
+    let (trailing, trimmed_end) = (gap.space, item.close_edge());
+
+    let pieces = if let Item::Text(pieces) = item { pieces } else { Vec::new() };
+
+    let runs = runs(&pieces);
+    let mut lines = tag_lines(elem, depth);
+    let end = format!("{}{}", "  ".repeat(depth), end_tag(elem));
+
+    // Spaces at the ends can be dropped only where the reader trims
+    // them.
+    if lines.len() == 1 && (trimmed_start || !leading) && (trimmed_end || !trailing) {
+        if let Some(text) = sik::single_line(&runs) {
+            let line = format!("{}{}{}", lines[0], text, end_tag(elem));
+
+            if line.chars().count() <= WIDTH || pieces.is_empty() {
+                return vec![line];
+            }
+        }
+    }
+
+    let (open, close) = (leading || trimmed_start, trailing || trimmed_end);
+
+    if open {
+        lines.extend(sik::wrap(&runs, depth + 1));
+    } else {
+        glue(&mut lines, sik::wrap(&runs, depth + 1));
+    }
+
+    if close {
+        lines.push(end);
+    } else {
+        glue(&mut lines, vec![end]);
+    }
+
+    lines
+}
+
+// Write a person on one line if it fits.
+fn person(elem: &Element, depth: usize) -> Option<String> {
+    let mut line = format!("{}{}", "  ".repeat(depth), start_tag(elem));
+
+    for child in elem.children.iter() {
+        match child {
+            Node::Element(child) if TEXT.contains(&child.name.as_str()) => {
+                match text(child, 0).as_slice() {
+                    [text] => line.push_str(text),
+                    _ => return None,
+                }
+            },
+            Node::Text(text) if text.chars().all(is_space) => {},
+            _ => return None,
+        }
+    }
+
+    line.push_str(&end_tag(elem));
+    Some(line).filter(|line| line.chars().count() <= WIDTH)
+}
@@ -60,8 +60,11 @@ use quick_xml::events::BytesText;
 use quick_xml::events::Event;
 use quick_xml::name::QName;
 
+use std::error::Error;
+use std::fmt;
 use std::str;
 
+use crate::config::Config;
 use crate::document::*;
 use crate::text::parser::Parser;
 
@@ -70,6 +73,8 @@ mod macros;
 
 /// Stack alphabet
 pub enum State {
+    Act        (TextElement     <Act        >),
+    Action     (TextElement     <Action     >),
     Attribution(TextElement     <Attribution>),
     Authors    (ContainerElement<Authors    >),
     Backmatter (ContainerElement<Backmatter >),
@@ -77,10 +82,19 @@ pub enum State {
     Blockquote (ContainerElement<Blockquote >),
     Body       (ContainerElement<Body       >),
     Br         (EmptyElement    <Br         >),
+    CastList   (ContainerElement<CastList   >),
     Chapter    (TextElement     <Chapter    >),
+    Character  (TextElement     <Character  >),
     Contact    (TextElement     <Contact    >),
+    Dd         (ContainerElement<Dd         >),
+    Dedication (ContainerElement<Dedication >),
+    Dialogue   (ContainerElement<Dialogue   >),
+    Direction  (TextElement     <Direction  >),
     Div        (EmptyElement    <Div        >),
+    Dl         (ContainerElement<Dl         >),
+    Dt         (TextElement     <Dt         >),
     Em         (TextElement     <Em         >),
+    Epigraph   (ContainerElement<Epigraph   >),
     Footnote   (ContainerElement<Footnote   >),
     Frontmatter(ContainerElement<Frontmatter>),
     Gn         (TextElement     <Gn         >),
@@ -91,16 +105,27 @@ pub enum State {
     Ol         (ContainerElement<Ol         >),
     P          (TextElement     <P          >),
     PageBreak  (EmptyElement    <PageBreak  >),
+    Parenthetical(TextElement   <Parenthetical>),
     Part       (TextElement     <Part       >),
     Person     (ContainerElement<Person     >),
+    Pre        (TextElement     <Pre        >),
     Prefix     (TextElement     <Prefix     >),
+    Scene      (TextElement     <Scene      >),
+    SceneHeading(TextElement    <SceneHeading>),
+    Screenplay (ContainerElement<Screenplay >),
     Section    (TextElement     <Section    >),
     Sn         (TextElement     <Sn         >),
+    Speaker    (TextElement     <Speaker    >),
+    Speech     (ContainerElement<Speech     >),
     Sub        (TextElement     <Sub        >),
     Subtitle   (TextElement     <Subtitle   >),
     Suffix     (TextElement     <Suffix     >),
     Sup        (TextElement     <Sup        >),
+    Table      (ContainerElement<Table      >),
+    Td         (TextElement     <Td         >),
     Title      (TextElement     <Title      >),
+    Tr         (ContainerElement<Tr         >),
+    Transition (TextElement     <Transition >),
     Ul         (ContainerElement<Ul         >),
 }
 
@@ -109,6 +134,14 @@ impl State {
 
     fn on_exit(self) -> ElementType {
         match self {
+            State::Act(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Act(elem)
+            },
+            State::Action(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Action(elem)
+            },
             State::Attribution(mut elem) => {
                 State::trim_whitespace(&mut elem.tokens);
                 ElementType::Attribution(elem)
@@ -141,20 +174,105 @@ impl State {
             State::Br(elem) => {
                 ElementType::Br(elem)
             },
+            State::CastList(mut elem) => {
+                for child in elem.children.iter_mut() {
+                    match child {
+                        ElementType::P(child) => {
+                            State::trim_whitespace(&mut child.tokens);
+                        },
+                        _ => {},
+                    }
+                }
+
+                State::remove_empty_paragraphs(&mut elem.children);
+                ElementType::CastList(elem)
+            },
             State::Chapter(mut elem) => {
                 State::trim_whitespace(&mut elem.tokens);
                 ElementType::Chapter(elem)
             },
+            State::Character(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Character(elem)
+            },
             State::Contact(mut elem) => {
                 State::trim_whitespace(&mut elem.tokens);
                 ElementType::Contact(elem)
             },
+            State::Dd(mut elem) => {
+                for child in elem.children.iter_mut() {
+                    match child {
+                        ElementType::P(child) => {
+                            State::trim_whitespace(&mut child.tokens);
+                        },
+                        _ => {},
+                    }
+                }
+
+                State::remove_empty_paragraphs(&mut elem.children);
+                ElementType::Dd(elem)
+            },
+            State::Dedication(mut elem) => {
+                for child in elem.children.iter_mut() {
+                    match child {
+                        ElementType::P(child) => {
+                            State::trim_whitespace(&mut child.tokens);
+                        },
+                        _ => {},
+                    }
+                }
+                
+                ElementType::Dedication(elem)
+            },
+            State::Dialogue(mut elem) => {
+                for child in elem.children.iter_mut() {
+                    match child {
+                        ElementType::P(child) => {
+                            State::trim_whitespace(&mut child.tokens);
+                        },
+                        _ => {},
+                    }
+                }
+
+                State::remove_empty_paragraphs(&mut elem.children);
+                ElementType::Dialogue(elem)
+            },
+            State::Direction(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Direction(elem)
+            },
             State::Div(elem) => {
                 ElementType::Div(elem)
             },
+            State::Dl(elem) => {
+                ElementType::Dl(elem)
+            },
+            State::Dt(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Dt(elem)
+            },
             State::Em(elem) => {
                 ElementType::Em(elem)
             },
+            State::Epigraph(mut elem) => {
+                // Discard trailing whitespace after the attribution.
+                if let Some(ElementType::P(last_child)) = elem.children.last() {
+                    if State::contains_only_whitespace(&last_child.tokens) {
+                        elem.children.pop();
+                    }
+                }
+
+                for child in elem.children.iter_mut() {
+                    match child {
+                        ElementType::P(child) => {
+                            State::trim_whitespace(&mut child.tokens);
+                        },
+                        _ => {},
+                    }
+                }
+                
+                ElementType::Epigraph(elem)
+            },
             State::Footnote(mut elem) => {
                 for child in elem.children.iter_mut() {
                     match child {
@@ -186,7 +304,8 @@ impl State {
                         _ => {},
                     }
                 }
-                
+
+                State::remove_empty_paragraphs(&mut elem.children);
                 ElementType::Li(elem)
             },
             State::Manuscript(elem) => {
@@ -204,6 +323,10 @@ impl State {
             },
             State::PageBreak(elem) => {
                 ElementType::PageBreak(elem)
+            },
+            State::Parenthetical(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Parenthetical(elem)
             },
              State::Part(mut elem) => {
                 State::trim_whitespace(&mut elem.tokens);
@@ -212,10 +335,29 @@ impl State {
             State::Person(elem) => {
                 ElementType::Person(elem)
             },
+            State::Pre(mut elem) => {
+                let text = elem.tokens.iter()
+                    .map(|token| token.text())
+                    .collect::<String>();
+
+                elem.tokens = State::split_lines(&text);
+                ElementType::Pre(elem)
+            },
             State::Prefix(mut elem) => {
                 State::trim_whitespace(&mut elem.tokens);
                 ElementType::Prefix(elem)
             },
+            State::Scene(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Scene(elem)
+            },
+            State::SceneHeading(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::SceneHeading(elem)
+            },
+            State::Screenplay(elem) => {
+                ElementType::Screenplay(elem)
+            },
             State::Section(mut elem) => {
                 State::trim_whitespace(&mut elem.tokens);
                 ElementType::Section(elem)
@@ -224,6 +366,23 @@ impl State {
                 State::trim_whitespace(&mut elem.tokens);
                 ElementType::Sn(elem)
             },
+            State::Speaker(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Speaker(elem)
+            },
+            State::Speech(mut elem) => {
+                for child in elem.children.iter_mut() {
+                    match child {
+                        ElementType::P(child) => {
+                            State::trim_whitespace(&mut child.tokens);
+                        },
+                        _ => {},
+                    }
+                }
+
+                State::remove_empty_paragraphs(&mut elem.children);
+                ElementType::Speech(elem)
+            },
             State::Sub(elem) => {
                 ElementType::Sub(elem)
             },
@@ -238,10 +397,24 @@ impl State {
             State::Sup(elem) => {
                 ElementType::Sup(elem)
             },
+            State::Table(elem) => {
+                ElementType::Table(elem)
+            },
+            State::Td(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Td(elem)
+            },
             State::Title(mut elem) => {
                 State::trim_whitespace(&mut elem.tokens);
                 ElementType::Title(elem)
             },
+            State::Tr(elem) => {
+                ElementType::Tr(elem)
+            },
+            State::Transition(mut elem) => {
+                State::trim_whitespace(&mut elem.tokens);
+                ElementType::Transition(elem)
+            },
             State::Ul(elem) => {
                 ElementType::Ul(elem)
             },
@@ -252,6 +425,12 @@ impl State {
 
     fn on_resume(mut self, child: ElementType) -> Self {
         match self {
+            State::Act(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
+            State::Action(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
             State::Attribution(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
@@ -271,15 +450,101 @@ impl State {
             State::Body(ref mut elem) => {
                 elem.children.push(child);
             },
+            State::CastList(ref mut elem) => {
+                match child {
+                    ElementType::P(_) | ElementType::Speaker(_) => {
+                        elem.children.push(child);
+                    },
+                    _ => {
+                        let line_spacing = elem.attributes.line_spacing;
+                        State::resume_stage_element(&mut elem.children, child,
+                                                    line_spacing);
+                    },
+                }
+            },
             State::Chapter(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
+            State::Character(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
             State::Contact(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
+            State::Dd(ref mut elem) => {
+                match child {
+                    ElementType::Dl(_) | ElementType::Ol(_) | ElementType::Ul(_) => {
+                        elem.children.push(child);
+                    },
+                    _ => {
+                        let left_margin = elem.attributes.left_margin;
+                        resume_mixed_content!(elem, child, left_margin,
+                                              RIGHT_MARGIN);
+                    },
+                }
+            },
+            State::Dedication(ref mut elem) => {
+                resume_mixed_content!(elem, child, LEFT_MARGIN, RIGHT_MARGIN);
+            },
+            State::Dialogue(ref mut elem) => {
+                match child {
+                    ElementType::Character(_) | ElementType::Parenthetical(_) => {
+                        elem.children.push(child);
+                    },
+                    ElementType::P(_) => {
+                        elem.children.push(child);
+                    },
+                    _ => { // inline content not wrapped in p tags
+                        if let Some(ElementType::P(wrapper)) = elem.children.last_mut() {
+                            State::resume_text_element(wrapper, child);
+
+                        } else {
+                            let mut wrapper = TextElement::new(P {
+                                indent: 0,
+                                line_spacing: LineSpacing::Single,
+                                left_margin: DIALOGUE_MARGIN,
+                                right_margin: DIALOGUE_RIGHT_MARGIN,
+                            });
+
+                            State::resume_text_element(&mut wrapper, child);
+                            elem.children.push(ElementType::P(wrapper));
+                        }
+                    },
+                }
+            },
+            State::Direction(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
+            State::Dl(ref mut elem) => {
+                elem.children.push(child);
+            },
+            State::Dt(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
             State::Em(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
+            State::Epigraph(ref mut elem) => {
+                match child {
+                    ElementType::Attribution(child) => {
+                        // If the last child contains only whitespace,
+                        // discard it.
+                        if let Some(ElementType::P(last_child))
+                            = elem.children.last()
+                        {
+                            if State::contains_only_whitespace(&last_child.tokens) {
+                                elem.children.pop();
+                            }
+                        }
+
+                        elem.children.push(ElementType::Attribution(child));
+                    },
+                    child => {
+                        resume_mixed_content!(elem, child, EPIGRAPH_MARGIN,
+                                              RIGHT_MARGIN);
+                    },
+                }
+            },
             State::Footnote(ref mut elem) => {
                 resume_mixed_content!(elem, child, LEFT_MARGIN, RIGHT_MARGIN);
             },
@@ -293,8 +558,16 @@ impl State {
                 elem.children.push(child);
             },
             State::Li(ref mut elem) => {
-                resume_mixed_content!(elem, child, LEFT_MARGIN + INDENT,
-                                      RIGHT_MARGIN);
+                match child {
+                    ElementType::Dl(_) | ElementType::Ol(_) | ElementType::Ul(_) => {
+                        elem.children.push(child);
+                    },
+                    _ => {
+                        let left_margin = elem.attributes.left_margin;
+                        resume_mixed_content!(elem, child, left_margin,
+                                              RIGHT_MARGIN);
+                    },
+                }
             },
             State::Manuscript(ref mut elem) => {
                 elem.children.push(child);
@@ -305,6 +578,9 @@ impl State {
             State::P(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
+            State::Parenthetical(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
             State::Part(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
@@ -314,21 +590,59 @@ impl State {
             State::Prefix(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
+            State::Scene(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
+            State::SceneHeading(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
+            State::Screenplay(ref mut elem) => {
+                elem.children.push(child);
+            },
             State::Section(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
             State::Sn(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
+            State::Speaker(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
+            State::Speech(ref mut elem) => {
+                match child {
+                    ElementType::Direction(_) | ElementType::P(_)
+                        | ElementType::Speaker(_) =>
+                    {
+                        elem.children.push(child);
+                    },
+                    _ => {
+                        let line_spacing = elem.attributes.line_spacing;
+                        State::resume_stage_element(&mut elem.children, child,
+                                                    line_spacing);
+                    },
+                }
+            },
             State::Subtitle(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
             State::Suffix(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
+            State::Table(ref mut elem) => {
+                elem.children.push(child);
+            },
+            State::Td(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
             State::Title(ref mut elem) => {
                 State::resume_text_element(elem, child);
             },
+            State::Tr(ref mut elem) => {
+                elem.children.push(child);
+            },
+            State::Transition(ref mut elem) => {
+                State::resume_text_element(elem, child);
+            },
             State::Ul(ref mut elem) => {
                 elem.children.push(child);
             },
@@ -382,6 +696,94 @@ impl State {
         }
     }
 
+    /// Add inline content that is not wrapped in p tags to a speech
+    /// or cast list, starting a new paragraph after a speaker or a
+    /// stage direction.
+    fn resume_stage_element(children: &mut ElementList, child: ElementType,
+                            line_spacing: LineSpacing)
+    {
+        if let Some(ElementType::P(wrapper)) = children.last_mut() {
+            State::resume_text_element(wrapper, child);
+
+        } else {
+            let mut wrapper = TextElement::new(P {
+                indent: 0,
+                line_spacing: line_spacing,
+                left_margin: LEFT_MARGIN,
+                right_margin: RIGHT_MARGIN,
+            });
+
+            State::resume_text_element(&mut wrapper, child);
+            children.push(ElementType::P(wrapper));
+        }
+    }
+
+    /// Convert the raw contents of a <tt>pre</tt> element to one word
+    /// token per line, separated by mandatory line breaks.
+    fn split_lines(text: &str) -> TokenList {
+        // Expand tabs.
+        let mut lines: Vec<String> = Vec::new();
+        
+        for line in text.lines() {
+            let mut expanded = String::new();
+            let mut x: usize = 0;
+
+            for ch in line.trim_end().chars() {
+                if ch == '\t' {
+                    let n = TAB_WIDTH - x % TAB_WIDTH;
+                    expanded.push_str(&" ".repeat(n));
+                    x += n;
+                } else {
+                    expanded.push(ch);
+                    x += 1;
+                }
+            }
+
+            lines.push(expanded);
+        }
+
+        // Drop blank lines at the start and the end.
+        while let Some(line) = lines.first() {
+            if line.is_empty() {
+                lines.remove(0);
+            } else {
+                break;
+            }
+        }
+
+        while let Some(line) = lines.last() {
+            if line.is_empty() {
+                lines.pop();
+            } else {
+                break;
+            }
+        }
+
+        // Remove the common indentation.
+        let margin = lines.iter()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.chars().take_while(|ch| *ch == ' ').count())
+            .min()
+            .unwrap_or(0);
+
+        let mut tokens: TokenList = Vec::with_capacity(lines.len() * 2);
+
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                tokens.push(TokenType::LineBreak(Token {
+                    data: LineBreakData {},
+                    dpy: Default::default(),
+                    frm: FormatFlags::MLB,
+                }));
+            }
+
+            let text = line.chars().skip(margin).collect::<String>();
+            tokens.push(TokenType::Word(Token::from(&text[..])));
+        }
+
+        tokens
+    }
+
     fn trim_whitespace(tokens: &mut TokenList) {
         if let Some(TokenType::Space(_)) = tokens.first() {
             tokens.remove(0);
@@ -402,6 +804,161 @@ impl State {
 
         true
     }
+
+    /// Discard paragraphs left empty by the whitespace around nested
+    /// lists.
+    fn remove_empty_paragraphs(children: &mut ElementList) {
+        children.retain(|child| match child {
+            ElementType::P(child) => !child.tokens.is_empty(),
+            _ => true,
+        });
+    }
+}
+
+/// Malformed XML, or an element where the schema does not allow one
+///
+/// # Examples
+///
+/// ```
+/// # use kosik::document::reader::Reader;
+/// let reader = Reader::new("<pre>int <em>x</em>;</pre>");
+/// let error = reader.run().unwrap_err();
+/// assert_eq!(error.position, 9);
+/// assert_eq!(error.to_string(), "<em> is not allowed in <pre>, which holds only text");
+/// ```
+#[derive(Debug)]
+pub struct SyntaxError {
+    /// Byte offset in the input at which the error was detected
+    pub position: usize,
+    /// Description of the error
+    pub message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for SyntaxError {}
+
+/// Numbers that the next footnote and the next heading of each kind
+/// will take, unless they are given numbers of their own
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct Numbering {
+    pub note: i32,
+    pub part: i32,
+    pub chapter: i32,
+    pub section: i32,
+    pub act: i32,
+    pub scene: i32,
+}
+
+impl Default for Numbering {
+    fn default() -> Self {
+        Self {
+            note: 1,
+            part: 1,
+            chapter: 1,
+            section: 1,
+            act: 1,
+            scene: 1,
+        }
+    }
+}
+
+/// Kinds of heading found in a manuscript, which decide how deeply
+/// each kind is nested
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub(crate) struct Structure {
+    pub parts: bool,
+    pub chapters: bool,
+    pub sections: bool,
+    pub acts: bool,
+    pub scenes: bool,
+}
+
+impl Structure {
+    /// Add the kinds of heading found in another part of the
+    /// manuscript.
+    pub fn add(&mut self, other: Structure) {
+        self.parts |= other.parts;
+        self.chapters |= other.chapters;
+        self.sections |= other.sections;
+        self.acts |= other.acts;
+        self.scenes |= other.scenes;
+    }
+
+    /// Set the depth of each heading in the body of a manuscript, and
+    /// whether the manuscript has any structure.
+    pub fn apply(&self, elem: &mut ContainerElement<Manuscript>) {
+        let part_depth = if self.parts {
+            elem.attributes.has_structure = true;
+            0
+        } else {
+            -1
+        };
+
+        let chapter_depth = if self.chapters {
+            elem.attributes.has_structure = true;
+            if part_depth >= 0 { 1 } else { 0 }
+        } else {
+            -1
+        };
+
+        let section_depth = if self.sections {
+            elem.attributes.has_structure = true;
+            if part_depth >= 0 && chapter_depth >= 0 { 2 } else { 1 }
+        } else {
+            -1
+        };
+
+        let act_depth = if self.acts {
+            elem.attributes.has_structure = true;
+            0
+        } else {
+            -1
+        };
+
+        let scene_depth = if self.scenes {
+            elem.attributes.has_structure = true;
+            if act_depth >= 0 { 1 } else { 0 }
+        } else {
+            -1
+        };
+
+        if let Some(body) = elem.body() {
+            for child in body.children.iter_mut() {
+                match child {
+                    ElementType::Act(child) => {
+                        child.attributes.depth = act_depth;
+                    },
+                    ElementType::Chapter(child) => {
+                        child.attributes.depth = chapter_depth;
+                    },
+                    ElementType::Part(child) => {
+                        child.attributes.depth = part_depth;
+                    },
+                    ElementType::Scene(child) => {
+                        child.attributes.depth = scene_depth;
+                    },
+                    ElementType::Section(child) => {
+                        child.attributes.depth = section_depth;
+                    },
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+/// One element of a manuscript read on its own, with what the reader
+/// carries on to the elements after it
+pub(crate) struct Fragment {
+    pub root: Option<ElementType>,
+    pub numbering: Numbering,
+    pub structure: Structure,
+    pub word_count: usize,
 }
 
 /// Input driver
@@ -411,14 +968,10 @@ pub struct Reader<'a> {
     /// A [`quick_xml`] reader
     xml_reader: quick_xml::Reader<&'a [u8]>,
     stack: Vec<State>,
-    next_note_no: i32,
-    next_part_no: i32,
-    next_chapter_no: i32,
-    next_section_no: i32,
-    next_li_no: Option<i32>,
-    has_parts: bool,
-    has_chapters: bool,
-    has_sections: bool,
+    numbering: Numbering,
+    structure: Structure,
+    first_page: i32,
+    line_spacing: LineSpacing,
 
     /// Element accumulator
     pub root: Option<ElementType>,
@@ -440,19 +993,55 @@ impl<'a> Reader<'a> {
         Reader {
             xml_reader: quick_xml::Reader::from_str(xml_string),
             stack: Vec::with_capacity(16),
-            next_note_no: 1,
-            next_part_no: 1,
-            next_chapter_no: 1,
-            next_section_no: 1,
-            next_li_no: None,
-            has_parts: false,
-            has_chapters: false,
-            has_sections: false,
+            numbering: Default::default(),
+            structure: Default::default(),
+            first_page: 1,
+            line_spacing: LineSpacing::Double,
             root: None,
             word_count: 0,
         }
     }
 
+    /// Construct a new reader that takes the defaults for the first
+    /// page number and paragraph line spacing from project settings
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kosik::config::Config;
+    /// use kosik::document::ElementType;
+    /// use kosik::document::reader::Reader;
+    ///
+    /// let config = Config {
+    ///     first_page: Some(5),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let reader = Reader::with_config("<manuscript></manuscript>", &config);
+    ///
+    /// match reader.run().unwrap() {
+    ///     Some(ElementType::Manuscript(elem)) => assert_eq!(elem.attributes.first_page, 5),
+    ///     _ => panic!(),
+    /// }
+    /// ```
+    pub fn with_config(xml_string: &'a str, config: &Config) -> Self {
+        let mut reader = Reader::new(xml_string);
+        reader.first_page = config.first_page.unwrap_or(reader.first_page);
+        reader.line_spacing = config.line_spacing.unwrap_or(reader.line_spacing);
+        reader
+    }
+
+    /// Left margin of a definition list term at the current nesting
+    /// level.  List items and descriptions are indented two steps
+    /// further.
+    fn list_margin(&self) -> usize {
+        let depth = self.stack.iter()
+            .filter(|x| matches!(x, State::Dd(_) | State::Li(_)))
+            .count();
+
+        LEFT_MARGIN + 2 * INDENT * depth
+    }
+
     /// Push a state onto the stack
     fn push(&mut self, next: State) {
         if let Some(prev) = self.stack.last() {
@@ -484,23 +1073,119 @@ impl<'a> Reader<'a> {
     /// ```
     /// # use kosik::document::reader::Reader;
     /// let reader = Reader::new("<em>Ulysses</em>");
-    /// let root = reader.run();
+    /// let root = reader.run().unwrap();
     /// assert!(root.is_some());
     /// ```
-    pub fn run(mut self) -> Option<ElementType> {
+    pub fn run(mut self) -> Result<Option<ElementType>, SyntaxError> {
+        self.read()?;
+
+        if let Some(ElementType::Manuscript(elem)) = &mut self.root {
+            elem.attributes.word_count = self.word_count;
+            self.structure.apply(elem);
+        }
+
+        Ok(self.root)
+    }
+
+    /// Process the XML events of one element of a manuscript, leaving
+    /// out what needs the whole manuscript: the heading depths and the
+    /// word count of the manuscript element.
+    pub(crate) fn run_fragment(mut self) -> Result<Fragment, SyntaxError> {
+        self.read()?;
+
+        Ok(Fragment {
+            root: self.root,
+            numbering: self.numbering,
+            structure: self.structure,
+            word_count: self.word_count,
+        })
+    }
+
+    /// Start numbering where an earlier part of the manuscript left
+    /// off.
+    pub(crate) fn with_numbering(mut self, numbering: Numbering) -> Self {
+        self.numbering = numbering;
+        self
+    }
+
+    fn read(&mut self) -> Result<(), SyntaxError> {
         loop {
-            match self.xml_reader.read_event().unwrap() {
+            let start = self.xml_reader.buffer_position();
+
+            let event = match self.xml_reader.read_event() {
+                Ok(event) => event,
+                Err(error) => {
+                    return Err(SyntaxError {
+                        position: self.xml_reader.buffer_position(),
+                        message: error.to_string(),
+                    });
+                },
+            };
+
+            // The schema allows only text in a pre element, which
+            // has nowhere to keep a child element.
+            if let (Event::Start(tag) | Event::Empty(tag), Some(State::Pre(_)))
+                = (&event, self.stack.last())
+            {
+                return Err(SyntaxError {
+                    position: start,
+                    message: format!("<{}> is not allowed in <pre>, which holds only text",
+                                     String::from_utf8_lossy(tag.local_name().into_inner())),
+                });
+            }
+
+            match event {
                 Event::Start(ref event) => {
                     match event.local_name().into_inner() {
-                        b"attribution" => {
-                            let line_spacing = fetch_enum_attr!(
-                                event, b"lineSpacing", LineSpacing,
-                                |x| LineSpacing::from(x)
-                            ).unwrap_or(LineSpacing::Single);
-
-                            let elem = TextElement::new(Attribution {
-                                line_spacing: line_spacing,
-                            });
+                        b"act" => {
+                            let number;
+
+	                    if let Some(n)
+                                = fetch_numeric_attr!(event, b"number", i32)
+                            {
+	                        number = n;
+                                self.numbering.act = number + 1;
+
+	                    } else {
+                                number = self.numbering.act;
+                                self.numbering.act += 1;
+	                    }
+
+                            self.numbering.scene = 1; // reset scene number
+
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = TextElement::new(Act {
+                                number: number,
+                                line_spacing: line_spacing,
+                                depth: -1,
+                            });
+
+                            self.structure.acts = true;
+                            self.push(State::Act(elem));
+                        },
+                        b"action" => {
+                            let centered = fetch_bool_attr!(event, b"centered")
+                                .unwrap_or(false);
+
+                            let elem = TextElement::new(Action {
+                                centered: centered,
+                            });
+
+                            self.push(State::Action(elem));
+                        },
+                        b"attribution" => {
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = TextElement::new(Attribution {
+                                line_spacing: line_spacing,
+                            });
 
                             self.push(State::Attribution(elem));
                         },
@@ -552,6 +1237,18 @@ impl<'a> Reader<'a> {
                             let elem = ContainerElement::new(Body {});
                             self.push(State::Body(elem));
                         },
+                        b"castList" => {
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = ContainerElement::new(CastList {
+                                line_spacing: line_spacing,
+                            });
+
+                            self.push(State::CastList(elem));
+                        },
                         b"chapter" => {
                             let number;
                             
@@ -559,14 +1256,14 @@ impl<'a> Reader<'a> {
                                 = fetch_numeric_attr!(event, b"number", i32)
                             {
 	                        number = n;
-                                self.next_chapter_no = number + 1;
+                                self.numbering.chapter = number + 1;
 
 	                    } else {
-                                number = self.next_chapter_no;
-                                self.next_chapter_no += 1;
+                                number = self.numbering.chapter;
+                                self.numbering.chapter += 1;
 	                    }
 
-                            self.next_section_no = 1; // reset section number
+                            self.numbering.section = 1; // reset section number
 
                             let line_spacing = fetch_enum_attr!(
                                 event, b"lineSpacing", LineSpacing,
@@ -579,9 +1276,13 @@ impl<'a> Reader<'a> {
                                 depth: -1,
                             });
 
-                            self.has_chapters = true;
+                            self.structure.chapters = true;
                             self.push(State::Chapter(elem));
                         },
+                        b"character" => {
+                            let elem = TextElement::new(Character {});
+                            self.push(State::Character(elem));
+                        },
                         b"contact" => {
                             let line_spacing = fetch_enum_attr!(
                                 event, b"lineSpacing", LineSpacing,
@@ -594,10 +1295,104 @@ impl<'a> Reader<'a> {
 
                             self.push(State::Contact(elem));
                         },
+                        b"dd" => {
+                            let mut line_spacing = LineSpacing::Single;
+
+                            if let Some(State::Dl(parent)) = self.stack.last() {
+                                line_spacing = parent.attributes.line_spacing;
+                            }
+
+	                    if let Some(value) =
+                                fetch_enum_attr!(event, b"lineSpacing", LineSpacing,
+                                                 |x| LineSpacing::from(x))
+                            {
+	                        line_spacing = value;
+	                    }
+
+                            let elem = ContainerElement::new(Dd {
+                                line_spacing: line_spacing,
+                                left_margin: self.list_margin() + 2 * INDENT,
+                            });
+
+                            self.push(State::Dd(elem));
+                        },
+                        b"dedication" => {
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = ContainerElement::new(Dedication {
+                                line_spacing: line_spacing,
+                            });
+
+                            self.push(State::Dedication(elem));
+                        },
+                        b"dialogue" => {
+                            let elem = ContainerElement::new(Dialogue {});
+                            self.push(State::Dialogue(elem));
+                        },
+                        b"direction" => {
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = TextElement::new(Direction {
+                                line_spacing: line_spacing,
+                            });
+
+                            self.push(State::Direction(elem));
+                        },
+                        b"dl" => {
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = ContainerElement::new(Dl {
+                                line_spacing: line_spacing,
+                            });
+
+                            self.push(State::Dl(elem));
+                        },
+                        b"dt" => {
+                            let mut line_spacing = LineSpacing::Single;
+
+                            if let Some(State::Dl(parent)) = self.stack.last() {
+                                line_spacing = parent.attributes.line_spacing;
+                            }
+
+	                    if let Some(value) =
+                                fetch_enum_attr!(event, b"lineSpacing", LineSpacing,
+                                                 |x| LineSpacing::from(x))
+                            {
+	                        line_spacing = value;
+	                    }
+
+                            let elem = TextElement::new(Dt {
+                                line_spacing: line_spacing,
+                                left_margin: self.list_margin(),
+                            });
+
+                            self.push(State::Dt(elem));
+                        },
                         b"em" => {
                             let elem = TextElement::new(Em {});
                             self.push(State::Em(elem));
                         },
+                        b"epigraph" => {
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = ContainerElement::new(Epigraph {
+                                line_spacing: line_spacing,
+                            });
+
+                            self.push(State::Epigraph(elem));
+                        },
                         b"footnote" => {
                             let label;
                             
@@ -605,12 +1400,12 @@ impl<'a> Reader<'a> {
 	                        label = s;
 
                                 if let Ok(n) = label.parse::<i32>() {
-                                    self.next_note_no = n + 1;
+                                    self.numbering.note = n + 1;
                                 }
                                 
 	                    } else {
-                                label = format!("{}", self.next_note_no);
-                                self.next_note_no += 1;
+                                label = format!("{}", self.numbering.note);
+                                self.numbering.note += 1;
                             }
 
                             let line_spacing = fetch_enum_attr!(
@@ -643,31 +1438,43 @@ impl<'a> Reader<'a> {
                         },
                         b"li" => {
                             let mut number: Option<i32> = None;
-                            
-                            if let Some(n) = self.next_li_no {
-	                        if let Some(n) = fetch_numeric_attr!(event, b"number", i32) {
-	                            number = Some(n);
-                                    self.next_li_no = Some(n + 1);
-	                        } else {
-                                    number = Some(n);
-                                    self.next_li_no = Some(n + 1);
-                                }
-                            }
-
+                            let mut level: usize = 0;
                             let mut line_spacing = LineSpacing::Single;
 
                             if let Some(state) = self.stack.last() {
                                 match state {
                                     State::Ol(parent) => {
+                                        // Continue from the previous item.
+                                        number = parent.children.iter().rev()
+                                            .find_map(|child| match child {
+                                                ElementType::Li(child) => {
+                                                    child.attributes.number
+                                                },
+                                                _ => None,
+                                            })
+                                            .map(|n| n + 1)
+                                            .or(Some(parent.attributes.start_no));
+                                        level = self.stack.iter()
+                                            .filter(|x| matches!(x, State::Ol(_)))
+                                            .count() - 1;
                                         line_spacing = parent.attributes.line_spacing;
                                     },
                                     State::Ul(parent) => {
+                                        level = self.stack.iter()
+                                            .filter(|x| matches!(x, State::Ul(_)))
+                                            .count() - 1;
                                         line_spacing = parent.attributes.line_spacing;
                                     },
                                     _ => (),
                                 }
                             }
 
+                            if number.is_some() {
+	                        if let Some(n) = fetch_numeric_attr!(event, b"number", i32) {
+	                            number = Some(n);
+	                        }
+                            }
+
 	                    if let Some(value) =
                                 fetch_enum_attr!(event, b"lineSpacing", LineSpacing,
                                                  |x| LineSpacing::from(x))
@@ -677,7 +1484,9 @@ impl<'a> Reader<'a> {
                             
                             let elem = ContainerElement::new(Li {
                                 number: number,
+                                level: level,
                                 line_spacing: line_spacing,
+                                left_margin: self.list_margin() + 2 * INDENT,
                             });
 
                             self.push(State::Li(elem));
@@ -685,7 +1494,7 @@ impl<'a> Reader<'a> {
                         b"manuscript" => {
                             let first_page = fetch_numeric_attr!(
                                 event, b"firstPage", i32
-                            ).unwrap_or(1);
+                            ).unwrap_or(self.first_page);
 
                             let elem = ContainerElement::new(Manuscript {
                                 first_page: first_page,
@@ -700,8 +1509,6 @@ impl<'a> Reader<'a> {
                                 event, b"startNo", i32
                             ).unwrap_or(1);
 
-                            self.next_li_no = Some(start_no);
-
                             let line_spacing = fetch_enum_attr!(
                                 event, b"lineSpacing", LineSpacing,
                                 |x| LineSpacing::from(x)
@@ -715,10 +1522,8 @@ impl<'a> Reader<'a> {
                             self.push(State::Ol(elem));
                         },
                         b"p" => {
-                            let indent = fetch_numeric_attr!(event, b"indent", usize)
-                                .unwrap_or(INDENT);
-                            
-                            let mut line_spacing = LineSpacing::Double;
+                            let mut indent = INDENT;
+                            let mut line_spacing = self.line_spacing;
                             let mut left_margin = LEFT_MARGIN;
                             let mut right_margin = RIGHT_MARGIN;
                             
@@ -729,17 +1534,49 @@ impl<'a> Reader<'a> {
                                         left_margin += INDENT;
                                         right_margin -= INDENT;
                                     },
+                                    State::CastList(parent) => {
+                                        indent = 0;
+                                        line_spacing = parent.attributes.line_spacing;
+                                    },
+                                    State::Dedication(parent) => {
+                                        line_spacing = parent.attributes.line_spacing;
+                                    },
+                                    State::Epigraph(parent) => {
+                                        indent = 0;
+                                        line_spacing = parent.attributes.line_spacing;
+                                        left_margin = EPIGRAPH_MARGIN;
+                                    },
                                     State::Footnote(parent) => {
                                         line_spacing = parent.attributes.line_spacing;
                                     },
+                                    State::Dd(parent) => {
+                                        line_spacing = parent.attributes.line_spacing;
+                                        left_margin = parent.attributes.left_margin;
+                                    },
+                                    State::Dialogue(_) => {
+                                        indent = 0;
+                                        line_spacing = LineSpacing::Single;
+                                        left_margin = DIALOGUE_MARGIN;
+                                        right_margin = DIALOGUE_RIGHT_MARGIN;
+                                    },
                                     State::Li(parent) => {
                                         line_spacing = parent.attributes.line_spacing;
-                                        left_margin += INDENT * 2;
+                                        left_margin = parent.attributes.left_margin;
+                                    },
+                                    State::Speech(parent) => {
+                                        indent = 0;
+                                        line_spacing = parent.attributes.line_spacing;
                                     },
                                     _ => (),
                                 }
                             }
 
+	                    if let Some(value) =
+                                fetch_numeric_attr!(event, b"indent", usize)
+                            {
+	                        indent = value;
+                            }
+
 	                    if let Some(value) =
                                 fetch_enum_attr!(event, b"lineSpacing", LineSpacing,
                                                  |x| LineSpacing::from(x))
@@ -756,19 +1593,23 @@ impl<'a> Reader<'a> {
 
                             self.push(State::P(elem));
                         },
+                        b"parenthetical" => {
+                            let elem = TextElement::new(Parenthetical {});
+                            self.push(State::Parenthetical(elem));
+                        },
                         b"part" => {
                             let number;
                             
 	                    if let Some(n) = fetch_numeric_attr!(event, b"number", i32) {
 	                        number = n;
-                                self.next_part_no = number + 1;
+                                self.numbering.part = number + 1;
 	                    } else {
-                                number = self.next_part_no;
-                                self.next_part_no += 1;
+                                number = self.numbering.part;
+                                self.numbering.part += 1;
 	                    }
 
-                            self.next_chapter_no = 1; // reset chapter number
-                            self.next_section_no = 1; // reset section number
+                            self.numbering.chapter = 1; // reset chapter number
+                            self.numbering.section = 1; // reset section number
 
                             let line_spacing = fetch_enum_attr!(
                                 event, b"lineSpacing", LineSpacing,
@@ -781,17 +1622,78 @@ impl<'a> Reader<'a> {
                                 depth: -1,
                             });
 
-                            self.has_parts = true;
+                            self.structure.parts = true;
                             self.push(State::Part(elem));
                         },
                         b"person" => {
                             let elem = ContainerElement::new(Person {});
                             self.push(State::Person(elem));
                         },
+                        b"pre" => {
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = TextElement::new(Pre {
+                                line_spacing: line_spacing,
+                            });
+
+                            self.push(State::Pre(elem));
+                        },
                         b"prefix" => {
                             let elem = TextElement::new(Prefix {});
                             self.push(State::Prefix(elem));
                         },
+                        b"scene" => {
+                            let mut padding_before: i32 = -1;
+
+                            if let Some(State::Body(parent))
+                                = self.stack.last()
+                            {
+                                if let Some(ElementType::Act(_))
+                                    = parent.children.last()
+                                {
+                                    padding_before = 0;
+                                }
+                            }
+
+                            let number;
+
+	                    if let Some(n)
+                                = fetch_numeric_attr!(event, b"number", i32)
+                            {
+	                        number = n;
+                                self.numbering.scene = number + 1;
+
+	                    } else {
+                                number = self.numbering.scene;
+                                self.numbering.scene += 1;
+	                    }
+
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = TextElement::new(Scene {
+                                number: number,
+                                line_spacing: line_spacing,
+                                padding_before: padding_before,
+                                depth: -1,
+                            });
+
+                            self.structure.scenes = true;
+                            self.push(State::Scene(elem));
+                        },
+                        b"sceneHeading" => {
+                            let elem = TextElement::new(SceneHeading {});
+                            self.push(State::SceneHeading(elem));
+                        },
+                        b"screenplay" => {
+                            let elem = ContainerElement::new(Screenplay {});
+                            self.push(State::Screenplay(elem));
+                        },
                         b"section" => {
                             let mut padding_before: i32 = -1;
                             
@@ -811,11 +1713,11 @@ impl<'a> Reader<'a> {
                                 = fetch_numeric_attr!(event, b"number", i32)
                             {
 	                        number = n;
-                                self.next_section_no = number + 1;
+                                self.numbering.section = number + 1;
 
 	                    } else {
-                                number = self.next_section_no;
-                                self.next_section_no += 1;
+                                number = self.numbering.section;
+                                self.numbering.section += 1;
 	                    }
 
                             let line_spacing = fetch_enum_attr!(
@@ -830,13 +1732,29 @@ impl<'a> Reader<'a> {
                                 depth: -1,
                             });
                             
-                            self.has_sections = true;
+                            self.structure.sections = true;
                             self.push(State::Section(elem));
                         },
                         b"sn" => {
                             let elem = TextElement::new(Sn {});
                             self.push(State::Sn(elem));
                         },
+                        b"speaker" => {
+                            let elem = TextElement::new(Speaker {});
+                            self.push(State::Speaker(elem));
+                        },
+                        b"speech" => {
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = ContainerElement::new(Speech {
+                                line_spacing: line_spacing,
+                            });
+
+                            self.push(State::Speech(elem));
+                        },
                         b"sub" => {
                             let elem = TextElement::new(Sub {});
                             self.push(State::Sub(elem));
@@ -867,6 +1785,14 @@ impl<'a> Reader<'a> {
                             let elem = TextElement::new(Sup {});
                             self.push(State::Sup(elem));
                         },
+                        b"table" => {
+                            let elem = ContainerElement::new(Table {});
+                            self.push(State::Table(elem));
+                        },
+                        b"td" => {
+                            let elem = TextElement::new(Td {});
+                            self.push(State::Td(elem));
+                        },
                         b"title" => {
                             let line_spacing = fetch_enum_attr!(
                                 event, b"lineSpacing", LineSpacing,
@@ -879,6 +1805,20 @@ impl<'a> Reader<'a> {
 
                             self.push(State::Title(elem));
                         },
+                        b"tr" => {
+                            let header = fetch_bool_attr!(event, b"header")
+                                .unwrap_or(false);
+
+                            let elem = ContainerElement::new(Tr {
+                                header: header,
+                            });
+
+                            self.push(State::Tr(elem));
+                        },
+                        b"transition" => {
+                            let elem = TextElement::new(Transition {});
+                            self.push(State::Transition(elem));
+                        },
                         b"ul" => {
                             let line_spacing = fetch_enum_attr!(
                                 event, b"lineSpacing", LineSpacing,
@@ -897,6 +1837,37 @@ impl<'a> Reader<'a> {
                 Event::End(_) => self.pop(),
 	        Event::Empty(ref event) => {
                     match event.local_name().into_inner() {
+                        b"act" => {
+                            let number;
+
+	                    if let Some(n)
+                                = fetch_numeric_attr!(event, b"number", i32)
+                            {
+	                        number = n;
+                                self.numbering.act = number + 1;
+
+	                    } else {
+                                number = self.numbering.act;
+                                self.numbering.act += 1;
+	                    }
+
+                            self.numbering.scene = 1; // reset scene number
+
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = TextElement::new(Act {
+                                number: number,
+                                line_spacing: line_spacing,
+                                depth: -1,
+                            });
+
+                            self.structure.acts = true;
+                            self.push(State::Act(elem));
+                            self.pop();
+                        },
                         b"br" => {
                             self.push(State::Br(EmptyElement::new(Br {})));
                             self.pop();
@@ -908,14 +1879,14 @@ impl<'a> Reader<'a> {
                                 = fetch_numeric_attr!(event, b"number", i32)
                             {
 	                        number = n;
-                                self.next_chapter_no = number + 1;
+                                self.numbering.chapter = number + 1;
 
 	                    } else {
-                                number = self.next_chapter_no;
-                                self.next_chapter_no += 1;
+                                number = self.numbering.chapter;
+                                self.numbering.chapter += 1;
 	                    }
 
-                            self.next_section_no = 1; // reset section number
+                            self.numbering.section = 1; // reset section number
 
                             let line_spacing = fetch_enum_attr!(
                                 event, b"lineSpacing", LineSpacing,
@@ -948,41 +1919,13 @@ impl<'a> Reader<'a> {
                             self.push(State::PageBreak(EmptyElement::new(PageBreak {})));
                             self.pop();
                         },
-                        b"part" => {
-                            let number;
-                            
-	                    if let Some(n) = fetch_numeric_attr!(event, b"number", i32) {
-	                        number = n;
-                                self.next_part_no = number + 1;
-	                    } else {
-                                number = self.next_part_no;
-                                self.next_part_no += 1;
-	                    }
-
-                            self.next_chapter_no = 1; // reset chapter number
-                            self.next_section_no = 1; // reset section number
-
-                            let line_spacing = fetch_enum_attr!(
-                                event, b"lineSpacing", LineSpacing,
-                                |x| LineSpacing::from(x)
-                            ).unwrap_or(LineSpacing::Single);
-
-                            let elem = TextElement::new(Part {
-                                number: number,
-                                line_spacing: line_spacing,
-                                depth: -1,
-                            });
-
-                            self.push(State::Part(elem));
-                            self.pop();
-                        },
-                        b"section" => {
+                        b"scene" => {
                             let mut padding_before: i32 = -1;
-                            
+
                             if let Some(State::Body(parent))
                                 = self.stack.last()
                             {
-                                if let Some(ElementType::Chapter(_))
+                                if let Some(ElementType::Act(_))
                                     = parent.children.last()
                                 {
                                     padding_before = 0;
@@ -990,16 +1933,16 @@ impl<'a> Reader<'a> {
                             }
 
                             let number;
-                            
+
 	                    if let Some(n)
                                 = fetch_numeric_attr!(event, b"number", i32)
                             {
 	                        number = n;
-                                self.next_section_no = number + 1;
+                                self.numbering.scene = number + 1;
 
 	                    } else {
-                                number = self.next_section_no;
-                                self.next_section_no += 1;
+                                number = self.numbering.scene;
+                                self.numbering.scene += 1;
 	                    }
 
                             let line_spacing = fetch_enum_attr!(
@@ -1007,7 +1950,77 @@ impl<'a> Reader<'a> {
                                 |x| LineSpacing::from(x)
                             ).unwrap_or(LineSpacing::Single);
 
-                            let elem = TextElement::new(Section {
+                            let elem = TextElement::new(Scene {
+                                number: number,
+                                line_spacing: line_spacing,
+                                padding_before: padding_before,
+                                depth: -1,
+                            });
+
+                            self.structure.scenes = true;
+                            self.push(State::Scene(elem));
+                            self.pop();
+                        },
+                        b"part" => {
+                            let number;
+                            
+	                    if let Some(n) = fetch_numeric_attr!(event, b"number", i32) {
+	                        number = n;
+                                self.numbering.part = number + 1;
+	                    } else {
+                                number = self.numbering.part;
+                                self.numbering.part += 1;
+	                    }
+
+                            self.numbering.chapter = 1; // reset chapter number
+                            self.numbering.section = 1; // reset section number
+
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = TextElement::new(Part {
+                                number: number,
+                                line_spacing: line_spacing,
+                                depth: -1,
+                            });
+
+                            self.push(State::Part(elem));
+                            self.pop();
+                        },
+                        b"section" => {
+                            let mut padding_before: i32 = -1;
+                            
+                            if let Some(State::Body(parent))
+                                = self.stack.last()
+                            {
+                                if let Some(ElementType::Chapter(_))
+                                    = parent.children.last()
+                                {
+                                    padding_before = 0;
+                                }
+                            }
+
+                            let number;
+                            
+	                    if let Some(n)
+                                = fetch_numeric_attr!(event, b"number", i32)
+                            {
+	                        number = n;
+                                self.numbering.section = number + 1;
+
+	                    } else {
+                                number = self.numbering.section;
+                                self.numbering.section += 1;
+	                    }
+
+                            let line_spacing = fetch_enum_attr!(
+                                event, b"lineSpacing", LineSpacing,
+                                |x| LineSpacing::from(x)
+                            ).unwrap_or(LineSpacing::Single);
+
+                            let elem = TextElement::new(Section {
                                 number: number,
                                 line_spacing: line_spacing,
                                 padding_before: padding_before,
@@ -1024,6 +2037,22 @@ impl<'a> Reader<'a> {
                     let n: usize;
                     
                     match self.stack.pop() {
+                        Some(State::Act(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+
+                            self.word_count += n;
+                            self.stack.push(State::Act(elem));
+                        },
+                        Some(State::Action(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+
+                            self.word_count += n;
+                            self.stack.push(State::Action(elem));
+                        },
                         Some(State::Attribution(mut elem)) => {
                             (n, elem.tokens) = self
                                 .parse_text(event, elem.tokens,
@@ -1073,6 +2102,39 @@ impl<'a> Reader<'a> {
 
                             self.stack.push(State::Blockquote(elem));
                         },
+                        Some(State::CastList(mut elem)) => {
+                            if let Some(ElementType::P(_))
+                                = elem.children.last()
+                            {
+                                if let Some(ElementType::P(mut wrapper))
+                                    = elem.children.pop()
+                                {
+                                    (n, wrapper.tokens) = self
+                                        .parse_text(event, wrapper.tokens,
+                                                    Default::default());
+
+                                    self.word_count += n;
+                                    elem.children.push(ElementType::P(wrapper));
+                                }
+
+                            } else {
+                                let mut wrapper = TextElement::new(P {
+                                    indent: 0,
+                                    line_spacing: elem.attributes.line_spacing,
+                                    left_margin: LEFT_MARGIN,
+                                    right_margin: RIGHT_MARGIN,
+                                });
+
+                                (n, wrapper.tokens) = self
+                                    .parse_text(event, wrapper.tokens,
+                                                Default::default());
+
+                                self.word_count += n;
+                                elem.children.push(ElementType::P(wrapper));
+                            }
+
+                            self.stack.push(State::CastList(elem));
+                        },
                         Some(State::Chapter(mut elem)) => {
                             (n, elem.tokens) = self
                                 .parse_text(event, elem.tokens,
@@ -1081,6 +2143,14 @@ impl<'a> Reader<'a> {
                             self.word_count += n;
                             self.stack.push(State::Chapter(elem));
                         },
+                        Some(State::Character(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+
+                            self.word_count += n;
+                            self.stack.push(State::Character(elem));
+                        },
                         Some(State::Contact(mut elem)) => {
                             (n, elem.tokens) = self
                                 .parse_text(event, elem.tokens,
@@ -1089,6 +2159,121 @@ impl<'a> Reader<'a> {
                             self.word_count += n;
                             self.stack.push(State::Contact(elem));
                         },
+                        Some(State::Dedication(mut elem)) => {
+                            if let Some(ElementType::P(_)) =
+                                elem.children.last()
+                            {
+                                if let Some(ElementType::P(mut wrapper))
+                                    = elem.children.pop()
+                                {
+                                    (n, wrapper.tokens) = self
+                                        .parse_text(event, wrapper.tokens,
+                                                    Default::default());
+                                    
+                                    self.word_count += n;
+                                    elem.children.push(ElementType::P(wrapper));
+                                }
+
+                            } else {
+                                let mut wrapper = TextElement::new(P {
+                                    indent: 0,
+                                    line_spacing: elem.attributes.line_spacing,
+                                    left_margin: LEFT_MARGIN,
+                                    right_margin: RIGHT_MARGIN,
+                                });
+
+                                (n, wrapper.tokens) = self
+                                    .parse_text(event, wrapper.tokens,
+                                                Default::default());
+
+                                self.word_count += n;
+                                elem.children.push(ElementType::P(wrapper));
+                            }
+
+                            self.stack.push(State::Dedication(elem));
+                        },
+                        Some(State::Dialogue(mut elem)) => {
+                            if let Some(ElementType::P(_))
+                                = elem.children.last()
+                            {
+                                if let Some(ElementType::P(mut wrapper))
+                                    = elem.children.pop()
+                                {
+                                    (n, wrapper.tokens) = self
+                                        .parse_text(event, wrapper.tokens,
+                                                    Default::default());
+
+                                    self.word_count += n;
+                                    elem.children.push(ElementType::P(wrapper));
+                                }
+
+                            } else {
+                                let mut wrapper = TextElement::new(P {
+                                    indent: 0,
+                                    line_spacing: LineSpacing::Single,
+                                    left_margin: DIALOGUE_MARGIN,
+                                    right_margin: DIALOGUE_RIGHT_MARGIN,
+                                });
+
+                                (n, wrapper.tokens) = self
+                                    .parse_text(event, wrapper.tokens,
+                                                Default::default());
+
+                                self.word_count += n;
+                                elem.children.push(ElementType::P(wrapper));
+                            }
+
+                            self.stack.push(State::Dialogue(elem));
+                        },
+                        Some(State::Direction(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+
+                            self.word_count += n;
+                            self.stack.push(State::Direction(elem));
+                        },
+                        Some(State::Dd(mut elem)) => {
+                            if let Some(ElementType::P(_))
+                                = elem.children.last()
+                            {
+                                if let Some(ElementType::P(mut wrapper))
+                                    = elem.children.pop()
+                                {
+                                    (n, wrapper.tokens) = self
+                                        .parse_text(event, wrapper.tokens,
+                                                    Default::default());
+
+                                    self.word_count += n;
+                                    elem.children.push(ElementType::P(wrapper));
+                                }
+
+                            } else {
+                                let mut wrapper = TextElement::new(P {
+                                    indent: 0,
+                                    line_spacing: elem.attributes.line_spacing,
+                                    left_margin: elem.attributes.left_margin,
+                                    right_margin: RIGHT_MARGIN,
+                                });
+
+                                (n, wrapper.tokens) = self
+                                    .parse_text(event, wrapper.tokens,
+                                                Default::default());
+
+                                self.word_count += n;
+                                elem.children.push(ElementType::P(wrapper));
+                            }
+
+                            self.stack.push(State::Dd(elem));
+                        },
+                        Some(State::Dt(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+                            
+                            self.word_count += n;
+                            self.stack.push(State::Dt(elem));
+                        },
                         Some(State::Em(mut elem)) => {
                             (n, elem.tokens) = self.
                                 parse_text(event, elem.tokens,
@@ -1097,6 +2282,39 @@ impl<'a> Reader<'a> {
                             self.word_count += n;
                             self.stack.push(State::Em(elem));
                         },
+                        Some(State::Epigraph(mut elem)) => {
+                            if let Some(ElementType::P(_)) =
+                                elem.children.last()
+                            {
+                                if let Some(ElementType::P(mut wrapper))
+                                    = elem.children.pop()
+                                {
+                                    (n, wrapper.tokens) = self
+                                        .parse_text(event, wrapper.tokens,
+                                                    Default::default());
+                                    
+                                    self.word_count += n;
+                                    elem.children.push(ElementType::P(wrapper));
+                                }
+
+                            } else {
+                                let mut wrapper = TextElement::new(P {
+                                    indent: 0,
+                                    line_spacing: elem.attributes.line_spacing,
+                                    left_margin: EPIGRAPH_MARGIN,
+                                    right_margin: RIGHT_MARGIN,
+                                });
+
+                                (n, wrapper.tokens) = self
+                                    .parse_text(event, wrapper.tokens,
+                                                Default::default());
+
+                                self.word_count += n;
+                                elem.children.push(ElementType::P(wrapper));
+                            }
+
+                            self.stack.push(State::Epigraph(elem));
+                        },
                         Some(State::Footnote(mut elem)) => {
                             if let Some(ElementType::P(_))
                                 = elem.children.last()
@@ -1157,7 +2375,7 @@ impl<'a> Reader<'a> {
                                 let mut wrapper = TextElement::new(P {
                                     indent: 0,
                                     line_spacing: elem.attributes.line_spacing,
-                                    left_margin: LEFT_MARGIN + 2 * INDENT,
+                                    left_margin: elem.attributes.left_margin,
                                     right_margin: RIGHT_MARGIN,
                                 });
 
@@ -1179,6 +2397,14 @@ impl<'a> Reader<'a> {
                             self.word_count += n;
                             self.stack.push(State::P(elem));
                         },
+                        Some(State::Parenthetical(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+
+                            self.word_count += n;
+                            self.stack.push(State::Parenthetical(elem));
+                        },
                         Some(State::Part(mut elem)) => {
                             (n, elem.tokens) = self
                                 .parse_text(event, elem.tokens,
@@ -1187,6 +2413,13 @@ impl<'a> Reader<'a> {
                             self.word_count += n;
                             self.stack.push(State::Part(elem));
                         },
+                        Some(State::Pre(mut elem)) => {
+                            // Bypass the text parser.
+                            let text = event.unescape().unwrap();
+                            self.word_count += text.split_whitespace().count();
+                            elem.tokens.push(TokenType::Word(Token::from(&text[..])));
+                            self.stack.push(State::Pre(elem));
+                        },
                         Some(State::Prefix(mut elem)) => {
                             (n, elem.tokens) = self
                                 .parse_text(event, elem.tokens,
@@ -1195,6 +2428,22 @@ impl<'a> Reader<'a> {
                             self.word_count += n;
                             self.stack.push(State::Prefix(elem));
                         },
+                        Some(State::Scene(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+
+                            self.word_count += n;
+                            self.stack.push(State::Scene(elem));
+                        },
+                        Some(State::SceneHeading(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+
+                            self.word_count += n;
+                            self.stack.push(State::SceneHeading(elem));
+                        },
                         Some(State::Section(mut elem)) => {
                             (n, elem.tokens) = self
                                 .parse_text(event, elem.tokens,
@@ -1227,6 +2476,47 @@ impl<'a> Reader<'a> {
                             self.word_count += n;
                             self.stack.push(State::Sn(elem));
                         },
+                        Some(State::Speaker(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+
+                            self.word_count += n;
+                            self.stack.push(State::Speaker(elem));
+                        },
+                        Some(State::Speech(mut elem)) => {
+                            if let Some(ElementType::P(_))
+                                = elem.children.last()
+                            {
+                                if let Some(ElementType::P(mut wrapper))
+                                    = elem.children.pop()
+                                {
+                                    (n, wrapper.tokens) = self
+                                        .parse_text(event, wrapper.tokens,
+                                                    Default::default());
+
+                                    self.word_count += n;
+                                    elem.children.push(ElementType::P(wrapper));
+                                }
+
+                            } else {
+                                let mut wrapper = TextElement::new(P {
+                                    indent: 0,
+                                    line_spacing: elem.attributes.line_spacing,
+                                    left_margin: LEFT_MARGIN,
+                                    right_margin: RIGHT_MARGIN,
+                                });
+
+                                (n, wrapper.tokens) = self
+                                    .parse_text(event, wrapper.tokens,
+                                                Default::default());
+
+                                self.word_count += n;
+                                elem.children.push(ElementType::P(wrapper));
+                            }
+
+                            self.stack.push(State::Speech(elem));
+                        },
                         Some(State::Subtitle(mut elem)) => {
                             (n, elem.tokens) = self
                                 .parse_text(event, elem.tokens,
@@ -1243,6 +2533,14 @@ impl<'a> Reader<'a> {
                             self.word_count += n;
                             self.stack.push(State::Sup(elem));
                         },
+                        Some(State::Td(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+                            
+                            self.word_count += n;
+                            self.stack.push(State::Td(elem));
+                        },
                         Some(State::Title(mut elem)) => {
                             (n, elem.tokens) = self
                                 .parse_text(event, elem.tokens,
@@ -1251,12 +2549,33 @@ impl<'a> Reader<'a> {
                             self.word_count += n;
                             self.stack.push(State::Title(elem));
                         },
+                        Some(State::Transition(mut elem)) => {
+                            (n, elem.tokens) = self
+                                .parse_text(event, elem.tokens,
+                                            Default::default());
+
+                            self.word_count += n;
+                            self.stack.push(State::Transition(elem));
+                        },
                         Some(state) => self.stack.push(state),
                         None => (),
                     }
                 },
 	        Event::Comment(_) => (), // ignore comments
-	        Event::CData(_) => (), // not handled
+	        Event::CData(ref event) => {
+                    // Character data is only meaningful in preformatted
+                    // text.
+                    match self.stack.pop() {
+                        Some(State::Pre(mut elem)) => {
+                            let text = String::from_utf8_lossy(event);
+                            self.word_count += text.split_whitespace().count();
+                            elem.tokens.push(TokenType::Word(Token::from(&text[..])));
+                            self.stack.push(State::Pre(elem));
+                        },
+                        Some(state) => self.stack.push(state),
+                        None => (),
+                    }
+                },
 	        Event::Decl(_) => (), // ignore declaration
 	        Event::PI(_) => (), // not handled
 	        Event::DocType(_) => (), // not handled
@@ -1264,56 +2583,7 @@ impl<'a> Reader<'a> {
             }
         }
 
-        // post-processing
-        
-        if let Some(elem) = &mut self.root {
-            match elem {
-                ElementType::Manuscript(elem) => {
-                    elem.attributes.word_count = self.word_count;
-
-                    let part_depth = if self.has_parts {
-                        elem.attributes.has_structure = true;
-                        0
-                    } else {
-                        -1
-                    };
-
-                    let chapter_depth = if self.has_chapters {
-                        elem.attributes.has_structure = true;
-                        if part_depth >= 0 { 1 } else { 0 }
-                    } else {
-                        -1
-                    };
-
-                    let section_depth = if self.has_sections {
-                        elem.attributes.has_structure = true;
-                        if part_depth >= 0 && chapter_depth >= 0 { 2 } else { 1 }
-                    } else {
-                        -1
-                    };
-
-                    if let Some(body) = elem.body() {
-                        for child in body.children.iter_mut() {
-                            match child {
-                                ElementType::Chapter(child) => {
-                                    child.attributes.depth = chapter_depth;
-                                },
-                                ElementType::Part(child) => {
-                                    child.attributes.depth = part_depth;
-                                },
-                                ElementType::Section(child) => {
-                                    child.attributes.depth = section_depth;
-                                },
-                                _ => (),
-                            }
-                        }
-                    }
-                },
-                _ => (),
-            }
-        }
-        
-        self.root
+        Ok(())
     }
 
     fn parse_text(&mut self, event: &BytesText, tokens: TokenList, dpy: DisplayFlags)
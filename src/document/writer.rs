@@ -20,16 +20,18 @@
 //! # Examples
 //!
 //! ```rust,no_run
-//! use kosik::document::{Page, Typescript};
+//! use kosik::document::{Layout, Page, Typescript};
 //! use kosik::document::writer::Writer;
 //! use kosik::text::{Line, Segment};
 //!
 //! let typescript = Typescript {
+//!     layout: Layout::Manuscript,
 //!     contact: None,
 //!     word_count: None,
 //!     has_structure: false,
 //!     short_title: Segment::from("WORKING TITLE"),
 //!     short_author_name: Segment::from("ANONYMOUS"),
+//!     running_heads: Vec::new(),
 //!     pages: vec![Page {
 //!         number: 1,
 //!         height: 54,
@@ -47,9 +49,7 @@ use std::str;
 
 use encoding::{Encoding, EncoderTrap};
 use encoding::all::ISO_8859_15;
-use math::round;
 use regex::Regex;
-use thousands::Separable;
 
 use crate::PROGRAM_NAME;
 use crate::PROLOGUE_FILE;
@@ -59,18 +59,29 @@ use crate::document::*;
 pub struct Writer<'a> {
     typescript: &'a Typescript,
     real_page_no: usize,
+    output: Box<dyn Write + 'a>,
 }
 
-impl<'a> Writer<'_> {
+impl<'a> Writer<'a> {
     /// Creates a document writer
     pub fn new(typescript: &'a Typescript) -> Writer<'a> {
+        Writer::with_output(typescript, Box::new(io::stdout()))
+    }
+
+    /// Creates a document writer that writes somewhere other than the
+    /// standard output
+    pub fn with_output(typescript: &'a Typescript, output: Box<dyn Write + 'a>)
+                       -> Writer<'a>
+    {
         Writer {
             typescript: typescript,
             real_page_no: 1,
+            output: output,
         }
     }
 
-    /// Writes the document to the standard output
+    /// Writes the document to the standard output, or to the output
+    /// given to [`Writer::with_output`]
     pub fn run(&mut self) -> Result<(), Box<dyn Error>> {
         self.write_prologue()?;
 
@@ -94,7 +105,7 @@ impl<'a> Writer<'_> {
                     Some(line) => {
                         let x = (line.column as f32 * CHAR_WIDTH).round() as i32;
 
-                        writeln(&format!("{} {} moveto {}", x, y, line.ps()))?;
+                        self.writeln(&format!("{} {} moveto {}", x, y, line.ps()))?;
 
                         y -= LINE_HEIGHT.round() as i32;
                     },
@@ -108,14 +119,14 @@ impl<'a> Writer<'_> {
                 let x = (LEFT_MARGIN as f32 * CHAR_WIDTH).round() as i32;
                 y = ((BOTTOM_LINE + page.footer.len() + 2) as f32 * LINE_HEIGHT).round() as i32;
 
-                writeln(&format!("{} {} moveto (____________________) show ", x, y))?;
+                self.writeln(&format!("{} {} moveto (____________________) show ", x, y))?;
 
                 y -= (2.0 * LINE_HEIGHT).round() as i32;
 
                 for line in page.footer.iter() {
                     match line {
 			Some(line) => {
-		            writeln(&format!("{} {} moveto {}", x, y, line.ps()))?;
+		            self.writeln(&format!("{} {} moveto {}", x, y, line.ps()))?;
                             y -= LINE_HEIGHT.round() as i32;
 			},
 			None => {
@@ -125,10 +136,12 @@ impl<'a> Writer<'_> {
                 }
             }
 
-            writeln("page-end")?;
+            self.writeln("page-end")?;
         }
 
-        writeln("%%Trailer")
+        self.writeln("%%Trailer")?;
+        self.output.flush()?;
+        Ok(())
     }
 
     #[doc(hidden)]
@@ -147,13 +160,19 @@ impl<'a> Writer<'_> {
         prologue = creator_pat.replace(&prologue, &creator).to_string();
         prologue = pages_pat.replace(&prologue, &num_pages).to_string();
 
-        write(&prologue)
+        self.write(&prologue)
     }
 
     #[doc(hidden)]
     fn write_contact(&mut self) -> Result<(), Box<dyn Error>> {
         if let Some(block) = &self.typescript.contact {
-            let mut y = (TOP_LINE as f32 * LINE_HEIGHT as f32).round() as i32;
+            let line = match self.typescript.layout {
+                Layout::Manuscript => TOP_LINE,
+                // bottom left corner of the title page
+                Layout::Screenplay => BOTTOM_LINE + block.count_lines() - 1,
+            };
+
+            let mut y = (line as f32 * LINE_HEIGHT as f32).round() as i32;
 
             for (i, line) in block.lines.iter().enumerate() {
                 if i > 0 && block.line_spacing == LineSpacing::Double {
@@ -162,7 +181,7 @@ impl<'a> Writer<'_> {
             
                 let x = (line.column as f32 * CHAR_WIDTH).round() as i32;
 
-                write(&format!("{} {} moveto {}", x, y, line.ps()))?;
+                self.write(&format!("{} {} moveto {}", x, y, line.ps()))?;
                 y -= LINE_HEIGHT.round() as i32;
             }
         }
@@ -172,28 +191,10 @@ impl<'a> Writer<'_> {
         
     #[doc(hidden)]
     fn write_word_count(&mut self) -> Result<(), Box<dyn Error>> {
-        if let Some(word_count) = self.typescript.word_count {
-            let n = if word_count > 1000 {
-                // nearest thousand
-                (round::half_to_even(word_count as f64 / 10000.0, 1)
-                 * 10000.0) as i32
-            } else {
-                // nearest hundred
-                (round::half_to_even(word_count as f64 / 1000.0, 1)
-                 * 1000.0) as i32
-            };
-            
-            let s = format!("Approx. {} words", n.separate_with_commas());
-            
-            let line = Line {
-                column: RIGHT_MARGIN - s.chars().count(),
-                segments: vec![Segment::from(s)],
-                note_refs: Vec::new(),
-            };
-                
+        if let Some(line) = self.typescript.word_count_line() {
             let x = (line.column as f32 * CHAR_WIDTH).round() as i32;
             let y = (TOP_LINE as f32 * LINE_HEIGHT as f32).round() as i32;
-            write(&format!("{} {} moveto {}", x, y, line.ps()))?;
+            self.write(&format!("{} {} moveto {}", x, y, line.ps()))?;
         }
 
         Ok(())
@@ -201,47 +202,35 @@ impl<'a> Writer<'_> {
 
     #[doc(hidden)]
     fn start_a_new_page(&mut self, page_no: i32) -> Result<(), Box<dyn Error>> {
-        writeln(&format!("%%Page: {} {}", self.real_page_no, self.real_page_no))?;
-        writeln("page-begin")?;
+        self.writeln(&format!("%%Page: {} {}", self.real_page_no, self.real_page_no))?;
+        self.writeln("page-begin")?;
 
         self.real_page_no += 1;
 
-        if page_no > 1
-            || (page_no == 1 && self.typescript.has_structure)
-            || (page_no == 1
-                && !self.typescript.has_structure
-                && self.typescript.contact.is_none()
-                && self.typescript.word_count.is_none())
-        {
-            // write slug line
-            let x = (LEFT_MARGIN as f32 * CHAR_WIDTH).round() as i32;
-            let y = (SLUG_LINE as f32 * LINE_HEIGHT).round() as i32; 
-
-            write(&format!("{} {} moveto ", x, y))?;
-            write(&self.typescript.short_author_name.ps)?;
-            write(&format!("(/) show "))?;
-            write(&self.typescript.short_title.ps)?;
-            writeln(&format!("(/{}) show ", page_no))
+        if let Some(line) = self.typescript.slug_line(page_no) {
+            let x = (line.column as f32 * CHAR_WIDTH).round() as i32;
+            let y = (SLUG_LINE as f32 * LINE_HEIGHT).round() as i32;
+
+            self.writeln(&format!("{} {} moveto {}", x, y, line.ps()))
         } else {
             Ok(())
         }
     }
-}
 
-/// Converts UTF-8 characters to ISO/IEC 8859-15 and writes them to
-/// the standard output
-fn write(text: &str) -> Result<(), Box<dyn Error>> {
-    let chars = ISO_8859_15.encode(text, EncoderTrap::Replace)?;
-    io::stdout().write_all(&chars)?;
-    Ok(())
-}
+    /// Converts UTF-8 characters to ISO/IEC 8859-15 and writes them
+    /// to the output
+    fn write(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        let chars = ISO_8859_15.encode(text, EncoderTrap::Replace)?;
+        self.output.write_all(&chars)?;
+        Ok(())
+    }
 
-/// Converts UTF-8 characters to ISO/IEC 8859-15 and writes them to
-/// the standard output, appending a newline
-fn writeln(text: &str) -> Result<(), Box<dyn Error>> {
-    let mut chars = ISO_8859_15.encode(text, EncoderTrap::Replace)?;
-    chars.push(b'\n');
-    io::stdout().write_all(&chars)?;
-    Ok(())
+    /// Converts UTF-8 characters to ISO/IEC 8859-15 and writes them
+    /// to the output, appending a newline
+    fn writeln(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        let mut chars = ISO_8859_15.encode(text, EncoderTrap::Replace)?;
+        chars.push(b'\n');
+        self.output.write_all(&chars)?;
+        Ok(())
+    }
 }
-    
@@ -0,0 +1,248 @@
+// Kosik Exporters
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Converts manuscripts to other output formats
+//!
+//! Paged exporters work from the [`Typescript`] built by the
+//! [`compositor`], so that they show the same lines and pages as the
+//! Postscript output.  Reflowed exporters work from the element tree
+//! built by the [`reader`].
+//!
+//! * The [`docx`] module writes a Word document in standard manuscript
+//!   format.
+//! * The [`epub`] module packages the reading view as an e-book.
+//! * The [`html`] module writes a paged preview or a reflowed
+//!   reading view.
+//! * The [`latex`] module writes a LaTeX document for typeset proofs.
+//! * The [`pdf`] module writes the typed pages as a PDF document.
+//! * The [`sik`] module writes the element tree back out as manuscript
+//!   XML.
+//! * The [`stats`] module reports word, sentence and page counts.
+//! * The [`svg`] module writes one image per page.
+//! * The [`text`] module writes the typed pages as plain text.
+//!
+//! [`Typescript`]: crate::document::Typescript
+//! [`compositor`]: crate::document::compositor
+//! [`reader`]: crate::document::reader
+
+pub mod docx;
+pub mod epub;
+pub mod html;
+pub mod latex;
+pub mod pdf;
+pub mod sik;
+pub mod stats;
+pub mod svg;
+pub mod text;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::document::*;
+use crate::lut::ROMAN_NUMERALS;
+use crate::text::{Line, Segment};
+use crate::text::tokens::TokenType;
+
+// Escape the markup characters in character data or attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Flatten a token list to plain text as it is typed, with the
+// typewriter equivalents of dashes and quotation marks.
+pub(crate) fn typed_text(tokens: &[TokenType]) -> String {
+    let mut text = String::new();
+
+    for token in tokens.iter() {
+        match token {
+            TokenType::LineBreak(_) => text.push(' '),
+            TokenType::NoteRef(_) => {},
+            token => text.push_str(&token.text()),
+        }
+    }
+
+    text
+}
+
+// Flatten a personal name to text, with a function that flattens the
+// parts of the name.
+pub(crate) fn person_name(elem: &ContainerElement<Person>,
+                          flatten: fn(&[TokenType]) -> String) -> String
+{
+    let mut names: Vec<String> = Vec::new();
+
+    for child in elem.children.iter() {
+        match child {
+            ElementType::Gn(child) => names.push(flatten(&child.tokens[..])),
+            ElementType::Prefix(child) => names.push(flatten(&child.tokens[..])),
+            ElementType::Sn(child) => names.push(flatten(&child.tokens[..])),
+            ElementType::Suffix(child) => {
+                let suffix = flatten(&child.tokens[..]);
+
+                match names.last_mut() {
+                    Some(name) if child.attributes.comma => {
+                        name.push(',');
+                        names.push(suffix);
+                    },
+                    _ => names.push(suffix),
+                }
+            },
+            _ => {},
+        }
+    }
+
+    names.join(" ")
+}
+
+// Format the current time as a W3C date and time, as required by
+// the metadata of e-books and word processor documents.
+fn modified() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs()) as i64;
+
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Convert days since the epoch to a civil date, counting eras of
+    // 400 years from 1 March 0000.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            year, month, day, secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+// Collect the footnotes attached to a person.
+fn person_footnotes(elem: &ContainerElement<Person>) -> Vec<&ElementType> {
+    let mut footnotes: Vec<&ElementType> = Vec::new();
+
+    for child in elem.children.iter() {
+        match child {
+            ElementType::Footnote(_) => footnotes.push(child),
+            ElementType::Gn(child) => footnotes.extend(child.footnotes.iter()),
+            ElementType::Prefix(child) => footnotes.extend(child.footnotes.iter()),
+            ElementType::Sn(child) => footnotes.extend(child.footnotes.iter()),
+            ElementType::Suffix(child) => footnotes.extend(child.footnotes.iter()),
+            _ => {},
+        }
+    }
+
+    footnotes
+}
+
+// Format the heading label for a part or an act.
+fn roman_label(name: &str, number: i32) -> String {
+    match ROMAN_NUMERALS.numeral(number as usize) {
+        Some(roman_numeral) => format!("{} {}", name, roman_numeral),
+        None => format!("{} {}", name, number),
+    }
+}
+
+// Format the heading label for a section, which is lettered.
+fn section_label(number: i32) -> String {
+    match char::from_u32('@' as u32 + number as u32) {
+        Some(ch) => format!("Section {}", ch),
+        None => format!("Section {}", number),
+    }
+}
+
+/// Baseline shift for subscripts and superscripts in points
+const SCRIPT_SHIFT: f32 = 6.0;
+
+/// Distance from the baseline to the underline in points
+const UNDERLINE_POSITION: f32 = 1.0;
+
+/// Underline thickness in points
+const UNDERLINE_THICKNESS: f32 = 0.5;
+
+// Place the lines of a page, returning each with the line number of
+// its baseline, counting up from the bottom of the page.  The
+// positions are the same as in the Postscript output.
+fn place_lines(typescript: &Typescript, i: usize) -> Vec<(f32, Line)> {
+    let page = &typescript.pages[i];
+    let mut lines: Vec<(f32, Line)> = Vec::with_capacity(page.lines.len() + 1);
+
+    if let Some(line) = typescript.slug_line(page.number) {
+        lines.push((SLUG_LINE as f32, line));
+    }
+
+    if i == 0 {
+        if let Some(block) = &typescript.contact {
+            let mut y = match typescript.layout {
+                Layout::Manuscript => TOP_LINE,
+                // bottom left corner of the title page
+                Layout::Screenplay => BOTTOM_LINE + block.count_lines() - 1,
+            } as f32;
+
+            for (i, line) in block.lines.iter().enumerate() {
+                if i > 0 && block.line_spacing == LineSpacing::Double {
+                    y -= 1.0;
+                }
+
+                lines.push((y, line.clone()));
+                y -= 1.0;
+            }
+        }
+
+        if let Some(line) = typescript.word_count_line() {
+            lines.push((TOP_LINE as f32, line));
+        }
+    }
+
+    let mut y = TOP_LINE as f32;
+
+    for line in page.lines.iter() {
+        if let Some(line) = line {
+            lines.push((y, line.clone()));
+        }
+
+        y -= 1.0;
+    }
+
+    if !page.footer.is_empty() {
+        y = (BOTTOM_LINE + page.footer.len() + 2) as f32;
+
+        lines.push((y, Line {
+            column: LEFT_MARGIN,
+            segments: vec![Segment::from("____________________")],
+            note_refs: Vec::new(),
+        }));
+
+        y -= 2.0;
+
+        for line in page.footer.iter() {
+            if let Some(line) = line {
+                let mut line = line.clone();
+                line.column = LEFT_MARGIN;
+                lines.push((y, line));
+            }
+
+            y -= 1.0;
+        }
+    }
+
+    lines
+}
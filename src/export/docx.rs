@@ -0,0 +1,1325 @@
+// Kosik DOCX Exporter
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Writes a Word document in standard manuscript format
+//!
+//! The document follows the same conventions as the Postscript
+//! output: Courier 12 on one-inch margins, paragraphs double-spaced
+//! with their first lines indented, emphasis underlined, and a header
+//! carrying the slug line with a page number field.  The title page
+//! has the contact information in the top left corner and the word
+//! count in the top right, with the title halfway down.  Parts and
+//! chapters start on a new page, and footnotes become Word footnotes,
+//! keeping their labels.
+//!
+//! Indents and margins are measured in character columns, and
+//! converted to twentieths of a point.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::*;
+//! use kosik::export::docx;
+//! use kosik::text::tokens::*;
+//!
+//! let mut elem = TextElement::new(P {
+//!     indent: 5,
+//!     line_spacing: LineSpacing::Double,
+//!     left_margin: LEFT_MARGIN,
+//!     right_margin: RIGHT_MARGIN,
+//! });
+//!
+//! elem.tokens.push(TokenType::Word(Token::new(WordData::from("foo"),
+//!                                             Default::default(),
+//!                                             Default::default())));
+//!
+//! let document = docx::from_elements(&ElementType::P(elem)).unwrap();
+//!
+//! // A DOCX file is a ZIP archive.
+//! assert_eq!(&document[0..4], b"PK\x03\x04");
+//! ```
+//!
+//! Dashes, ellipses and quotation marks are written as the characters
+//! in the source, not as their typewriter equivalents:
+//!
+//! ```
+//! use std::io::{Cursor, Read};
+//! use kosik::document::reader::Reader;
+//! use kosik::export::docx;
+//!
+//! let root = Reader::new("<p>so to speak\u{2014}the sea</p>").run().unwrap().unwrap();
+//! let document = docx::from_elements(&root).unwrap();
+//!
+//! let mut archive = zip::ZipArchive::new(Cursor::new(document)).unwrap();
+//! let mut xml = String::new();
+//! archive.by_name("word/document.xml").unwrap().read_to_string(&mut xml).unwrap();
+//!
+//! assert!(xml.contains("so to speak\u{2014}the sea"));
+//! assert!(!xml.contains("--"));
+//! ```
+
+use std::cmp::min;
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{Cursor, Write};
+
+use zip::{CompressionMethod, ZipWriter};
+use zip::write::FileOptions;
+
+use crate::document::*;
+use crate::document::formatter::{ordered_label, UNORDERED_MARKERS};
+use crate::text::tokens::*;
+
+use super::{escape, modified, person_footnotes, person_name, roman_label,
+            section_label};
+use super::html::{plain_text, typographic_text};
+
+/// Width of a character column in twentieths of a point
+const COLUMN: usize = 144;
+
+/// Height of a single-spaced line in twentieths of a point
+const LINE: usize = 240;
+
+/// Page width in twentieths of a point
+const PAGE_WIDTH: usize = 12240;
+
+/// Page height in twentieths of a point
+const PAGE_HEIGHT: usize = 15840;
+
+const CONTENT_TYPES: &str = "\
+<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">
+<Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>
+<Default Extension=\"xml\" ContentType=\"application/xml\"/>
+<Override PartName=\"/word/document.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml\"/>
+<Override PartName=\"/word/styles.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml\"/>
+<Override PartName=\"/word/settings.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.settings+xml\"/>
+<Override PartName=\"/word/footnotes.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.footnotes+xml\"/>
+<Override PartName=\"/word/header1.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.wordprocessingml.header+xml\"/>
+<Override PartName=\"/docProps/core.xml\" ContentType=\"application/vnd.openxmlformats-package.core-properties+xml\"/>
+</Types>
+";
+
+const PACKAGE_RELATIONSHIPS: &str = "\
+<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument\" Target=\"word/document.xml\"/>
+<Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties\" Target=\"docProps/core.xml\"/>
+</Relationships>
+";
+
+const DOCUMENT_RELATIONSHIPS: &str = "\
+<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">
+<Relationship Id=\"rId1\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles\" Target=\"styles.xml\"/>
+<Relationship Id=\"rId2\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/settings\" Target=\"settings.xml\"/>
+<Relationship Id=\"rId3\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/footnotes\" Target=\"footnotes.xml\"/>
+<Relationship Id=\"rId4\" Type=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships/header\" Target=\"header1.xml\"/>
+</Relationships>
+";
+
+const SETTINGS: &str = "\
+<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<w:settings xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\">
+<w:footnotePr><w:footnote w:id=\"-1\"/><w:footnote w:id=\"0\"/></w:footnotePr>
+</w:settings>
+";
+
+const STYLES: &str = "\
+<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>
+<w:styles xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\">
+<w:docDefaults>
+<w:rPrDefault><w:rPr><w:rFonts w:ascii=\"Courier New\" w:hAnsi=\"Courier New\" w:cs=\"Courier New\"/><w:sz w:val=\"24\"/><w:szCs w:val=\"24\"/><w:lang w:val=\"en-US\"/></w:rPr></w:rPrDefault>
+<w:pPrDefault><w:pPr><w:spacing w:after=\"0\" w:line=\"240\" w:lineRule=\"auto\"/></w:pPr></w:pPrDefault>
+</w:docDefaults>
+<w:style w:type=\"paragraph\" w:default=\"1\" w:styleId=\"Normal\"><w:name w:val=\"Normal\"/><w:qFormat/></w:style>
+@headings@<w:style w:type=\"paragraph\" w:styleId=\"FootnoteText\"><w:name w:val=\"footnote text\"/><w:basedOn w:val=\"Normal\"/></w:style>
+<w:style w:type=\"paragraph\" w:styleId=\"Header\"><w:name w:val=\"header\"/><w:basedOn w:val=\"Normal\"/></w:style>
+<w:style w:type=\"character\" w:styleId=\"FootnoteReference\"><w:name w:val=\"footnote reference\"/><w:rPr><w:vertAlign w:val=\"superscript\"/></w:rPr></w:style>
+</w:styles>
+";
+
+/// Write the element tree as a Word document.
+pub fn from_elements(elem: &ElementType) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut builder = Builder {
+        margin: LEFT_MARGIN,
+        ..Default::default()
+    };
+
+    let head = match elem {
+        ElementType::Manuscript(elem) => {
+            builder.word_count = Some(elem.attributes.word_count);
+            elem.head()
+        },
+        ElementType::Screenplay(elem) => {
+            builder.margin = SCREENPLAY_MARGIN;
+            builder.screenplay = true;
+            elem.head()
+        },
+        _ => None,
+    };
+
+    let title = match head.and_then(|x| x.title()) {
+        Some(title) => plain_text(&title.tokens[..]),
+        None => "Working Title".to_string(),
+    };
+
+    let authors: Vec<String> = match head.and_then(|x| x.authors()) {
+        Some(authors) => authors.children.iter()
+            .filter_map(|child| match child {
+                ElementType::Person(child) => Some(person_name(child, plain_text)),
+                _ => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    builder.element(elem);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("[Content_Types].xml", options)?;
+    zip.write_all(CONTENT_TYPES.as_bytes())?;
+
+    zip.start_file("_rels/.rels", options)?;
+    zip.write_all(PACKAGE_RELATIONSHIPS.as_bytes())?;
+
+    zip.start_file("docProps/core.xml", options)?;
+    zip.write_all(core_properties(&title, &authors).as_bytes())?;
+
+    zip.start_file("word/_rels/document.xml.rels", options)?;
+    zip.write_all(DOCUMENT_RELATIONSHIPS.as_bytes())?;
+
+    zip.start_file("word/document.xml", options)?;
+    zip.write_all(builder.document_part(elem).as_bytes())?;
+
+    zip.start_file("word/footnotes.xml", options)?;
+    zip.write_all(builder.footnotes_part().as_bytes())?;
+
+    zip.start_file("word/header1.xml", options)?;
+    zip.write_all(header(elem).as_bytes())?;
+
+    zip.start_file("word/settings.xml", options)?;
+    zip.write_all(SETTINGS.as_bytes())?;
+
+    zip.start_file("word/styles.xml", options)?;
+    zip.write_all(styles().as_bytes())?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+// Write the document properties shown by the word processor.
+fn core_properties(title: &str, authors: &[String]) -> String {
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <cp:coreProperties \
+             xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\" \
+             xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+             xmlns:dcterms=\"http://purl.org/dc/terms/\" \
+             xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n\
+             <dc:title>{}</dc:title>\n\
+             <dc:creator>{}</dc:creator>\n\
+             <dcterms:modified xsi:type=\"dcterms:W3CDTF\">{}</dcterms:modified>\n\
+             </cp:coreProperties>\n",
+            escape(title), escape(&authors.join(", ")), modified())
+}
+
+// Write the page header.  Manuscripts carry the slug line, and
+// screenplays the page number alone, flush right.
+fn header(elem: &ElementType) -> String {
+    let page = "<w:r><w:fldChar w:fldCharType=\"begin\"/></w:r>\
+                <w:r><w:instrText xml:space=\"preserve\"> PAGE </w:instrText></w:r>\
+                <w:r><w:fldChar w:fldCharType=\"separate\"/></w:r>\
+                <w:r><w:t>1</w:t></w:r>\
+                <w:r><w:fldChar w:fldCharType=\"end\"/></w:r>";
+
+    let paragraph = match elem {
+        ElementType::Manuscript(elem) => {
+            let short_title = match elem.short_title() {
+                Some(segment) => segment.text,
+                None => "Working Title".to_string(),
+            };
+
+            let short_author_name = match elem.short_author_name() {
+                Some(segment) => segment.text,
+                None => "ANONYMOUS".to_string(),
+            };
+
+            format!("<w:p><w:pPr><w:pStyle w:val=\"Header\"/></w:pPr>\
+                     {}{}</w:p>",
+                    run(&format!("{}/{}/", short_author_name,
+                                 short_title.to_uppercase()),
+                        Default::default()),
+                    page)
+        },
+        ElementType::Screenplay(_) => {
+            format!("<w:p><w:pPr><w:pStyle w:val=\"Header\"/><w:jc w:val=\"right\"/></w:pPr>\
+                     {}{}</w:p>",
+                    page, run(".", Default::default()))
+        },
+        _ => "<w:p><w:pPr><w:pStyle w:val=\"Header\"/></w:pPr></w:p>".to_string(),
+    };
+
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+             <w:hdr xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\">\n\
+             {}\n\
+             </w:hdr>\n",
+            paragraph)
+}
+
+// Write the style sheet, with a heading style for each level of the
+// document structure so that word processors can build an outline.
+fn styles() -> String {
+    let mut headings = String::new();
+
+    for level in 1..=6 {
+        headings.push_str(&format!(
+            "<w:style w:type=\"paragraph\" w:styleId=\"Heading{0}\">\
+             <w:name w:val=\"heading {0}\"/><w:basedOn w:val=\"Normal\"/>\
+             <w:next w:val=\"Normal\"/><w:qFormat/>\
+             <w:pPr><w:keepNext/><w:jc w:val=\"center\"/>\
+             <w:outlineLvl w:val=\"{1}\"/></w:pPr></w:style>\n",
+            level, level - 1
+        ));
+    }
+
+    STYLES.replace("@headings@", &headings)
+}
+
+// Format a run of text with the same display flags.  Emphasis is
+// underlined, as on a typewriter.
+fn run(text: &str, dpy: DisplayFlags) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+
+    let mut rpr = String::new();
+
+    if dpy.intersects(DisplayFlags::EM) {
+        rpr.push_str("<w:u w:val=\"single\"/>");
+    }
+
+    if dpy.intersects(DisplayFlags::SUB) {
+        rpr.push_str("<w:vertAlign w:val=\"subscript\"/>");
+
+    } else if dpy.intersects(DisplayFlags::SUP) {
+        rpr.push_str("<w:vertAlign w:val=\"superscript\"/>");
+    }
+
+    if rpr.is_empty() {
+        format!("<w:r><w:t xml:space=\"preserve\">{}</w:t></w:r>", escape(text))
+    } else {
+        format!("<w:r><w:rPr>{}</w:rPr><w:t xml:space=\"preserve\">{}</w:t></w:r>",
+                rpr, escape(text))
+    }
+}
+
+// Measure the distance between two columns.
+fn twips(from: usize, to: usize) -> usize {
+    to.saturating_sub(from) * COLUMN
+}
+
+/// Paragraph properties
+#[derive(Default)]
+struct Properties {
+    style: Option<String>,
+    keep_next: bool,
+    page_break: bool,
+    /// Position of a right-aligned tab stop
+    tab: Option<usize>,
+    /// Space before and after, in lines
+    before: usize,
+    after: usize,
+    line_spacing: Option<LineSpacing>,
+    left: usize,
+    right: usize,
+    first_line: usize,
+    hanging: usize,
+    align: Option<&'static str>,
+}
+
+impl Properties {
+    // Write the properties in the order required by the schema.
+    fn xml(&self) -> String {
+        let mut xml = String::from("<w:pPr>");
+
+        if let Some(style) = &self.style {
+            xml.push_str(&format!("<w:pStyle w:val=\"{}\"/>", style));
+        }
+
+        if self.keep_next {
+            xml.push_str("<w:keepNext/>");
+        }
+
+        if self.page_break {
+            xml.push_str("<w:pageBreakBefore/>");
+        }
+
+        if let Some(tab) = self.tab {
+            xml.push_str(&format!("<w:tabs><w:tab w:val=\"right\" w:pos=\"{}\"/></w:tabs>",
+                                  tab));
+        }
+
+        let line = match self.line_spacing {
+            Some(LineSpacing::Double) => 2 * LINE,
+            _ => LINE,
+        };
+
+        xml.push_str(&format!("<w:spacing w:before=\"{}\" w:after=\"{}\" \
+                               w:line=\"{}\" w:lineRule=\"auto\"/>",
+                              self.before * LINE, self.after * LINE, line));
+
+        if self.hanging > 0 {
+            xml.push_str(&format!("<w:ind w:left=\"{}\" w:right=\"{}\" w:hanging=\"{}\"/>",
+                                  self.left, self.right, self.hanging));
+        } else {
+            xml.push_str(&format!("<w:ind w:left=\"{}\" w:right=\"{}\" w:firstLine=\"{}\"/>",
+                                  self.left, self.right, self.first_line));
+        }
+
+        if let Some(align) = self.align {
+            xml.push_str(&format!("<w:jc w:val=\"{}\"/>", align));
+        }
+
+        xml.push_str("</w:pPr>");
+        xml
+    }
+}
+
+/// Word document builder
+///
+/// Paragraphs are written to the document body as the element tree
+/// is walked, and footnotes to a separate part, numbered in order.
+/// A footnote keeps its label, which is written as a custom mark at
+/// both the reference and the note.
+#[derive(Default)]
+struct Builder {
+    body: String,
+    notes: String,
+    /// Footnote numbers, by label
+    note_ids: HashMap<String, usize>,
+    next_note_id: usize,
+    /// True while writing a footnote
+    in_note: bool,
+    /// Runs to write at the start of the next paragraph, such as a
+    /// list marker or a footnote label
+    prefix: Option<String>,
+    /// Page margin, in columns from the edge of the page
+    margin: usize,
+    screenplay: bool,
+    word_count: Option<usize>,
+}
+
+impl Builder {
+    fn element(&mut self, elem: &ElementType) {
+        match elem {
+            ElementType::Act(elem) => {
+                let label = roman_label("Act", elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes, true, CHAPTER_SKIP);
+            },
+            ElementType::Action(elem) => {
+                let props = Properties {
+                    before: 1,
+                    align: if elem.attributes.centered {
+                        Some("center")
+                    } else {
+                        None
+                    },
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Attribution(elem) => {
+                let props = Properties {
+                    after: 1,
+                    line_spacing: Some(elem.attributes.line_spacing),
+                    align: Some("right"),
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Authors(elem) => {
+                self.authors(elem, 3);
+            },
+            ElementType::Backmatter(elem) => {
+                self.label(&elem.attributes.label);
+                self.children(&elem.children);
+            },
+            ElementType::BibRef(elem) => {
+                let props = Properties {
+                    line_spacing: Some(elem.attributes.line_spacing),
+                    hanging: INDENT * COLUMN,
+                    left: INDENT * COLUMN,
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Blockquote(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Body(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Br(_) => {
+                self.paragraph(Default::default(), "");
+            },
+            ElementType::CastList(elem) => {
+                self.label("Cast of Characters");
+
+                let mut children = elem.children.iter().peekable();
+
+                while let Some(child) = children.next() {
+                    match child {
+                        ElementType::Speaker(child) => {
+                            self.register(child.footnotes.iter());
+
+                            let mut runs = self.runs(&child.tokens[..]);
+                            let mut footnotes: Vec<&ElementType> =
+                                child.footnotes.iter().collect();
+
+                            if let Some(ElementType::P(description)) = children.peek() {
+                                self.register(description.footnotes.iter());
+
+                                if !matches!(description.tokens.first(),
+                                             Some(TokenType::Punct(_)))
+                                {
+                                    runs.push_str(&run(", ", Default::default()));
+                                }
+
+                                runs.push_str(&self.runs(&description.tokens[..]));
+                                footnotes.extend(description.footnotes.iter());
+                                children.next();
+                            }
+
+                            let props = Properties {
+                                after: 1,
+                                left: INDENT * COLUMN,
+                                hanging: INDENT * COLUMN,
+                                ..Default::default()
+                            };
+
+                            self.paragraph(props, &runs);
+                            self.footnotes(footnotes.into_iter());
+                        },
+                        child => self.element(child),
+                    }
+                }
+            },
+            ElementType::Chapter(elem) => {
+                let label = format!("Chapter {}", elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes, true, CHAPTER_SKIP);
+            },
+            ElementType::Character(elem) => {
+                let props = Properties {
+                    keep_next: true,
+                    before: 1,
+                    left: twips(self.margin, CHARACTER_MARGIN),
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Contact(elem) => {
+                let props = Properties {
+                    line_spacing: Some(elem.attributes.line_spacing),
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Dd(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Dedication(elem) => {
+                for child in elem.children.iter() {
+                    match child {
+                        ElementType::P(child) => {
+                            let props = Properties {
+                                after: 1,
+                                line_spacing: Some(child.attributes.line_spacing),
+                                align: Some("center"),
+                                ..Default::default()
+                            };
+
+                            self.text(props, &child.tokens[..], &child.footnotes);
+                        },
+                        child => self.element(child),
+                    }
+                }
+            },
+            ElementType::Dialogue(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Direction(elem) => {
+                // Stage directions are parenthesized unless the author
+                // has already done so.
+                let parenthesized = matches!(
+                    elem.tokens.first(),
+                    Some(TokenType::Open(token)) if token.data.text == "("
+                );
+
+                self.register(elem.footnotes.iter());
+
+                let mut runs = self.runs(&elem.tokens[..]);
+
+                if !parenthesized {
+                    runs = format!("{}{}{}", run("(", Default::default()), runs,
+                                   run(")", Default::default()));
+                }
+
+                let props = Properties {
+                    after: 1,
+                    left: twips(self.margin, DIRECTION_MARGIN),
+                    ..Default::default()
+                };
+
+                self.paragraph(props, &runs);
+                self.footnotes(elem.footnotes.iter());
+            },
+            ElementType::Div(_) => {
+                let props = Properties {
+                    line_spacing: Some(LineSpacing::Double),
+                    align: Some("center"),
+                    ..Default::default()
+                };
+
+                self.paragraph(props, &run("#", Default::default()));
+            },
+            ElementType::Dl(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Dt(elem) => {
+                let props = Properties {
+                    keep_next: true,
+                    line_spacing: Some(elem.attributes.line_spacing),
+                    left: twips(self.margin, elem.attributes.left_margin),
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Em(elem) => {
+                self.text(Default::default(), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Epigraph(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Footnote(_) => {
+                self.register(std::iter::once(elem));
+                self.footnotes(std::iter::once(elem));
+            },
+            ElementType::Frontmatter(elem) => {
+                self.label(&elem.attributes.label);
+                self.children(&elem.children);
+            },
+            ElementType::Gn(elem) => {
+                self.text(Default::default(), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Head(elem) => {
+                self.title_page(elem);
+            },
+            ElementType::Li(elem) => {
+                let marker = match elem.attributes.number {
+                    Some(n) => format!("{}.", ordered_label(n, elem.attributes.level)),
+                    None => String::from(UNORDERED_MARKERS[elem.attributes.level % 3]),
+                };
+
+                self.prefix = Some(format!("{}<w:r><w:tab/></w:r>",
+                                           run(&marker, Default::default())));
+                self.children(&elem.children);
+                self.prefix = None;
+            },
+            ElementType::Manuscript(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::NoteRef(elem) => {
+                let runs = self.note_ref(&elem.attributes.label);
+                self.paragraph(Default::default(), &runs);
+            },
+            ElementType::Ol(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::P(elem) => {
+                let mut props = Properties {
+                    line_spacing: Some(elem.attributes.line_spacing),
+                    left: twips(self.margin, elem.attributes.left_margin),
+                    right: twips(elem.attributes.right_margin, RIGHT_MARGIN),
+                    first_line: elem.attributes.indent * COLUMN,
+                    ..Default::default()
+                };
+
+                // The first paragraph of a list item hangs from its
+                // marker.
+                if self.prefix.is_some() && !self.in_note {
+                    props.hanging = INDENT * COLUMN;
+                }
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::PageBreak(_) => {
+                self.body.push_str("<w:p><w:r><w:br w:type=\"page\"/></w:r></w:p>\n");
+            },
+            ElementType::Parenthetical(elem) => {
+                let props = Properties {
+                    keep_next: true,
+                    left: twips(self.margin, PARENTHETICAL_MARGIN),
+                    right: twips(PARENTHETICAL_RIGHT_MARGIN, RIGHT_MARGIN),
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Part(elem) => {
+                let label = roman_label("Part", elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes, true, PART_SKIP);
+            },
+            ElementType::Person(elem) => {
+                let footnotes = person_footnotes(elem);
+                self.register(footnotes.iter().copied());
+
+                let runs = self.person(elem);
+                let props = Properties {
+                    align: Some("center"),
+                    ..Default::default()
+                };
+
+                self.paragraph(props, &runs);
+                self.footnotes(footnotes.into_iter());
+            },
+            ElementType::Pre(elem) => {
+                let mut runs = String::new();
+
+                for token in elem.tokens.iter() {
+                    match token {
+                        TokenType::LineBreak(_) => runs.push_str("<w:r><w:br/></w:r>"),
+                        token => runs.push_str(&run(&token.text(), Default::default())),
+                    }
+                }
+
+                let props = Properties {
+                    after: 1,
+                    line_spacing: Some(elem.attributes.line_spacing),
+                    ..Default::default()
+                };
+
+                self.paragraph(props, &runs);
+            },
+            ElementType::Prefix(elem) => {
+                self.text(Default::default(), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Scene(elem) => {
+                let label = format!("Scene {}", elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes, elem.attributes.padding_before < 0,
+                             SECTION_SKIP);
+            },
+            ElementType::SceneHeading(elem) => {
+                let props = Properties {
+                    keep_next: true,
+                    before: 1,
+                    after: 1,
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Screenplay(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Section(elem) => {
+                let label = section_label(elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes, elem.attributes.padding_before < 0,
+                             SECTION_SKIP);
+            },
+            ElementType::Sn(elem) => {
+                self.text(Default::default(), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Speaker(elem) => {
+                let props = Properties {
+                    keep_next: true,
+                    align: Some("center"),
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Speech(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Sub(elem) => {
+                self.text(Default::default(), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Subtitle(elem) => {
+                let props = Properties {
+                    after: 2,
+                    line_spacing: Some(elem.attributes.line_spacing),
+                    align: Some("center"),
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Suffix(elem) => {
+                self.text(Default::default(), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Sup(elem) => {
+                self.text(Default::default(), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Table(elem) => {
+                self.table(&elem.children);
+            },
+            ElementType::Td(elem) => {
+                self.text(Default::default(), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Title(elem) => {
+                let props = Properties {
+                    after: 2,
+                    line_spacing: Some(elem.attributes.line_spacing),
+                    align: Some("center"),
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Tr(_) => {
+                self.table(std::slice::from_ref(elem));
+            },
+            ElementType::Transition(elem) => {
+                let props = Properties {
+                    before: 1,
+                    after: 1,
+                    align: Some("right"),
+                    ..Default::default()
+                };
+
+                self.text(props, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Ul(elem) => {
+                self.children(&elem.children);
+            },
+        }
+    }
+
+    fn children(&mut self, children: &ElementList) {
+        for child in children.iter() {
+            self.element(child);
+        }
+    }
+
+    // Write a paragraph, starting with any pending prefix.
+    fn paragraph(&mut self, mut props: Properties, runs: &str) {
+        if self.in_note {
+            props.style = Some("FootnoteText".to_string());
+        }
+
+        self.body.push_str("<w:p>");
+        self.body.push_str(&props.xml());
+
+        if let Some(prefix) = self.prefix.take() {
+            self.body.push_str(&prefix);
+        }
+
+        self.body.push_str(runs);
+        self.body.push_str("</w:p>\n");
+    }
+
+    fn text(&mut self, props: Properties, tokens: &[TokenType],
+            footnotes: &ElementList)
+    {
+        self.register(footnotes.iter());
+        let runs = self.runs(tokens);
+        self.paragraph(props, &runs);
+        self.footnotes(footnotes.iter());
+    }
+
+    // Headings use the outline level of their depth in the document
+    // structure.  The label and the title share a paragraph, so that
+    // they make a single entry in the outline.
+    fn heading(&mut self, depth: i32, label: &str, tokens: &[TokenType],
+               footnotes: &ElementList, page_break: bool, skip: usize)
+    {
+        self.register(footnotes.iter());
+
+        let mut runs = run(label, Default::default());
+
+        if !tokens.is_empty() {
+            runs.push_str("<w:r><w:br/><w:br/></w:r>");
+            runs.push_str(&self.runs(tokens));
+        }
+
+        let props = Properties {
+            style: Some(format!("Heading{}", min(depth.max(0) + 1, 6))),
+            page_break: page_break,
+            before: if page_break { 0 } else { 2 },
+            after: skip,
+            align: Some("center"),
+            ..Default::default()
+        };
+
+        self.paragraph(props, &runs);
+        self.footnotes(footnotes.iter());
+    }
+
+    // Front and back matter labels are headings at the top level.
+    fn label(&mut self, label: &str) {
+        let props = Properties {
+            style: Some("Heading1".to_string()),
+            page_break: true,
+            after: CHAPTER_SKIP,
+            align: Some("center"),
+            ..Default::default()
+        };
+
+        self.paragraph(props, &run(label, Default::default()));
+    }
+
+    // Write the title page.  In a manuscript, the contact information
+    // goes in the top left corner, with the word count on its first
+    // line, flush right.  In a screenplay, it goes below the authors,
+    // and the script starts on the next page.  The title is set
+    // halfway down the page, followed by the subtitle and the authors.
+    fn title_page(&mut self, elem: &ContainerElement<Head>) {
+        let mut contact_lines: usize = 0;
+
+        let contact = elem.children.iter().find_map(|child| match child {
+            ElementType::Contact(child) => Some(child),
+            _ => None,
+        });
+
+        if !self.screenplay {
+            let word_count = self.word_count
+                .map(|n| format!("<w:r><w:tab/></w:r>{}",
+                                 run(&word_count_text(n, 0), Default::default())));
+
+            if contact.is_some() || word_count.is_some() {
+                let tokens: &[TokenType] = match contact {
+                    Some(contact) => &contact.tokens[..],
+                    None => &[],
+                };
+
+                // The word count goes at the end of the first line.
+                let i = tokens.iter()
+                    .position(|token| matches!(token, TokenType::LineBreak(_)))
+                    .unwrap_or(tokens.len());
+
+                if let Some(contact) = contact {
+                    self.register(contact.footnotes.iter());
+                }
+
+                let mut runs = self.runs(&tokens[..i]);
+
+                if let Some(word_count) = &word_count {
+                    runs.push_str(word_count);
+                }
+
+                runs.push_str(&self.runs(&tokens[i..]));
+
+                contact_lines = tokens.iter()
+                    .filter(|token| matches!(token, TokenType::LineBreak(_)))
+                    .count() + 1;
+
+                let props = Properties {
+                    tab: Some(twips(self.margin, RIGHT_MARGIN + 1)),
+                    line_spacing: contact.map(|x| x.attributes.line_spacing),
+                    ..Default::default()
+                };
+
+                self.paragraph(props, &runs);
+
+                if let Some(contact) = contact {
+                    self.footnotes(contact.footnotes.iter());
+                }
+            }
+        }
+
+        let mut first = true;
+
+        for child in elem.children.iter() {
+            match child {
+                ElementType::Authors(child) => {
+                    self.authors(child, if first { 0 } else { 3 });
+                    first = false;
+                },
+                ElementType::Subtitle(_) | ElementType::Title(_) => {
+                    if first {
+                        // Drop the title to the middle of the page.
+                        let before = MIDDLE_LINE.saturating_sub(contact_lines + 6);
+
+                        let props = Properties {
+                            before: before.saturating_sub(1),
+                            ..Default::default()
+                        };
+
+                        self.paragraph(props, "");
+                        first = false;
+                    }
+
+                    self.element(child);
+                },
+                _ => {},
+            }
+        }
+
+        if self.screenplay {
+            if let Some(contact) = contact {
+                let props = Properties {
+                    before: SECTION_SKIP,
+                    line_spacing: Some(contact.attributes.line_spacing),
+                    ..Default::default()
+                };
+
+                self.text(props, &contact.tokens[..], &contact.footnotes);
+            }
+
+            self.body.push_str("<w:p><w:r><w:br w:type=\"page\"/></w:r></w:p>\n");
+        }
+    }
+
+    fn authors(&mut self, elem: &ContainerElement<Authors>, before: usize) {
+        let n = elem.children.len();
+        let mut footnotes: Vec<&ElementType> = Vec::new();
+
+        for child in elem.children.iter() {
+            if let ElementType::Person(child) = child {
+                footnotes.extend(person_footnotes(child).into_iter());
+            }
+        }
+
+        self.register(footnotes.iter().copied());
+
+        let mut runs = run("by ", Default::default());
+
+        for (i, child) in elem.children.iter().enumerate() {
+            if i > 0 {
+                if i == n - 1 {
+                    runs.push_str(&run(" and ", Default::default()));
+                } else {
+                    runs.push_str(&run(", ", Default::default()));
+                }
+            }
+
+            if let ElementType::Person(child) = child {
+                runs.push_str(&self.person(child));
+            }
+        }
+
+        let props = Properties {
+            before: before,
+            after: 3,
+            align: Some("center"),
+            ..Default::default()
+        };
+
+        self.paragraph(props, &runs);
+        self.footnotes(footnotes.into_iter());
+    }
+
+    // Write a table with equal columns, repeating the header rows on
+    // each page.
+    fn table(&mut self, children: &[ElementType]) {
+        let columns = children.iter()
+            .map(|child| match child {
+                ElementType::Tr(child) => child.children.len(),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let width = twips(self.margin, RIGHT_MARGIN + 1) / columns;
+        let mut footnotes: Vec<&ElementType> = Vec::new();
+
+        for child in children.iter() {
+            if let ElementType::Tr(child) = child {
+                for child in child.children.iter() {
+                    if let ElementType::Td(child) = child {
+                        footnotes.extend(child.footnotes.iter());
+                    }
+                }
+            }
+        }
+
+        self.register(footnotes.iter().copied());
+
+        self.body.push_str("<w:tbl><w:tblPr><w:tblW w:w=\"0\" w:type=\"auto\"/>\
+                            <w:tblBorders>\
+                            <w:top w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"auto\"/>\
+                            <w:bottom w:val=\"single\" w:sz=\"4\" w:space=\"0\" w:color=\"auto\"/>\
+                            </w:tblBorders></w:tblPr><w:tblGrid>");
+
+        for _ in 0..columns {
+            self.body.push_str(&format!("<w:gridCol w:w=\"{}\"/>", width));
+        }
+
+        self.body.push_str("</w:tblGrid>\n");
+
+        for child in children.iter() {
+            if let ElementType::Tr(child) = child {
+                self.body.push_str("<w:tr>");
+
+                if child.attributes.header {
+                    self.body.push_str("<w:trPr><w:tblHeader/></w:trPr>");
+                }
+
+                for child in child.children.iter() {
+                    if let ElementType::Td(child) = child {
+                        let runs = self.runs(&child.tokens[..]);
+
+                        self.body.push_str(&format!(
+                            "<w:tc><w:tcPr><w:tcW w:w=\"{}\" w:type=\"dxa\"/></w:tcPr>",
+                            width
+                        ));
+                        self.paragraph(Default::default(), &runs);
+                        self.body.push_str("</w:tc>");
+                    }
+                }
+
+                self.body.push_str("</w:tr>\n");
+            }
+        }
+
+        self.body.push_str("</w:tbl>\n");
+        self.footnotes(footnotes.into_iter());
+    }
+
+    // Number each footnote before the note references to it are
+    // written.
+    fn register<'a, I>(&mut self, footnotes: I)
+    where
+        I: Iterator<Item = &'a ElementType>,
+    {
+        for footnote in footnotes {
+            if let ElementType::Footnote(footnote) = footnote {
+                self.next_note_id += 1;
+                self.note_ids.insert(footnote.attributes.label.clone(),
+                                     self.next_note_id);
+            }
+        }
+    }
+
+    // Write footnotes to the footnotes part, each starting with its
+    // label.
+    fn footnotes<'a, I>(&mut self, footnotes: I)
+    where
+        I: Iterator<Item = &'a ElementType>,
+    {
+        for footnote in footnotes {
+            if let ElementType::Footnote(footnote) = footnote {
+                let label = &footnote.attributes.label;
+
+                let id = match self.note_ids.get(label) {
+                    Some(&id) if !self.in_note => id,
+                    _ => continue,
+                };
+
+                let body = std::mem::take(&mut self.body);
+                let prefix = self.prefix.take();
+
+                self.in_note = true;
+                self.prefix = Some(format!(
+                    "<w:r><w:rPr><w:rStyle w:val=\"FootnoteReference\"/></w:rPr>\
+                     <w:t xml:space=\"preserve\">{}</w:t></w:r>{}",
+                    escape(label), run(" ", Default::default())
+                ));
+
+                self.children(&footnote.children);
+
+                // A footnote needs at least one paragraph.
+                if self.prefix.is_some() {
+                    self.paragraph(Default::default(), "");
+                }
+
+                self.in_note = false;
+                self.prefix = prefix;
+
+                let note = std::mem::replace(&mut self.body, body);
+                self.notes.push_str(&format!("<w:footnote w:id=\"{}\">\n{}</w:footnote>\n",
+                                             id, note));
+            }
+        }
+    }
+
+    // Refer to a footnote, marking the reference with its label.
+    // Note references within footnotes are written as plain
+    // superscripts.
+    fn note_ref(&self, label: &str) -> String {
+        match self.note_ids.get(label) {
+            Some(id) if !self.in_note => format!(
+                "<w:r><w:rPr><w:rStyle w:val=\"FootnoteReference\"/></w:rPr>\
+                 <w:footnoteReference w:customMarkFollows=\"1\" w:id=\"{}\"/>\
+                 <w:t>{}</w:t></w:r>",
+                id, escape(label)
+            ),
+            _ => run(label, DisplayFlags::SUP),
+        }
+    }
+
+    // Group a token list into runs with the same display flags.
+    fn runs(&self, tokens: &[TokenType]) -> String {
+        let mut xml = String::new();
+        let mut text = String::new();
+        let mut dpy: DisplayFlags = Default::default();
+
+        for token in tokens.iter() {
+            match token {
+                TokenType::LineBreak(_) => {
+                    xml.push_str(&run(&text, dpy));
+                    xml.push_str("<w:r><w:br/></w:r>");
+                    text.clear();
+                },
+                TokenType::NoteRef(token) => {
+                    xml.push_str(&run(&text, dpy));
+                    xml.push_str(&self.note_ref(&token.data.text));
+                    text.clear();
+                },
+                token => {
+                    let next = token.display_flags();
+
+                    if next != dpy {
+                        xml.push_str(&run(&text, dpy));
+                        text.clear();
+                        dpy = next;
+                    }
+
+                    text.push_str(&typographic_text(token));
+                },
+            }
+        }
+
+        xml.push_str(&run(&text, dpy));
+        xml
+    }
+
+    // Format a personal name.
+    fn person(&self, elem: &ContainerElement<Person>) -> String {
+        let mut runs = String::new();
+
+        for (i, child) in elem.children.iter().enumerate() {
+            match child {
+                ElementType::Footnote(child) => {
+                    runs.push_str(&self.note_ref(&child.attributes.label));
+                },
+                ElementType::NoteRef(child) => {
+                    runs.push_str(&self.note_ref(&child.attributes.label));
+                },
+                ElementType::Gn(child) => {
+                    if i > 0 {
+                        runs.push_str(&run(" ", Default::default()));
+                    }
+
+                    runs.push_str(&self.runs(&child.tokens[..]));
+                },
+                ElementType::Prefix(child) => {
+                    if i > 0 {
+                        runs.push_str(&run(" ", Default::default()));
+                    }
+
+                    runs.push_str(&self.runs(&child.tokens[..]));
+                },
+                ElementType::Sn(child) => {
+                    if i > 0 {
+                        runs.push_str(&run(" ", Default::default()));
+                    }
+
+                    runs.push_str(&self.runs(&child.tokens[..]));
+                },
+                ElementType::Suffix(child) => {
+                    if child.attributes.comma {
+                        runs.push_str(&run(",", Default::default()));
+                    }
+
+                    if i > 0 {
+                        runs.push_str(&run(" ", Default::default()));
+                    }
+
+                    runs.push_str(&self.runs(&child.tokens[..]));
+                },
+                _ => {},
+            }
+        }
+
+        runs
+    }
+
+    // Write the main document part, with the page setup at the end.
+    fn document_part(&self, elem: &ElementType) -> String {
+        let mut xml = String::new();
+
+        xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n");
+        xml.push_str("<w:document \
+                      xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\" \
+                      xmlns:r=\"http://schemas.openxmlformats.org/officeDocument/2006/relationships\">\n");
+        xml.push_str("<w:body>\n");
+        xml.push_str(&self.body);
+
+        // The title page has no header.  If the manuscript has parts
+        // or chapters, its body starts on the next page with the
+        // first page number.
+        let (first_page, title_page) = match elem {
+            ElementType::Manuscript(elem) => {
+                if elem.attributes.has_structure {
+                    (elem.attributes.first_page - 1, true)
+                } else {
+                    (elem.attributes.first_page, true)
+                }
+            },
+            ElementType::Screenplay(_) => (0, true),
+            _ => (1, false),
+        };
+
+        xml.push_str("<w:sectPr>");
+        xml.push_str("<w:headerReference w:type=\"default\" r:id=\"rId4\"/>");
+        xml.push_str(&format!("<w:pgSz w:w=\"{}\" w:h=\"{}\"/>", PAGE_WIDTH, PAGE_HEIGHT));
+        xml.push_str(&format!("<w:pgMar w:top=\"1440\" w:right=\"{}\" w:bottom=\"1440\" \
+                               w:left=\"{}\" w:header=\"720\" w:footer=\"720\" \
+                               w:gutter=\"0\"/>",
+                              PAGE_WIDTH - (RIGHT_MARGIN + 1) * COLUMN,
+                              self.margin * COLUMN));
+        xml.push_str(&format!("<w:pgNumType w:start=\"{}\"/>", first_page));
+
+        if title_page {
+            xml.push_str("<w:titlePg/>");
+        }
+
+        xml.push_str("</w:sectPr>\n");
+        xml.push_str("</w:body>\n");
+        xml.push_str("</w:document>\n");
+        xml
+    }
+
+    // Write the footnotes part, after the separators that Word
+    // expects.
+    fn footnotes_part(&self) -> String {
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+                 <w:footnotes \
+                 xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\">\n\
+                 <w:footnote w:type=\"separator\" w:id=\"-1\">\
+                 <w:p><w:r><w:separator/></w:r></w:p></w:footnote>\n\
+                 <w:footnote w:type=\"continuationSeparator\" w:id=\"0\">\
+                 <w:p><w:r><w:continuationSeparator/></w:r></w:p></w:footnote>\n\
+                 {}</w:footnotes>\n",
+                self.notes)
+    }
+}
@@ -0,0 +1,357 @@
+// Kosik EPUB Exporter
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Packages the reading view as an EPUB 3 e-book
+//!
+//! The head of the manuscript supplies the package metadata and the
+//! title page.  The front matter, body and back matter each become a
+//! document in the spine, and every part, chapter and section heading
+//! gets an entry in the navigation document.  Footnotes become popup
+//! notes, which reading systems show when the note reference is
+//! tapped.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::*;
+//! use kosik::export::epub;
+//! use kosik::text::tokens::*;
+//!
+//! let mut elem = TextElement::new(P {
+//!     indent: 5,
+//!     line_spacing: LineSpacing::Double,
+//!     left_margin: LEFT_MARGIN,
+//!     right_margin: RIGHT_MARGIN,
+//! });
+//!
+//! elem.tokens.push(TokenType::Word(Token::new(WordData::from("foo"),
+//!                                             Default::default(),
+//!                                             Default::default())));
+//!
+//! let book = epub::from_elements(&ElementType::P(elem)).unwrap();
+//!
+//! // The first file in the archive is the uncompressed media type.
+//! assert_eq!(&book[30..38], b"mimetype");
+//! assert_eq!(&book[38..58], b"application/epub+zip");
+//! ```
+
+use std::error::Error;
+use std::io::{Cursor, Write};
+
+use zip::{CompressionMethod, ZipWriter};
+use zip::write::FileOptions;
+
+use crate::document::*;
+
+use super::{escape, modified, person_name};
+use super::html::{plain_text, Heading, Reflow, READING_STYLE};
+
+const CONTAINER: &str = "\
+<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+<rootfiles>
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>
+</rootfiles>
+</container>
+";
+
+// FNV-1a parameters for 64-bit hashes
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Make the unique identifier of a book from its title and authors
+///
+/// The identifier is an FNV-1a hash, which does not change from one
+/// build of Kosik to the next, so that a book keeps its identifier
+/// when it is written again.
+///
+/// # Examples
+///
+/// ```
+/// use kosik::export::epub;
+///
+/// let authors = vec!["Joseph Conrad".to_string()];
+/// assert_eq!(epub::identifier("Youth", &authors), "urn:kosik:f5f9c1178dab0162");
+/// ```
+pub fn identifier(title: &str, authors: &[String]) -> String {
+    let mut hash = FNV_OFFSET_BASIS;
+
+    // Each name is ended with a null, so that moving text from one
+    // name to the next changes the hash.
+    for text in std::iter::once(title).chain(authors.iter().map(|x| &x[..])) {
+        for byte in text.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    format!("urn:kosik:{:016x}", hash)
+}
+
+// Document in the spine
+struct Item {
+    name: String,
+    html: String,
+    headings: Vec<Heading>,
+}
+
+/// Write the element tree as an EPUB archive.
+pub fn from_elements(elem: &ElementType) -> Result<Vec<u8>, Box<dyn Error>> {
+    let head = match elem {
+        ElementType::Manuscript(elem) => elem.head(),
+        ElementType::Screenplay(elem) => elem.head(),
+        _ => None,
+    };
+
+    let title = match head.and_then(|x| x.title()) {
+        Some(title) => plain_text(&title.tokens[..]),
+        None => "Working Title".to_string(),
+    };
+
+    let authors: Vec<String> = match head.and_then(|x| x.authors()) {
+        Some(authors) => authors.children.iter()
+            .filter_map(|child| match child {
+                ElementType::Person(child) => Some(person_name(child, plain_text)),
+                _ => None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let items = spine(elem);
+
+    let identifier = identifier(&title, &authors);
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = FileOptions::default()
+        .compression_method(CompressionMethod::Stored);
+    let deflated = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated);
+
+    // The media type must come first, uncompressed, so that the
+    // archive can be recognized by its leading bytes.
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(package(&identifier, &title, &authors, &items).as_bytes())?;
+
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav(&title, &items).as_bytes())?;
+
+    zip.start_file("OEBPS/style.css", deflated)?;
+    zip.write_all(READING_STYLE.as_bytes())?;
+
+    for item in items.iter() {
+        zip.start_file(format!("OEBPS/{}", item.name), deflated)?;
+        zip.write_all(xhtml(&title, &item.html).as_bytes())?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+// Split the document into the files of the spine.  A single reflow
+// builder is shared between them, so that fragment identifiers are
+// unique across the book.
+fn spine(elem: &ElementType) -> Vec<Item> {
+    let mut reflow = Reflow::default();
+    let mut items: Vec<Item> = Vec::new();
+
+    let children = match elem {
+        ElementType::Manuscript(elem) => &elem.children,
+        ElementType::Screenplay(elem) => &elem.children,
+        elem => {
+            reflow.element(elem);
+            push_item(&mut items, &mut reflow, "body.xhtml".to_string());
+            return items;
+        },
+    };
+
+    let mut frontmatter_no = 0;
+    let mut backmatter_no = 0;
+    let mut in_body = false;
+
+    for child in children.iter() {
+        match child {
+            ElementType::Backmatter(_) => {
+                if in_body {
+                    push_item(&mut items, &mut reflow, "body.xhtml".to_string());
+                    in_body = false;
+                }
+
+                backmatter_no += 1;
+                reflow.element(child);
+                push_item(&mut items, &mut reflow,
+                          format!("backmatter-{}.xhtml", backmatter_no));
+            },
+            ElementType::Frontmatter(_) => {
+                frontmatter_no += 1;
+                reflow.element(child);
+                push_item(&mut items, &mut reflow,
+                          format!("frontmatter-{}.xhtml", frontmatter_no));
+            },
+            ElementType::Head(_) => {
+                reflow.element(child);
+                push_item(&mut items, &mut reflow, "title.xhtml".to_string());
+            },
+            // Everything else in a screenplay belongs to the body.
+            child => {
+                reflow.element(child);
+                in_body = true;
+            },
+        }
+    }
+
+    if in_body {
+        push_item(&mut items, &mut reflow, "body.xhtml".to_string());
+    }
+
+    items
+}
+
+fn push_item(items: &mut Vec<Item>, reflow: &mut Reflow, name: String) {
+    items.push(Item {
+        name: name,
+        html: std::mem::take(&mut reflow.html),
+        headings: std::mem::take(&mut reflow.headings),
+    });
+}
+
+// Wrap the markup for a spine item in an XHTML document.
+fn xhtml(title: &str, body: &str) -> String {
+    format!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <!DOCTYPE html>\n\
+             <html xmlns=\"http://www.w3.org/1999/xhtml\" \
+             xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+             <head>\n\
+             <meta charset=\"utf-8\"/>\n\
+             <title>{}</title>\n\
+             <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n\
+             </head>\n\
+             <body>\n{}</body>\n\
+             </html>\n",
+            escape(title), body)
+}
+
+// Write the package document, listing the metadata, the files in the
+// archive and their reading order.
+fn package(identifier: &str, title: &str, authors: &[String], items: &[Item])
+           -> String
+{
+    let mut opf = String::new();
+
+    opf.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    opf.push_str("<package xmlns=\"http://www.idpf.org/2007/opf\" \
+                  version=\"3.0\" unique-identifier=\"uid\">\n");
+    opf.push_str("<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n");
+    opf.push_str(&format!("<dc:identifier id=\"uid\">{}</dc:identifier>\n",
+                          escape(identifier)));
+    opf.push_str(&format!("<dc:title>{}</dc:title>\n", escape(title)));
+
+    for author in authors.iter() {
+        opf.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape(author)));
+    }
+
+    opf.push_str("<dc:language>en</dc:language>\n");
+    opf.push_str(&format!("<meta property=\"dcterms:modified\">{}</meta>\n",
+                          modified()));
+    opf.push_str("</metadata>\n");
+
+    opf.push_str("<manifest>\n");
+    opf.push_str("<item id=\"nav\" href=\"nav.xhtml\" \
+                  media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n");
+    opf.push_str("<item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n");
+
+    for (i, item) in items.iter().enumerate() {
+        opf.push_str(&format!("<item id=\"item-{}\" href=\"{}\" \
+                               media-type=\"application/xhtml+xml\"/>\n",
+                              i + 1, item.name));
+    }
+
+    opf.push_str("</manifest>\n");
+
+    opf.push_str("<spine>\n");
+
+    for i in 0..items.len() {
+        opf.push_str(&format!("<itemref idref=\"item-{}\"/>\n", i + 1));
+    }
+
+    opf.push_str("</spine>\n");
+    opf.push_str("</package>\n");
+    opf
+}
+
+// Write the navigation document, nesting the table of contents by the
+// depth of each heading.
+fn nav(title: &str, items: &[Item]) -> String {
+    let mut toc = String::new();
+    // Depth of each open entry, and whether it has a nested list
+    let mut open: Vec<(i32, bool)> = Vec::new();
+
+    for item in items.iter() {
+        for heading in item.headings.iter() {
+            while let Some(&(depth, nested)) = open.last() {
+                if depth < heading.depth {
+                    break;
+                }
+
+                if nested {
+                    toc.push_str("</ol>\n");
+                }
+
+                toc.push_str("</li>\n");
+                open.pop();
+            }
+
+            if let Some(parent) = open.last_mut() {
+                if !parent.1 {
+                    toc.push_str("\n<ol>\n");
+                    parent.1 = true;
+                }
+            }
+
+            toc.push_str(&format!("<li><a href=\"{}#{}\">{}</a>",
+                                  item.name, heading.id, escape(&heading.text)));
+            open.push((heading.depth, false));
+        }
+    }
+
+    while let Some((_, nested)) = open.pop() {
+        if nested {
+            toc.push_str("</ol>\n");
+        }
+
+        toc.push_str("</li>\n");
+    }
+
+    // A table of contents needs at least one entry.
+    if toc.is_empty() {
+        if let Some(item) = items.first() {
+            toc.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n",
+                                  item.name, escape(title)));
+        }
+    }
+
+    xhtml(title, &format!("<nav epub:type=\"toc\" id=\"toc\">\n\
+                           <h1>Contents</h1>\n\
+                           <ol>\n{}</ol>\n\
+                           </nav>\n",
+                          toc))
+}
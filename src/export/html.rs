@@ -0,0 +1,861 @@
+// Kosik HTML Exporter
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Writes manuscripts as HTML for previewing in a browser
+//!
+//! [`from_typescript`] writes a paged preview.  Each page is a
+//! fixed-size box in which every line is placed at the same column
+//! and line number as in the Postscript output, with the slug line at
+//! the top and footnotes under a rule at the bottom.  Emphasis is
+//! underlined, as on a typewriter.
+//!
+//! [`from_elements`] writes a plain reading view instead, in which
+//! the text is reflowed to fit the browser window.  Footnotes are set
+//! after the paragraph that refers to them.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::{Layout, Page, Typescript};
+//! use kosik::export::html;
+//! use kosik::text::{Line, Segment};
+//!
+//! let typescript = Typescript {
+//!     layout: Layout::Manuscript,
+//!     contact: None,
+//!     word_count: None,
+//!     has_structure: false,
+//!     short_title: Segment::from("Working Title"),
+//!     short_author_name: Segment::from("ANONYMOUS"),
+//!     running_heads: Vec::new(),
+//!     pages: vec![Page {
+//!         number: 1,
+//!         height: 54,
+//!         lines: vec![Some(Line::from(Segment::from("R&D")))],
+//!         footer: Vec::new(),
+//!     }],
+//! };
+//!
+//! let html = html::from_typescript(&typescript);
+//!
+//! assert!(html.contains("ANONYMOUS/WORKING TITLE/1"));
+//! assert!(html.contains(">R&amp;D</div>"));
+//! ```
+
+use std::cmp::{max, min};
+use std::collections::HashMap;
+
+use crate::document::*;
+use crate::text::{Line, Segment};
+use crate::text::tokens::*;
+
+use super::{escape, person_footnotes, place_lines, roman_label, section_label};
+
+/// Page width in points
+const PAGE_WIDTH: f32 = 612.0;
+
+/// Page height in points
+const PAGE_HEIGHT: f32 = 792.0;
+
+/// Distance from the top of a line box to the baseline in points
+const BASELINE: f32 = 9.0;
+
+const PAGED_STYLE: &str = "\
+body { background: #ccc; margin: 0; padding: 1em 0; }
+.page { position: relative; overflow: hidden; margin: 0 auto 1em auto;
+        background: #fff; box-shadow: 0 0 4px #888; }
+.line { position: absolute; white-space: pre;
+        font: 12pt/12pt Courier, monospace; }
+sub, sup { font-size: inherit; line-height: 0; }
+sub { vertical-align: -6pt; }
+sup { vertical-align: 6pt; }
+";
+
+pub(super) const READING_STYLE: &str = "\
+body { max-width: 36em; margin: 2em auto; padding: 0 1em;
+       font: 12pt/1.5 Georgia, serif; }
+h1, h2, h3, h4, h5, h6, header { text-align: center; }
+header .authors, .subtitle, .centered, .speaker, .div { text-align: center; }
+.speaker, .cast .name { text-transform: uppercase; }
+.attribution, .transition { text-align: right; }
+.epigraph { font-style: italic; }
+.direction, .parenthetical { font-style: italic; margin-left: 3em; }
+.scene-heading, .character { text-transform: uppercase; }
+.character { margin: 1em 0 0 12em; }
+.dialogue p { margin: 0 6em; }
+.speech p { margin: 0; }
+.speech { margin: 1em 0; }
+.note { font-size: 10pt; border-top: 1px solid #888; margin: 0 0 1em 0; }
+table { border-collapse: collapse; margin: 1em auto; }
+td, th { border: 1px solid #888; padding: 0 0.5em; }
+";
+
+/// Write the composed pages as a paged HTML preview.
+pub fn from_typescript(typescript: &Typescript) -> String {
+    let mut html = String::new();
+
+    html.push_str(&prologue(&typescript.short_title.text, PAGED_STYLE));
+
+    for i in 0..typescript.pages.len() {
+        html.push_str(&format!(
+            "<div class=\"page\" id=\"page-{}\" style=\"width: {}pt; height: {}pt\">\n",
+            i + 1, PAGE_WIDTH, PAGE_HEIGHT
+        ));
+
+        for (y, line) in place_lines(typescript, i).iter() {
+            html.push_str(&paged_line(line, *y));
+        }
+
+        html.push_str("</div>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Write the element tree as a reflowed HTML reading view.
+///
+/// # Examples
+///
+/// ```
+/// use kosik::document::*;
+/// use kosik::export::html;
+/// use kosik::text::tokens::*;
+///
+/// let mut elem = TextElement::new(P {
+///     indent: 5,
+///     line_spacing: LineSpacing::Double,
+///     left_margin: LEFT_MARGIN,
+///     right_margin: RIGHT_MARGIN,
+/// });
+///
+/// elem.tokens.push(TokenType::Word(Token::new(WordData::from("foo"),
+///                                             DisplayFlags::EM,
+///                                             Default::default())));
+///
+/// let html = html::from_elements(&ElementType::P(elem));
+/// assert!(html.contains("<p><em>foo</em></p>"));
+/// ```
+pub fn from_elements(elem: &ElementType) -> String {
+    let title = match elem {
+        ElementType::Manuscript(elem) => elem.short_title(),
+        ElementType::Screenplay(elem) => elem.short_title(),
+        _ => None,
+    };
+
+    let title = match title {
+        Some(segment) => segment.text,
+        None => "Working Title".to_string(),
+    };
+
+    let mut reflow = Reflow::default();
+    reflow.element(elem);
+
+    let mut html = prologue(&title, READING_STYLE);
+    html.push_str(&reflow.html);
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+// Start an HTML document.
+fn prologue(title: &str, style: &str) -> String {
+    format!("<!DOCTYPE html>\n\
+             <html>\n\
+             <head>\n\
+             <meta charset=\"utf-8\"/>\n\
+             <title>{}</title>\n\
+             <style>\n{}</style>\n\
+             </head>\n\
+             <body>\n",
+            escape(title), style)
+}
+
+// Place a line at its column and line number on the page.
+fn paged_line(line: &Line, y: f32) -> String {
+    let left = line.column as f32 * CHAR_WIDTH;
+    let top = PAGE_HEIGHT - y * LINE_HEIGHT - BASELINE;
+
+    let text: String = line.segments.iter().map(paged_segment).collect();
+
+    format!("<div class=\"line\" style=\"left: {:.1}pt; top: {:.1}pt\">{}</div>\n",
+            left, top, text)
+}
+
+// Mark up a line segment, underlining emphasis as on a typewriter.
+// Indentation is kept outside the markup, so that it is not raised
+// with a footnote label.
+fn paged_segment(segment: &Segment) -> String {
+    let text = segment.text.trim_start_matches(' ');
+    let indent = &segment.text[..segment.text.len() - text.len()];
+    let mut html = escape(text);
+
+    if segment.dpy.intersects(DisplayFlags::EM) {
+        html = format!("<u>{}</u>", html);
+    }
+
+    if segment.dpy.intersects(DisplayFlags::SUB) {
+        html = format!("<sub>{}</sub>", html);
+
+    } else if segment.dpy.intersects(DisplayFlags::SUP) {
+        html = format!("<sup>{}</sup>", html);
+    }
+
+    format!("{}{}", indent, html)
+}
+
+fn open_tags(dpy: DisplayFlags) -> String {
+    let mut html = String::new();
+
+    if dpy.intersects(DisplayFlags::EM) {
+        html.push_str("<em>");
+    }
+
+    if dpy.intersects(DisplayFlags::SUB) {
+        html.push_str("<sub>");
+
+    } else if dpy.intersects(DisplayFlags::SUP) {
+        html.push_str("<sup>");
+    }
+
+    html
+}
+
+fn close_tags(dpy: DisplayFlags) -> String {
+    let mut html = String::new();
+
+    if dpy.intersects(DisplayFlags::SUB) {
+        html.push_str("</sub>");
+
+    } else if dpy.intersects(DisplayFlags::SUP) {
+        html.push_str("</sup>");
+    }
+
+    if dpy.intersects(DisplayFlags::EM) {
+        html.push_str("</em>");
+    }
+
+    html
+}
+
+// Open a tag, with a class attribute if one is given.
+fn start_tag(name: &str, class: Option<&str>) -> String {
+    match class {
+        Some(class) => format!("<{} class=\"{}\">", name, class),
+        None => format!("<{}>", name),
+    }
+}
+
+/// Heading recorded for a table of contents
+pub(super) struct Heading {
+    /// Depth in the document structure, starting from zero
+    pub depth: i32,
+    /// Fragment identifier of the heading
+    pub id: String,
+    /// Plain text of the heading
+    pub text: String,
+}
+
+/// Reading view builder
+///
+/// Headings are given fragment identifiers and recorded, so that a
+/// table of contents can link to them.  Note references link to the
+/// footnotes, which are set after the block that first refers to
+/// them.
+#[derive(Default)]
+pub(super) struct Reflow {
+    /// Markup written so far
+    pub html: String,
+    /// Headings written so far
+    pub headings: Vec<Heading>,
+    /// Fragment identifiers of the footnotes, by label
+    notes: HashMap<String, String>,
+    next_id: usize,
+}
+
+impl Reflow {
+    pub fn element(&mut self, elem: &ElementType) {
+        match elem {
+            ElementType::Act(elem) => {
+                let label = roman_label("Act", elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes);
+            },
+            ElementType::Action(elem) => {
+                let class = if elem.attributes.centered {
+                    "action centered"
+                } else {
+                    "action"
+                };
+
+                self.text("p", Some(class), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Attribution(elem) => {
+                self.text("p", Some("attribution"), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Authors(elem) => {
+                let n = elem.children.len();
+                let mut footnotes: Vec<&ElementType> = Vec::new();
+
+                for child in elem.children.iter() {
+                    if let ElementType::Person(child) = child {
+                        footnotes.extend(person_footnotes(child).into_iter());
+                    }
+                }
+
+                self.register(footnotes.iter().copied());
+                self.html.push_str("<p class=\"authors\">by ");
+
+                for (i, child) in elem.children.iter().enumerate() {
+                    if i > 0 {
+                        if i == n - 1 {
+                            self.html.push_str(" and ");
+                        } else {
+                            self.html.push_str(", ");
+                        }
+                    }
+
+                    if let ElementType::Person(child) = child {
+                        let html = self.person(child);
+                        self.html.push_str(&html);
+                    }
+                }
+
+                self.html.push_str("</p>\n");
+                self.footnotes(footnotes.into_iter());
+            },
+            ElementType::Backmatter(elem) => {
+                self.html.push_str("<section class=\"backmatter\">\n");
+                self.label(&elem.attributes.label);
+                self.children(&elem.children);
+                self.html.push_str("</section>\n");
+            },
+            ElementType::BibRef(elem) => {
+                self.text("p", Some("bibref"), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Blockquote(elem) => {
+                self.container("blockquote", None, &elem.children);
+            },
+            ElementType::Body(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Br(_) => {
+                self.html.push_str("<br/>\n");
+            },
+            ElementType::CastList(elem) => {
+                self.html.push_str("<section class=\"cast\">\n");
+                self.label("Cast of Characters");
+
+                let mut children = elem.children.iter().peekable();
+
+                while let Some(child) = children.next() {
+                    match child {
+                        ElementType::Speaker(child) => {
+                            self.register(child.footnotes.iter());
+                            self.html.push_str("<p><span class=\"name\">");
+                            let html = self.inline(&child.tokens[..]);
+                            self.html.push_str(&html);
+                            self.html.push_str("</span>");
+
+                            let mut footnotes: Vec<&ElementType> =
+                                child.footnotes.iter().collect();
+
+                            if let Some(ElementType::P(description)) = children.peek() {
+                                self.register(description.footnotes.iter());
+
+                                if !matches!(description.tokens.first(),
+                                             Some(TokenType::Punct(_)))
+                                {
+                                    self.html.push_str(", ");
+                                }
+
+                                let html = self.inline(&description.tokens[..]);
+                                self.html.push_str(&html);
+                                footnotes.extend(description.footnotes.iter());
+                                children.next();
+                            }
+
+                            self.html.push_str("</p>\n");
+                            self.footnotes(footnotes.into_iter());
+                        },
+                        child => self.element(child),
+                    }
+                }
+
+                self.html.push_str("</section>\n");
+            },
+            ElementType::Chapter(elem) => {
+                let label = format!("Chapter {}", elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes);
+            },
+            ElementType::Character(elem) => {
+                self.text("p", Some("character"), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Contact(elem) => {
+                self.text("address", None, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Dd(elem) => {
+                self.container("dd", None, &elem.children);
+            },
+            ElementType::Dedication(elem) => {
+                self.container("div", Some("dedication"), &elem.children);
+            },
+            ElementType::Dialogue(elem) => {
+                self.container("div", Some("dialogue"), &elem.children);
+            },
+            ElementType::Direction(elem) => {
+                // Stage directions are parenthesized unless the author
+                // has already done so.
+                let parenthesized = matches!(
+                    elem.tokens.first(),
+                    Some(TokenType::Open(token)) if token.data.text == "("
+                );
+
+                self.register(elem.footnotes.iter());
+                self.html.push_str("<p class=\"direction\">");
+
+                let html = self.inline(&elem.tokens[..]);
+
+                if parenthesized {
+                    self.html.push_str(&html);
+                } else {
+                    self.html.push_str(&format!("({})", html));
+                }
+
+                self.html.push_str("</p>\n");
+                self.footnotes(elem.footnotes.iter());
+            },
+            ElementType::Div(_) => {
+                self.html.push_str("<p class=\"div\">#</p>\n");
+            },
+            ElementType::Dl(elem) => {
+                self.container("dl", None, &elem.children);
+            },
+            ElementType::Dt(elem) => {
+                self.text("dt", None, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Em(elem) => {
+                let html = self.inline(&elem.tokens[..]);
+                self.html.push_str(&format!("<em>{}</em>", html));
+            },
+            ElementType::Epigraph(elem) => {
+                self.container("blockquote", Some("epigraph"), &elem.children);
+            },
+            ElementType::Footnote(_) => {
+                self.register(std::iter::once(elem));
+                self.footnotes(std::iter::once(elem));
+            },
+            ElementType::Frontmatter(elem) => {
+                self.html.push_str("<section class=\"frontmatter\">\n");
+                self.label(&elem.attributes.label);
+                self.children(&elem.children);
+                self.html.push_str("</section>\n");
+            },
+            ElementType::Gn(elem) => {
+                let html = self.inline(&elem.tokens[..]);
+                self.html.push_str(&html);
+            },
+            ElementType::Head(elem) => {
+                self.container("header", None, &elem.children);
+            },
+            ElementType::Li(elem) => {
+                self.container("li", None, &elem.children);
+            },
+            ElementType::Manuscript(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::NoteRef(elem) => {
+                let html = self.note_ref(&elem.attributes.label);
+                self.html.push_str(&format!("<sup>{}</sup>", html));
+            },
+            ElementType::Ol(elem) => {
+                self.html.push_str(&format!("<ol start=\"{}\">\n",
+                                            elem.attributes.start_no));
+                self.children(&elem.children);
+                self.html.push_str("</ol>\n");
+            },
+            ElementType::P(elem) => {
+                self.text("p", None, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::PageBreak(_) => {},
+            ElementType::Parenthetical(elem) => {
+                self.text("p", Some("parenthetical"), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Part(elem) => {
+                let label = roman_label("Part", elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes);
+            },
+            ElementType::Person(elem) => {
+                let html = self.person(elem);
+                self.html.push_str(&html);
+            },
+            ElementType::Pre(elem) => {
+                self.html.push_str("<pre>");
+
+                for token in elem.tokens.iter() {
+                    match token {
+                        TokenType::LineBreak(_) => self.html.push('\n'),
+                        token => self.html.push_str(&escape(&token.text())),
+                    }
+                }
+
+                self.html.push_str("</pre>\n");
+            },
+            ElementType::Prefix(elem) => {
+                let html = self.inline(&elem.tokens[..]);
+                self.html.push_str(&html);
+            },
+            ElementType::Scene(elem) => {
+                let label = format!("Scene {}", elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes);
+            },
+            ElementType::SceneHeading(elem) => {
+                self.text("h2", Some("scene-heading"), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Screenplay(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Section(elem) => {
+                let label = section_label(elem.attributes.number);
+                self.heading(elem.attributes.depth, &label, &elem.tokens[..],
+                             &elem.footnotes);
+            },
+            ElementType::Sn(elem) => {
+                let html = self.inline(&elem.tokens[..]);
+                self.html.push_str(&html);
+            },
+            ElementType::Speaker(elem) => {
+                self.text("p", Some("speaker"), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Speech(elem) => {
+                self.container("div", Some("speech"), &elem.children);
+            },
+            ElementType::Sub(elem) => {
+                let html = self.inline(&elem.tokens[..]);
+                self.html.push_str(&format!("<sub>{}</sub>", html));
+            },
+            ElementType::Subtitle(elem) => {
+                self.text("p", Some("subtitle"), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Suffix(elem) => {
+                let html = self.inline(&elem.tokens[..]);
+                self.html.push_str(&html);
+            },
+            ElementType::Sup(elem) => {
+                let html = self.inline(&elem.tokens[..]);
+                self.html.push_str(&format!("<sup>{}</sup>", html));
+            },
+            ElementType::Table(elem) => {
+                self.container("table", None, &elem.children);
+            },
+            ElementType::Td(elem) => {
+                self.text("td", None, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Title(elem) => {
+                self.text("h1", None, &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Tr(elem) => {
+                let name = if elem.attributes.header { "th" } else { "td" };
+                let mut footnotes: Vec<&ElementType> = Vec::new();
+
+                for child in elem.children.iter() {
+                    if let ElementType::Td(child) = child {
+                        footnotes.extend(child.footnotes.iter());
+                    }
+                }
+
+                self.register(footnotes.iter().copied());
+                self.html.push_str("<tr>");
+
+                for child in elem.children.iter() {
+                    if let ElementType::Td(child) = child {
+                        let html = self.inline(&child.tokens[..]);
+                        self.html.push_str(&format!("<{0}>{1}</{0}>", name, html));
+                    }
+                }
+
+                self.html.push_str("</tr>\n");
+                self.footnotes(footnotes.into_iter());
+            },
+            ElementType::Transition(elem) => {
+                self.text("p", Some("transition"), &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Ul(elem) => {
+                self.container("ul", None, &elem.children);
+            },
+        }
+    }
+
+    fn children(&mut self, children: &ElementList) {
+        for child in children.iter() {
+            self.element(child);
+        }
+    }
+
+    fn container(&mut self, name: &str, class: Option<&str>,
+                 children: &ElementList)
+    {
+        self.html.push_str(&start_tag(name, class));
+        self.html.push('\n');
+        self.children(children);
+        self.html.push_str(&format!("</{}>\n", name));
+    }
+
+    fn text(&mut self, name: &str, class: Option<&str>, tokens: &[TokenType],
+            footnotes: &ElementList)
+    {
+        self.register(footnotes.iter());
+        self.html.push_str(&start_tag(name, class));
+        let html = self.inline(tokens);
+        self.html.push_str(&html);
+        self.html.push_str(&format!("</{}>\n", name));
+        self.footnotes(footnotes.iter());
+    }
+
+    // Headings run from <h2> for the top level of the document
+    // structure down to <h6>, since <h1> is kept for the title.
+    fn heading(&mut self, depth: i32, label: &str, tokens: &[TokenType],
+               footnotes: &ElementList)
+    {
+        let level = min(max(depth, 0) + 2, 6);
+        let id = self.next_id("heading");
+        let mut text = label.to_string();
+
+        self.register(footnotes.iter());
+        self.html.push_str(&format!("<h{} id=\"{}\">{}", level, id, escape(label)));
+
+        if !tokens.is_empty() {
+            self.html.push_str("<br/>\n");
+            let html = self.inline(tokens);
+            self.html.push_str(&html);
+
+            text.push_str(": ");
+            text.push_str(&plain_text(tokens));
+        }
+
+        self.html.push_str(&format!("</h{}>\n", level));
+        self.footnotes(footnotes.iter());
+
+        self.headings.push(Heading {
+            depth: max(depth, 0),
+            id: id,
+            text: text,
+        });
+    }
+
+    // Front and back matter labels are headings at the top level.
+    fn label(&mut self, label: &str) {
+        let id = self.next_id("heading");
+
+        self.html.push_str(&format!("<h2 id=\"{}\">{}</h2>\n", id, escape(label)));
+
+        self.headings.push(Heading {
+            depth: 0,
+            id: id,
+            text: label.to_string(),
+        });
+    }
+
+    fn next_id(&mut self, prefix: &str) -> String {
+        self.next_id += 1;
+        format!("{}-{}", prefix, self.next_id)
+    }
+
+    // Give each footnote a fragment identifier before the note
+    // references to it are written.
+    fn register<'a, I>(&mut self, footnotes: I)
+    where
+        I: Iterator<Item = &'a ElementType>,
+    {
+        for footnote in footnotes {
+            if let ElementType::Footnote(footnote) = footnote {
+                let id = self.next_id("note");
+                self.notes.insert(footnote.attributes.label.clone(), id);
+            }
+        }
+    }
+
+    fn footnotes<'a, I>(&mut self, footnotes: I)
+    where
+        I: Iterator<Item = &'a ElementType>,
+    {
+        for footnote in footnotes {
+            if let ElementType::Footnote(footnote) = footnote {
+                let label = &footnote.attributes.label;
+
+                let id = match self.notes.get(label) {
+                    Some(id) => format!(" id=\"{}\"", id),
+                    None => String::new(),
+                };
+
+                self.html.push_str(&format!(
+                    "<aside class=\"note\" epub:type=\"footnote\"{}>\n<sup>{}</sup>\n",
+                    id, escape(label)
+                ));
+                self.children(&footnote.children);
+                self.html.push_str("</aside>\n");
+            }
+        }
+    }
+
+    // Link a note reference to the latest footnote with its label.
+    fn note_ref(&self, label: &str) -> String {
+        match self.notes.get(label) {
+            Some(id) => format!("<a epub:type=\"noteref\" href=\"#{}\">{}</a>",
+                                id, escape(label)),
+            None => escape(label),
+        }
+    }
+
+    // Mark up a token list, opening and closing tags wherever the
+    // display flags change.
+    fn inline(&self, tokens: &[TokenType]) -> String {
+        let mut html = String::new();
+        let mut dpy: DisplayFlags = Default::default();
+
+        for token in tokens.iter() {
+            let next = token.display_flags();
+
+            if next != dpy {
+                html.push_str(&close_tags(dpy));
+                html.push_str(&open_tags(next));
+                dpy = next;
+            }
+
+            match token {
+                TokenType::LineBreak(_) => html.push_str("<br/>\n"),
+                TokenType::NoteRef(token) => html.push_str(&self.note_ref(&token.data.text)),
+                token => html.push_str(&escape(&typographic_text(token))),
+            }
+        }
+
+        html.push_str(&close_tags(dpy));
+        html
+    }
+
+    // Format a personal name.
+    fn person(&self, elem: &ContainerElement<Person>) -> String {
+        let mut html = String::new();
+
+        for (i, child) in elem.children.iter().enumerate() {
+            match child {
+                ElementType::Footnote(child) => {
+                    html.push_str(&format!("<sup>{}</sup>",
+                                           self.note_ref(&child.attributes.label)));
+                },
+                ElementType::NoteRef(child) => {
+                    html.push_str(&format!("<sup>{}</sup>",
+                                           self.note_ref(&child.attributes.label)));
+                },
+                ElementType::Gn(child) => {
+                    if i > 0 {
+                        html.push(' ');
+                    }
+
+                    html.push_str(&self.inline(&child.tokens[..]));
+                },
+                ElementType::Prefix(child) => {
+                    if i > 0 {
+                        html.push(' ');
+                    }
+
+                    html.push_str(&self.inline(&child.tokens[..]));
+                },
+                ElementType::Sn(child) => {
+                    if i > 0 {
+                        html.push(' ');
+                    }
+
+                    html.push_str(&self.inline(&child.tokens[..]));
+                },
+                ElementType::Suffix(child) => {
+                    if child.attributes.comma {
+                        html.push(',');
+                    }
+
+                    if i > 0 {
+                        html.push(' ');
+                    }
+
+                    html.push_str(&self.inline(&child.tokens[..]));
+                },
+                _ => {},
+            }
+        }
+
+        html
+    }
+}
+
+/// Flatten a token list to plain text, dropping note references.
+/// Dashes, ellipses and quotation marks are given back the
+/// characters that the parser replaced with their typewriter
+/// equivalents.
+///
+/// # Examples
+///
+/// ```
+/// use kosik::document::ElementType;
+/// use kosik::document::reader::Reader;
+/// use kosik::export::html;
+///
+/// let xml = "<p>\u{201c}So to speak\u{2014}the sea\u{201d}</p>";
+///
+/// match Reader::new(xml).run().unwrap() {
+///     Some(ElementType::P(elem)) => {
+///         assert_eq!(html::plain_text(&elem.tokens[..]), "\u{201c}So to speak\u{2014}the sea\u{201d}");
+///     },
+///     _ => panic!(),
+/// }
+/// ```
+pub fn plain_text(tokens: &[TokenType]) -> String {
+    let mut text = String::new();
+
+    for token in tokens.iter() {
+        match token {
+            TokenType::LineBreak(_) => text.push(' '),
+            TokenType::NoteRef(_) => {},
+            token => text.push_str(&typographic_text(token)),
+        }
+    }
+
+    text
+}
+
+// Restore the typographic characters that the parser replaced with
+// their typewriter equivalents.
+pub(crate) fn typographic_text(token: &TokenType) -> String {
+    match token {
+        TokenType::Close(token) => match &token.data.text[..] {
+            "\"" => "\u{201d}".to_string(),
+            "'" => "\u{2019}".to_string(),
+            text => text.to_string(),
+        },
+        TokenType::Open(token) => match &token.data.text[..] {
+            "\"" => "\u{201c}".to_string(),
+            "'" => "\u{2018}".to_string(),
+            text => text.to_string(),
+        },
+        TokenType::Punct(token) => match token.data.glyph {
+            Some(glyph) => glyph.to_string(),
+            None => token.data.text.clone(),
+        },
+        token => token.text(),
+    }
+}
@@ -0,0 +1,799 @@
+// Kosik LaTeX Exporter
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Writes a LaTeX document for typeset proofs
+//!
+//! The document uses the standard book class.  The head becomes the
+//! title page, front and back matter switch the page numbering, and
+//! parts, chapters and sections keep the numbers they have in the
+//! manuscript.  Emphasis is set in italics, and footnotes are placed
+//! where they are referred to.
+//!
+//! The typewriter conventions of the manuscript are undone: curly
+//! quotes, dashes and ellipses are written with their LaTeX
+//! ligatures and commands, and the double space after a full stop is
+//! left to TeX.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::*;
+//! use kosik::export::latex;
+//! use kosik::text::parser::Parser;
+//!
+//! let mut elem = TextElement::new(P {
+//!     indent: 5,
+//!     line_spacing: LineSpacing::Double,
+//!     left_margin: LEFT_MARGIN,
+//!     right_margin: RIGHT_MARGIN,
+//! });
+//!
+//! let parser = Parser::new("\u{201c}100% sure\u{2014}really.\u{201d}",
+//!                          Vec::new(), Default::default());
+//! (_, elem.tokens) = parser.run();
+//!
+//! let tex = latex::from_elements(&ElementType::P(elem));
+//! assert!(tex.contains("``100\\% sure---really.''"));
+//!
+//! let parser = Parser::new("\u{201c}Say \u{2018}no.\u{2019}\u{201d}",
+//!                          Vec::new(), Default::default());
+//! let mut elem = TextElement::new(P {
+//!     indent: 5,
+//!     line_spacing: LineSpacing::Double,
+//!     left_margin: LEFT_MARGIN,
+//!     right_margin: RIGHT_MARGIN,
+//! });
+//! (_, elem.tokens) = parser.run();
+//!
+//! let tex = latex::from_elements(&ElementType::P(elem));
+//! assert!(tex.contains("``Say `no.'{}''"));
+//! ```
+
+use crate::document::*;
+use crate::text::tokens::*;
+
+use super::roman_label;
+use super::html::{plain_text, typographic_text};
+
+/// Counters for the labels of nested ordered lists
+const ENUM_COUNTERS: [&str; 4] = ["enumi", "enumii", "enumiii", "enumiv"];
+
+/// Write the element tree as a LaTeX document.
+pub fn from_elements(elem: &ElementType) -> String {
+    let mut builder = Builder::default();
+    builder.element(elem);
+
+    let mut tex = String::new();
+
+    tex.push_str("\\documentclass[12pt]{book}\n");
+    tex.push_str("\\usepackage[T1]{fontenc}\n");
+    tex.push_str("\\usepackage[utf8]{inputenc}\n");
+    tex.push_str("\\usepackage{textcomp}\n");
+    tex.push('\n');
+    tex.push_str("% Sections are lettered within each chapter.\n");
+    tex.push_str("\\renewcommand{\\thesection}{\\Alph{section}}\n");
+    tex.push('\n');
+
+    if let Some(title) = &builder.title {
+        tex.push_str(&format!("\\title{{{}}}\n", title));
+        tex.push_str(&format!("\\author{{{}}}\n", builder.author));
+        tex.push_str("\\date{}\n");
+        tex.push('\n');
+    }
+
+    tex.push_str("\\begin{document}\n\n");
+    tex.push_str(&builder.tex);
+    tex.push_str("\\end{document}\n");
+    tex
+}
+
+// Escape the characters that LaTeX treats specially.
+fn escape(text: &str) -> String {
+    let mut tex = String::with_capacity(text.len());
+
+    for ch in text.chars() {
+        match ch {
+            '\\' => tex.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '%' | '_' => {
+                tex.push('\\');
+                tex.push(ch);
+            },
+            '^' => tex.push_str("\\textasciicircum{}"),
+            '~' => tex.push_str("\\textasciitilde{}"),
+            '<' => tex.push_str("\\textless{}"),
+            '>' => tex.push_str("\\textgreater{}"),
+            '|' => tex.push_str("\\textbar{}"),
+            '"' => tex.push_str("\\textquotedbl{}"),
+            ch => tex.push(ch),
+        }
+    }
+
+    tex
+}
+
+// Write a token in TeX, from the typographic characters that the
+// parser replaced with their typewriter equivalents.
+fn token_to_tex(token: &TokenType) -> String {
+    match token {
+        // TeX sets its own space after a full stop.
+        TokenType::Space(_) => " ".to_string(),
+        // The only symbol written as a space is a tilde, which marks
+        // a non-breaking space.
+        TokenType::Symbol(token) if token.data.text == " " => "~".to_string(),
+        token => typographic_tex(&typographic_text(token)),
+    }
+}
+
+// Write typographic characters with the ligatures and commands that
+// TeX sets them from, and escape the rest.
+fn typographic_tex(text: &str) -> String {
+    let mut tex = String::with_capacity(text.len());
+
+    for ch in text.chars() {
+        match ch {
+            '\u{2018}' => push_tex(&mut tex, "`"),
+            '\u{2019}' => push_tex(&mut tex, "'"),
+            '\u{201c}' => push_tex(&mut tex, "``"),
+            '\u{201d}' => push_tex(&mut tex, "''"),
+            '\u{00ab}' => push_tex(&mut tex, "\\guillemotleft{}"),
+            '\u{00bb}' => push_tex(&mut tex, "\\guillemotright{}"),
+            '\u{2013}' => push_tex(&mut tex, "--"),
+            '\u{2014}' => push_tex(&mut tex, "---"),
+            '\u{2026}' => push_tex(&mut tex, "\\dots{}"),
+            ch => push_tex(&mut tex, &escape(ch.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    tex
+}
+
+// Append TeX, keeping the characters on either side from running
+// together into a ligature, as a closing single quote followed by a
+// closing double quote would.
+fn push_tex(tex: &mut String, text: &str) {
+    let ligature = matches!((tex.chars().last(), text.chars().next()),
+                            (Some('`'), Some('`'))
+                            | (Some('\''), Some('\''))
+                            | (Some('-'), Some('-'))
+                            | (Some('!'), Some('`'))
+                            | (Some('?'), Some('`'))
+                            | (Some(','), Some(',')));
+
+    if ligature {
+        tex.push_str("{}");
+    }
+
+    tex.push_str(text);
+}
+
+fn open_groups(dpy: DisplayFlags) -> String {
+    let mut tex = String::new();
+
+    if dpy.intersects(DisplayFlags::EM) {
+        tex.push_str("\\emph{");
+    }
+
+    if dpy.intersects(DisplayFlags::SUB) {
+        tex.push_str("\\textsubscript{");
+
+    } else if dpy.intersects(DisplayFlags::SUP) {
+        tex.push_str("\\textsuperscript{");
+    }
+
+    tex
+}
+
+fn close_groups(dpy: DisplayFlags) -> String {
+    let mut tex = String::new();
+
+    if dpy.intersects(DisplayFlags::SUB) || dpy.intersects(DisplayFlags::SUP) {
+        tex.push('}');
+    }
+
+    if dpy.intersects(DisplayFlags::EM) {
+        tex.push('}');
+    }
+
+    tex
+}
+
+// Begin a paragraph, suppressing the indent if the manuscript has
+// none.
+fn paragraph_start(indent: usize) -> &'static str {
+    if indent == 0 {
+        "\\noindent "
+    } else {
+        ""
+    }
+}
+
+/// LaTeX document builder
+#[derive(Default)]
+struct Builder {
+    tex: String,
+    /// Title and authors for the title page, from the head
+    title: Option<String>,
+    author: String,
+    /// Nesting depth of ordered lists
+    enum_depth: usize,
+    /// True once the front matter has begun
+    in_frontmatter: bool,
+    /// True once the main matter has begun
+    in_mainmatter: bool,
+    /// True once the back matter has begun
+    in_backmatter: bool,
+}
+
+impl Builder {
+    fn element(&mut self, elem: &ElementType) {
+        match elem {
+            ElementType::Act(elem) => {
+                let label = roman_label("Act", elem.attributes.number);
+                self.starred_heading("chapter", &label, &elem.tokens[..],
+                                     &elem.footnotes);
+            },
+            ElementType::Action(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+
+                if elem.attributes.centered {
+                    self.environment("center", &text);
+                } else {
+                    self.tex.push_str(&format!("\\noindent {}\n\n", text));
+                }
+            },
+            ElementType::Attribution(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.environment("flushright", &text);
+            },
+            ElementType::Authors(elem) => {
+                let text = self.authors(elem, ", ", "footnote");
+                self.environment("center", &format!("by {}", text));
+            },
+            ElementType::Backmatter(elem) => {
+                if !self.in_backmatter {
+                    self.tex.push_str("\\backmatter\n\n");
+                    self.in_backmatter = true;
+                }
+
+                self.tex.push_str(&format!("\\chapter{{{}}}\n\n",
+                                           escape(&elem.attributes.label)));
+                self.children(&elem.children);
+            },
+            ElementType::BibRef(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&format!("\\noindent\\hangindent=2em\\hangafter=1 {}\n\n",
+                                           text));
+            },
+            ElementType::Blockquote(elem) => {
+                self.container("quote", &elem.children);
+            },
+            ElementType::Body(elem) => {
+                self.mainmatter();
+                self.children(&elem.children);
+            },
+            ElementType::Br(_) => {
+                self.tex.push_str("\\vspace{\\baselineskip}\n\n");
+            },
+            ElementType::CastList(elem) => {
+                self.tex.push_str("\\chapter*{Cast of Characters}\n");
+                self.tex.push_str("\\addcontentsline{toc}{chapter}{Cast of Characters}\n\n");
+                self.tex.push_str("\\begin{description}\n");
+
+                let mut children = elem.children.iter().peekable();
+
+                while let Some(child) = children.next() {
+                    match child {
+                        ElementType::Speaker(child) => {
+                            let name = self.inline(&child.tokens[..], &child.footnotes,
+                                                   "footnote");
+                            self.tex.push_str(&format!("\\item[\\textsc{{{}}}]", name));
+
+                            if let Some(ElementType::P(description)) = children.peek() {
+                                let text = self.inline(&description.tokens[..],
+                                                       &description.footnotes,
+                                                       "footnote");
+                                self.tex.push_str(&format!(" {}", text));
+                                children.next();
+                            }
+
+                            self.tex.push('\n');
+                        },
+                        child => self.element(child),
+                    }
+                }
+
+                self.tex.push_str("\\end{description}\n\n");
+            },
+            ElementType::Chapter(elem) => {
+                self.mainmatter();
+                self.tex.push_str(&format!("\\setcounter{{chapter}}{{{}}}\n",
+                                           elem.attributes.number - 1));
+                self.heading("chapter", &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Character(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.environment("center", &text);
+            },
+            ElementType::Contact(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.environment("flushleft", &text);
+            },
+            ElementType::Dd(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Dedication(elem) => {
+                self.container("center", &elem.children);
+            },
+            ElementType::Dialogue(elem) => {
+                self.container("quote", &elem.children);
+            },
+            ElementType::Direction(elem) => {
+                // Stage directions are parenthesized unless the author
+                // has already done so.
+                let parenthesized = matches!(
+                    elem.tokens.first(),
+                    Some(TokenType::Open(token)) if token.data.text == "("
+                );
+
+                let mut text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+
+                if !parenthesized {
+                    text = format!("({})", text);
+                }
+
+                self.environment("quote", &format!("\\itshape {}", text));
+            },
+            ElementType::Div(_) => {
+                self.environment("center", "*\\quad*\\quad*");
+            },
+            ElementType::Dl(elem) => {
+                self.container("description", &elem.children);
+            },
+            ElementType::Dt(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&format!("\\item[{}]\n", text));
+            },
+            ElementType::Em(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&text);
+            },
+            ElementType::Epigraph(elem) => {
+                self.tex.push_str("\\begin{quote}\n\\small\n");
+                self.children(&elem.children);
+                self.tex.push_str("\\end{quote}\n\n");
+            },
+            ElementType::Footnote(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Frontmatter(elem) => {
+                if !self.in_frontmatter {
+                    self.tex.push_str("\\frontmatter\n\n");
+                    self.in_frontmatter = true;
+                }
+
+                self.tex.push_str(&format!("\\chapter{{{}}}\n\n",
+                                           escape(&elem.attributes.label)));
+                self.children(&elem.children);
+            },
+            ElementType::Gn(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&text);
+            },
+            ElementType::Head(elem) => {
+                self.head(elem);
+            },
+            ElementType::Li(elem) => {
+                self.tex.push_str("\\item ");
+                self.children(&elem.children);
+            },
+            ElementType::Manuscript(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::NoteRef(elem) => {
+                self.tex.push_str(&format!("\\textsuperscript{{{}}}",
+                                           escape(&elem.attributes.label)));
+            },
+            ElementType::Ol(elem) => {
+                self.tex.push_str("\\begin{enumerate}\n");
+
+                if let Some(counter) = ENUM_COUNTERS.get(self.enum_depth) {
+                    if elem.attributes.start_no != 1 {
+                        self.tex.push_str(&format!("\\setcounter{{{}}}{{{}}}\n",
+                                                   counter, elem.attributes.start_no - 1));
+                    }
+                }
+
+                self.enum_depth += 1;
+                self.children(&elem.children);
+                self.enum_depth -= 1;
+                self.tex.push_str("\\end{enumerate}\n\n");
+            },
+            ElementType::P(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(paragraph_start(elem.attributes.indent));
+                self.tex.push_str(&text);
+                self.tex.push_str("\n\n");
+            },
+            ElementType::PageBreak(_) => {
+                self.tex.push_str("\\clearpage\n\n");
+            },
+            ElementType::Parenthetical(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.environment("center", &text);
+            },
+            ElementType::Part(elem) => {
+                self.mainmatter();
+                self.tex.push_str(&format!("\\setcounter{{part}}{{{}}}\n",
+                                           elem.attributes.number - 1));
+                self.heading("part", &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Person(elem) => {
+                let text = self.person(elem, "footnote");
+                self.tex.push_str(&text);
+            },
+            ElementType::Pre(elem) => {
+                self.tex.push_str("\\begin{verbatim}\n");
+
+                for token in elem.tokens.iter() {
+                    match token {
+                        TokenType::LineBreak(_) => self.tex.push('\n'),
+                        token => self.tex.push_str(&token.text()),
+                    }
+                }
+
+                self.tex.push_str("\n\\end{verbatim}\n\n");
+            },
+            ElementType::Prefix(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&text);
+            },
+            ElementType::Scene(elem) => {
+                let label = format!("Scene {}", elem.attributes.number);
+                self.starred_heading("section", &label, &elem.tokens[..],
+                                     &elem.footnotes);
+            },
+            ElementType::SceneHeading(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&format!("\\section*{{{}}}\n\n", text));
+            },
+            ElementType::Screenplay(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Section(elem) => {
+                self.tex.push_str(&format!("\\setcounter{{section}}{{{}}}\n",
+                                           elem.attributes.number - 1));
+                self.heading("section", &elem.tokens[..], &elem.footnotes);
+            },
+            ElementType::Sn(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&text);
+            },
+            ElementType::Speaker(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.environment("center", &format!("\\textsc{{{}}}", text));
+            },
+            ElementType::Speech(elem) => {
+                self.children(&elem.children);
+            },
+            ElementType::Sub(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&text);
+            },
+            ElementType::Subtitle(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.environment("center", &format!("\\large {}", text));
+            },
+            ElementType::Suffix(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&text);
+            },
+            ElementType::Sup(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&text);
+            },
+            ElementType::Table(elem) => {
+                self.table(&elem.children);
+            },
+            ElementType::Td(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.tex.push_str(&text);
+            },
+            ElementType::Title(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.environment("center", &format!("\\LARGE {}", text));
+            },
+            ElementType::Tr(_) => {
+                self.table(std::slice::from_ref(elem));
+            },
+            ElementType::Transition(elem) => {
+                let text = self.inline(&elem.tokens[..], &elem.footnotes, "footnote");
+                self.environment("flushright", &text);
+            },
+            ElementType::Ul(elem) => {
+                self.container("itemize", &elem.children);
+            },
+        }
+    }
+
+    fn children(&mut self, children: &ElementList) {
+        for child in children.iter() {
+            self.element(child);
+        }
+    }
+
+    fn container(&mut self, name: &str, children: &ElementList) {
+        self.tex.push_str(&format!("\\begin{{{}}}\n", name));
+        self.children(children);
+        self.tex.push_str(&format!("\\end{{{}}}\n\n", name));
+    }
+
+    fn environment(&mut self, name: &str, text: &str) {
+        self.tex.push_str(&format!("\\begin{{{0}}}\n{1}\n\\end{{{0}}}\n\n", name, text));
+    }
+
+    // The main matter starts with the body, or with the first part
+    // or chapter of a document without one.
+    fn mainmatter(&mut self) {
+        if !self.in_mainmatter && !self.in_backmatter {
+            self.tex.push_str("\\mainmatter\n\n");
+            self.in_mainmatter = true;
+        }
+    }
+
+    // Write a numbered heading.  A heading with footnotes has a short
+    // form without them for the table of contents and the running
+    // heads.
+    fn heading(&mut self, command: &str, tokens: &[TokenType],
+               footnotes: &ElementList)
+    {
+        let text = self.inline(tokens, footnotes, "footnote");
+
+        if footnotes.is_empty() {
+            self.tex.push_str(&format!("\\{}{{{}}}\n\n", command, text));
+        } else {
+            self.tex.push_str(&format!("\\{}[{}]{{{}}}\n\n", command,
+                                       escape(&plain_text(tokens)), text));
+        }
+    }
+
+    // Write an unnumbered heading with its own label, adding it to the
+    // table of contents.
+    fn starred_heading(&mut self, command: &str, label: &str, tokens: &[TokenType],
+                       footnotes: &ElementList)
+    {
+        let mut entry = escape(label);
+
+        if tokens.is_empty() {
+            self.tex.push_str(&format!("\\{}*{{{}}}\n", command, entry));
+        } else {
+            let text = self.inline(tokens, footnotes, "footnote");
+            self.tex.push_str(&format!("\\{}*{{{}\\\\ {}}}\n", command, entry, text));
+
+            entry.push_str(": ");
+            entry.push_str(&escape(&plain_text(tokens)));
+        }
+
+        self.tex.push_str(&format!("\\addcontentsline{{toc}}{{{}}}{{{}}}\n\n",
+                                   command, entry));
+    }
+
+    // Collect the title and authors for the title page.  Footnotes on
+    // the title page become thanks.
+    fn head(&mut self, elem: &ContainerElement<Head>) {
+        let mut title = String::new();
+        let mut subtitle = String::new();
+
+        for child in elem.children.iter() {
+            match child {
+                ElementType::Authors(child) => {
+                    self.author = self.authors(child, " \\and ", "thanks");
+                },
+                ElementType::Subtitle(child) => {
+                    subtitle = self.inline(&child.tokens[..], &child.footnotes, "thanks");
+                },
+                ElementType::Title(child) => {
+                    title = self.inline(&child.tokens[..], &child.footnotes, "thanks");
+                },
+                _ => {},
+            }
+        }
+
+        if !subtitle.is_empty() {
+            title = format!("{}\\\\\n\\large {}", title, subtitle);
+        }
+
+        self.title = Some(title);
+        self.tex.push_str("\\maketitle\n\n");
+    }
+
+    fn authors(&mut self, elem: &ContainerElement<Authors>, separator: &str,
+               note_command: &str) -> String
+    {
+        let n = elem.children.len();
+        let mut tex = String::new();
+
+        for (i, child) in elem.children.iter().enumerate() {
+            if i > 0 {
+                if separator == ", " && i == n - 1 {
+                    tex.push_str(" and ");
+                } else {
+                    tex.push_str(separator);
+                }
+            }
+
+            if let ElementType::Person(child) = child {
+                tex.push_str(&self.person(child, note_command));
+            }
+        }
+
+        tex
+    }
+
+    // Write a table with a rule under the header rows.
+    fn table(&mut self, children: &[ElementType]) {
+        let columns = children.iter()
+            .map(|child| match child {
+                ElementType::Tr(child) => child.children.len(),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        self.tex.push_str("\\begin{center}\n");
+        self.tex.push_str(&format!("\\begin{{tabular}}{{{}}}\n", "l".repeat(columns)));
+        self.tex.push_str("\\hline\n");
+
+        let mut header = false;
+
+        for child in children.iter() {
+            if let ElementType::Tr(child) = child {
+                if header && !child.attributes.header {
+                    self.tex.push_str("\\hline\n");
+                }
+
+                header = child.attributes.header;
+
+                let cells: Vec<String> = child.children.iter()
+                    .filter_map(|child| match child {
+                        ElementType::Td(child) => Some(
+                            self.inline(&child.tokens[..], &child.footnotes, "footnote")
+                        ),
+                        _ => None,
+                    })
+                    .collect();
+
+                self.tex.push_str(&cells.join(" & "));
+                self.tex.push_str(" \\\\\n");
+            }
+        }
+
+        self.tex.push_str("\\hline\n");
+        self.tex.push_str("\\end{tabular}\n");
+        self.tex.push_str("\\end{center}\n\n");
+    }
+
+    // Write a footnote's paragraphs as the argument of a footnote
+    // command.
+    fn note(&mut self, footnote: &ContainerElement<Footnote>, command: &str) -> String {
+        let tex = std::mem::take(&mut self.tex);
+        self.children(&footnote.children);
+        let note = std::mem::replace(&mut self.tex, tex);
+
+        format!("\\{}{{{}}}", command, note.trim_end())
+    }
+
+    // Mark up a token list, opening and closing groups wherever the
+    // display flags change.  Note references are replaced by the
+    // footnotes they refer to.
+    fn inline(&mut self, tokens: &[TokenType], footnotes: &ElementList,
+              note_command: &str) -> String
+    {
+        let mut tex = String::new();
+        let mut dpy: DisplayFlags = Default::default();
+
+        for token in tokens.iter() {
+            let next = match token {
+                TokenType::NoteRef(_) => Default::default(),
+                token => token.display_flags(),
+            };
+
+            if next != dpy {
+                tex.push_str(&close_groups(dpy));
+                tex.push_str(&open_groups(next));
+                dpy = next;
+            }
+
+            match token {
+                TokenType::LineBreak(_) => tex.push_str("\\\\\n"),
+                TokenType::NoteRef(token) => {
+                    let footnote = footnotes.iter().find_map(|child| match child {
+                        ElementType::Footnote(child)
+                            if child.attributes.label == token.data.text => Some(child),
+                        _ => None,
+                    });
+
+                    match footnote {
+                        Some(footnote) => tex.push_str(&self.note(footnote, note_command)),
+                        None => tex.push_str(&format!("\\textsuperscript{{{}}}",
+                                                      escape(&token.data.text))),
+                    }
+                },
+                token => push_tex(&mut tex, &token_to_tex(token)),
+            }
+        }
+
+        tex.push_str(&close_groups(dpy));
+        tex
+    }
+
+    // Format a personal name.
+    fn person(&mut self, elem: &ContainerElement<Person>, note_command: &str) -> String {
+        let mut tex = String::new();
+
+        for (i, child) in elem.children.iter().enumerate() {
+            match child {
+                ElementType::Footnote(child) => {
+                    tex.push_str(&self.note(child, note_command));
+                },
+                ElementType::NoteRef(child) => {
+                    tex.push_str(&format!("\\textsuperscript{{{}}}",
+                                          escape(&child.attributes.label)));
+                },
+                ElementType::Gn(child) => {
+                    if i > 0 {
+                        tex.push(' ');
+                    }
+
+                    tex.push_str(&self.inline(&child.tokens[..], &child.footnotes,
+                                              note_command));
+                },
+                ElementType::Prefix(child) => {
+                    if i > 0 {
+                        tex.push(' ');
+                    }
+
+                    tex.push_str(&self.inline(&child.tokens[..], &child.footnotes,
+                                              note_command));
+                },
+                ElementType::Sn(child) => {
+                    if i > 0 {
+                        tex.push(' ');
+                    }
+
+                    tex.push_str(&self.inline(&child.tokens[..], &child.footnotes,
+                                              note_command));
+                },
+                ElementType::Suffix(child) => {
+                    if child.attributes.comma {
+                        tex.push(',');
+                    }
+
+                    if i > 0 {
+                        tex.push(' ');
+                    }
+
+                    tex.push_str(&self.inline(&child.tokens[..], &child.footnotes,
+                                              note_command));
+                },
+                _ => {},
+            }
+        }
+
+        tex
+    }
+}
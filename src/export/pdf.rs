@@ -0,0 +1,215 @@
+// Kosik PDF Exporter
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Writes the typed pages as a PDF document
+//!
+//! The pages are set in Courier, which every PDF viewer has built in,
+//! so no font is embedded.  Lines are placed at the same coordinates
+//! as in the Postscript output.  Text is encoded as Windows-1252, the
+//! standard encoding for the built-in fonts, and characters outside
+//! it are replaced with question marks.  Emphasis is underlined, and
+//! subscripts and superscripts are shifted half a line down or up.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::{Layout, Page, Typescript};
+//! use kosik::export::pdf;
+//! use kosik::text::{Line, Segment};
+//!
+//! let typescript = Typescript {
+//!     layout: Layout::Manuscript,
+//!     contact: None,
+//!     word_count: None,
+//!     has_structure: false,
+//!     short_title: Segment::from("Working Title"),
+//!     short_author_name: Segment::from("ANONYMOUS"),
+//!     running_heads: Vec::new(),
+//!     pages: vec![Page {
+//!         number: 1,
+//!         height: 54,
+//!         lines: vec![Some(Line {
+//!             column: 10,
+//!             segments: vec![Segment::from("foo")],
+//!             note_refs: Vec::new(),
+//!         })],
+//!         footer: Vec::new(),
+//!     }],
+//! };
+//!
+//! let pdf = pdf::from_typescript(&typescript);
+//! let text = String::from_utf8_lossy(&pdf);
+//!
+//! assert!(pdf.starts_with(b"%PDF-1.4"));
+//! assert!(text.contains("/Count 1"));
+//! assert!(text.contains("72.0 708.0 Td (foo) Tj"));
+//! ```
+
+use encoding::{EncoderTrap, Encoding};
+use encoding::all::WINDOWS_1252;
+
+use crate::document::*;
+use crate::text::Line;
+use crate::text::tokens::DisplayFlags;
+
+use super::place_lines;
+use super::{SCRIPT_SHIFT, UNDERLINE_POSITION, UNDERLINE_THICKNESS};
+
+/// Page width in points
+const PAGE_WIDTH: f32 = 612.0;
+
+/// Page height in points
+const PAGE_HEIGHT: f32 = 792.0;
+
+// Objects that come before the pages
+const CATALOG: usize = 1;
+const PAGES: usize = 2;
+const FONT: usize = 3;
+const INFO: usize = 4;
+
+/// Write the composed pages as a PDF document.
+pub fn from_typescript(typescript: &Typescript) -> Vec<u8> {
+    let n = typescript.pages.len();
+    let mut objects: Vec<Vec<u8>> = Vec::with_capacity(INFO + n * 2);
+
+    // Each page is followed by its content stream.
+    let kids = (0..n)
+        .map(|i| format!("{} 0 R", INFO + 1 + i * 2))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    objects.push(format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES).into_bytes());
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, n).into_bytes());
+    objects.push(b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier \
+                   /Encoding /WinAnsiEncoding >>".to_vec());
+
+    let mut info = b"<< /Title ".to_vec();
+    info.extend(string(&typescript.short_title.text));
+    info.extend(b" /Author ");
+    info.extend(string(&typescript.short_author_name.text));
+    info.extend(b" /Creator (kosik) >>");
+    objects.push(info);
+
+    for i in 0..n {
+        let mut content: Vec<u8> = Vec::new();
+
+        for (y, line) in place_lines(typescript, i).iter() {
+            content.extend(line_to_pdf(line, *y));
+        }
+
+        objects.push(format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 {} 0 R >> >> /Contents {} 0 R >>",
+            PAGES, PAGE_WIDTH, PAGE_HEIGHT, FONT, INFO + 2 + i * 2
+        ).into_bytes());
+
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend(content);
+        stream.extend(b"endstream");
+        objects.push(stream);
+    }
+
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets: Vec<usize> = Vec::with_capacity(objects.len());
+
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", i + 1).into_bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+
+    for offset in offsets.iter() {
+        pdf.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+    }
+
+    pdf.extend(format!(
+        "trailer\n<< /Size {} /Root {} 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1, CATALOG, INFO, xref
+    ).into_bytes());
+
+    pdf
+}
+
+// Draw the segments of a line, starting each one at its own column.
+fn line_to_pdf(line: &Line, y: f32) -> Vec<u8> {
+    let mut pdf: Vec<u8> = Vec::new();
+    let mut column = line.column;
+    let baseline = y * LINE_HEIGHT;
+
+    for segment in line.segments.iter() {
+        let n = segment.text.chars().count();
+
+        if n > 0 {
+            let x = column as f32 * CHAR_WIDTH;
+
+            let y = if segment.dpy.intersects(DisplayFlags::SUB) {
+                baseline - SCRIPT_SHIFT
+            } else if segment.dpy.intersects(DisplayFlags::SUP) {
+                baseline + SCRIPT_SHIFT
+            } else {
+                baseline
+            };
+
+            pdf.extend(format!("BT /F1 12 Tf {:.1} {:.1} Td ", x, y).into_bytes());
+            pdf.extend(string(&segment.text));
+            pdf.extend(b" Tj ET\n");
+
+            if segment.dpy.intersects(DisplayFlags::EM) {
+                pdf.extend(format!(
+                    "{} w {:.1} {:.1} m {:.1} {:.1} l S\n",
+                    UNDERLINE_THICKNESS,
+                    x, y - UNDERLINE_POSITION,
+                    x + n as f32 * CHAR_WIDTH, y - UNDERLINE_POSITION
+                ).into_bytes());
+            }
+        }
+
+        column += n;
+    }
+
+    pdf
+}
+
+// Encode text as a PDF string literal, escaping the delimiters and
+// writing bytes outside of ASCII in octal.
+fn string(text: &str) -> Vec<u8> {
+    let bytes = WINDOWS_1252.encode(text, EncoderTrap::Replace)
+        .unwrap_or_else(|_| text.bytes().filter(u8::is_ascii).collect());
+    let mut pdf = vec![b'('];
+
+    for byte in bytes {
+        match byte {
+            b'(' | b')' | b'\\' => {
+                pdf.push(b'\\');
+                pdf.push(byte);
+            },
+            b' '..=b'~' => {
+                pdf.push(byte);
+            },
+            _ => {
+                pdf.extend(format!("\\{:03o}", byte).into_bytes());
+            },
+        }
+    }
+
+    pdf.push(b')');
+    pdf
+}
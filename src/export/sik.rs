@@ -0,0 +1,1043 @@
+// Kosik Manuscript Serializer
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Writes the element tree back out as manuscript XML
+//!
+//! The output conforms to the [manuscript schema], and reading it
+//! again gives the same tree.  Block elements are indented two spaces
+//! per level, and text is wrapped to the source width.  Attributes
+//! are only written where they differ from the values the [`reader`]
+//! would supply, so that automatic numbering and inherited line
+//! spacing stay implicit.
+//!
+//! Text is reconstructed from the token lists.  Quotes, dashes and
+//! ellipses are written with their typographic characters, and the
+//! escapes for a backslash, a tilde and a space after a full stop
+//! that does not end a sentence are restored.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::reader::Reader;
+//! use kosik::export::sik;
+//!
+//! let xml = "<p>Mr.\\ Smith said, \u{201c}Hello\u{2026}\u{201d}</p>";
+//! let root = Reader::new(xml).run().unwrap().unwrap();
+//!
+//! assert_eq!(sik::from_elements(&root),
+//!            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+//!             <p>Mr.\\ Smith said, \u{201c}Hello\u{2026}\u{201d}</p>\n");
+//! ```
+//!
+//! Writing out a manuscript that has been read back in gives the same
+//! text again.
+//!
+//! ```
+//! use kosik::document::reader::Reader;
+//! use kosik::export::sik;
+//!
+//! let xml = "<manuscript><head><title>Youth</title>\
+//!            <authors><person>Joseph Conrad</person></authors></head>\
+//!            <body><chapter>The Judea</chapter>\
+//!            <p>\u{201c}Pass the bottle\u{2014}\u{201d} he said, and did not\
+//!            finish.<footnote><p>See page 4.</p></footnote></p>\
+//!            <chapter>Bangkok</chapter><p>The <em>old</em> ship\u{2026}</p></body>\
+//!            </manuscript>";
+//! let root = Reader::new(xml).run().unwrap().unwrap();
+//! let first = sik::from_elements(&root);
+//!
+//! let root = Reader::new(&first).run().unwrap().unwrap();
+//! let second = sik::from_elements(&root);
+//!
+//! assert_eq!(second, first);
+//! ```
+//!
+//! [manuscript schema]: <http://www.matchlock.com/kosik/manuscript.xsd>
+//! [`reader`]: crate::document::reader
+
+use crate::document::*;
+use crate::text::tokens::*;
+
+use super::escape;
+
+/// Width of the source text
+pub const WIDTH: usize = 76;
+
+const SCHEMA_LOCATION: &str = "http://www.matchlock.com/kosik/manuscript.xsd";
+
+/// Write the element tree as manuscript XML.
+pub fn from_elements(elem: &ElementType) -> String {
+    let mut serializer = Serializer::new();
+    serializer.xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    serializer.element(elem, 0, Default::default());
+    serializer.xml
+}
+
+// Unit of inline content for line wrapping
+pub(crate) enum Run {
+    Text(String),
+    // A space token, which may be replaced by a line break
+    Space,
+    // A place where whitespace is ignored, so that a line break may be
+    // inserted
+    Gap,
+    // A place where a line break is required
+    Newline,
+}
+
+/// Defaults that an element inherits from its parent
+#[derive(Clone, Copy)]
+struct Container {
+    /// Paragraph indent
+    indent: usize,
+    /// Line spacing of paragraphs and list items
+    line_spacing: LineSpacing,
+    /// Line spacing of the paragraphs that the reader makes from text
+    /// outside p tags, if the parent has mixed content
+    bare: Option<LineSpacing>,
+    /// Number that the reader would give the next list item
+    item_no: Option<i32>,
+}
+
+impl Default for Container {
+    fn default() -> Self {
+        Container {
+            indent: INDENT,
+            line_spacing: LineSpacing::Double,
+            bare: None,
+            item_no: None,
+        }
+    }
+}
+
+impl Container {
+    fn mixed(indent: usize, line_spacing: LineSpacing) -> Self {
+        Container {
+            indent: indent,
+            line_spacing: line_spacing,
+            bare: Some(line_spacing),
+            item_no: None,
+        }
+    }
+
+    fn list(line_spacing: LineSpacing) -> Self {
+        Container {
+            indent: INDENT,
+            line_spacing: line_spacing,
+            bare: None,
+            item_no: None,
+        }
+    }
+}
+
+fn line_spacing_attr(attrs: &mut Vec<(&str, String)>, value: LineSpacing,
+                     default: LineSpacing)
+{
+    if value != default {
+        let value = match value {
+            LineSpacing::Double => "double",
+            LineSpacing::Single => "single",
+        };
+
+        attrs.push(("lineSpacing", value.to_string()));
+    }
+}
+
+fn start_tag(name: &str, attrs: &[(&str, String)]) -> String {
+    let mut tag = format!("<{}", name);
+
+    for (key, value) in attrs.iter() {
+        tag.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+    }
+
+    tag.push('>');
+    tag
+}
+
+fn empty_tag(name: &str, attrs: &[(&str, String)]) -> String {
+    let mut tag = start_tag(name, attrs);
+    tag.pop();
+    tag.push_str("/>");
+    tag
+}
+
+fn group_name(dpy: DisplayFlags) -> &'static str {
+    if dpy.intersects(DisplayFlags::SUB) {
+        "sub"
+    } else if dpy.intersects(DisplayFlags::SUP) {
+        "sup"
+    } else {
+        "em"
+    }
+}
+
+// Restore the characters that the parser replaced or consumed.
+fn token_to_sik(token: &TokenType) -> String {
+    let text = match token {
+        TokenType::Close(token) => match &token.data.text[..] {
+            "\"" => "\u{201d}".to_string(),
+            "'" => "\u{2019}".to_string(),
+            text => text.to_string(),
+        },
+        TokenType::Open(token) => match &token.data.text[..] {
+            "\"" => "\u{201c}".to_string(),
+            "'" => "\u{2018}".to_string(),
+            text => text.to_string(),
+        },
+        TokenType::Punct(token) => match token.data.glyph {
+            Some(glyph) => glyph.to_string(),
+            None => token.data.text.clone(),
+        },
+        TokenType::Symbol(token) => match &token.data.text[..] {
+            " " => "~".to_string(),
+            "\\" => "\\\\".to_string(),
+            "~" => "\\~".to_string(),
+            text => text.to_string(),
+        },
+        token => token.text(),
+    };
+
+    escape(&text)
+}
+
+// True if the parser would take a space after these tokens to follow
+// the end of a sentence.
+fn at_full_stop(tokens: &[TokenType]) -> bool {
+    for token in tokens.iter().rev() {
+        match token {
+            TokenType::Close(_) | TokenType::NoteRef(_) => {},
+            TokenType::Punct(token) => {
+                return matches!(&token.data.text[..], "!" | "." | ":" | "?");
+            },
+            _ => break,
+        }
+    }
+
+    false
+}
+
+// Join runs into words, each with the separator that precedes it.
+fn words(runs: &[Run]) -> Vec<(&Run, String)> {
+    let mut words: Vec<(&Run, String)> = Vec::new();
+    let mut separator = &Run::Newline;
+    let mut word = String::new();
+
+    for run in runs.iter() {
+        match run {
+            Run::Text(text) => word.push_str(text),
+            run => {
+                if !word.is_empty() {
+                    words.push((separator, std::mem::take(&mut word)));
+                    separator = run;
+                } else if matches!(run, Run::Newline)
+                    || matches!((separator, run), (Run::Gap, Run::Space))
+                {
+                    separator = run;
+                }
+            },
+        }
+    }
+
+    if !word.is_empty() {
+        words.push((separator, word));
+    }
+
+    words
+}
+
+// Write the runs on one line, or return None if they contain a
+// mandatory line break.
+pub(crate) fn single_line(runs: &[Run]) -> Option<String> {
+    let mut line = String::new();
+
+    for (i, (separator, word)) in words(runs).iter().enumerate() {
+        match separator {
+            Run::Newline if i > 0 => return None,
+            Run::Space => line.push(' '),
+            _ => {},
+        }
+
+        line.push_str(word);
+    }
+
+    Some(line)
+}
+
+// Write the runs on one line, dropping any line breaks.
+pub(crate) fn flatten(runs: &[Run]) -> String {
+    let mut line = String::new();
+
+    for (separator, word) in words(runs).iter() {
+        if let Run::Space = separator {
+            line.push(' ');
+        }
+
+        line.push_str(word);
+    }
+
+    line
+}
+
+// Fill lines with words up to the source width, indenting them to the
+// given depth.
+pub(crate) fn wrap(runs: &[Run], depth: usize) -> Vec<String> {
+    let width = WIDTH.saturating_sub(2 * depth);
+    let indent = "  ".repeat(depth);
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+
+    for (separator, word) in words(runs).into_iter() {
+        let space = match separator {
+            Run::Space => " ",
+            _ => "",
+        };
+
+        let overflow = line.chars().count() + space.len() + word.chars().count()
+            > width;
+
+        if matches!(separator, Run::Newline) || (overflow && !line.is_empty()) {
+            if !line.is_empty() {
+                lines.push(format!("{}{}", indent, line));
+            }
+
+            line = word;
+
+        } else {
+            line.push_str(space);
+            line.push_str(&word);
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(format!("{}{}", indent, line));
+    }
+
+    lines
+}
+
+/// Manuscript XML builder
+struct Serializer {
+    xml: String,
+    next_note_no: i32,
+    next_part_no: i32,
+    next_chapter_no: i32,
+    next_section_no: i32,
+    next_act_no: i32,
+    next_scene_no: i32,
+}
+
+impl Serializer {
+    fn new() -> Self {
+        Serializer {
+            xml: String::new(),
+            next_note_no: 1,
+            next_part_no: 1,
+            next_chapter_no: 1,
+            next_section_no: 1,
+            next_act_no: 1,
+            next_scene_no: 1,
+        }
+    }
+
+    fn element(&mut self, elem: &ElementType, depth: usize, container: Container) {
+        match elem {
+            ElementType::Act(elem) => {
+                let mut attrs = Vec::new();
+                let number = elem.attributes.number;
+                Serializer::number_attr(&mut attrs, number, &mut self.next_act_no);
+                self.next_scene_no = 1;
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.heading("act", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Action(elem) => {
+                let mut attrs = Vec::new();
+
+                if elem.attributes.centered {
+                    attrs.push(("centered", "true".to_string()));
+                }
+
+                self.text("action", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Attribution(elem) => {
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.text("attribution", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Authors(elem) => {
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.container("authors", &attrs, &elem.children, depth,
+                               Default::default());
+            },
+            ElementType::Backmatter(elem) => {
+                let mut attrs = Vec::new();
+
+                if elem.attributes.label != "BACKMATTER" {
+                    attrs.push(("label", elem.attributes.label.clone()));
+                }
+
+                self.container("backmatter", &attrs, &elem.children, depth,
+                               Default::default());
+            },
+            ElementType::BibRef(elem) => {
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.text("bibRef", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Blockquote(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, line_spacing, LineSpacing::Single);
+                self.container("blockquote", &attrs, &elem.children, depth,
+                               Container::mixed(INDENT, line_spacing));
+            },
+            ElementType::Body(elem) => {
+                self.container("body", &[], &elem.children, depth, Default::default());
+            },
+            ElementType::Br(_) => {
+                self.line(depth, "<br/>");
+            },
+            ElementType::CastList(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, line_spacing, LineSpacing::Single);
+                self.container("castList", &attrs, &elem.children, depth,
+                               Container::mixed(0, line_spacing));
+            },
+            ElementType::Chapter(elem) => {
+                let mut attrs = Vec::new();
+                let number = elem.attributes.number;
+                Serializer::number_attr(&mut attrs, number, &mut self.next_chapter_no);
+                self.next_section_no = 1;
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.heading("chapter", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Character(elem) => {
+                self.text("character", &[], &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Contact(elem) => {
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.text("contact", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Dd(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, line_spacing, container.line_spacing);
+                self.container("dd", &attrs, &elem.children, depth,
+                               Container::mixed(INDENT, line_spacing));
+            },
+            ElementType::Dedication(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, line_spacing, LineSpacing::Single);
+                self.container("dedication", &attrs, &elem.children, depth,
+                               Container::mixed(INDENT, line_spacing));
+            },
+            ElementType::Dialogue(elem) => {
+                self.container("dialogue", &[], &elem.children, depth,
+                               Container::mixed(0, LineSpacing::Single));
+            },
+            ElementType::Direction(elem) => {
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.text("direction", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Div(_) => {
+                self.line(depth, "<div/>");
+            },
+            ElementType::Dl(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, line_spacing, LineSpacing::Single);
+                self.container("dl", &attrs, &elem.children, depth,
+                               Container::list(line_spacing));
+            },
+            ElementType::Dt(elem) => {
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  container.line_spacing);
+                self.text("dt", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Em(elem) => {
+                self.inline_element("em", &elem.tokens, &elem.footnotes,
+                                    DisplayFlags::EM, depth);
+            },
+            ElementType::Epigraph(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, line_spacing, LineSpacing::Single);
+                self.container("epigraph", &attrs, &elem.children, depth,
+                               Container::mixed(0, line_spacing));
+            },
+            ElementType::Footnote(elem) => {
+                let runs = self.footnote(elem);
+                let text = flatten(&runs);
+                self.line(depth, &text);
+            },
+            ElementType::Frontmatter(elem) => {
+                let mut attrs = Vec::new();
+
+                if elem.attributes.label != "FRONTMATTER" {
+                    attrs.push(("label", elem.attributes.label.clone()));
+                }
+
+                self.container("frontmatter", &attrs, &elem.children, depth,
+                               Default::default());
+            },
+            ElementType::Gn(elem) => {
+                self.text("gn", &[], &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Head(elem) => {
+                self.container("head", &[], &elem.children, depth, Default::default());
+            },
+            ElementType::Li(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+
+                if let (Some(number), Some(item_no))
+                    = (elem.attributes.number, container.item_no)
+                {
+                    if number != item_no {
+                        attrs.push(("number", number.to_string()));
+                    }
+                }
+
+                line_spacing_attr(&mut attrs, line_spacing, container.line_spacing);
+                self.container("li", &attrs, &elem.children, depth,
+                               Container::mixed(INDENT, line_spacing));
+            },
+            ElementType::Manuscript(elem) => {
+                let mut attrs = Vec::new();
+
+                if elem.attributes.first_page != 1 {
+                    attrs.push(("firstPage", elem.attributes.first_page.to_string()));
+                }
+
+                self.root("manuscript", &attrs, &elem.children);
+            },
+            ElementType::NoteRef(elem) => {
+                let text = self.note_ref(&elem.attributes.label);
+                self.line(depth, &text);
+            },
+            ElementType::Ol(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+
+                if elem.attributes.start_no != 1 {
+                    attrs.push(("startNo", elem.attributes.start_no.to_string()));
+                }
+
+                line_spacing_attr(&mut attrs, line_spacing, LineSpacing::Single);
+                self.line(depth, &start_tag("ol", &attrs));
+
+                // Items are numbered on from the previous item.
+                let mut item_no = elem.attributes.start_no;
+
+                for child in elem.children.iter() {
+                    let mut container = Container::list(line_spacing);
+                    container.item_no = Some(item_no);
+
+                    if let ElementType::Li(child) = child {
+                        if let Some(number) = child.attributes.number {
+                            item_no = number + 1;
+                        }
+                    }
+
+                    self.element(child, depth + 1, container);
+                }
+
+                self.line(depth, "</ol>");
+            },
+            ElementType::P(elem) => {
+                let mut attrs = Vec::new();
+
+                if elem.attributes.indent != container.indent {
+                    attrs.push(("indent", elem.attributes.indent.to_string()));
+                }
+
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  container.line_spacing);
+                self.text("p", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::PageBreak(_) => {
+                self.line(depth, "<pageBreak/>");
+            },
+            ElementType::Parenthetical(elem) => {
+                self.text("parenthetical", &[], &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Part(elem) => {
+                let mut attrs = Vec::new();
+                let number = elem.attributes.number;
+                Serializer::number_attr(&mut attrs, number, &mut self.next_part_no);
+                self.next_chapter_no = 1;
+                self.next_section_no = 1;
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.heading("part", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Person(elem) => {
+                let text = self.person(elem);
+                self.line(depth, &text);
+            },
+            ElementType::Pre(elem) => {
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.line(depth, &start_tag("pre", &attrs));
+
+                // The reader removes the common indentation.
+                for token in elem.tokens.iter() {
+                    if let TokenType::Word(token) = token {
+                        if token.data.text.is_empty() {
+                            self.xml.push('\n');
+                        } else {
+                            self.line(depth + 1, &escape(&token.data.text));
+                        }
+                    }
+                }
+
+                self.line(depth, "</pre>");
+            },
+            ElementType::Prefix(elem) => {
+                self.text("prefix", &[], &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Scene(elem) => {
+                let mut attrs = Vec::new();
+                let number = elem.attributes.number;
+                Serializer::number_attr(&mut attrs, number, &mut self.next_scene_no);
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.heading("scene", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::SceneHeading(elem) => {
+                self.text("sceneHeading", &[], &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Screenplay(elem) => {
+                self.root("screenplay", &[], &elem.children);
+            },
+            ElementType::Section(elem) => {
+                let mut attrs = Vec::new();
+                let number = elem.attributes.number;
+                Serializer::number_attr(&mut attrs, number, &mut self.next_section_no);
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.heading("section", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Sn(elem) => {
+                self.text("sn", &[], &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Speaker(elem) => {
+                self.text("speaker", &[], &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Speech(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, line_spacing, LineSpacing::Single);
+                self.container("speech", &attrs, &elem.children, depth,
+                               Container::mixed(0, line_spacing));
+            },
+            ElementType::Sub(elem) => {
+                self.inline_element("sub", &elem.tokens, &elem.footnotes,
+                                    DisplayFlags::SUB, depth);
+            },
+            ElementType::Subtitle(elem) => {
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.text("subtitle", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Suffix(elem) => {
+                let mut attrs = Vec::new();
+
+                if elem.attributes.comma {
+                    attrs.push(("comma", "true".to_string()));
+                }
+
+                self.text("suffix", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Sup(elem) => {
+                self.inline_element("sup", &elem.tokens, &elem.footnotes,
+                                    DisplayFlags::SUP, depth);
+            },
+            ElementType::Table(elem) => {
+                self.container("table", &[], &elem.children, depth, Default::default());
+            },
+            ElementType::Td(elem) => {
+                self.text("td", &[], &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Title(elem) => {
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, elem.attributes.line_spacing,
+                                  LineSpacing::Single);
+                self.text("title", &attrs, &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Tr(elem) => {
+                let mut attrs = Vec::new();
+
+                if elem.attributes.header {
+                    attrs.push(("header", "true".to_string()));
+                }
+
+                self.container("tr", &attrs, &elem.children, depth, Default::default());
+            },
+            ElementType::Transition(elem) => {
+                self.text("transition", &[], &elem.tokens, &elem.footnotes, depth);
+            },
+            ElementType::Ul(elem) => {
+                let line_spacing = elem.attributes.line_spacing;
+                let mut attrs = Vec::new();
+                line_spacing_attr(&mut attrs, line_spacing, LineSpacing::Single);
+                self.container("ul", &attrs, &elem.children, depth,
+                               Container::list(line_spacing));
+            },
+        }
+    }
+
+    // Write a number attribute if the reader would not count to the
+    // same number.
+    fn number_attr(attrs: &mut Vec<(&str, String)>, number: i32, next_no: &mut i32) {
+        if number != *next_no {
+            attrs.push(("number", number.to_string()));
+        }
+
+        *next_no = number + 1;
+    }
+
+    fn line(&mut self, depth: usize, text: &str) {
+        self.xml.push_str(&"  ".repeat(depth));
+        self.xml.push_str(text);
+        self.xml.push('\n');
+    }
+
+    // Write the root element.  Its children are not indented.
+    fn root(&mut self, name: &str, attrs: &[(&str, String)], children: &ElementList) {
+        self.xml.push_str(&format!("<{}\n", name));
+        self.xml.push_str("    xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\"\n");
+        self.xml.push_str(&format!("    xsi:noNamespaceSchemaLocation=\"{}\"",
+                                   SCHEMA_LOCATION));
+
+        for (key, value) in attrs.iter() {
+            self.xml.push_str(&format!("\n    {}=\"{}\"", key, escape(value)));
+        }
+
+        self.xml.push_str(">\n");
+        self.children(children, 0, Default::default());
+        self.xml.push_str(&format!("</{}>\n", name));
+    }
+
+    fn container(&mut self, name: &str, attrs: &[(&str, String)],
+                 children: &ElementList, depth: usize, container: Container)
+    {
+        if children.is_empty() {
+            let text = format!("{}</{}>", start_tag(name, attrs), name);
+            self.line(depth, &text);
+            return;
+        }
+
+        self.line(depth, &start_tag(name, attrs));
+        self.children(children, depth + 1, container);
+        self.line(depth, &format!("</{}>", name));
+    }
+
+    // Write the children of a block element.  In mixed content, a
+    // paragraph that the reader would make from bare text is written
+    // without p tags.
+    fn children(&mut self, children: &ElementList, depth: usize, container: Container) {
+        let mut after_p = false;
+
+        for child in children.iter() {
+            match child {
+                ElementType::P(child) if !after_p && self.is_bare(child, container) => {
+                    let runs = self.inline(&child.tokens, &child.footnotes,
+                                           Default::default());
+                    self.wrap(&runs, depth);
+                },
+                child => self.element(child, depth, container),
+            }
+
+            after_p = matches!(child, ElementType::P(_));
+        }
+    }
+
+    fn is_bare(&self, elem: &TextElement<P>, container: Container) -> bool {
+        container.bare == Some(elem.attributes.line_spacing)
+            && elem.attributes.indent == 0
+            && !elem.tokens.is_empty()
+    }
+
+    // Write a part, chapter, section, act or scene, which may be empty.
+    fn heading(&mut self, name: &str, attrs: &[(&str, String)], tokens: &[TokenType],
+               footnotes: &ElementList, depth: usize)
+    {
+        if tokens.is_empty() {
+            self.line(depth, &empty_tag(name, attrs));
+        } else {
+            self.text(name, attrs, tokens, footnotes, depth);
+        }
+    }
+
+    // Write a text element on one line if it fits, or else with its
+    // text wrapped between the tags.
+    fn text(&mut self, name: &str, attrs: &[(&str, String)], tokens: &[TokenType],
+            footnotes: &ElementList, depth: usize)
+    {
+        let start = start_tag(name, attrs);
+        let end = format!("</{}>", name);
+        let runs = self.inline(tokens, footnotes, Default::default());
+
+        if let Some(text) = single_line(&runs) {
+            let width = 2 * depth + start.chars().count() + text.chars().count()
+                + end.chars().count();
+
+            if width <= WIDTH {
+                self.line(depth, &format!("{}{}{}", start, text, end));
+                return;
+            }
+        }
+
+        self.line(depth, &start);
+        self.wrap(&runs, depth + 1);
+        self.line(depth, &end);
+    }
+
+    // Write an element whose whitespace is not trimmed by the reader.
+    fn inline_element(&mut self, name: &str, tokens: &[TokenType],
+                      footnotes: &ElementList, dpy: DisplayFlags, depth: usize)
+    {
+        let runs = self.inline(tokens, footnotes, dpy);
+        let text = format!("<{0}>{1}</{0}>", name, flatten(&runs));
+        self.line(depth, &text);
+    }
+
+    fn wrap(&mut self, runs: &[Run], depth: usize) {
+        for line in wrap(runs, depth) {
+            self.xml.push_str(&line);
+            self.xml.push('\n');
+        }
+    }
+
+    // Convert a token list to runs of text, with em, sub and sup tags
+    // around changes in the display flags.  The footnotes are written
+    // where they are referred to.
+    fn inline(&mut self, tokens: &[TokenType], footnotes: &ElementList,
+              base: DisplayFlags) -> Vec<Run>
+    {
+        let mut runs: Vec<Run> = Vec::new();
+        let mut notes: Vec<&ContainerElement<Footnote>> = footnotes.iter()
+            .filter_map(|child| match child {
+                ElementType::Footnote(child) => Some(child),
+                _ => None,
+            })
+            .collect();
+
+        // The display flags of the open tag, and the index of its
+        // first token, which is where the parser starts a new token
+        // list
+        let mut dpy = base;
+        let mut start = 0;
+
+        for (i, token) in tokens.iter().enumerate() {
+            if let TokenType::NoteRef(token) = token {
+                let position = notes.iter()
+                    .position(|note| note.attributes.label == token.data.text);
+
+                match position {
+                    Some(position) => {
+                        // A footnote inside an em tag would be lost.
+                        if dpy != base {
+                            runs.push(Run::Text(format!("</{}>", group_name(dpy))));
+                            dpy = base;
+                            start = 0;
+                        }
+
+                        let note = notes.remove(position);
+                        runs.extend(self.footnote(note));
+                    },
+                    None => {
+                        runs.push(Run::Text(self.note_ref(&token.data.text)));
+                    },
+                }
+
+                continue;
+            }
+
+            let next = token.display_flags()
+                & (DisplayFlags::EM | DisplayFlags::SUB | DisplayFlags::SUP);
+
+            if next != dpy {
+                if dpy != base {
+                    runs.push(Run::Text(format!("</{}>", group_name(dpy))));
+                }
+
+                if next != base {
+                    runs.push(Run::Text(format!("<{}>", group_name(next))));
+                    start = i;
+                } else {
+                    start = 0;
+                }
+
+                dpy = next;
+            }
+
+            match token {
+                TokenType::LineBreak(_) => {
+                    runs.push(Run::Text("<br/>".to_string()));
+                    runs.push(Run::Newline);
+                },
+                TokenType::Space(token) => {
+                    // A single space after a full stop was escaped.
+                    // The escape is kept on the same line.
+                    if token.data.text == " " && at_full_stop(&tokens[start..i]) {
+                        runs.push(Run::Text("\\ ".to_string()));
+                    } else {
+                        runs.push(Run::Space);
+                    }
+                },
+                token => {
+                    runs.push(Run::Text(token_to_sik(token)));
+                },
+            }
+        }
+
+        if dpy != base {
+            runs.push(Run::Text(format!("</{}>", group_name(dpy))));
+        }
+
+        runs
+    }
+
+    // Write a footnote as inline content, numbering it the way the
+    // reader would.
+    fn footnote(&mut self, elem: &ContainerElement<Footnote>) -> Vec<Run> {
+        let label = &elem.attributes.label;
+        let line_spacing = elem.attributes.line_spacing;
+        let mut attrs = Vec::new();
+
+        if *label == self.next_note_no.to_string() {
+            self.next_note_no += 1;
+
+        } else {
+            attrs.push(("label", label.clone()));
+
+            if let Ok(n) = label.parse::<i32>() {
+                self.next_note_no = n + 1;
+            }
+        }
+
+        line_spacing_attr(&mut attrs, line_spacing, LineSpacing::Single);
+
+        let container = Container::mixed(INDENT, line_spacing);
+        let mut runs = vec![Run::Text(start_tag("footnote", &attrs))];
+        let mut after_p = false;
+
+        for (i, child) in elem.children.iter().enumerate() {
+            if i > 0 {
+                runs.push(Run::Gap);
+            }
+
+            if let ElementType::P(child) = child {
+                if !after_p && self.is_bare(child, container) {
+                    runs.extend(self.inline(&child.tokens, &child.footnotes,
+                                            Default::default()));
+                } else {
+                    let mut attrs = Vec::new();
+
+                    if child.attributes.indent != container.indent {
+                        attrs.push(("indent", child.attributes.indent.to_string()));
+                    }
+
+                    line_spacing_attr(&mut attrs, child.attributes.line_spacing,
+                                      container.line_spacing);
+                    runs.push(Run::Text(start_tag("p", &attrs)));
+                    runs.extend(self.inline(&child.tokens, &child.footnotes,
+                                            Default::default()));
+                    runs.push(Run::Text("</p>".to_string()));
+                }
+            }
+
+            after_p = matches!(child, ElementType::P(_));
+        }
+
+        runs.push(Run::Text("</footnote>".to_string()));
+        runs
+    }
+
+    fn note_ref(&self, label: &str) -> String {
+        if label == "*" {
+            "<noteRef/>".to_string()
+        } else {
+            empty_tag("noteRef", &[("label", label.to_string())])
+        }
+    }
+
+    // Write a personal name on one line.
+    fn person(&mut self, elem: &ContainerElement<Person>) -> String {
+        let mut text = String::from("<person>");
+
+        for child in elem.children.iter() {
+            let (name, attrs, runs) = match child {
+                ElementType::Footnote(child) => {
+                    let runs = self.footnote(child);
+                    text.push_str(&flatten(&runs));
+                    continue;
+                },
+                ElementType::NoteRef(child) => {
+                    text.push_str(&self.note_ref(&child.attributes.label));
+                    continue;
+                },
+                ElementType::Gn(child) => {
+                    ("gn", Vec::new(),
+                     self.inline(&child.tokens, &child.footnotes, Default::default()))
+                },
+                ElementType::Prefix(child) => {
+                    ("prefix", Vec::new(),
+                     self.inline(&child.tokens, &child.footnotes, Default::default()))
+                },
+                ElementType::Sn(child) => {
+                    ("sn", Vec::new(),
+                     self.inline(&child.tokens, &child.footnotes, Default::default()))
+                },
+                ElementType::Suffix(child) => {
+                    let mut attrs = Vec::new();
+
+                    if child.attributes.comma {
+                        attrs.push(("comma", "true".to_string()));
+                    }
+
+                    ("suffix", attrs,
+                     self.inline(&child.tokens, &child.footnotes, Default::default()))
+                },
+                _ => continue,
+            };
+
+            text.push_str(&start_tag(name, &attrs));
+            text.push_str(&flatten(&runs));
+            text.push_str(&format!("</{}>", name));
+        }
+
+        text.push_str("</person>");
+        text
+    }
+}
@@ -0,0 +1,773 @@
+// Kosik Statistics Report
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Reports word, sentence and page counts for a manuscript
+//!
+//! The counts are made from the element tree built by the
+//! [`reader`], and broken down by part, chapter and section, or by
+//! act and scene.  Text before the first heading in the body is
+//! counted as a division of its own.  The title page is not counted,
+//! and words in footnotes are counted separately from the text.
+//!
+//! A sentence ends with a full stop, a question mark or an
+//! exclamation mark, or at the end of a paragraph.  Words inside
+//! quotation marks, and in screenplay dialogue and stage play
+//! speeches, are counted as dialogue.
+//!
+//! A part or an act is counted together with its chapters or
+//! scenes, and a chapter with its sections, but the total counts each
+//! word only once.
+//!
+//! Page counts come from the [`Compositor`], and are filled in by
+//! [`Statistics::paginate`].  A division is counted on every page it
+//! appears on, so sections that share a page both count it.
+//!
+//! The word count for the title page is worked out by
+//! [`Statistics::word_count`], according to a [`WordCountPolicy`].
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::reader::Reader;
+//! use kosik::export::stats;
+//!
+//! let xml = "<p>\u{201c}Hello,\u{201d} she said.  He did not answer.</p>";
+//! let root = Reader::new(xml).run().unwrap().unwrap();
+//! let statistics = stats::from_elements(&root);
+//!
+//! assert_eq!(statistics.total.words, 7);
+//! assert_eq!(statistics.total.sentences, 2);
+//! assert_eq!(statistics.total.paragraphs, 1);
+//! assert_eq!(statistics.total.dialogue_words, 1);
+//!
+//! let xml = "<p>He went (slowly and quietly and carefully) home [see note].</p>";
+//! let root = Reader::new(xml).run().unwrap().unwrap();
+//! let statistics = stats::from_elements(&root);
+//!
+//! assert_eq!(statistics.total.words, 10);
+//! assert_eq!(statistics.total.dialogue_words, 0);
+//!
+//! let xml = "<manuscript><body><part>One</part><p>Two words.</p>\
+//!            <chapter>Three</chapter><p>Four.</p></body></manuscript>";
+//! let root = Reader::new(xml).run().unwrap().unwrap();
+//! let statistics = stats::from_elements(&root);
+//!
+//! assert_eq!(statistics.divisions[0].counts.words, 5);
+//! assert_eq!(statistics.divisions[1].counts.words, 2);
+//! assert_eq!(statistics.total.words, 5);
+//! ```
+//!
+//! [`reader`]: crate::document::reader
+
+use thousands::Separable;
+
+use crate::document::*;
+use crate::document::compositor::Compositor;
+use crate::text::tokens::*;
+
+use super::{roman_label, section_label};
+use super::html::plain_text;
+
+/// Reading speed used to estimate the reading time, in words per
+/// minute
+pub const READING_SPEED: usize = 250;
+
+// Width of the division column in the table
+const LABEL_WIDTH: usize = 34;
+
+/// Counts for a division, or for the whole manuscript
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counts {
+    /// Words in the text, not counting footnotes
+    pub words: usize,
+    /// Sentences in the text
+    pub sentences: usize,
+    /// Paragraphs with at least one word in them
+    pub paragraphs: usize,
+    /// Words in footnotes
+    pub footnote_words: usize,
+    /// Words in quotations or dialogue
+    pub dialogue_words: usize,
+    /// Characters in the text and footnotes, counting each space as
+    /// one
+    pub characters: usize,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Counts) {
+        self.words += other.words;
+        self.sentences += other.sentences;
+        self.paragraphs += other.paragraphs;
+        self.footnote_words += other.footnote_words;
+        self.dialogue_words += other.dialogue_words;
+        self.characters += other.characters;
+    }
+
+    /// Fraction of the words in the text that are dialogue
+    pub fn dialogue_ratio(&self) -> f64 {
+        if self.words == 0 {
+            0.0
+        } else {
+            self.dialogue_words as f64 / self.words as f64
+        }
+    }
+
+    /// Estimated reading time in minutes, including the footnotes
+    pub fn reading_time(&self) -> usize {
+        (self.words + self.footnote_words).div_ceil(READING_SPEED)
+    }
+}
+
+/// A part, chapter, section, act or scene, or the front or back
+/// matter
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Division {
+    /// Heading label, such as <tt>Chapter 3</tt>
+    pub label: String,
+    /// Heading text, which may be empty
+    pub title: String,
+    /// Subdivision depth, counting from zero
+    pub depth: i32,
+    /// Counts for the division, including its subdivisions
+    pub counts: Counts,
+    /// Number of pages the division and its subdivisions appear on,
+    /// if known
+    pub pages: Option<usize>,
+    // Counts for the division, not including its subdivisions
+    own: Counts,
+    // True if the compositor records the page of the heading
+    heading: bool,
+    // True for front and back matter
+    matter: bool,
+    // Number of typed lines on the pages of the division
+    lines: usize,
+}
+
+/// Statistics for a manuscript
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Statistics {
+    /// Divisions in document order
+    pub divisions: Vec<Division>,
+    /// Counts for the whole manuscript
+    pub total: Counts,
+    /// Number of pages in the typescript, including the title page
+    /// and the table of contents, if known
+    pub pages: Option<usize>,
+    // Number of typed lines on the numbered pages before the table of
+    // contents, if known
+    lines: Option<usize>,
+}
+
+/// Count the words, sentences and paragraphs in an element tree.
+pub fn from_elements(elem: &ElementType) -> Statistics {
+    let mut counter = Counter {
+        divisions: Vec::new(),
+        in_footnote: false,
+    };
+
+    counter.element(elem, false);
+
+    // Drop the divisions opened at the start of a body with nothing
+    // before its first heading.
+    let mut divisions: Vec<Division> = counter.divisions.into_iter()
+        .filter(|division| division.heading || division.counts != Default::default())
+        .collect();
+
+    let mut total = Counts::default();
+
+    for division in divisions.iter_mut() {
+        division.own = division.counts;
+        total.add(&division.own);
+    }
+
+    for i in 0..divisions.len() {
+        for j in subdivisions(&divisions, i) {
+            let own = divisions[j].own;
+            divisions[i].counts.add(&own);
+        }
+    }
+
+    Statistics {
+        divisions: divisions,
+        total: total,
+        pages: None,
+        lines: None,
+    }
+}
+
+impl Statistics {
+    /// Fill in the page counts from a compositor that has been run on
+    /// the same element tree.
+    pub fn paginate(&mut self, compositor: &Compositor) {
+        let end = compositor.toc_page.unwrap_or(compositor.pages.len());
+
+        // Only the numbered pages are counted for the word count, so
+        // that the title page and dedication are left out.
+        let lines = |first: usize, last: usize| -> usize {
+            compositor.pages[first..=last].iter()
+                .filter(|page| page.number > 0)
+                .map(|page| page.lines.iter().flatten().count() + page.footer.len())
+                .sum()
+        };
+
+        self.pages = Some(compositor.pages.len());
+        self.lines = Some(if end > 0 { lines(0, end - 1) } else { 0 });
+
+        if self.divisions.len() == 1 && !self.divisions[0].heading {
+            self.divisions[0].pages = Some(end);
+            return;
+        }
+
+        let headings: Vec<usize> = (0..self.divisions.len())
+            .filter(|&i| self.divisions[i].heading)
+            .collect();
+
+        // The headings are matched up in order, which only works if
+        // there are as many of them.
+        if headings.is_empty() || headings.len() != compositor.headings.len() {
+            return;
+        }
+
+        let mut ranges: Vec<Option<(usize, usize)>> = vec![None; self.divisions.len()];
+
+        for (n, &i) in headings.iter().enumerate() {
+            let (first, _) = compositor.headings[n];
+
+            let last = match compositor.headings.get(n + 1) {
+                Some((next, true)) => next - 1,
+                Some((next, false)) => *next,
+                None => end.max(first + 1) - 1,
+            };
+
+            ranges[i] = Some((first, last));
+        }
+
+        // Text before the first heading runs from the first numbered
+        // page up to the heading.  If there is front matter before
+        // it, its pages are not known.
+        if !self.divisions[0].heading {
+            let first = compositor.pages.iter()
+                .position(|page| page.number > 0)
+                .unwrap_or(0);
+
+            let last = match compositor.headings[0] {
+                (next, true) => next.saturating_sub(1),
+                (next, false) => next,
+            };
+
+            ranges[0] = Some((first.min(last), last));
+        }
+
+        // A division runs on to the last page of its last subdivision.
+        for i in 0..self.divisions.len() {
+            let last = subdivisions(&self.divisions, i)
+                .filter_map(|j| ranges[j])
+                .next_back();
+
+            if let (Some((first, _)), Some((_, last))) = (ranges[i], last) {
+                ranges[i] = Some((first, last));
+            }
+        }
+
+        for (division, range) in self.divisions.iter_mut().zip(ranges) {
+            if let Some((first, last)) = range {
+                division.pages = Some(last + 1 - first);
+                division.lines = lines(first, last);
+            }
+        }
+    }
+
+    /// Work out the word count for the title page according to a
+    /// policy.  Counting by lines requires the page counts.
+    pub fn word_count(&self, policy: &WordCountPolicy) -> Option<usize> {
+        let mut counts = self.total;
+        let mut lines = self.lines;
+
+        if policy.body_only {
+            for division in self.divisions.iter().filter(|division| division.matter) {
+                counts.words -= division.counts.words;
+                counts.footnote_words -= division.counts.footnote_words;
+                counts.characters -= division.counts.characters;
+                lines = lines.map(|n| n.saturating_sub(division.lines));
+            }
+        }
+
+        match policy.method {
+            WordCountMethod::Exact => Some(counts.words + counts.footnote_words),
+            WordCountMethod::Lines => lines.map(|n| n * 10),
+            WordCountMethod::Typesetter => Some(counts.characters.div_ceil(6)),
+        }
+    }
+
+    /// Format the statistics as a table, followed by the word count
+    /// for the title page.
+    pub fn to_table(&self, policy: &WordCountPolicy) -> String {
+        let mut table = format!("{:<width$}{:>9}{:>11}{:>12}{:>8}{:>7}{:>10}\n",
+                                "Division", "Words", "Sentences", "Paragraphs",
+                                "Notes", "Pages", "Dialogue", width = LABEL_WIDTH);
+
+        table.push_str(&"-".repeat(LABEL_WIDTH + 57));
+        table.push('\n');
+
+        for division in self.divisions.iter() {
+            let mut label = "  ".repeat(division.depth.max(0) as usize);
+            label.push_str(&division.label);
+
+            if !division.title.is_empty() {
+                label.push_str(". ");
+                label.push_str(&division.title);
+            }
+
+            table.push_str(&row(&label, &division.counts, division.pages));
+        }
+
+        table.push_str(&"-".repeat(LABEL_WIDTH + 57));
+        table.push('\n');
+        table.push_str(&row("Total", &self.total, self.pages));
+
+        let minutes = self.total.reading_time();
+
+        table.push_str(&format!("\nReading time: about {}\n", if minutes < 60 {
+            format!("{} min", minutes)
+        } else {
+            format!("{} h {} min", minutes / 60, minutes % 60)
+        }));
+
+        if let Some(word_count) = self.word_count(policy) {
+            table.push_str(&format!("Word count: {}\n",
+                                    word_count_text(word_count, policy.granularity)));
+        }
+
+        table
+    }
+
+    /// Format the statistics as JSON, with the word count for the
+    /// title page before and after rounding.  Page counts that are not
+    /// known are left out.
+    pub fn to_json(&self, policy: &WordCountPolicy) -> String {
+        let mut json = String::from("{\n");
+
+        if let Some(pages) = self.pages {
+            json.push_str(&format!("  \"pages\": {},\n", pages));
+        }
+
+        json.push_str(&counts_json(&self.total, "  "));
+        json.push_str(&format!(",\n  \"readingTime\": {},\n",
+                               self.total.reading_time()));
+
+        if let Some(word_count) = self.word_count(policy) {
+            json.push_str(&format!("  \"wordCount\": {},\n", word_count));
+            json.push_str(&format!("  \"approximateWordCount\": {},\n",
+                                   round_word_count(word_count, policy.granularity)));
+        }
+
+        json.push_str("  \"divisions\": [");
+
+        for (i, division) in self.divisions.iter().enumerate() {
+            json.push_str(if i == 0 { "\n" } else { ",\n" });
+            json.push_str("    {\n");
+            json.push_str(&format!("      \"label\": {},\n", json_string(&division.label)));
+            json.push_str(&format!("      \"title\": {},\n", json_string(&division.title)));
+            json.push_str(&format!("      \"depth\": {},\n", division.depth));
+
+            if let Some(pages) = division.pages {
+                json.push_str(&format!("      \"pages\": {},\n", pages));
+            }
+
+            json.push_str(&counts_json(&division.counts, "      "));
+            json.push_str("\n    }");
+        }
+
+        json.push_str(if self.divisions.is_empty() { "]\n}\n" } else { "\n  ]\n}\n" });
+        json
+    }
+}
+
+// Find the divisions under a part, chapter or act, which are the
+// headings after it that are nested deeper than it is.
+fn subdivisions(divisions: &[Division], i: usize) -> std::ops::Range<usize> {
+    let division = &divisions[i];
+
+    if !division.heading || division.matter {
+        return i + 1..i + 1;
+    }
+
+    let end = divisions[i + 1..].iter()
+        .position(|next| !next.heading || next.matter || next.depth <= division.depth)
+        .map_or(divisions.len(), |n| i + 1 + n);
+
+    i + 1..end
+}
+
+fn row(label: &str, counts: &Counts, pages: Option<usize>) -> String {
+    let mut label: String = label.chars().take(LABEL_WIDTH - 1).collect();
+
+    if label.chars().count() == LABEL_WIDTH - 1 {
+        label.pop();
+        label.push('\u{2026}');
+    }
+
+    format!("{:<width$}{:>9}{:>11}{:>12}{:>8}{:>7}{:>9.0}%\n",
+            label,
+            counts.words.separate_with_commas(),
+            counts.sentences.separate_with_commas(),
+            counts.paragraphs.separate_with_commas(),
+            counts.footnote_words.separate_with_commas(),
+            pages.map_or("-".to_string(), |n| n.separate_with_commas()),
+            counts.dialogue_ratio() * 100.0,
+            width = LABEL_WIDTH)
+}
+
+fn counts_json(counts: &Counts, indent: &str) -> String {
+    [
+        format!("{}\"words\": {}", indent, counts.words),
+        format!("{}\"sentences\": {}", indent, counts.sentences),
+        format!("{}\"paragraphs\": {}", indent, counts.paragraphs),
+        format!("{}\"footnoteWords\": {}", indent, counts.footnote_words),
+        format!("{}\"dialogueWords\": {}", indent, counts.dialogue_words),
+        format!("{}\"dialogueRatio\": {:.3}", indent, counts.dialogue_ratio()),
+        format!("{}\"characters\": {}", indent, counts.characters),
+    ].join(",\n")
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+
+    for ch in text.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            ch if (ch as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => json.push(ch),
+        }
+    }
+
+    json.push('"');
+    json
+}
+
+struct Counter {
+    divisions: Vec<Division>,
+    in_footnote: bool,
+}
+
+impl Counter {
+    fn element(&mut self, elem: &ElementType, dialogue: bool) {
+        match elem {
+            ElementType::Act(elem) => {
+                self.division(roman_label("Act", elem.attributes.number),
+                              &elem.tokens, elem.attributes.depth);
+                self.footnotes(&elem.footnotes);
+            },
+            ElementType::Action(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, true, false);
+            },
+            ElementType::Attribution(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Authors(_) => {},
+            ElementType::Backmatter(elem) => {
+                self.divisions.push(Division {
+                    label: elem.attributes.label.clone(),
+                    heading: true,
+                    matter: true,
+                    ..Default::default()
+                });
+
+                self.children(&elem.children, false);
+            },
+            ElementType::BibRef(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Blockquote(elem) => {
+                self.children(&elem.children, dialogue);
+            },
+            ElementType::Body(elem) => {
+                self.divisions.push(Division {
+                    label: "Body".to_string(),
+                    ..Default::default()
+                });
+
+                self.children(&elem.children, false);
+            },
+            ElementType::Br(_) => {},
+            ElementType::CastList(elem) => {
+                self.children(&elem.children, false);
+            },
+            ElementType::Chapter(elem) => {
+                self.division(format!("Chapter {}", elem.attributes.number),
+                              &elem.tokens, elem.attributes.depth);
+                self.footnotes(&elem.footnotes);
+            },
+            ElementType::Character(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Contact(_) => {},
+            ElementType::Dd(elem) => {
+                self.children(&elem.children, dialogue);
+            },
+            ElementType::Dedication(elem) => {
+                self.children(&elem.children, false);
+            },
+            ElementType::Dialogue(elem) => {
+                self.children(&elem.children, true);
+            },
+            ElementType::Direction(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Div(_) => {},
+            ElementType::Dl(elem) => {
+                self.children(&elem.children, dialogue);
+            },
+            ElementType::Dt(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, dialogue);
+            },
+            ElementType::Em(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, dialogue);
+            },
+            ElementType::Epigraph(elem) => {
+                self.children(&elem.children, false);
+            },
+            ElementType::Footnote(elem) => {
+                let in_footnote = self.in_footnote;
+                self.in_footnote = true;
+                self.children(&elem.children, false);
+                self.in_footnote = in_footnote;
+            },
+            ElementType::Frontmatter(elem) => {
+                self.divisions.push(Division {
+                    label: elem.attributes.label.clone(),
+                    heading: true,
+                    matter: true,
+                    ..Default::default()
+                });
+
+                self.children(&elem.children, false);
+            },
+            ElementType::Gn(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Head(_) => {},
+            ElementType::Li(elem) => {
+                self.children(&elem.children, dialogue);
+            },
+            ElementType::Manuscript(elem) => {
+                self.children(&elem.children, false);
+            },
+            ElementType::NoteRef(_) => {},
+            ElementType::Ol(elem) => {
+                self.children(&elem.children, dialogue);
+            },
+            ElementType::P(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, true, dialogue);
+            },
+            ElementType::PageBreak(_) => {},
+            ElementType::Parenthetical(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Part(elem) => {
+                self.division(roman_label("Part", elem.attributes.number),
+                              &elem.tokens, elem.attributes.depth);
+                self.footnotes(&elem.footnotes);
+            },
+            ElementType::Person(elem) => {
+                self.children(&elem.children, false);
+            },
+            ElementType::Pre(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, true, false);
+            },
+            ElementType::Prefix(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Scene(elem) => {
+                self.division(format!("Scene {}", elem.attributes.number),
+                              &elem.tokens, elem.attributes.depth);
+                self.footnotes(&elem.footnotes);
+            },
+            ElementType::SceneHeading(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Screenplay(elem) => {
+                self.children(&elem.children, false);
+            },
+            ElementType::Section(elem) => {
+                self.division(section_label(elem.attributes.number),
+                              &elem.tokens, elem.attributes.depth);
+                self.footnotes(&elem.footnotes);
+            },
+            ElementType::Sn(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Speaker(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Speech(elem) => {
+                self.children(&elem.children, true);
+            },
+            ElementType::Sub(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, dialogue);
+            },
+            ElementType::Subtitle(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Suffix(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Sup(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, dialogue);
+            },
+            ElementType::Table(elem) => {
+                self.children(&elem.children, false);
+            },
+            ElementType::Td(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Title(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Tr(elem) => {
+                self.children(&elem.children, false);
+            },
+            ElementType::Transition(elem) => {
+                self.text(&elem.tokens, &elem.footnotes, false, false);
+            },
+            ElementType::Ul(elem) => {
+                self.children(&elem.children, dialogue);
+            },
+        }
+    }
+
+    fn children(&mut self, children: &ElementList, dialogue: bool) {
+        for child in children.iter() {
+            self.element(child, dialogue);
+        }
+    }
+
+    fn footnotes(&mut self, footnotes: &ElementList) {
+        self.children(footnotes, false);
+    }
+
+    // Start a division at a heading.  The words of the heading are
+    // counted, but not as a paragraph or a sentence.
+    fn division(&mut self, label: String, tokens: &[TokenType], depth: i32) {
+        let counts = count(tokens, false);
+
+        self.divisions.push(Division {
+            label: label,
+            title: plain_text(tokens).split_whitespace().collect::<Vec<&str>>().join(" "),
+            depth: depth,
+            counts: Counts {
+                words: counts.words,
+                characters: counts.characters,
+                ..Default::default()
+            },
+            pages: None,
+            own: Default::default(),
+            heading: true,
+            matter: false,
+            lines: 0,
+        });
+    }
+
+    fn current(&mut self) -> &mut Counts {
+        if self.divisions.is_empty() {
+            self.divisions.push(Default::default());
+        }
+
+        let n = self.divisions.len();
+        &mut self.divisions[n - 1].counts
+    }
+
+    fn text(&mut self, tokens: &[TokenType], footnotes: &ElementList,
+            paragraph: bool, dialogue: bool)
+    {
+        let counts = count(tokens, dialogue);
+
+        self.current().characters += counts.characters;
+
+        if self.in_footnote {
+            self.current().footnote_words += counts.words;
+        } else {
+            let current = self.current();
+            current.words += counts.words;
+            current.sentences += counts.sentences;
+            current.dialogue_words += counts.dialogue_words;
+
+            if paragraph && counts.words > 0 {
+                current.paragraphs += 1;
+            }
+        }
+
+        self.footnotes(footnotes);
+    }
+}
+
+// Parentheses and brackets are groups too, but what is inside them
+// is not dialogue.
+fn is_quotation_mark(text: &str) -> bool {
+    matches!(text, "\"" | "'" | "\u{ab}" | "\u{bb}")
+}
+
+// Count the words and sentences in a token list.  Quotation marks
+// are matched within the list only, so that a quotation that runs on
+// into the next paragraph is reopened there.
+fn count(tokens: &[TokenType], dialogue: bool) -> Counts {
+    let mut counts = Counts::default();
+    let mut quotes = 0;
+    let mut in_sentence = false;
+
+    for token in tokens.iter() {
+        if !matches!(token, TokenType::NoteRef(_) | TokenType::Space(_)) {
+            counts.characters += token.text().chars().count();
+        }
+
+        match token {
+            TokenType::Close(token) if is_quotation_mark(&token.data.text) => {
+                quotes = usize::saturating_sub(quotes, 1);
+            },
+            TokenType::Open(token) if is_quotation_mark(&token.data.text) => {
+                quotes += 1;
+            },
+            TokenType::Punct(token) if token.frm.contains(FormatFlags::FS)
+                && matches!(&token.data.text[..], "!" | "." | "?") =>
+            {
+                if in_sentence {
+                    counts.sentences += 1;
+                    in_sentence = false;
+                }
+            },
+            TokenType::Space(_) => {
+                counts.characters += 1;
+            },
+            TokenType::Word(_) => {
+                counts.words += 1;
+                in_sentence = true;
+
+                if dialogue || quotes > 0 {
+                    counts.dialogue_words += 1;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    if in_sentence {
+        counts.sentences += 1;
+    }
+
+    counts
+}
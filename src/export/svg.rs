@@ -0,0 +1,142 @@
+// Kosik SVG Exporter
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Writes each page as a standalone SVG image
+//!
+//! Lines are placed at the same coordinates as in the Postscript
+//! output, measured from the top of the page instead of the bottom.
+//! Each line segment is positioned separately, so that the columns
+//! line up whatever monospaced font the viewer substitutes for
+//! Courier.  Emphasis is underlined, and subscripts and superscripts
+//! are shifted half a line down or up, as on a typewriter.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::{Layout, Page, Typescript};
+//! use kosik::export::svg;
+//! use kosik::text::{Line, Segment};
+//!
+//! let typescript = Typescript {
+//!     layout: Layout::Manuscript,
+//!     contact: None,
+//!     word_count: None,
+//!     has_structure: false,
+//!     short_title: Segment::from("Working Title"),
+//!     short_author_name: Segment::from("ANONYMOUS"),
+//!     running_heads: Vec::new(),
+//!     pages: vec![Page {
+//!         number: 1,
+//!         height: 54,
+//!         lines: vec![Some(Line {
+//!             column: 10,
+//!             segments: vec![Segment::from("foo")],
+//!             note_refs: Vec::new(),
+//!         })],
+//!         footer: Vec::new(),
+//!     }],
+//! };
+//!
+//! let pages = svg::from_typescript(&typescript);
+//!
+//! assert_eq!(pages.len(), 1);
+//! assert!(pages[0].contains("<text x=\"72.0\" y=\"84.0\">foo</text>"));
+//! ```
+
+use crate::document::*;
+use crate::text::Line;
+use crate::text::tokens::DisplayFlags;
+
+use super::{escape, place_lines};
+use super::{SCRIPT_SHIFT, UNDERLINE_POSITION, UNDERLINE_THICKNESS};
+
+/// Page width in points
+const PAGE_WIDTH: f32 = 612.0;
+
+/// Page height in points
+const PAGE_HEIGHT: f32 = 792.0;
+
+/// Write each composed page as an SVG document.
+pub fn from_typescript(typescript: &Typescript) -> Vec<String> {
+    let mut pages: Vec<String> = Vec::with_capacity(typescript.pages.len());
+
+    for i in 0..typescript.pages.len() {
+        let mut svg = String::new();
+
+        svg.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        svg.push_str(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+             width=\"{0}pt\" height=\"{1}pt\" viewBox=\"0 0 {0} {1}\">\n",
+            PAGE_WIDTH, PAGE_HEIGHT
+        ));
+        svg.push_str(&format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n",
+            PAGE_WIDTH, PAGE_HEIGHT
+        ));
+        svg.push_str("<g font-family=\"Courier, monospace\" font-size=\"12\" \
+                      fill=\"black\" xml:space=\"preserve\">\n");
+
+        for (y, line) in place_lines(typescript, i).iter() {
+            svg.push_str(&line_to_svg(line, *y));
+        }
+
+        svg.push_str("</g>\n</svg>\n");
+        pages.push(svg);
+    }
+
+    pages
+}
+
+// Draw the segments of a line, starting each one at its own column.
+fn line_to_svg(line: &Line, y: f32) -> String {
+    let mut svg = String::new();
+    let mut column = line.column;
+    let baseline = PAGE_HEIGHT - y * LINE_HEIGHT;
+
+    for segment in line.segments.iter() {
+        let n = segment.text.chars().count();
+
+        if n > 0 {
+            let x = column as f32 * CHAR_WIDTH;
+
+            let y = if segment.dpy.intersects(DisplayFlags::SUB) {
+                baseline + SCRIPT_SHIFT
+            } else if segment.dpy.intersects(DisplayFlags::SUP) {
+                baseline - SCRIPT_SHIFT
+            } else {
+                baseline
+            };
+
+            svg.push_str(&format!("<text x=\"{:.1}\" y=\"{:.1}\">{}</text>\n",
+                                  x, y, escape(&segment.text)));
+
+            if segment.dpy.intersects(DisplayFlags::EM) {
+                svg.push_str(&format!(
+                    "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" \
+                     stroke=\"black\" stroke-width=\"{}\"/>\n",
+                    x, y + UNDERLINE_POSITION,
+                    x + n as f32 * CHAR_WIDTH, y + UNDERLINE_POSITION,
+                    UNDERLINE_THICKNESS
+                ));
+            }
+        }
+
+        column += n;
+    }
+
+    svg
+}
@@ -0,0 +1,107 @@
+// Kosik Plain Text Exporter
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Writes the typed pages as plain text
+//!
+//! Each page is laid out on a grid of characters, one row to a line
+//! of the Postscript output, so the text lines up as it does on
+//! paper.  Pages are separated by form feeds, and blank lines at the
+//! bottom of a page are left out.  Emphasis, subscripts and
+//! superscripts cannot be shown, so they are written as plain text.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::document::{Layout, Page, Typescript};
+//! use kosik::export::text;
+//! use kosik::text::{Line, Segment};
+//!
+//! let typescript = Typescript {
+//!     layout: Layout::Manuscript,
+//!     contact: None,
+//!     word_count: None,
+//!     has_structure: false,
+//!     short_title: Segment::from("Working Title"),
+//!     short_author_name: Segment::from("ANONYMOUS"),
+//!     running_heads: Vec::new(),
+//!     pages: vec![Page {
+//!         number: 1,
+//!         height: 54,
+//!         lines: vec![Some(Line {
+//!             column: 10,
+//!             segments: vec![Segment::from("foo")],
+//!             note_refs: Vec::new(),
+//!         })],
+//!         footer: Vec::new(),
+//!     }],
+//! };
+//!
+//! let text = text::from_typescript(&typescript);
+//!
+//! assert!(text.ends_with("\n          foo\n"));
+//! ```
+
+use crate::document::*;
+
+use super::place_lines;
+
+/// Number of lines on a page
+const PAGE_LINES: usize = 66;
+
+/// Write the composed pages as plain text.
+pub fn from_typescript(typescript: &Typescript) -> String {
+    let mut pages: Vec<String> = Vec::with_capacity(typescript.pages.len());
+
+    for i in 0..typescript.pages.len() {
+        let mut rows: Vec<Vec<char>> = vec![Vec::new(); PAGE_LINES];
+
+        for (y, line) in place_lines(typescript, i).iter() {
+            let row = (PAGE_LINES as f32 - y).round().max(0.0) as usize;
+
+            if row >= PAGE_LINES {
+                continue;
+            }
+
+            // Lines can share a row, such as the contact information
+            // and the word count on the title page.
+            let chars = line.segments.iter().flat_map(|x| x.text.chars());
+
+            for (column, ch) in (line.column..).zip(chars) {
+                if rows[row].len() <= column {
+                    rows[row].resize(column + 1, ' ');
+                }
+
+                rows[row][column] = ch;
+            }
+        }
+
+        while rows.last().is_some_and(Vec::is_empty) {
+            rows.pop();
+        }
+
+        let mut page = String::new();
+
+        for row in rows.iter() {
+            page.extend(row.iter());
+            page.push('\n');
+        }
+
+        pages.push(page);
+    }
+
+    pages.join("\x0c")
+}
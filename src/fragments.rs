@@ -17,74 +17,90 @@
 
 macro_rules! write_block {
     ($elem:ident, $name:literal, &$args:ident) => {
+        let mut dump = String::new();
+
         if $args.elements {
-            println!("{:?}", &$elem);
+            dump.push_str(&format!("{:?}\n", &$elem));
 
             if !$args.blocks {
-                return Ok(());
+                return write_output(&$args.shared, dump.as_bytes());
             }
         }
 
         let block: Block = $elem.into();
 
         if $args.blocks {
-            println!("{:?}", &block);
+            dump.push_str(&format!("{:?}\n", &block));
         }
 
         if $args.elements || $args.blocks {
-            return Ok(());
+            return write_output(&$args.shared, dump.as_bytes());
         }
 
         let mut compositor = Compositor::new(1, false);
         compositor = compositor.run(vec![block]);
 
+        if $args.pages {
+            return write_output(&$args.shared,
+                                format!("{:?}\n", &compositor.pages).as_bytes());
+        }
+
         let typescript = Typescript {
+            layout: Layout::Manuscript,
             contact: None,
             word_count: None,
             has_structure: false,
             short_title: Segment::from(&$name[..]),
             short_author_name: Segment::from(&(*PROGRAM_NAME)[..]),
+            running_heads: Vec::new(),
             pages: compositor.pages,
         };
 
-        let mut writer = Writer::new(&typescript);
-        writer.run()?;
+        write_typescript(&typescript, $args)?;
     };
 }
 
 macro_rules! write_container {
     ($elem:ident, $name:literal, &$args:ident) => {
+        let mut dump = String::new();
+
         if $args.elements {
-            println!("{:?}", &$elem);
+            dump.push_str(&format!("{:?}\n", &$elem));
 
             if !$args.blocks {
-                return Ok(());
+                return write_output(&$args.shared, dump.as_bytes());
             }
         }
 
         let blocks: BlockList = $elem.into();
 
         if $args.blocks {
-            println!("{:?}", &blocks);
+            dump.push_str(&format!("{:?}\n", &blocks));
         }
 
         if $args.elements || $args.blocks {
-            return Ok(());
+            return write_output(&$args.shared, dump.as_bytes());
         }
 
         let mut compositor = Compositor::new(1, false);
         compositor = compositor.run(blocks);
 
+        if $args.pages {
+            return write_output(&$args.shared,
+                                format!("{:?}\n", &compositor.pages).as_bytes());
+        }
+
         let typescript = Typescript {
+            layout: Layout::Manuscript,
             contact: None,
             word_count: None,
             has_structure: false,
             short_title: Segment::from(&$name[..]),
             short_author_name: Segment::from(&(*PROGRAM_NAME)[..]),
+            running_heads: Vec::new(),
             pages: compositor.pages,
         };
 
-        let mut writer = Writer::new(&typescript);
-        writer.run()?;
+        write_typescript(&typescript, $args)?;
     };
 }
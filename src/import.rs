@@ -0,0 +1,61 @@
+// Kosik Importers
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Converts other input formats to the manuscript schema
+//!
+//! Each importer produces XML text, which then goes through the same
+//! [`reader`], [`formatter`] and [`compositor`] as a manuscript
+//! written by hand.
+//!
+//! * The [`fountain`] module imports screenplays written in
+//!   Fountain.
+//! * The [`markdown`] module imports CommonMark with YAML front
+//!   matter.
+//!
+//! [`reader`]: crate::document::reader
+//! [`formatter`]: crate::document::formatter
+//! [`compositor`]: crate::document::compositor
+
+pub mod fountain;
+pub mod markdown;
+
+// Escape the markup characters in character data or attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Split a personal name into given names and surname.  Names may be
+// written as "Joseph Conrad" or as "Conrad, Joseph".
+fn person(name: &str) -> String {
+    let (gn, sn) = match name.split_once(',') {
+        Some((sn, gn)) => (gn.trim(), sn.trim()),
+        None => match name.trim().rsplit_once(char::is_whitespace) {
+            Some((gn, sn)) => (gn.trim(), sn),
+            None => ("", name.trim()),
+        },
+    };
+
+    if gn.is_empty() {
+        format!("<person><sn>{}</sn></person>\n", escape(sn))
+    } else {
+        format!("<person><gn>{}</gn><sn>{}</sn></person>\n",
+                escape(gn), escape(sn))
+    }
+}
@@ -0,0 +1,359 @@
+// Kosik Fountain Importer
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Imports screenplays written in Fountain
+//!
+//! The title page keys <tt>Title</tt>, <tt>Author</tt> (or
+//! <tt>Authors</tt>) and <tt>Contact</tt> supply the document header.
+//! Other keys are ignored, and the credit is always printed as
+//! <i>Written by</i>.
+//!
+//! The body is converted as follows:
+//!
+//! | Fountain                                   | Screenplay            |
+//! |--------------------------------------------|-----------------------|
+//! | `INT.`, `EXT.`, `EST.`, `I/E` or `.` line  | `sceneHeading`        |
+//! | uppercase line, or `@` line, before speech | `character`           |
+//! | `(` line within speech                     | `parenthetical`       |
+//! | speech                                     | `p` within `dialogue` |
+//! | uppercase line ending in `TO:`, or `>` line| `transition`          |
+//! | `>centered<`                               | centered `action`     |
+//! | `===`                                      | `pageBreak`           |
+//! | anything else, or `!` line                 | `action`              |
+//! | `*x*`, `**x**` or `_x_`                    | `em`                  |
+//!
+//! Scene numbers are dropped, and so are boneyard comments, notes,
+//! sections and synopses.  Dual dialogue is set one speech after the
+//! other.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::import::fountain;
+//!
+//! let text = "Title: Brick & Steel\nAuthor: Stu Maschwitz\n\n\
+//!             EXT. BRICK'S PATIO - DAY\n\n\
+//!             A gorgeous day.\n\n\
+//!             STEEL\n(beat)\nBeer's ready!\n";
+//! let sik = fountain::to_sik(text).unwrap();
+//!
+//! assert!(sik.contains("BRICK'S PATIO - DAY</sceneHeading>"));
+//! assert!(sik.contains("<character>STEEL</character>"));
+//! assert!(sik.contains("<parenthetical>(beat)</parenthetical>"));
+//! ```
+
+use std::error::Error;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{escape, person};
+
+lazy_static! {
+    #[doc(hidden)]
+    static ref BONEYARD: Regex = Regex::new(r"(?s)/\*.*?\*/").unwrap();
+
+    #[doc(hidden)]
+    static ref NOTE: Regex = Regex::new(r"(?s)\[\[.*?\]\]").unwrap();
+
+    #[doc(hidden)]
+    static ref SCENE_HEADING: Regex = Regex::new(
+        r"(?i)^(?:INT\.?/EXT|INT|EXT|EST|I/E)[. ]"
+    ).unwrap();
+
+    #[doc(hidden)]
+    static ref SCENE_NUMBER: Regex = Regex::new(r"\s*#[^#]*#\s*$").unwrap();
+
+    #[doc(hidden)]
+    static ref PAGE_BREAK: Regex = Regex::new(r"^={3,}$").unwrap();
+
+    #[doc(hidden)]
+    static ref TITLE_KEY: Regex = Regex::new(r"^([A-Za-z][A-Za-z ]*):(.*)$").unwrap();
+
+    #[doc(hidden)]
+    static ref EMPHASIS: [Regex; 4] = [
+        Regex::new(r"\*\*\*([^\s*](?:[^*]*[^\s*])?)\*\*\*").unwrap(),
+        Regex::new(r"\*\*([^\s*](?:[^*]*[^\s*])?)\*\*").unwrap(),
+        Regex::new(r"\*([^\s*](?:[^*]*[^\s*])?)\*").unwrap(),
+        Regex::new(r"_([^\s_](?:[^_]*[^\s_])?)_").unwrap(),
+    ];
+}
+
+/// Convert a Fountain screenplay to manuscript XML.
+pub fn to_sik(text: &str) -> Result<String, Box<dyn Error>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text)
+        .replace("\r\n", "\n");
+    let text = BONEYARD.replace_all(&text, "");
+    let text = NOTE.replace_all(&text, "");
+
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+
+    out.push_str("<screenplay>\n");
+    let start = write_head(&lines, &mut out);
+    write_body(&lines[start..], &mut out);
+    out.push_str("</screenplay>\n");
+
+    Ok(out)
+}
+
+// Write the document header from the title page, returning the index
+// of the first line of the body.
+fn write_head(lines: &[&str], out: &mut String) -> usize {
+    let mut title: Vec<String> = Vec::new();
+    let mut authors: Vec<String> = Vec::new();
+    let mut contact: Vec<String> = Vec::new();
+    let mut key = String::new();
+    let mut i: usize = 0;
+
+    match lines.first() {
+        Some(line) if TITLE_KEY.is_match(line) => (),
+        _ => return 0,
+    }
+
+    while i < lines.len() && !lines[i].trim().is_empty() {
+        let line = lines[i];
+        i += 1;
+
+        // Values may continue on indented lines.
+        let value = if line.starts_with(char::is_whitespace) {
+            line.trim()
+        } else if let Some(captures) = TITLE_KEY.captures(line) {
+            key = captures[1].trim().to_lowercase();
+            captures.get(2).map_or("", |x| x.as_str().trim())
+        } else {
+            continue;
+        };
+
+        if value.is_empty() {
+            continue;
+        }
+
+        match &key[..] {
+            "title" => title.push(inline(value)),
+            "author" | "authors" => {
+                authors.extend(value.split('&')
+                               .map(|x| x.trim().to_string())
+                               .filter(|x| !x.is_empty()));
+            },
+            "contact" => contact.push(inline(value)),
+            _ => (),
+        }
+    }
+
+    if title.is_empty() && authors.is_empty() {
+        return i;
+    }
+
+    out.push_str("<head>\n");
+
+    if title.is_empty() {
+        out.push_str("<title>Untitled</title>\n");
+    } else {
+        out.push_str(&format!("<title>{}</title>\n", title.join("<br/>")));
+    }
+
+    out.push_str("<authors>\n");
+
+    if authors.is_empty() {
+        out.push_str("<person><sn>Anonymous</sn></person>\n");
+    } else {
+        for name in authors.iter() {
+            out.push_str(&person(name));
+        }
+    }
+
+    out.push_str("</authors>\n");
+
+    if !contact.is_empty() {
+        out.push_str(&format!("<contact>{}</contact>\n", contact.join("<br/>\n")));
+    }
+
+    out.push_str("</head>\n");
+    i
+}
+
+// Write the elements of the script proper.
+fn write_body(lines: &[&str], out: &mut String) {
+    let blank = |i: usize| lines.get(i).map_or(true, |x| x.trim().is_empty());
+    let mut i: usize = 0;
+
+    while i < lines.len() {
+        let line = lines[i].trim();
+        let prev_blank = i == 0 || blank(i - 1);
+        let next_blank = blank(i + 1);
+
+        if line.is_empty() {
+            i += 1;
+            continue;
+        }
+
+        if PAGE_BREAK.is_match(line) {
+            out.push_str("<pageBreak/>\n");
+            i += 1;
+
+        } else if line.starts_with('#') || line.starts_with('=') {
+            i += 1; // section or synopsis
+
+        } else if prev_blank && next_blank && is_scene_heading(line) {
+            let heading = line.strip_prefix('.').unwrap_or(line);
+            // A full stop in a scene heading does not end a sentence.
+            let heading = SCENE_NUMBER.replace(heading, "").replace(". ", ".\\ ");
+            out.push_str(&format!("<sceneHeading>{}</sceneHeading>\n",
+                                  inline(&heading)));
+            i += 1;
+
+        } else if is_centered(line) {
+            let mut centered: Vec<String> = Vec::new();
+
+            while i < lines.len() && is_centered(lines[i].trim()) {
+                let line = lines[i].trim();
+                centered.push(inline(line[1..line.len() - 1].trim()));
+                i += 1;
+            }
+
+            out.push_str(&format!("<action centered=\"true\">{}</action>\n",
+                                  centered.join("<br/>")));
+
+        } else if prev_blank && next_blank && is_transition(line) {
+            let transition = line.strip_prefix('>').unwrap_or(line).trim();
+            out.push_str(&format!("<transition>{}</transition>\n",
+                                  inline(transition)));
+            i += 1;
+
+        } else if prev_blank && !next_blank && is_character(line) {
+            let cue = line.strip_prefix('@').unwrap_or(line);
+            let cue = cue.strip_suffix('^').unwrap_or(cue).trim();
+            let mut speech: Vec<String> = Vec::new();
+
+            out.push_str("<dialogue>\n");
+            out.push_str(&format!("<character>{}</character>\n", inline(cue)));
+            i += 1;
+
+            while i < lines.len() && !blank(i) {
+                let line = lines[i].trim();
+
+                if line.starts_with('(') && line.ends_with(')') {
+                    write_speech(&mut speech, out);
+                    out.push_str(&format!("<parenthetical>{}</parenthetical>\n",
+                                          inline(line)));
+                } else {
+                    speech.push(inline(line));
+                }
+
+                i += 1;
+            }
+
+            write_speech(&mut speech, out);
+            out.push_str("</dialogue>\n");
+
+        } else {
+            let mut action: Vec<String> = Vec::new();
+
+            while i < lines.len() && !blank(i) {
+                let line = lines[i].trim();
+                action.push(inline(line.strip_prefix('!').unwrap_or(line)));
+                i += 1;
+            }
+
+            out.push_str(&format!("<action>{}</action>\n", action.join("<br/>\n")));
+        }
+    }
+}
+
+// Write the lines of speech collected since the last parenthetical.
+fn write_speech(speech: &mut Vec<String>, out: &mut String) {
+    if !speech.is_empty() {
+        out.push_str(&format!("<p>{}</p>\n", speech.join("<br/>\n")));
+        speech.clear();
+    }
+}
+
+fn is_scene_heading(line: &str) -> bool {
+    SCENE_HEADING.is_match(line)
+        || (line.starts_with('.') && !line.starts_with(".."))
+}
+
+fn is_transition(line: &str) -> bool {
+    (line.starts_with('>') && !line.ends_with('<'))
+        || (line.ends_with("TO:") && !line.chars().any(char::is_lowercase))
+}
+
+fn is_centered(line: &str) -> bool {
+    line.len() > 1 && line.starts_with('>') && line.ends_with('<')
+}
+
+// A character cue is in uppercase, apart from any extension such as
+// (cont'd), unless it is forced with @.
+fn is_character(line: &str) -> bool {
+    if line.starts_with('@') {
+        return true;
+    }
+
+    if line.starts_with('!') {
+        return false;
+    }
+
+    let name = line.split('(').next().unwrap_or("");
+
+    name.chars().any(char::is_alphabetic)
+        && !name.chars().any(char::is_lowercase)
+}
+
+// Convert a line of Fountain to mixed content.
+fn inline(text: &str) -> String {
+    // Protect escaped emphasis markers.
+    let mut out = escape(text)
+        .replace("\\*", "\u{e000}")
+        .replace("\\_", "\u{e001}");
+
+    for re in EMPHASIS.iter() {
+        out = re.replace_all(&out, "<em>$1</em>").to_string();
+    }
+
+    // Combined markers such as _**x**_ nest, but em does not.
+    let mut flat = String::with_capacity(out.len());
+    let mut depth: usize = 0;
+    let mut rest = &out[..];
+
+    while let Some(i) = rest.find("<em>").into_iter()
+        .chain(rest.find("</em>"))
+        .min()
+    {
+        flat.push_str(&rest[..i]);
+
+        if rest[i..].starts_with("<em>") {
+            if depth == 0 {
+                flat.push_str("<em>");
+            }
+
+            depth += 1;
+            rest = &rest[i + 4..];
+        } else {
+            depth = depth.saturating_sub(1);
+
+            if depth == 0 {
+                flat.push_str("</em>");
+            }
+
+            rest = &rest[i + 5..];
+        }
+    }
+
+    flat.push_str(rest);
+    flat.replace('\u{e000}', "*").replace('\u{e001}', "_")
+}
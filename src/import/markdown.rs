@@ -0,0 +1,379 @@
+// Kosik Markdown Importer
+// Copyright (C) 2023 Gene Yu
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <https://www.gnu.org/licenses/>.
+
+//! Imports CommonMark documents
+//!
+//! The YAML front matter supplies the document header:
+//!
+//! ```yaml
+//! ---
+//! title: "*Youth*"
+//! subtitle: A Narrative
+//! authors:
+//!   - Joseph Conrad
+//! contact: |
+//!   MATCHLOCK PRESS
+//!   P.O.\ Box 90606
+//!   Brooklyn, NY 11209
+//! ---
+//! ```
+//!
+//! The key <tt>author</tt> may be used instead of <tt>authors</tt>
+//! for a single author.  Only this simple subset of YAML is
+//! understood.
+//!
+//! The body is converted as follows:
+//!
+//! | Markdown                 | Manuscript                         |
+//! |--------------------------|------------------------------------|
+//! | `#` heading              | `part`                             |
+//! | `##` heading             | `chapter`                          |
+//! | `###` heading and deeper | `section`                          |
+//! | paragraph                | `p`                                |
+//! | `*x*` or `**x**`         | `em`                               |
+//! | `[^1]`                   | `footnote`, then `noteRef`         |
+//! | `>` block quote          | `blockquote`                       |
+//! | numbered list            | `ol`                               |
+//! | bulleted list            | `ul`                               |
+//! | `***` thematic break     | `div` (scene break)                |
+//! | code block               | `pre`                              |
+//! | table                    | `table`                            |
+//!
+//! A footnote is placed at its first reference, and later references
+//! to the same footnote become note references.  Links and images are
+//! reduced to their text.  Raw HTML is passed through, so manuscript
+//! elements can be embedded directly, and backslash escapes such as
+//! <tt>P.O.\ Box</tt> keep their manuscript meaning.
+//!
+//! # Examples
+//!
+//! ```
+//! use kosik::import::markdown;
+//!
+//! let text = "---\ntitle: Youth\nauthor: Joseph Conrad\n---\n\n## Chapter One\n\n\
+//!             It was *the* sea.\n";
+//! let sik = markdown::to_sik(text).unwrap();
+//!
+//! assert!(sik.contains("<chapter>Chapter One</chapter>"));
+//! assert!(sik.contains("<p>It was <em>the</em> sea.</p>"));
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag};
+
+use super::{escape, person};
+
+// Front matter fields, each holding one or more values
+type FrontMatter = HashMap<String, Vec<String>>;
+
+/// Convert a Markdown document to manuscript XML.
+pub fn to_sik(text: &str) -> Result<String, Box<dyn Error>> {
+    let (front_matter, body) = split_front_matter(text)?;
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+
+    out.push_str("<manuscript>\n");
+    write_head(&front_matter, &mut out);
+    out.push_str("<body>\n");
+    Converter::new(body).run(&mut out);
+    out.push_str("</body>\n</manuscript>\n");
+
+    Ok(out)
+}
+
+// Separate the YAML front matter, if any, from the rest of the text.
+fn split_front_matter(text: &str) -> Result<(FrontMatter, &str), Box<dyn Error>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut front_matter = FrontMatter::new();
+    let mut lines = text.split_inclusive('\n');
+
+    match lines.next() {
+        Some(line) if line.trim_end() == "---" => (),
+        _ => return Ok((front_matter, text)),
+    }
+
+    let mut offset = text.find('\n').map_or(text.len(), |i| i + 1);
+    let mut key: Option<String> = None;
+    let mut folded = false;
+
+    for line in lines {
+        offset += line.len();
+        let trimmed = line.trim();
+
+        if line.trim_end() == "---" || line.trim_end() == "..." {
+            return Ok((front_matter, &text[offset..]));
+        }
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with(char::is_whitespace) || trimmed.starts_with("- ") {
+            // continuation of the last key
+            if let Some(values) = key.as_ref().and_then(|k| front_matter.get_mut(k)) {
+                if let Some(item) = trimmed.strip_prefix("- ") {
+                    values.push(unquote(item));
+                } else if folded && !values.is_empty() {
+                    let last = values.len() - 1;
+                    values[last] = format!("{} {}", values[last], trimmed);
+                } else {
+                    values.push(trimmed.to_string());
+                }
+            }
+
+            continue;
+        }
+
+        match trimmed.split_once(':') {
+            Some((k, value)) => {
+                let value = value.trim();
+                let mut values: Vec<String> = Vec::new();
+
+                folded = value == ">";
+
+                if value.starts_with('[') && value.ends_with(']') {
+                    values.extend(value[1..value.len() - 1]
+                                  .split(',')
+                                  .map(|x| unquote(x.trim()))
+                                  .filter(|x| !x.is_empty()));
+                } else if !value.is_empty() && value != "|" && value != ">" {
+                    values.push(unquote(value));
+                }
+
+                key = Some(k.trim().to_string());
+                front_matter.insert(k.trim().to_string(), values);
+            },
+            None => {
+                return Err(format!("front matter: expected key: value, found {}",
+                                   trimmed).into());
+            },
+        }
+    }
+
+    Err("front matter: missing closing ---".into())
+}
+
+// Remove the quotation marks from a YAML scalar.
+fn unquote(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return value[1..value.len() - 1].to_string();
+        }
+    }
+
+    value.to_string()
+}
+
+// Write the document header from the front matter.
+fn write_head(front_matter: &FrontMatter, out: &mut String) {
+    let title = front_matter.get("title").and_then(|x| x.first());
+    let authors = front_matter.get("authors")
+        .or_else(|| front_matter.get("author"));
+
+    if title.is_none() && authors.is_none() {
+        return;
+    }
+
+    out.push_str("<head>\n");
+    out.push_str(&format!("<title>{}</title>\n",
+                          inline(title.map_or("Untitled", |x| &x[..]))));
+
+    if let Some(subtitle) = front_matter.get("subtitle").and_then(|x| x.first()) {
+        out.push_str(&format!("<subtitle>{}</subtitle>\n", inline(subtitle)));
+    }
+
+    out.push_str("<authors>\n");
+
+    match authors {
+        Some(authors) if !authors.is_empty() => {
+            for name in authors {
+                out.push_str(&person(name));
+            }
+        },
+        _ => out.push_str("<person><sn>Anonymous</sn></person>\n"),
+    }
+
+    out.push_str("</authors>\n");
+
+    if let Some(contact) = front_matter.get("contact") {
+        let lines: Vec<String> = contact.iter().map(|x| escape(x)).collect();
+        out.push_str(&format!("<contact>{}</contact>\n", lines.join("<br/>\n")));
+    }
+
+    out.push_str("</head>\n");
+}
+
+// Convert a line of Markdown to mixed content.
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    let mut converter = Converter::new(text);
+    let events = std::mem::take(&mut converter.events);
+
+    converter.render(&events, true, &mut out);
+    out.trim().to_string()
+}
+
+fn heading(level: HeadingLevel) -> &'static str {
+    match level {
+        HeadingLevel::H1 => "part",
+        HeadingLevel::H2 => "chapter",
+        _ => "section",
+    }
+}
+
+// Converts the Markdown event stream to XML
+struct Converter<'a> {
+    events: Vec<Event<'a>>,
+    notes: HashMap<String, Vec<Event<'a>>>,
+    note_numbers: HashMap<String, usize>,
+    next_note_no: usize,
+}
+
+impl<'a> Converter<'a> {
+    // Parse the text, setting the footnote definitions aside.
+    fn new(text: &'a str) -> Self {
+        let mut options = Options::empty();
+        options.insert(Options::ENABLE_FOOTNOTES);
+        options.insert(Options::ENABLE_TABLES);
+        options.insert(Options::ENABLE_STRIKETHROUGH);
+
+        let mut events: Vec<Event<'a>> = Vec::new();
+        let mut notes: HashMap<String, Vec<Event<'a>>> = HashMap::new();
+        let mut note: Option<(String, Vec<Event<'a>>)> = None;
+
+        for event in Parser::new_ext(text, options) {
+            match event {
+                Event::Start(Tag::FootnoteDefinition(label)) => {
+                    note = Some((label.to_string(), Vec::new()));
+                },
+                Event::End(Tag::FootnoteDefinition(_)) => {
+                    if let Some((label, note_events)) = note.take() {
+                        notes.insert(label, note_events);
+                    }
+                },
+                event => match note {
+                    Some((_, ref mut note_events)) => note_events.push(event),
+                    None => events.push(event),
+                },
+            }
+        }
+
+        Self {
+            events: events,
+            notes: notes,
+            note_numbers: HashMap::new(),
+            next_note_no: 1,
+        }
+    }
+
+    fn run(mut self, out: &mut String) {
+        let events = std::mem::take(&mut self.events);
+        self.render(&events, false, out);
+    }
+
+    // Write the events as XML.  If <tt>bare</tt> is true, paragraph
+    // tags are left out.
+    fn render(&mut self, events: &[Event<'a>], bare: bool, out: &mut String) {
+        for event in events {
+            match event {
+                Event::Start(tag) => self.start(tag, bare, out),
+                Event::End(tag) => self.end(tag, bare, out),
+                Event::Text(text) | Event::Code(text) => {
+                    out.push_str(&escape(text));
+                },
+                Event::Html(html) => out.push_str(html),
+                Event::FootnoteReference(label) => {
+                    self.footnote(label, out);
+                },
+                Event::SoftBreak => out.push('\n'),
+                Event::HardBreak => out.push_str("<br/>\n"),
+                Event::Rule => out.push_str("<div/>\n"),
+                Event::TaskListMarker(checked) => {
+                    out.push_str(if *checked { "[x] " } else { "[ ] " });
+                },
+            }
+        }
+    }
+
+    fn start(&mut self, tag: &Tag<'a>, bare: bool, out: &mut String) {
+        match tag {
+            Tag::Paragraph => if !bare { out.push_str("<p>") },
+            Tag::Heading(level, _, _) => {
+                out.push_str(&format!("<{}>", heading(*level)));
+            },
+            Tag::BlockQuote => out.push_str("<blockquote>\n"),
+            Tag::CodeBlock(_) => out.push_str("<pre>"),
+            Tag::List(Some(1)) => out.push_str("<ol>\n"),
+            Tag::List(Some(n)) => {
+                out.push_str(&format!("<ol startNo=\"{}\">\n", n));
+            },
+            Tag::List(None) => out.push_str("<ul>\n"),
+            Tag::Item => out.push_str("<li>"),
+            Tag::Table(_) => out.push_str("<table>\n"),
+            Tag::TableHead => out.push_str("<tr header=\"true\">"),
+            Tag::TableRow => out.push_str("<tr>"),
+            Tag::TableCell => out.push_str("<td>"),
+            Tag::Emphasis | Tag::Strong => out.push_str("<em>"),
+            _ => (),
+        }
+    }
+
+    fn end(&mut self, tag: &Tag<'a>, bare: bool, out: &mut String) {
+        match tag {
+            Tag::Paragraph => if !bare { out.push_str("</p>\n") },
+            Tag::Heading(level, _, _) => {
+                out.push_str(&format!("</{}>\n", heading(*level)));
+            },
+            Tag::BlockQuote => out.push_str("</blockquote>\n"),
+            Tag::CodeBlock(_) => out.push_str("</pre>\n"),
+            Tag::List(Some(_)) => out.push_str("</ol>\n"),
+            Tag::List(None) => out.push_str("</ul>\n"),
+            Tag::Item => out.push_str("</li>\n"),
+            Tag::Table(_) => out.push_str("</table>\n"),
+            Tag::TableHead | Tag::TableRow => out.push_str("</tr>\n"),
+            Tag::TableCell => out.push_str("</td>"),
+            Tag::Emphasis | Tag::Strong => out.push_str("</em>"),
+            _ => (),
+        }
+    }
+
+    // Place a footnote at its first reference, and refer back to it
+    // afterward.
+    fn footnote(&mut self, label: &str, out: &mut String) {
+        if let Some(n) = self.note_numbers.get(label) {
+            out.push_str(&format!("<noteRef label=\"{}\"/>", n));
+
+        } else if let Some(note_events) = self.notes.remove(label) {
+            let n = self.next_note_no;
+            self.next_note_no += 1;
+            self.note_numbers.insert(label.to_string(), n);
+
+            // A single paragraph is written inline.
+            let paragraphs = note_events.iter()
+                .filter(|x| matches!(x, Event::Start(Tag::Paragraph)))
+                .count();
+
+            out.push_str(&format!("<footnote label=\"{}\">", n));
+            self.render(&note_events, paragraphs <= 1, out);
+            out.push_str("</footnote>");
+
+        } else {
+            out.push_str(&escape(&format!("[^{}]", label)));
+        }
+    }
+}
@@ -96,7 +96,9 @@
 //!      lines: [
 //!           Line {
 //!                column: 10,
-//!                segments: [Segment { text: "", ps: "() show " }],
+//!                segments: [
+//!                     Segment { text: "", ps: "() show ", dpy: (empty) }
+//!                ],
 //!                note_refs: []
 //!           }
 //!      ],
@@ -112,98 +114,1873 @@
 //! Kosik will render the individual element in Postscript.  In all
 //! cases, a single top-level element is expected.
 //!
+//! Each job also has a subcommand.  <tt>kosik render</tt> is the
+//! same as <tt>kosik</tt> with no subcommand, and <tt>kosik dump</tt>
+//! takes the stage to show, <tt>elements</tt>, <tt>blocks</tt>,
+//! <tt>pages</tt> or <tt>xml</tt>, in place of the <tt>-e</tt>,
+//! <tt>-b</tt>, <tt>-p</tt> and <tt>-x</tt> flags.  <tt>kosik
+//! convert</tt> writes another format, <tt>kosik toc</tt> lists the
+//! table of contents with page numbers, <tt>kosik validate</tt>
+//! checks that manuscripts can be read and typed, and <tt>kosik
+//! new</tt> starts a manuscript from a skeleton.  <tt>kosik
+//! help</tt> lists them all:
+//!
+//! ```sh
+//! $ kosik new --title Youth --author "Joseph Conrad" youth.sik
+//! $ kosik dump pages minimal.sik
+//! $ kosik convert -o conrad.docx conrad.sik
+//! $ kosik toc conrad.sik
+//! $ kosik validate conrad.sik youth.sik
+//! ```
+//!
+//! The <tt>-f</tt> flag selects another output format.  With <tt>-f
+//! html</tt>, Kosik writes an HTML preview of the typed pages, and
+//! with <tt>-f reading</tt>, an HTML reading view in which the text
+//! is reflowed to fit the browser window.  With <tt>-f pdf</tt>, it
+//! writes the typed pages as a PDF document, and with <tt>-f
+//! text</tt>, as plain text.  With <tt>-f svg</tt>, it writes each
+//! page to a numbered SVG file in the current directory.  With <tt>-f
+//! docx</tt>, it writes a Word document in standard manuscript
+//! format, with <tt>-f epub</tt>, an EPUB e-book, and with <tt>-f
+//! latex</tt>, a LaTeX document for typeset proofs.  With <tt>-f
+//! sik</tt>, it writes the manuscript back out as XML in a consistent
+//! layout:
+//!
+//! ```sh
+//! $ kosik -f html conrad.sik > conrad.html
+//! $ kosik -f reading conrad.sik > conrad-reading.html
+//! $ kosik -f docx conrad.sik > conrad.docx
+//! $ kosik -f epub conrad.sik > conrad.epub
+//! $ kosik -f latex conrad.sik > conrad.tex
+//! $ kosik -f sik conrad.sik > conrad-normalized.sik
+//! $ kosik -f svg conrad.sik
+//! $ ls conrad-*.svg | head -3
+//! conrad-001.svg
+//! conrad-002.svg
+//! conrad-003.svg
+//! ```
+//!
+//! Output goes to the standard output, unless <tt>-o</tt> names a
+//! file.  The extension of the file selects the format, so
+//! <tt>.pdf</tt>, <tt>.txt</tt> and <tt>.html</tt> need no
+//! <tt>-f</tt> flag.  SVG pages are named after the file.  The
+//! subcommands that write a document or a report, <tt>render</tt>,
+//! <tt>convert</tt>, <tt>anthology</tt>, <tt>dump</tt>,
+//! <tt>stats</tt>, <tt>toc</tt> and <tt>lint</tt>, all take
+//! <tt>-o</tt>.  There are no options for the page geometry or the
+//! locale, since manuscripts are always typed on US letter paper,
+//! with American quotation marks and dashes.  An input file of
+//! <tt>-</tt> reads the manuscript from the standard input, so Kosik
+//! can sit at the end of a pipeline.  Files that are not in UTF-8 are
+//! read in the encoding named in their XML declaration:
+//!
+//! ```sh
+//! $ kosik -o conrad.pdf conrad.sik
+//! $ kosik toc -o contents.txt conrad.sik
+//! $ xsltproc normalize.xsl conrad.sik | kosik -o conrad.txt -
+//! ```
+//!
+//! When the pages are typed, a note reference with no footnote, a
+//! footnote that is never referenced, and a footnote that takes the
+//! label of another one that has not been printed yet are reported on
+//! the standard error.  With <tt>--strict</tt>, they are errors:
+//!
+//! ```sh
+//! $ kosik --strict conrad.sik > conrad.ps
+//! ```
+//!
+//! The <tt>stats</tt> subcommand reports word, sentence, paragraph
+//! and page counts for each part, chapter and section, with the
+//! footnotes counted separately, the share of the words that are
+//! dialogue and an estimated reading time.  Use <tt>--json</tt> for
+//! output that other programs can read:
+//!
+//! ```sh
+//! $ kosik stats conrad.sik
+//! $ kosik stats --json conrad.sik > conrad.json
+//! ```
+//!
+//! The word count on the title page is the exact count of the words
+//! in the manuscript, footnotes included, rounded to the nearest
+//! thousand, or hundred for short works.  Some publishers want it
+//! worked out another way, so <tt>--word-count lines</tt> counts ten
+//! words to each line of typescript, and <tt>--word-count
+//! typesetter</tt> counts one word to every six characters.  Use
+//! <tt>--round</tt> to round to some other number, and
+//! <tt>--body-only</tt> to leave out the front and back matter.  The
+//! <tt>stats</tt> subcommand takes the same options:
+//!
+//! ```sh
+//! $ kosik --word-count lines --round 500 conrad.sik > conrad.ps
+//! $ kosik stats --word-count typesetter --body-only conrad.sik
+//! ```
+//!
+//! The <tt>fmt</tt> subcommand lays out manuscript sources in place,
+//! wrapping the text and indenting the elements the same way every
+//! time, without changing how they read.  Comments are kept.  With
+//! <tt>--check</tt>, it only lists the files that would change, and
+//! fails if there are any, which is useful in continuous integration:
+//!
+//! ```sh
+//! $ kosik fmt conrad.sik chapters/*.sik
+//! $ kosik fmt --check conrad.sik chapters/*.sik
+//! $ echo $?
+//! 0
+//! ```
+//!
+//! The <tt>lint</tt> subcommand looks for slips in the text: repeated
+//! words, straight quotation marks, unbalanced parentheses and
+//! quotation marks, paragraphs with no full stop, double spaces
+//! inside sentences, note references with no footnote, chapters with
+//! no title and characters outside the Latin-9 character set.  Each
+//! finding is listed with its line and column, and the rule it broke,
+//! which <tt>-A</tt> switches off.  The exit status is 1 if anything
+//! was found:
+//!
+//! ```sh
+//! $ kosik lint -A missing-full-stop conrad.sik
+//! conrad.sik:902:27: "very" is repeated [repeated-word]
+//! ```
+//!
+//! With <tt>--watch</tt>, Kosik keeps running, and types the pages
+//! again whenever the input file or a file it includes is saved.  The
+//! pages go to a file named after the input file, such as
+//! <tt>conrad.ps</tt>, which a viewer that reloads on change can keep
+//! open.  Only the chapters that changed are read and formatted
+//! again, and pagination picks up from the first of them.  Watching
+//! works with the formats that show the typed pages: <tt>ps</tt>,
+//! <tt>pdf</tt>, <tt>html</tt>, <tt>svg</tt> and <tt>text</tt>:
+//!
+//! ```sh
+//! $ kosik --watch conrad.sik
+//! conrad.ps: 45 pages, 3 of 3 parts read again, 3 reformatted
+//! conrad.ps: 45 pages, 1 of 3 parts read again, 1 reformatted
+//! ```
+//!
+//! The <tt>batch</tt> subcommand types many manuscripts at once, on
+//! as many threads as there are processors unless <tt>-j</tt> says
+//! otherwise.  Patterns are expanded, so they can be quoted to get
+//! around the limits of the shell.  Each output file is written next
+//! to its source, or into the directory named by <tt>-d</tt>, and a
+//! line with the pages and words is printed for each manuscript.
+//! Errors are reported for each file, and the exit status is 1 if any
+//! of them failed:
+//!
+//! ```sh
+//! $ kosik batch -f pdf -d out 'stories/*.sik'
+//! stories/rain.sik: 12 pages, 2,914 words -> out/rain.pdf
+//! stories/youth.sik: 45 pages, 13,582 words -> out/youth.pdf
+//! ```
+//!
+//! The <tt>anthology</tt> subcommand types several manuscripts as
+//! one collection, with a title page of its own and a table of
+//! contents that lists the title and authors of each of them.  Each
+//! manuscript starts on a new page, the page numbers run on from one
+//! to the next, and the slug line names the manuscript each page
+//! belongs to.  The title page takes the contact information from
+//! the project settings:
+//!
+//! ```sh
+//! $ kosik anthology --title "Sea Stories" --author "Gene Yu" \
+//!     -o sea-stories.pdf youth.sik typhoon.sik
+//! ```
+//!
 //! [`conrad.pdf`]: <http://www.matchlock.com/kosik/conrad.pdf>
 
-use std::env;
-use std::error::Error;
-use std::ffi::OsStr;
-use std::fs;
-use std::path::Path;
-use std::path::PathBuf;
+use std::env;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use clap::parser::ValueSource;
+
+use lazy_static::lazy_static;
+
+use thousands::Separable;
+
+use crate::config::Config;
+use crate::document::*;
+use crate::document::reader::{Reader, SyntaxError};
+use crate::document::cache::Cache;
+use crate::document::collection::Collection;
+use crate::document::compositor::Compositor;
+use crate::document::include::Source;
+use crate::document::writer::Writer;
+use crate::text::*;
+use crate::text::tokens::*;
+    
+pub mod config;
+pub mod document;
+pub mod export;
+pub mod import;
+pub mod lut;
+pub mod text;
+
+//use crate::document::LEFT_MARGIN;
+//use crate::document::RIGHT_MARGIN;
+
+// configuration
+
+lazy_static! {
+    #[doc(hidden)]
+    pub static ref PROGRAM_NAME: String = match get_program_name() {
+        Some(name) => name,
+        None => "kosik".to_string(),
+    };
+
+    /// Path to prologue.ps
+    static ref PROLOGUE_FILE: PathBuf
+        = PathBuf::from("/home/gene/share/kosik/prologue.ps");
+
+    /// Path to roman_numerals.txt
+    static ref ROMAN_NUMERALS_FILE: PathBuf
+        = PathBuf::from("/home/gene/share/kosik/roman_numerals.txt");
+}
+
+/// Command-line arguments
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, about="Manuscript Typewriter")]
+pub struct Arguments {
+    /// An XML file conforming to the manuscript schema, or - for the standard input
+    pub input_file: PathBuf,
+
+    #[clap(short, long)]
+    /// Show the internal element representation instead of the usual output.
+    pub elements: bool,
+
+    #[clap(short, long)]
+    /// Show the internal block representation instead of the usual output.
+    pub blocks: bool,
+
+    #[clap(short, long)]
+    /// Show the composed pages instead of the usual output.
+    pub pages: bool,
+
+    #[clap(short = 'x', long)]
+    /// Show the XML input, after importing and expanding includes, instead of the usual output.
+    pub xml: bool,
+
+    #[clap(short, long, value_enum, default_value_t = Format::Ps)]
+    /// Output format, unless the extension of the output file or the project settings name another
+    pub format: Format,
+
+    #[clap(flatten)]
+    pub shared: SharedArguments,
+
+    #[clap(flatten)]
+    pub word_count: WordCountPolicy,
+
+    #[clap(long)]
+    /// Fail if a note reference has no footnote, or a footnote is not printed, instead of warning.
+    pub strict: bool,
+
+    #[clap(short, long)]
+    /// Keep running, and write the pages to a file again whenever the input changes.
+    pub watch: bool,
+
+    #[clap(skip)]
+    /// Project settings, from a <tt>kosik.toml</tt> file
+    pub config: Config,
+}
+
+/// Command-line options that are the same for every subcommand that
+/// writes a document or a report
+///
+/// There are no options for the page geometry or the locale, because
+/// manuscripts are always typed on US letter paper, with American
+/// quotation marks and dashes.
+#[derive(clap::Args, Clone, Default, Debug)]
+pub struct SharedArguments {
+    #[clap(short, long, value_name = "FILE")]
+    /// Write to a file instead of the standard output.
+    pub output: Option<PathBuf>,
+}
+
+/// Command line, with a subcommand or the arguments for rendering
+#[derive(Parser, Debug)]
+#[clap(author="Gene Yu", version, about="Manuscript Typewriter",
+       args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    #[clap(flatten)]
+    pub args: Arguments,
+}
+
+impl Cli {
+    /// Parse the command line, exiting with a usage message if it is
+    /// wrong, and fill in the options it leaves out from the project
+    /// settings
+    ///
+    /// With a subcommand, the arguments for rendering are left at
+    /// their defaults, since the input file they need is missing.
+    pub fn from_command_line() -> Result<Cli, Box<dyn Error>> {
+        let matches = Cli::command().get_matches();
+
+        let mut cli = match matches.subcommand() {
+            Some(_) => Command::from_arg_matches(&matches).map(|command| Cli {
+                command: Some(command),
+                args: Default::default(),
+            }),
+            None => Cli::from_arg_matches(&matches),
+        }.unwrap_or_else(|e| e.exit());
+
+        match (&mut cli.command, matches.subcommand()) {
+            (None, _) => {
+                cli.args.configure(&matches)?;
+            },
+            (Some(Command::Anthology(args)), Some((_, matches))) => {
+                let config = Config::discover(&args.input_files[0])?;
+                configure_word_count(&mut args.word_count, &config, matches);
+            },
+            (Some(Command::Batch(args)), Some((_, matches))) => {
+                args.matches = matches.clone();
+            },
+            (Some(Command::Convert(args)), Some((_, matches))) => {
+                let config = Config::discover(&args.input_file)?;
+                configure_word_count(&mut args.word_count, &config, matches);
+            },
+            (Some(Command::Render(args)), Some((_, matches))) => {
+                args.configure(matches)?;
+            },
+            (Some(Command::Stats(args)), Some((_, matches))) => {
+                let config = Config::discover(&args.input_file)?;
+                configure_word_count(&mut args.word_count, &config, matches);
+            },
+            _ => {},
+        }
+
+        Ok(cli)
+    }
+}
+
+impl Arguments {
+    /// Take the options that the command line leaves out from the
+    /// name of the output file, and then from the project settings
+    fn configure(&mut self, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+        let config = Config::discover(&self.input_file)?;
+
+        if !given(matches, "format") {
+            let format = self.shared.output.as_deref()
+                .and_then(Format::from_extension)
+                .or(config.format);
+
+            if let Some(format) = format {
+                self.format = format;
+            }
+        }
+
+        if !given(matches, "strict") {
+            self.strict = config.strict.unwrap_or(self.strict);
+        }
+
+        configure_word_count(&mut self.word_count, &config, matches);
+        self.config = config;
+        Ok(())
+    }
+}
+
+/// Take the word count options that the command line leaves out from
+/// the project settings
+fn configure_word_count(policy: &mut WordCountPolicy, config: &Config,
+                        matches: &ArgMatches)
+{
+    if !given(matches, "method") {
+        policy.method = config.method.unwrap_or(policy.method);
+    }
+
+    if !given(matches, "granularity") {
+        policy.granularity = config.granularity.unwrap_or(policy.granularity);
+    }
+
+    if !given(matches, "body_only") {
+        policy.body_only = config.body_only.unwrap_or(policy.body_only);
+    }
+}
+
+/// Check whether an option was given on the command line
+fn given(matches: &ArgMatches, id: &str) -> bool {
+    matches.value_source(id) == Some(ValueSource::CommandLine)
+}
+
+/// Subcommands
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Type several manuscripts as one collection
+    Anthology(AnthologyArguments),
+    /// Type many manuscripts at once, each to a file of its own
+    Batch(BatchArguments),
+    /// Convert a manuscript to another format
+    Convert(ConvertArguments),
+    /// Show the internal representation of a manuscript at some stage
+    Dump(DumpArguments),
+    /// Lay out manuscript sources in a canonical form
+    Fmt(FmtArguments),
+    /// Check manuscript sources for slips in the text
+    Lint(LintArguments),
+    /// Start a new manuscript from a skeleton
+    New(NewArguments),
+    /// Type a manuscript, as Kosik does without a subcommand
+    Render(Arguments),
+    /// Report word, sentence and page counts for a manuscript
+    Stats(StatsArguments),
+    /// List the table of contents of a manuscript, with page numbers
+    Toc(TocArguments),
+    /// Check that manuscripts can be read and typed
+    Validate(ValidateArguments),
+}
+
+/// Command-line arguments for <tt>kosik anthology</tt>
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik anthology",
+       about="Type several manuscripts as one collection")]
+pub struct AnthologyArguments {
+    /// XML files conforming to the manuscript schema, in the order they are collected
+    #[clap(required = true)]
+    pub input_files: Vec<PathBuf>,
+
+    #[clap(long, default_value = "Working Title")]
+    /// Title of the collection
+    pub title: String,
+
+    #[clap(long)]
+    /// Name of an editor or author of the collection, with the surname last.  May be given more than once.
+    pub author: Vec<String>,
+
+    #[clap(short, long, value_enum)]
+    /// Output format, if the output file and the project settings do not name one
+    pub format: Option<Format>,
+
+    #[clap(flatten)]
+    pub shared: SharedArguments,
+
+    #[clap(flatten)]
+    pub word_count: WordCountPolicy,
+
+    #[clap(long)]
+    /// Fail if a note reference has no footnote, or a footnote is not printed, instead of warning.
+    pub strict: bool,
+}
+
+/// Command-line arguments for <tt>kosik batch</tt>
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik batch",
+       about="Type many manuscripts at once, each to a file of its own")]
+pub struct BatchArguments {
+    /// XML files conforming to the manuscript schema, or patterns that match them, such as 'stories/*.sik'
+    #[clap(required = true)]
+    pub input_files: Vec<String>,
+
+    #[clap(short, long, value_enum)]
+    /// Output format, if the project settings do not name one
+    pub format: Option<Format>,
+
+    #[clap(short = 'd', long, value_name = "DIR")]
+    /// Write the output files into a directory instead of next to their sources.
+    pub output_dir: Option<PathBuf>,
+
+    #[clap(short, long, value_name = "N", default_value_t = 0)]
+    /// Type N manuscripts at a time.  By default, there is one thread for each processor.
+    pub jobs: usize,
+
+    #[clap(flatten)]
+    pub word_count: WordCountPolicy,
+
+    #[clap(long)]
+    /// Fail if a note reference has no footnote, or a footnote is not printed, instead of warning.
+    pub strict: bool,
+
+    #[clap(skip)]
+    /// Command line, to tell the word count options it gives from
+    /// those left at their defaults, which each file takes from its
+    /// own project settings
+    pub matches: ArgMatches,
+}
+
+/// Command-line arguments for <tt>kosik convert</tt>
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik convert",
+       about="Convert a manuscript to another format")]
+pub struct ConvertArguments {
+    /// An XML file conforming to the manuscript schema, or - for the standard input
+    pub input_file: PathBuf,
+
+    #[clap(short, long, value_enum)]
+    /// Output format, if the output file does not name one
+    pub format: Option<Format>,
+
+    #[clap(flatten)]
+    pub shared: SharedArguments,
+
+    #[clap(flatten)]
+    pub word_count: WordCountPolicy,
+}
+
+/// Command-line arguments for <tt>kosik dump</tt>
+#[derive(Parser, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik dump",
+       about="Show the internal representation of a manuscript")]
+pub struct DumpArguments {
+    #[clap(value_enum)]
+    /// Stage of processing to show
+    pub stage: Stage,
+
+    /// An XML file conforming to the manuscript schema, or - for the standard input
+    pub input_file: PathBuf,
+
+    #[clap(flatten)]
+    pub shared: SharedArguments,
+}
+
+/// Stages of processing that can be dumped
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Stage {
+    /// Text blocks, as formatted
+    Blocks,
+    /// Element tree, as read
+    Elements,
+    /// Pages, as composed
+    Pages,
+    /// XML input, after importing and expanding includes
+    Xml,
+}
+
+/// Command-line arguments for <tt>kosik new</tt>
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik new",
+       about="Start a new manuscript from a skeleton")]
+pub struct NewArguments {
+    /// File to create, which must not exist yet
+    pub output_file: PathBuf,
+
+    #[clap(long, default_value = "Working Title")]
+    /// Title of the work
+    pub title: String,
+
+    #[clap(long, default_value = "Given Surname")]
+    /// Name of the author, with the surname last
+    pub author: String,
+}
+
+/// Command-line arguments for <tt>kosik toc</tt>
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik toc",
+       about="List the table of contents of a manuscript")]
+pub struct TocArguments {
+    /// An XML file conforming to the manuscript schema, or - for the standard input
+    pub input_file: PathBuf,
+
+    #[clap(flatten)]
+    pub shared: SharedArguments,
+}
+
+/// Command-line arguments for <tt>kosik validate</tt>
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik validate",
+       about="Check that manuscripts can be read and typed")]
+pub struct ValidateArguments {
+    /// XML files conforming to the manuscript schema
+    #[clap(required = true)]
+    pub input_files: Vec<PathBuf>,
+
+    #[clap(long)]
+    /// Count footnote errors as failures instead of warnings.
+    pub strict: bool,
+}
+
+/// Command-line arguments for <tt>kosik fmt</tt>
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik fmt",
+       about="Lay out manuscript sources in a canonical form")]
+pub struct FmtArguments {
+    /// XML files conforming to the manuscript schema, which are rewritten in place
+    #[clap(required = true)]
+    pub input_files: Vec<PathBuf>,
+
+    #[clap(long)]
+    /// List the files that are not formatted, without rewriting them, and fail if there are any.
+    pub check: bool,
+}
+
+/// Command-line arguments for <tt>kosik lint</tt>
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik lint",
+       about="Check manuscript sources for slips in the text")]
+pub struct LintArguments {
+    /// XML files conforming to the manuscript schema
+    #[clap(required = true)]
+    pub input_files: Vec<PathBuf>,
+
+    #[clap(short = 'A', long, value_enum, value_name = "RULE")]
+    /// Switch off a rule.  May be given more than once.
+    pub allow: Vec<document::lint::Rule>,
+
+    #[clap(flatten)]
+    pub shared: SharedArguments,
+}
+
+/// Command-line arguments for <tt>kosik stats</tt>
+#[derive(Parser, Default, Debug)]
+#[clap(author="Gene Yu", version, bin_name="kosik stats",
+       about="Report word, sentence and page counts for a manuscript")]
+pub struct StatsArguments {
+    /// An XML file conforming to the manuscript schema
+    pub input_file: PathBuf,
+
+    #[clap(long)]
+    /// Write the statistics as JSON instead of a table.
+    pub json: bool,
+
+    #[clap(flatten)]
+    pub word_count: WordCountPolicy,
+
+    #[clap(flatten)]
+    pub shared: SharedArguments,
+}
+
+/// Output formats
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// Word document in standard manuscript format
+    Docx,
+    /// EPUB 3 e-book
+    Epub,
+    /// Paged HTML preview
+    Html,
+    /// LaTeX document for a book class
+    Latex,
+    /// PDF document in Courier
+    Pdf,
+    /// Postscript
+    #[default]
+    Ps,
+    /// Reflowed HTML reading view
+    Reading,
+    /// Manuscript XML, as read
+    Sik,
+    /// One SVG file per page, named after the input file
+    Svg,
+    /// Plain text, with a form feed between pages
+    Text,
+}
+
+impl Format {
+    /// Find the format that a file extension stands for
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use kosik::Format;
+    ///
+    /// assert_eq!(Format::from_extension(Path::new("conrad.pdf")), Some(Format::Pdf));
+    /// assert_eq!(Format::from_extension(Path::new("conrad")), None);
+    /// ```
+    pub fn from_extension(path: &Path) -> Option<Format> {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("docx") => Some(Format::Docx),
+            Some("epub") => Some(Format::Epub),
+            Some("htm") | Some("html") => Some(Format::Html),
+            Some("pdf") => Some(Format::Pdf),
+            Some("ps") => Some(Format::Ps),
+            Some("sik") | Some("xml") => Some(Format::Sik),
+            Some("svg") => Some(Format::Svg),
+            Some("tex") => Some(Format::Latex),
+            Some("txt") => Some(Format::Text),
+            _ => None,
+        }
+    }
+
+    /// Extension for a file in this format, without the dot
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kosik::Format;
+    ///
+    /// assert_eq!(Format::Latex.extension(), "tex");
+    /// assert_eq!(Format::Reading.extension(), "html");
+    /// ```
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Docx => "docx",
+            Format::Epub => "epub",
+            Format::Html | Format::Reading => "html",
+            Format::Latex => "tex",
+            Format::Pdf => "pdf",
+            Format::Ps => "ps",
+            Format::Sik => "sik",
+            Format::Svg => "svg",
+            Format::Text => "txt",
+        }
+    }
+
+    /// Check whether the format is one of typed pages, which are
+    /// composed as a typewriter would type them
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kosik::Format;
+    ///
+    /// assert!(Format::Pdf.is_typed());
+    /// assert!(!Format::Docx.is_typed());
+    /// ```
+    pub fn is_typed(&self) -> bool {
+        matches!(self, Format::Html | Format::Pdf | Format::Ps | Format::Svg | Format::Text)
+    }
+}
+
+impl From<&str> for Arguments {
+    // This method is for testing.
+    fn from(s: &str) -> Self {
+        Self {
+            input_file: PathBuf::from(s),
+            elements: false,
+            blocks: false,
+            pages: false,
+            xml: false,
+            format: Format::Ps,
+            shared: Default::default(),
+            word_count: Default::default(),
+            strict: false,
+            watch: false,
+            config: Default::default(),
+        }
+    }
+}
+
+/// Load the input file as XML, expanding any included files
+///
+/// Files with a <tt>.md</tt> or <tt>.markdown</tt> extension are
+/// imported from Markdown, and files with a <tt>.fountain</tt>
+/// extension from Fountain.  If the input file is <tt>-</tt>, XML is
+/// read from the standard input, and includes are resolved relative
+/// to the current directory.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let args = kosik::Arguments::from("dummy.md");
+/// let source = kosik::load(&args).unwrap();
+/// print!("{}", source.text);
+/// ```
+pub fn load(args: &Arguments) -> Result<Source, Box<dyn Error>> {
+    let path = &args.input_file;
+
+    if path == Path::new("-") {
+        let mut bytes: Vec<u8> = Vec::new();
+        io::stdin().read_to_end(&mut bytes)
+            .map_err(|e| format!("<stdin>: {}", e))?;
+
+        return Source::from_bytes(Path::new("<stdin>"), bytes);
+    }
+
+    match path.extension().and_then(OsStr::to_str) {
+        Some("md") | Some("markdown") => {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let xml_string = import::markdown::to_sik(&text)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok(Source::new(path, xml_string))
+        },
+        Some("fountain") => {
+            let text = fs::read_to_string(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let xml_string = import::fountain::to_sik(&text)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok(Source::new(path, xml_string))
+        },
+        _ => Source::open(path),
+    }
+}
+
+/// Read an input file and construct an element hierarchy from its
+/// contents
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let args = kosik::Arguments::from("dummy.sik");
+/// let root = kosik::read(&args).unwrap();
+/// ```
+pub fn read(args: &Arguments) -> Result<ElementType, Box<dyn Error>> {
+    parse(&load(args)?, &args.config)
+}
+
+/// Construct an element hierarchy from loaded text, reporting errors
+/// against the files the text came from, and fill in what the project
+/// settings supply
+fn parse(source: &Source, config: &Config) -> Result<ElementType, Box<dyn Error>> {
+    let reader = Reader::with_config(&source.text, config);
+    complete(source, config, reader.run())
+}
+
+/// Fill in the root element that was read from a source from the
+/// project settings, or report where the source could not be read
+fn complete(source: &Source, config: &Config,
+            result: Result<Option<ElementType>, SyntaxError>)
+            -> Result<ElementType, Box<dyn Error>>
+{
+    match result {
+        Ok(Some(mut root)) => {
+            config.complete(&mut root);
+            Ok(root)
+        },
+        Ok(None) => Err("No elements!".into()),
+        Err(error) => {
+            let (path, line) = source.locate(error.position);
+            Err(format!("{}:{}: {}", path.display(), line, error).into())
+        },
+    }
+}
+
+/// Type a manuscript, or show one of its internal representations if
+/// the arguments ask for it
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let args = kosik::Arguments::from("dummy.sik");
+/// kosik::render(&args).unwrap();
+/// ```
+pub fn render(args: &Arguments) -> Result<(), Box<dyn Error>> {
+    if args.xml {
+        return write_output(&args.shared, load(args)?.text.as_bytes());
+    }
+
+    if args.watch {
+        return watch(args);
+    }
+
+    let root = read(args)?;
+    write(root, args)
+}
+
+/// Type the pages to a file, and type them again whenever the input
+/// file or any file it includes changes
+///
+/// The pages are written to the file named by <tt>-o</tt>, or to a
+/// file named after the input file in the current directory, which is
+/// replaced in one step so that a viewer never sees it half-written.
+/// Only the parts of a manuscript whose source changed are read and
+/// formatted again, and the numbering of chapters and sections is
+/// carried over from the parts before them.  Errors are reported on
+/// the standard error, and the last good output is left in place.
+/// This function only returns if the output format cannot be
+/// watched.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// let mut args = kosik::Arguments::from("dummy.sik");
+/// args.watch = true;
+/// kosik::watch(&args).unwrap();
+/// ```
+pub fn watch(args: &Arguments) -> Result<(), Box<dyn Error>> {
+    if !args.format.is_typed() {
+        return Err("Only typed pages can be watched: ps, pdf, html, svg or text".into());
+    }
+
+    if args.input_file == Path::new("-") {
+        return Err("The standard input cannot be watched".into());
+    }
+
+    let mut cache = Cache::new();
+
+    loop {
+        let files = match load(args) {
+            Ok(source) => {
+                match retype(&source, args, &mut cache) {
+                    Ok(status) => eprintln!("{}", status),
+                    Err(error) => eprintln!("{}", error),
+                }
+
+                source.files().to_vec()
+            },
+            Err(error) => {
+                eprintln!("{}", error);
+                vec![args.input_file.clone()]
+            },
+        };
+
+        let stamps = modified(&files);
+
+        while modified(&files) == stamps {
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+}
+
+/// Read the modification times of the watched files
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files.iter()
+        .map(|path| fs::metadata(path).and_then(|x| x.modified()).ok())
+        .collect()
+}
+
+/// Type the pages of a watched document, and report what was done
+fn retype(source: &Source, args: &Arguments, cache: &mut Cache)
+          -> Result<String, Box<dyn Error>>
+{
+    let root = complete(source, &args.config,
+                        cache.read(&source.text, &args.config))?;
+    let mut statistics = export::stats::from_elements(&root);
+
+    let (typescript, status) = match root {
+        ElementType::Manuscript(elem) => {
+            let has_structure = elem.attributes.has_structure;
+            let (short_title, short_author_name) = short_names(
+                &args.config, elem.short_title(), elem.short_author_name()
+            );
+
+            let compositor = cache.run(elem);
+            check_footnotes(&compositor, args)?;
+            statistics.paginate(&compositor);
+
+            let word_count = statistics.word_count(&args.word_count)
+                .map(|words| WordCount {
+                    words: words,
+                    granularity: args.word_count.granularity,
+                });
+
+            let typescript = Typescript {
+                layout: Layout::Manuscript,
+                contact: compositor.contact,
+                word_count: word_count,
+                has_structure: has_structure,
+                short_title: short_title,
+                short_author_name: short_author_name,
+                running_heads: Vec::new(),
+                pages: compositor.pages,
+            };
+
+            (typescript, format!("{} of {} parts read again, {} reformatted",
+                                 cache.reread, cache.parts, cache.reformatted))
+        },
+        ElementType::Screenplay(elem) => {
+            let has_structure = elem.head().is_some();
+            let (short_title, short_author_name) = short_names(
+                &args.config, elem.short_title(), elem.short_author_name()
+            );
+
+            let compositor = Compositor::new(1, has_structure).run(elem.into());
+            check_footnotes(&compositor, args)?;
+
+            let typescript = Typescript {
+                layout: Layout::Screenplay,
+                contact: compositor.contact,
+                word_count: None,
+                has_structure: has_structure,
+                short_title: short_title,
+                short_author_name: short_author_name,
+                running_heads: Vec::new(),
+                pages: compositor.pages,
+            };
+
+            (typescript, "screenplay reformatted".to_string())
+        },
+        _ => {
+            return Err(format!("{}: only a manuscript or a screenplay can be watched",
+                               args.input_file.display()).into());
+        },
+    };
+
+    let pages = typescript.pages.len();
+
+    if args.format == Format::Svg {
+        write_typescript(&typescript, args)?;
+        return Ok(format!("{} pages, {}", pages, status));
+    }
+
+    let path = match &args.shared.output {
+        Some(path) => path.clone(),
+        None => output_name(args, &format!(".{}", args.format.extension())),
+    };
+
+    let mut partial = path.clone().into_os_string();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let mut buffer: Vec<u8> = Vec::new();
+    type_pages(&typescript, args.format, &mut buffer)?;
+    fs::write(&partial, &buffer)
+        .map_err(|e| format!("{}: {}", partial.display(), e))?;
+
+    fs::rename(&partial, &path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    Ok(format!("{}: {} pages, {}", path.display(), pages, status))
+}
+
+/// Format manuscript sources in place
+///
+/// With <tt>--check</tt>, the files are left alone, and those that
+/// would change are listed on the standard output.  Returns whether
+/// every file was already formatted.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::FmtArguments::parse_from(["kosik fmt", "--check", "dummy.sik"]);
+/// let formatted = kosik::fmt(&args).unwrap();
+/// ```
+pub fn fmt(args: &FmtArguments) -> Result<bool, Box<dyn Error>> {
+    let mut formatted = true;
+
+    for path in args.input_files.iter() {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let pretty = document::pretty::format(&text).map_err(|e| {
+            let line = text[..e.position.min(text.len())].matches('\n').count() + 1;
+            format!("{}:{}: {}", path.display(), line, e)
+        })?;
+
+        if pretty == text {
+            continue;
+        }
+
+        formatted = false;
+
+        if args.check {
+            println!("{}", path.display());
+        } else {
+            fs::write(path, pretty).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+    }
+
+    Ok(formatted)
+}
+
+/// Check manuscript sources and list the findings on the standard
+/// output
+///
+/// Returns true if nothing was found.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::LintArguments::parse_from(["kosik lint", "-A", "double-space",
+///                                              "dummy.sik"]);
+/// let clean = kosik::lint(&args).unwrap();
+/// ```
+pub fn lint(args: &LintArguments) -> Result<bool, Box<dyn Error>> {
+    let mut output = open_output(&args.shared)?;
+    let mut clean = true;
+
+    for path in args.input_files.iter() {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let findings = document::lint::check(&text, &args.allow).map_err(|e| {
+            let line = text[..e.position.min(text.len())].matches('\n').count() + 1;
+            format!("{}:{}: {}", path.display(), line, e)
+        })?;
+
+        clean = clean && findings.is_empty();
+
+        // A pipe closed by a reader that has seen enough, such as
+        // head, is not an error.
+        match write_findings(&mut output, path, &findings) {
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => return Ok(clean),
+            result => result?,
+        }
+    }
+
+    Ok(clean)
+}
+
+/// Write the findings in a file, each on a line of its own after the
+/// name of the file
+fn write_findings(output: &mut dyn Write, path: &Path,
+                  findings: &[document::lint::Finding]) -> io::Result<()>
+{
+    for finding in findings.iter() {
+        writeln!(output, "{}:{}", path.display(), finding)?;
+    }
+
+    output.flush()
+}
+
+/// Report statistics for a manuscript on the standard output
+///
+/// The pages are counted by composing the manuscript, as it would be
+/// for Postscript output.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::StatsArguments::parse_from(["kosik stats", "--json", "dummy.sik"]);
+/// kosik::stats(&args).unwrap();
+/// ```
+pub fn stats(args: &StatsArguments) -> Result<(), Box<dyn Error>> {
+    let root = read(&Arguments {
+        input_file: args.input_file.clone(),
+        config: Config::discover(&args.input_file)?,
+        ..Default::default()
+    })?;
+    let mut statistics = export::stats::from_elements(&root);
+
+    if let Some(compositor) = compose(root) {
+        statistics.paginate(&compositor);
+    }
+
+    let report = if args.json {
+        statistics.to_json(&args.word_count)
+    } else {
+        statistics.to_table(&args.word_count)
+    };
+
+    write_output(&args.shared, report.as_bytes())
+}
+
+/// Convert a manuscript to the format named by <tt>-f</tt>, or by the
+/// extension of the output file
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::ConvertArguments::parse_from(["kosik convert", "-o", "dummy.docx", "dummy.sik"]);
+/// kosik::convert(&args).unwrap();
+/// ```
+pub fn convert(args: &ConvertArguments) -> Result<(), Box<dyn Error>> {
+    let config = Config::discover(&args.input_file)?;
+
+    let format = args.format
+        .or_else(|| args.shared.output.as_deref().and_then(Format::from_extension))
+        .or(config.format)
+        .ok_or("Name a format with -f, or an output file with a known extension")?;
+
+    let args = Arguments {
+        input_file: args.input_file.clone(),
+        format: format,
+        shared: args.shared.clone(),
+        word_count: args.word_count,
+        config: config,
+        ..Default::default()
+    };
+
+    write(read(&args)?, &args)
+}
+
+/// Show the internal representation of a manuscript at one stage of
+/// processing
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::DumpArguments::parse_from(["kosik dump", "pages", "dummy.sik"]);
+/// kosik::dump(&args).unwrap();
+/// ```
+pub fn dump(args: &DumpArguments) -> Result<(), Box<dyn Error>> {
+    render(&Arguments {
+        input_file: args.input_file.clone(),
+        elements: args.stage == Stage::Elements,
+        blocks: args.stage == Stage::Blocks,
+        pages: args.stage == Stage::Pages,
+        xml: args.stage == Stage::Xml,
+        shared: args.shared.clone(),
+        config: Config::discover(&args.input_file)?,
+        ..Default::default()
+    })
+}
+
+/// Write a skeleton manuscript, with a head, contact information to
+/// fill in, and a body with one chapter
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::NewArguments::parse_from(["kosik new", "--title", "Youth", "youth.sik"]);
+/// kosik::new_manuscript(&args).unwrap();
+/// ```
+pub fn new_manuscript(args: &NewArguments) -> Result<(), Box<dyn Error>> {
+    let text = format!(include_str!("../data/skeleton.sik"),
+                       title = escape(&args.title),
+                       person = person_xml(&args.author));
+
+    let path = &args.output_file;
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    file.write_all(text.as_bytes())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    Ok(())
+}
+
+/// Escape the markup characters in text for manuscript XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Mark up a name as the given names and surname of a person, taking
+/// the last word as the surname
+fn person_xml(name: &str) -> String {
+    match name.trim().rsplit_once(' ') {
+        Some((gn, sn)) => format!("<gn>{}</gn><sn>{}</sn>", escape(gn.trim()), escape(sn)),
+        None => format!("<sn>{}</sn>", escape(name.trim())),
+    }
+}
+
+/// Type several manuscripts as one collection, with a title page and
+/// table of contents of its own
+///
+/// The collection takes its project settings from the first input
+/// file, and each manuscript takes its own.  Footnote errors are
+/// reported against the manuscript they are in.  The word count on
+/// the title page is the sum of the word counts of the manuscripts.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::AnthologyArguments::parse_from(["kosik anthology", "--title", "Sea Stories",
+///                                                   "-o", "dummy.pdf", "youth.sik", "typhoon.sik"]);
+/// kosik::anthology(&args).unwrap();
+/// ```
+pub fn anthology(args: &AnthologyArguments) -> Result<(), Box<dyn Error>> {
+    let first_file = args.input_files.first().ok_or("Name the manuscripts to collect")?;
+    let config = Config::discover(first_file)?;
+
+    let format = args.format
+        .or_else(|| args.shared.output.as_deref().and_then(Format::from_extension))
+        .or(config.format)
+        .unwrap_or_default();
+
+    if !format.is_typed() {
+        return Err("Only typed pages can be collected: ps, pdf, html, svg or text".into());
+    }
+
+    let persons: String = args.author.iter()
+        .map(|name| format!("<person>{}</person>", person_xml(name)))
+        .collect();
+
+    let authors = if persons.is_empty() {
+        persons
+    } else {
+        format!("<authors>{}</authors>", persons)
+    };
+
+    let xml = format!("<manuscript><head><title>{}</title>{}</head></manuscript>",
+                      escape(&args.title), authors);
+
+    let head = match parse(&Source::new(Path::new("<collection>"), xml), &config)? {
+        ElementType::Manuscript(elem) => elem,
+        _ => return Err("The collection has no head".into()),
+    };
+
+    let (short_title, short_author_name) = short_names(
+        &config, head.short_title(), head.short_author_name()
+    );
+
+    let mut collection = Collection::new(head);
+    let mut word_count = Some(0);
+
+    for path in args.input_files.iter() {
+        let story_config = Config::discover(path)?;
+        let story_args = Arguments {
+            input_file: path.clone(),
+            word_count: args.word_count,
+            strict: args.strict || story_config.strict == Some(true),
+            config: story_config,
+            ..Default::default()
+        };
+
+        let root = read(&story_args)?;
+        let mut statistics = export::stats::from_elements(&root);
+
+        let elem = match root {
+            ElementType::Manuscript(elem) => elem,
+            _ => return Err(format!("{}: only manuscripts can be collected",
+                                    path.display()).into()),
+        };
+
+        let (story_title, story_author_name) = short_names(
+            &story_args.config, elem.short_title(), elem.short_author_name()
+        );
+
+        // Each manuscript is typed on its own as well, to check its
+        // footnotes and count its words.
+        let compositor = collection.push(elem, story_title, story_author_name);
+        check_footnotes(&compositor, &story_args)?;
+        statistics.paginate(&compositor);
+
+        word_count = word_count.zip(statistics.word_count(&args.word_count))
+            .map(|(sum, words)| sum + words);
+    }
+
+    let (compositor, running_heads) = collection.finish();
+
+    let typescript = Typescript {
+        layout: Layout::Manuscript,
+        contact: compositor.contact,
+        word_count: word_count.map(|words| WordCount {
+            words: words,
+            granularity: args.word_count.granularity,
+        }),
+        has_structure: true,
+        short_title: short_title,
+        short_author_name: short_author_name,
+        running_heads: running_heads,
+        pages: compositor.pages,
+    };
+
+    write_typescript(&typescript, &Arguments {
+        input_file: PathBuf::from("-"),
+        format: format,
+        shared: args.shared.clone(),
+        ..Default::default()
+    })
+}
+
+/// List the entries in the table of contents of a manuscript, with
+/// the number of the page on which each one starts
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::TocArguments::parse_from(["kosik toc", "dummy.sik"]);
+/// kosik::toc(&args).unwrap();
+/// ```
+pub fn toc(args: &TocArguments) -> Result<(), Box<dyn Error>> {
+    let root = read(&Arguments {
+        input_file: args.input_file.clone(),
+        config: Config::discover(&args.input_file)?,
+        ..Default::default()
+    })?;
+
+    let compositor = match root {
+        ElementType::Manuscript(elem) => {
+            let first_page = elem.attributes.first_page;
+            let has_structure = elem.attributes.has_structure;
+            Compositor::new(first_page, has_structure).run(elem.into())
+        },
+        _ => {
+            return Err(format!("{}: only a manuscript has a table of contents",
+                               args.input_file.display()).into());
+        },
+    };
+
+    let contents: String = compositor.contents().iter()
+        .map(|(page_no, title)| format!("{:>4}  {}\n", page_no, title))
+        .collect();
+
+    write_output(&args.shared, contents.as_bytes())
+}
+
+/// Check that manuscripts can be read and typed, reporting any
+/// errors on the standard error
+///
+/// Footnote errors are warnings unless <tt>--strict</tt> is given.
+/// Returns whether every file is valid.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::ValidateArguments::parse_from(["kosik validate", "dummy.sik"]);
+/// let valid = kosik::validate(&args).unwrap();
+/// ```
+pub fn validate(args: &ValidateArguments) -> Result<bool, Box<dyn Error>> {
+    let mut valid = true;
+
+    for path in args.input_files.iter() {
+        let result = Config::discover(path).and_then(|config| {
+            let args = Arguments {
+                input_file: path.clone(),
+                strict: args.strict || config.strict == Some(true),
+                config: config,
+                ..Default::default()
+            };
+
+            match compose(read(&args)?) {
+                Some(compositor) => check_footnotes(&compositor, &args),
+                None => Ok(()),
+            }
+        });
+
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            valid = false;
+        }
+    }
+
+    Ok(valid)
+}
 
-use clap::Parser;
+/// Type many manuscripts, each to a file of its own, and list the
+/// pages and words in each of them on the standard output
+///
+/// The manuscripts are typed on a pool of threads.  Each file takes
+/// its options from its own project settings, where the command line
+/// leaves them out.  A file that fails is reported on the standard
+/// error, and the others are typed all the same.  If two files
+/// would be typed to the same output file, none of them is typed.
+/// Returns whether every file was typed.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use clap::Parser;
+///
+/// let args = kosik::BatchArguments::parse_from(["kosik batch", "-f", "pdf", "*.sik"]);
+/// let typed = kosik::batch(&args).unwrap();
+/// ```
+pub fn batch(args: &BatchArguments) -> Result<bool, Box<dyn Error>> {
+    let mut input_files: Vec<PathBuf> = Vec::new();
 
-use lazy_static::lazy_static;
+    for pattern in args.input_files.iter() {
+        if !pattern.contains(['*', '?', '[']) {
+            input_files.push(PathBuf::from(pattern));
+            continue;
+        }
 
-use crate::document::*;
-use crate::document::reader::Reader;
-use crate::document::compositor::Compositor;
-use crate::document::writer::Writer;
-use crate::text::*;
-use crate::text::tokens::*;
-    
-pub mod document;
-pub mod lut;
-pub mod text;
+        let matched = glob::glob(pattern)
+            .map_err(|e| format!("{}: {}", pattern, e))?
+            .collect::<Result<Vec<PathBuf>, _>>()?;
 
-//use crate::document::LEFT_MARGIN;
-//use crate::document::RIGHT_MARGIN;
+        if matched.is_empty() {
+            return Err(format!("{}: no files match", pattern).into());
+        }
 
-// configuration
+        input_files.extend(matched);
+    }
 
-lazy_static! {
-    #[doc(hidden)]
-    pub static ref PROGRAM_NAME: String = match get_program_name() {
-        Some(name) => name,
-        None => "kosik".to_string(),
+    let jobs = match args.jobs {
+        0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        n => n,
     };
 
-    /// Path to prologue.ps
-    static ref PROLOGUE_FILE: PathBuf
-        = PathBuf::from("/home/gene/share/kosik/prologue.ps");
+    // The options for each file are worked out first, so that two
+    // files that would be typed to the same output are caught before
+    // either of them is typed.  Errors are kept as text, since they
+    // cannot cross threads otherwise.
+    let planned: Vec<(&PathBuf, Result<Arguments, String>)> = input_files.iter()
+        .map(|path| (path, batch_arguments(path, args).map_err(|e| error_text(path, e))))
+        .collect();
 
-    /// Path to roman_numerals.txt
-    static ref ROMAN_NUMERALS_FILE: PathBuf
-        = PathBuf::from("/home/gene/share/kosik/roman_numerals.txt");
+    let outputs: Vec<(&PathBuf, &PathBuf)> = planned.iter()
+        .filter_map(|(path, args)| match args {
+            Ok(args) => args.shared.output.as_ref().map(|output| (*path, output)),
+            _ => None,
+        })
+        .collect();
+
+    for (i, (path, output)) in outputs.iter().enumerate() {
+        if let Some((earlier, _)) = outputs[..i].iter().find(|(_, x)| x == output) {
+            return Err(format!("{} and {} would both be typed to {}",
+                               earlier.display(), path.display(), output.display()).into());
+        }
+    }
+
+    if let Some(dir) = &args.output_dir {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+
+    // Each thread takes the next file that nobody has started on, and
+    // sends back what became of it.
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(input_files.len()) {
+            let sender = sender.clone();
+            let next = &next;
+            let planned = &planned;
+
+            scope.spawn(move || {
+                while let Some((path, args)) = planned.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    let result = match args {
+                        Ok(args) => type_one(args).map_err(|e| error_text(path, e)),
+                        Err(error) => Err(error.clone()),
+                    };
+
+                    sender.send((*path, result)).unwrap();
+                }
+            });
+        }
+    });
+
+    drop(sender);
+
+    let mut results: Vec<_> = receiver.into_iter().collect();
+    results.sort_by_key(|(path, _)| input_files.iter().position(|x| x == *path));
+
+    let mut failures = 0;
+
+    for (path, result) in results {
+        match result {
+            Ok(tally) => {
+                let pages = match tally.pages {
+                    Some(pages) => format!("{} pages", pages),
+                    None => "no pages".to_string(),
+                };
+
+                println!("{}: {}, {} words -> {}", path.display(), pages,
+                         tally.words.separate_with_commas(), tally.output.display());
+            },
+            Err(error) => {
+                eprintln!("{}", error);
+                failures += 1;
+            },
+        }
+    }
+
+    if failures > 0 {
+        eprintln!("{} of {} manuscripts failed", failures, input_files.len());
+    }
+
+    Ok(failures == 0)
 }
 
-/// Command-line arguments
-#[derive(Parser, Default, Debug)]
-#[clap(author="Gene Yu", version, about="Manuscript Typewriter")]
-pub struct Arguments {
-    /// An XML file conforming to the manuscript schema
-    pub input_file: PathBuf,
+// What a manuscript typed in a batch came to
+struct Tally {
+    output: PathBuf,
+    pages: Option<usize>,
+    words: usize,
+}
 
-    #[clap(short, long)]
-    /// Show the internal element representation instead of the usual output.
-    pub elements: bool,
+/// Name an error after the file of a batch it came from, unless it
+/// already is
+fn error_text(path: &Path, error: Box<dyn Error>) -> String {
+    let name = path.display().to_string();
 
-    #[clap(short, long)]
-    /// Show the internal block representation instead of the usual output.
-    pub blocks: bool,
+    match error.to_string() {
+        error if error.starts_with(&name) => error,
+        error => format!("{}: {}", name, error),
+    }
 }
 
-impl From<&str> for Arguments {
-    // This method is for testing.
-    fn from(s: &str) -> Self {
-        Self {
-            input_file: PathBuf::from(s),
-            elements: false,
-            blocks: false,
+/// Work out the options for typing one manuscript of a batch, next to
+/// its source or into the output directory
+fn batch_arguments(path: &Path, batch: &BatchArguments) -> Result<Arguments, Box<dyn Error>> {
+    if path == Path::new("-") {
+        return Err("The standard input cannot be typed in a batch".into());
+    }
+
+    let config = Config::discover(path)?;
+    let format = batch.format.or(config.format).unwrap_or_default();
+
+    let output = match &batch.output_dir {
+        Some(dir) => {
+            let name = path.file_name().ok_or_else(|| format!("{}: not a file", path.display()))?;
+            dir.join(name).with_extension(format.extension())
+        },
+        None => path.with_extension(format.extension()),
+    };
+
+    if output == path {
+        return Err(format!("{}: the output would replace the input", path.display()).into());
+    }
+
+    let mut word_count = batch.word_count;
+    configure_word_count(&mut word_count, &config, &batch.matches);
+
+    Ok(Arguments {
+        input_file: path.to_path_buf(),
+        format: format,
+        shared: SharedArguments {
+            output: Some(output),
+        },
+        word_count: word_count,
+        strict: batch.strict || config.strict == Some(true),
+        config: config,
+        ..Default::default()
+    })
+}
+
+/// Type one manuscript of a batch
+fn type_one(args: &Arguments) -> Result<Tally, Box<dyn Error>> {
+    let root = read(args)?;
+    let mut statistics = export::stats::from_elements(&root);
+
+    // The pages are counted whatever the output format.
+    if args.format.is_typed() {
+        let typescript = typeset(root, &mut statistics, args)?;
+        write_typescript(&typescript, args)?;
+    } else {
+        export_elements(&root, args)?;
+
+        if let Some(compositor) = compose(root) {
+            statistics.paginate(&compositor);
         }
     }
+
+    let words = statistics.word_count(&args.word_count)
+        .unwrap_or(statistics.total.words + statistics.total.footnote_words);
+
+    Ok(Tally {
+        output: args.shared.output.clone().unwrap_or_default(),
+        pages: statistics.pages,
+        words: words,
+    })
 }
 
-/// Read an XML input string and construct an element hierarchy from
-/// its contents
-///
-/// # Examples
-///
-/// ```rust,no_run
-/// let args = kosik::Arguments::from("dummy.sik");
-/// let root = kosik::read(&args).unwrap();
-/// ```
-pub fn read(args: &Arguments) -> Result<ElementType, Box<dyn Error>> {
-    let xml_string = fs::read_to_string(&args.input_file).unwrap();
-    let reader = Reader::new(&xml_string);
-    reader.run().ok_or("No elements!".into())
+/// Compose the pages of a manuscript or screenplay as they would be
+/// typed.  Other elements have no pages of their own.
+fn compose(root: ElementType) -> Option<Compositor> {
+    match root {
+        ElementType::Manuscript(elem) => {
+            let first_page = elem.attributes.first_page;
+            let has_structure = elem.attributes.has_structure;
+            Some(Compositor::new(first_page, has_structure).run(elem.into()))
+        },
+        ElementType::Screenplay(elem) => {
+            let has_structure = elem.head().is_some();
+            Some(Compositor::new(1, has_structure).run(elem.into()))
+        },
+        _ => None,
+    }
+}
+
+/// Choose the title and author name for the slug line: those in the
+/// project settings, or else those from the head, or else
+/// placeholders
+fn short_names(config: &Config, title: Option<Segment>, author: Option<Segment>)
+               -> (Segment, Segment)
+{
+    // The title is printed in uppercase, but kept in mixed case for
+    // the document properties.
+    let short_title = match (&config.short_title, title) {
+        (Some(text), _) => Segment {
+            text: text.clone(),
+            ..Segment::from(&text.to_uppercase()[..])
+        },
+        (None, Some(segment)) => segment,
+        (None, None) => Segment {
+            text: "Working Title".to_string(),
+            ps: "(WORKING TITLE) show ".to_string(),
+            dpy: Default::default(),
+        },
+    };
+
+    let short_author_name = match (&config.short_author_name, author) {
+        (Some(text), _) => Segment::from(&text.to_uppercase()[..]),
+        (None, Some(segment)) => segment,
+        (None, None) => Segment::from("ANONYMOUS"),
+    };
+
+    (short_title, short_author_name)
+}
+
+/// Report the footnotes and note references that the compositor could
+/// not print as written, failing in strict mode
+fn check_footnotes(compositor: &Compositor, args: &Arguments)
+                   -> Result<(), Box<dyn Error>>
+{
+    let errors = compositor.footnote_errors();
+
+    for error in errors.iter() {
+        eprintln!("{}: {}", args.input_file.display(), error);
+    }
+
+    if args.strict && !errors.is_empty() {
+        return Err(format!("{}: {} footnote error(s)", args.input_file.display(),
+                           errors.len()).into());
+    }
+
+    Ok(())
+}
+
+/// Write composed pages in the requested output format
+fn write_typescript(typescript: &Typescript, args: &Arguments)
+                    -> Result<(), Box<dyn Error>>
+{
+    if args.format == Format::Svg {
+        for (i, page) in export::svg::from_typescript(typescript).iter().enumerate() {
+            let path = output_name(args, &format!("-{:03}.svg", i + 1));
+            fs::write(&path, page).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        return Ok(());
+    }
+
+    let mut buffer: Vec<u8> = Vec::new();
+    type_pages(typescript, args.format, &mut buffer)?;
+    write_output(&args.shared, &buffer)
+}
+
+/// Write composed pages in a format that fits in one file
+fn type_pages(typescript: &Typescript, format: Format, output: &mut dyn Write)
+              -> Result<(), Box<dyn Error>>
+{
+    match format {
+        Format::Html => {
+            output.write_all(export::html::from_typescript(typescript).as_bytes())?;
+        },
+        Format::Pdf => {
+            output.write_all(&export::pdf::from_typescript(typescript))?;
+        },
+        Format::Text => {
+            output.write_all(export::text::from_typescript(typescript).as_bytes())?;
+        },
+        _ => {
+            Writer::with_output(typescript, Box::new(output)).run()?;
+        },
+    }
+
+    Ok(())
+}
+
+/// Write the output to the file named by <tt>-o</tt>, or to the
+/// standard output
+fn write_output(shared: &SharedArguments, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+    match &shared.output {
+        Some(path) => {
+            fs::write(path, bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+        },
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(bytes)?;
+            stdout.flush()?;
+        },
+    }
+
+    Ok(())
+}
+
+/// Open the file named by <tt>-o</tt> for writing, or else the
+/// standard output
+fn open_output(shared: &SharedArguments) -> Result<Box<dyn Write>, Box<dyn Error>> {
+    match &shared.output {
+        Some(path) => {
+            let file = fs::File::create(path)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok(Box::new(io::BufWriter::new(file)))
+        },
+        None => Ok(Box::new(io::stdout().lock())),
+    }
+}
+
+/// Name an output file after the file named by <tt>-o</tt>, or after
+/// the input file in the current directory
+fn output_name(args: &Arguments, suffix: &str) -> PathBuf {
+    let path = args.shared.output.as_ref().unwrap_or(&args.input_file);
+
+    let stem = match path.file_stem().and_then(OsStr::to_str) {
+        Some(stem) if path != Path::new("-") => stem,
+        _ => &PROGRAM_NAME,
+    };
+
+    match &args.shared.output {
+        Some(output) => output.with_file_name(format!("{}{}", stem, suffix)),
+        None => PathBuf::from(format!("{}{}", stem, suffix)),
+    }
+}
+
+/// Write an element hierarchy in a format made from the elements
+/// themselves, rather than from typed pages
+fn export_elements(elem: &ElementType, args: &Arguments) -> Result<(), Box<dyn Error>> {
+    match args.format {
+        Format::Docx => write_output(&args.shared, &export::docx::from_elements(elem)?),
+        Format::Epub => write_output(&args.shared, &export::epub::from_elements(elem)?),
+        Format::Latex => write_output(&args.shared, export::latex::from_elements(elem).as_bytes()),
+        Format::Reading => write_output(&args.shared, export::html::from_elements(elem).as_bytes()),
+        Format::Sik => write_output(&args.shared, export::sik::from_elements(elem).as_bytes()),
+        format => Err(format!("{:?} is a format of typed pages", format).into()),
+    }
+}
+
+/// Compose the pages of a manuscript or screenplay, check its
+/// footnotes, and fill in the page counts of its statistics
+fn typeset(root: ElementType, statistics: &mut export::stats::Statistics,
+           args: &Arguments) -> Result<Typescript, Box<dyn Error>>
+{
+    match root {
+        ElementType::Manuscript(elem) => {
+            let first_page = elem.attributes.first_page;
+            let has_structure = elem.attributes.has_structure;
+
+            let (short_title, short_author_name) = short_names(
+                &args.config, elem.short_title(), elem.short_author_name()
+            );
+
+            let compositor = Compositor::new(first_page, has_structure).run(elem.into());
+            check_footnotes(&compositor, args)?;
+            statistics.paginate(&compositor);
+
+            let word_count = statistics.word_count(&args.word_count)
+                .map(|words| WordCount {
+                    words: words,
+                    granularity: args.word_count.granularity,
+                });
+
+            Ok(Typescript {
+                layout: Layout::Manuscript,
+                contact: compositor.contact,
+                word_count: word_count,
+                has_structure: has_structure,
+                short_title: short_title,
+                short_author_name: short_author_name,
+                running_heads: Vec::new(),
+                pages: compositor.pages,
+            })
+        },
+        ElementType::Screenplay(elem) => {
+            let has_structure = elem.head().is_some();
+
+            let (short_title, short_author_name) = short_names(
+                &args.config, elem.short_title(), elem.short_author_name()
+            );
+
+            let compositor = Compositor::new(1, has_structure).run(elem.into());
+            check_footnotes(&compositor, args)?;
+            statistics.paginate(&compositor);
+
+            Ok(Typescript {
+                layout: Layout::Screenplay,
+                contact: compositor.contact,
+                word_count: None,
+                has_structure: has_structure,
+                short_title: short_title,
+                short_author_name: short_author_name,
+                running_heads: Vec::new(),
+                pages: compositor.pages,
+            })
+        },
+        _ => Err(format!("{}: only a manuscript or a screenplay can be typed",
+                         args.input_file.display()).into()),
+    }
 }
 
 #[doc(hidden)]
 #[macro_use]
 mod fragments;
 
-/// Write an element hierarchy to the standard output in Postscript
+/// Write an element hierarchy to the standard output in the requested
+/// format
 ///
 /// # Examples
 ///
@@ -215,7 +1992,25 @@ mod fragments;
 pub fn write(elem: ElementType, args: &Arguments)
              -> Result<(), Box<dyn Error>>
 {
+    if !args.elements && !args.blocks && !args.pages {
+        if !args.format.is_typed() {
+            return export_elements(&elem, args);
+        }
+
+        if matches!(elem, ElementType::Manuscript(_) | ElementType::Screenplay(_)) {
+            let mut statistics = export::stats::from_elements(&elem);
+            let typescript = typeset(elem, &mut statistics, args)?;
+            return write_typescript(&typescript, args);
+        }
+    }
+
     match elem {
+        ElementType::Act(elem) => {
+            write_container!(elem, "act", &args);
+        },
+        ElementType::Action(elem) => {
+            write_block!(elem, "action", &args);
+        },
         ElementType::Attribution(elem) => {
             write_block!(elem, "attribution", &args);
         },
@@ -237,18 +2032,50 @@ pub fn write(elem: ElementType, args: &Arguments)
         ElementType::Br(elem) => {
             write_block!(elem, "br", &args);
         },
+        ElementType::CastList(elem) => {
+            write_container!(elem, "castList", &args);
+        },
         ElementType::Chapter(elem) => {
             write_container!(elem, "chapter", &args);
         },
+        ElementType::Character(elem) => {
+            write_block!(elem, "character", &args);
+        },
         ElementType::Contact(elem) => {
             write_block!(elem, "contact", &args);
         },
+        ElementType::Dd(elem) => {
+            let mut wrapper = ContainerElement::new(Dl {
+                line_spacing: elem.attributes.line_spacing,
+            });
+            wrapper.children.push(ElementType::Dd(elem));
+            
+            write_container!(wrapper, "dd", &args);
+        },
+        ElementType::Dedication(elem) => {
+            write_block!(elem, "dedication", &args);
+        },
+        ElementType::Dialogue(elem) => {
+            write_block!(elem, "dialogue", &args);
+        },
+        ElementType::Direction(elem) => {
+            write_block!(elem, "direction", &args);
+        },
         ElementType::Div(elem) => {
             write_block!(elem, "div", &args);
         },
+        ElementType::Dl(elem) => {
+            write_container!(elem, "dl", &args);
+        },
+        ElementType::Dt(elem) => {
+            write_block!(elem, "dt", &args);
+        },
         ElementType::Em(elem) => {
             write_block!(elem, "em", &args);
         },
+        ElementType::Epigraph(elem) => {
+            write_container!(elem, "epigraph", &args);
+        },
         ElementType::Footnote(elem) => {
             let wrapper = TextElement {
                 attributes: P {
@@ -282,55 +2109,32 @@ pub fn write(elem: ElementType, args: &Arguments)
             write_container!(elem, "li", &args);
         },
         ElementType::Manuscript(elem) => {
+            let mut dump = String::new();
+
             if args.elements {
-                println!("{:?}", &elem);
+                dump.push_str(&format!("{:?}\n", &elem));
 
                 if !args.blocks {
-                    return Ok(());
+                    return write_output(&args.shared, dump.as_bytes());
                 }
             }
 
             let first_page = elem.attributes.first_page;
-            let word_count = elem.attributes.word_count;
             let has_structure = elem.attributes.has_structure;
-            
-            let short_title = match elem.short_title() {
-                Some(segment) => segment,
-                None => Segment {
-                    text: "Working Title".to_string(),
-                    ps: "(WORKING TITLE) show ".to_string(),
-                },
-            };
-            
-            let short_author_name = match elem.short_author_name() {
-                Some(segment) => segment,
-                None => Segment::from("ANONYMOUS"),
-            };
-            
             let blocks: BlockList = elem.into();
 
             if args.blocks {
-                println!("{:?}", &blocks);
+                dump.push_str(&format!("{:?}\n", &blocks));
             }
 
             if args.elements || args.blocks {
-                return Ok(());
+                return write_output(&args.shared, dump.as_bytes());
             }
-                
-            let mut compositor = Compositor::new(first_page, has_structure);
-            compositor = compositor.run(blocks);
-            
-            let typescript = Typescript {
-                contact: compositor.contact,
-                word_count: Some(word_count),
-                has_structure: has_structure,
-                short_title: short_title,
-                short_author_name: short_author_name,
-                pages: compositor.pages,
-            };
 
-            let mut writer = Writer::new(&typescript);
-            writer.run()?;
+            // Without a dump, it was typed above, so only the pages are
+            // left to show.
+            let compositor = Compositor::new(first_page, has_structure).run(blocks);
+            write_output(&args.shared, format!("{:?}\n", &compositor.pages).as_bytes())?;
         },
         ElementType::NoteRef(elem) => {
             write_block!(elem, "noteRef", &args);
@@ -344,21 +2148,66 @@ pub fn write(elem: ElementType, args: &Arguments)
         ElementType::PageBreak(elem) => {
             write_block!(elem, "pageBreak", &args);
         },
+        ElementType::Parenthetical(elem) => {
+            write_block!(elem, "parenthetical", &args);
+        },
         ElementType::Part(elem) => {
             write_container!(elem, "part", &args);
         },
         ElementType::Person(elem) => {
             write_container!(elem, "person", &args);
         },
+        ElementType::Pre(elem) => {
+            write_block!(elem, "pre", &args);
+        },
         ElementType::Prefix(elem) => {
             write_block!(elem, "prefix", &args);
         },
+        ElementType::Scene(elem) => {
+            write_container!(elem, "scene", &args);
+        },
+        ElementType::SceneHeading(elem) => {
+            write_block!(elem, "sceneHeading", &args);
+        },
+        ElementType::Screenplay(elem) => {
+            let mut dump = String::new();
+
+            if args.elements {
+                dump.push_str(&format!("{:?}\n", &elem));
+
+                if !args.blocks {
+                    return write_output(&args.shared, dump.as_bytes());
+                }
+            }
+
+            let has_structure = elem.head().is_some();
+            let blocks: BlockList = elem.into();
+
+            if args.blocks {
+                dump.push_str(&format!("{:?}\n", &blocks));
+            }
+
+            if args.elements || args.blocks {
+                return write_output(&args.shared, dump.as_bytes());
+            }
+
+            // Without a dump, it was typed above, so only the pages are
+            // left to show.
+            let compositor = Compositor::new(1, has_structure).run(blocks);
+            write_output(&args.shared, format!("{:?}\n", &compositor.pages).as_bytes())?;
+        },
         ElementType::Section(elem) => {
             write_container!(elem, "section", &args);
         },
         ElementType::Sn(elem) => {
             write_block!(elem, "sn", &args);
         },
+        ElementType::Speaker(elem) => {
+            write_block!(elem, "speaker", &args);
+        },
+        ElementType::Speech(elem) => {
+            write_container!(elem, "speech", &args);
+        },
         ElementType::Sub(elem) => {
             write_block!(elem, "sub", &args);
         },
@@ -371,9 +2220,30 @@ pub fn write(elem: ElementType, args: &Arguments)
         ElementType::Sup(elem) => {
             write_block!(elem, "sup", &args);
         },
+        ElementType::Table(elem) => {
+            write_container!(elem, "table", &args);
+        },
+        ElementType::Td(elem) => {
+            let mut row = ContainerElement::new(Tr { header: false });
+            row.children.push(ElementType::Td(elem));
+            
+            let mut wrapper = ContainerElement::new(Table {});
+            wrapper.children.push(ElementType::Tr(row));
+            
+            write_container!(wrapper, "td", &args);
+        },
         ElementType::Title(elem) => {
             write_block!(elem, "title", &args);
         },
+        ElementType::Tr(elem) => {
+            let mut wrapper = ContainerElement::new(Table {});
+            wrapper.children.push(ElementType::Tr(elem));
+            
+            write_container!(wrapper, "tr", &args);
+        },
+        ElementType::Transition(elem) => {
+            write_block!(elem, "transition", &args);
+        },
         ElementType::Ul(elem) => {
             write_container!(elem, "ul", &args);
         },
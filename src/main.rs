@@ -16,11 +16,49 @@
 // <https://www.gnu.org/licenses/>.
 
 use std::error::Error;
-use clap::Parser;
+
+use kosik::Command;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = kosik::Arguments::parse();
-    let root = kosik::read(&args)?;
-    kosik::write(root, &args)?;
-    Ok(())
+    let cli = kosik::Cli::from_command_line()?;
+
+    match cli.command {
+        Some(Command::Anthology(args)) => kosik::anthology(&args),
+        Some(Command::Batch(args)) => {
+            if !kosik::batch(&args)? {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        },
+        Some(Command::Convert(args)) => kosik::convert(&args),
+        Some(Command::Dump(args)) => kosik::dump(&args),
+        Some(Command::Fmt(args)) => {
+            if !kosik::fmt(&args)? && args.check {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        },
+        Some(Command::Lint(args)) => {
+            if !kosik::lint(&args)? {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        },
+        Some(Command::New(args)) => kosik::new_manuscript(&args),
+        Some(Command::Render(args)) => kosik::render(&args),
+        Some(Command::Stats(args)) => kosik::stats(&args),
+        Some(Command::Toc(args)) => kosik::toc(&args),
+        Some(Command::Validate(args)) => {
+            if !kosik::validate(&args)? {
+                std::process::exit(1);
+            }
+
+            Ok(())
+        },
+        None => kosik::render(&cli.args),
+    }
 }
+
@@ -72,6 +72,8 @@ pub struct Segment {
     pub text: String,
     /// The Postscript command to print the line segment
     pub ps: String,
+    /// The display flags shared by all of the tokens in the segment
+    pub dpy: DisplayFlags,
 }
 
 lazy_static! {
@@ -96,6 +98,7 @@ impl From<String> for Segment {
         Self {
             text: s,
             ps: format!("({}) show ", ps),
+            dpy: Default::default(),
         }
     }
 }
@@ -111,6 +114,7 @@ impl From<&str> for Segment {
         Self {
             text: s.to_string(),
             ps: format!("({}) show ", ps),
+            dpy: Default::default(),
         }
     }
 }
@@ -305,6 +309,7 @@ impl From<&[TokenType]> for Segment {
         Segment {
             text: text,
             ps: ps,
+            dpy: dpy,
         }
     }
 }
@@ -347,16 +347,19 @@ impl StateMachine {
                     },
                     '\u{2013}' => { // En dash
                         state.data.text.push_str("-");
+                        state.data.glyph = Some(ch.clone());
                         state.frm.insert(FormatFlags::DLB);
                         (StateMachine::Scan(state.into()), true)
                     },
                     '\u{2014}' => { // Em dash
                         state.data.text.push_str("--");
+                        state.data.glyph = Some(ch.clone());
                         state.frm.insert(FormatFlags::DLB);
                         (StateMachine::Scan(state.into()), true)
                     },
                     '\u{2026}' => { // Horizontal ellipsis
                         state.data.text.push_str(". . .");
+                        state.data.glyph = Some(ch.clone());
                         state.frm.insert(FormatFlags::DLB);
                         (StateMachine::Scan(state.into()), true)
                     },
@@ -703,6 +706,7 @@ impl From<State<ScanData>> for State<PunctData> {
         State {
             data: PunctData {
                 text: String::new(),
+                glyph: None,
             },
             tokens: state.tokens,
             word_count: state.word_count,
@@ -298,6 +298,9 @@ pub struct OpenData {
 #[derive(Debug, Clone)]
 pub struct PunctData {
     pub text: String,
+    /// The character as written, for dashes and ellipses, whose
+    /// text is typewritten
+    pub glyph: Option<char>,
 }
 
 /// Space characters
@@ -540,6 +543,7 @@ impl From<&str> for Token<PunctData> {
         Self {
             data: PunctData {
                 text: s.to_string(),
+                glyph: None,
             },
             dpy: Default::default(),
             frm: match s {